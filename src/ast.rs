@@ -1,29 +1,76 @@
+use num_bigint::BigInt;
 use pest::Span;
 
 /// Represents line and column information for debugging purposes.
-#[derive(Debug, Clone)]
+/// `file` names the script the position belongs to when it is not the script being run,
+/// such as a module loaded with `invoke`.
+#[derive(Debug, Clone, PartialEq)]
 pub struct LineInfo {
     pub line: usize,
     pub column: usize,
+    pub file: Option<String>,
 }
 
 impl LineInfo {
     /// Creates a `LineInfo` from a given `Span`.
     pub fn from_span(span: &Span) -> Self {
         let (line, column) = span.start_pos().line_col();
-        LineInfo { line, column }
+        LineInfo {
+            line,
+            column,
+            file: None,
+        }
     }
 }
 
+/// The location of a local variable, assigned by `resolver::resolve`: the number of scopes
+/// between the scope it is used in and the scope it is declared in, and its position among
+/// the variables of that scope.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+impl Slot {
+    /// Returns the slot at the given position of the current scope, which is where the
+    /// parameters of a function and the variables of an `orbit` or an oracle are bound.
+    pub fn current(index: usize) -> Self {
+        Slot { depth: 0, index }
+    }
+}
+
+/// The way a rune literal is written, kept so that the formatter writes it back the same way.
+/// `Raw` holds the number of `#` around a raw rune such as `r#"say "hi""#`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuneStyle {
+    Plain,
+    Raw(usize),
+    Multi,
+}
+
 /// Represents the abstract syntax tree (AST) for the language.
+/// The `Option<Slot>` of a variable is `None` until the resolver assigns it a slot,
+/// and stays `None` for global variables and variables looked up by name.
+/// An integer literal that does not fit in 64 bits is a `BigArcana`, which can only be
+/// evaluated in bigint mode.
+/// An `Interpolation` is an interpolated rune such as `$"x = {x}"`.
+/// A `Call` calls the function value of an expression, such as `fs[0](5)` or `mk()(3)`,
+/// while a `FuncCall` calls a function by its name.
 #[derive(Debug, Clone)]
 pub enum AST {
     Statement(Box<AST>, Option<LineInfo>),
     Omen(bool, Option<LineInfo>),
     Arcana(i64, Option<LineInfo>),
+    BigArcana(BigInt, Option<LineInfo>),
     Aether(f64, Option<LineInfo>),
-    Rune(String, Option<LineInfo>),
+    Rune(String, RuneStyle, Option<LineInfo>),
     Abyss(Option<LineInfo>),
+    Grimoire(Vec<AST>, Option<LineInfo>),
+    Codex(Vec<(AST, AST)>, Option<LineInfo>),
+    Index(Box<AST>, Box<AST>, Option<LineInfo>),
+    Field(Box<AST>, String, Option<LineInfo>),
+    Call(Box<AST>, Vec<AST>, Option<LineInfo>),
     Add(Box<AST>, Box<AST>, Option<LineInfo>),
     Sub(Box<AST>, Box<AST>, Option<LineInfo>),
     Mul(Box<AST>, Box<AST>, Option<LineInfo>),
@@ -45,17 +92,23 @@ pub enum AST {
         value: Box<AST>,
         var_type: Type,
         is_morph: bool,
+        slot: Option<Slot>,
         line_info: Option<LineInfo>,
     },
     Assignment {
         name: String,
+        accessors: Vec<Accessor>,
         value: Box<AST>,
         op: AssignmentOp,
+        slot: Option<Slot>,
         line_info: Option<LineInfo>,
     },
-    Var(String, Option<LineInfo>),
+    Var(String, Option<Slot>, Option<LineInfo>),
     Unveil(Vec<AST>, Option<LineInfo>),
+    Interpolation(Vec<RunePart>, Option<LineInfo>),
     Trans(Box<AST>, Type, Option<LineInfo>),
+    Curse(Box<AST>, Option<LineInfo>),
+    Attempt(Box<AST>, Option<LineInfo>),
     Reveal(Box<AST>, Option<LineInfo>),
     Oracle {
         is_match: bool,
@@ -83,6 +136,12 @@ pub enum AST {
         op: String,
         line_info: Option<LineInfo>,
     },
+    OrbitCollection {
+        name: String,
+        value_name: Option<String>,
+        collection: Box<AST>,
+        line_info: Option<LineInfo>,
+    },
     Resume(Option<String>, Option<LineInfo>),
     Eject(Option<String>, Option<LineInfo>),
     Engrave {
@@ -92,6 +151,12 @@ pub enum AST {
         body: Box<AST>,
         line_info: Option<LineInfo>,
     },
+    EngraveExpr {
+        params: Vec<AST>,
+        return_type: Type,
+        body: Box<AST>,
+        line_info: Option<LineInfo>,
+    },
     EngraveParam {
         name: String,
         param_type: Type,
@@ -103,6 +168,84 @@ pub enum AST {
         line_info: Option<LineInfo>,
     },
     Summon(String, Type, Option<LineInfo>),
+    Invoke {
+        path: String,
+        names: Option<Vec<String>>,
+        line_info: Option<LineInfo>,
+    },
+    Sigil {
+        name: String,
+        fields: Vec<(String, Type)>,
+        line_info: Option<LineInfo>,
+    },
+    SigilInstance {
+        name: String,
+        fields: Vec<(String, AST)>,
+        line_info: Option<LineInfo>,
+    },
+}
+
+impl AST {
+    /// Returns the location of the node in the source code.
+    pub fn line_info(&self) -> Option<&LineInfo> {
+        match self {
+            AST::Statement(_, line_info)
+            | AST::Omen(_, line_info)
+            | AST::Arcana(_, line_info)
+            | AST::BigArcana(_, line_info)
+            | AST::Aether(_, line_info)
+            | AST::Rune(_, _, line_info)
+            | AST::Abyss(line_info)
+            | AST::Grimoire(_, line_info)
+            | AST::Codex(_, line_info)
+            | AST::Index(_, _, line_info)
+            | AST::Field(_, _, line_info)
+            | AST::Call(_, _, line_info)
+            | AST::Add(_, _, line_info)
+            | AST::Sub(_, _, line_info)
+            | AST::Mul(_, _, line_info)
+            | AST::Div(_, _, line_info)
+            | AST::Mod(_, _, line_info)
+            | AST::PowArcana(_, _, line_info)
+            | AST::PowAether(_, _, line_info)
+            | AST::Equal(_, _, line_info)
+            | AST::NotEqual(_, _, line_info)
+            | AST::LessThan(_, _, line_info)
+            | AST::LessThanOrEqual(_, _, line_info)
+            | AST::GreaterThan(_, _, line_info)
+            | AST::GreaterThanOrEqual(_, _, line_info)
+            | AST::LogicalAnd(_, _, line_info)
+            | AST::LogicalOr(_, _, line_info)
+            | AST::LogicalNot(_, line_info)
+            | AST::Var(_, _, line_info)
+            | AST::Unveil(_, line_info)
+            | AST::Interpolation(_, line_info)
+            | AST::Trans(_, _, line_info)
+            | AST::Curse(_, line_info)
+            | AST::Attempt(_, line_info)
+            | AST::Reveal(_, line_info)
+            | AST::OracleDontCareItem(line_info)
+            | AST::Block(_, line_info)
+            | AST::Comment(_, line_info)
+            | AST::Resume(_, line_info)
+            | AST::Eject(_, line_info)
+            | AST::Summon(_, _, line_info)
+            | AST::VarAssign { line_info, .. }
+            | AST::Assignment { line_info, .. }
+            | AST::Oracle { line_info, .. }
+            | AST::OracleBranch { line_info, .. }
+            | AST::Orbit { line_info, .. }
+            | AST::OrbitParam { line_info, .. }
+            | AST::OrbitCollection { line_info, .. }
+            | AST::Engrave { line_info, .. }
+            | AST::EngraveExpr { line_info, .. }
+            | AST::EngraveParam { line_info, .. }
+            | AST::FuncCall { line_info, .. }
+            | AST::Invoke { line_info, .. }
+            | AST::Sigil { line_info, .. }
+            | AST::SigilInstance { line_info, .. } => line_info.as_ref(),
+        }
+    }
 }
 
 /// Represents a conditional assignment within an oracle statement.
@@ -121,6 +264,27 @@ pub enum Type {
     Rune,
     Omen,
     Abyss,
+    Grimoire(Box<Type>),
+    Codex(Box<Type>, Box<Type>),
+    Sigil(String),
+    Cursed(Box<Type>),
+    Engrave(Vec<Type>, Box<Type>), // A function value, with its parameter and return types
+}
+
+/// Represents an access into a variable on the left-hand side of an assignment, such as `xs[0]`
+/// or `point.x`. The line information points at the index or field itself.
+#[derive(Debug, Clone)]
+pub enum Accessor {
+    Index(Box<AST>, Option<LineInfo>),
+    Field(String, Option<LineInfo>),
+}
+
+/// Represents a part of an interpolated rune: a piece of its text, or an expression embedded in it
+/// with `{...}`. The line information of a piece of text points at its start.
+#[derive(Debug, Clone)]
+pub enum RunePart {
+    Text(String, Option<LineInfo>),
+    Expr(AST),
 }
 
 /// Represents an assignment operation.
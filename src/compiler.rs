@@ -0,0 +1,1219 @@
+use crate::ast::{Accessor, AssignmentOp, LineInfo, RunePart, Slot, Type, AST};
+use crate::env::{Closure, Function, Sigil};
+use crate::eval::{
+    builtin_arity, is_caught, misplaced_control, place_of, BinaryOp, EvalResult, ANONYMOUS_ENGRAVE,
+};
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// A single instruction of the stack machine run by `vm::run`. Every expression leaves exactly
+/// one value on the stack. Operands are indexes into the pools of the `Chunk` holding the
+/// instruction, or positions in its code for jumps.
+#[derive(Debug, Clone, Copy)]
+pub enum Instruction {
+    /// Pushes a constant.
+    Constant(usize),
+    /// Pushes a constant integer that does not fit in 64 bits, which is only allowed in
+    /// bigint mode.
+    BigConstant(usize),
+    /// Discards the value on top of the stack.
+    Pop,
+    /// Discards the value of a statement on top of the stack, raising it if it is a curse.
+    Drop,
+    /// Pushes the value of a local variable, or of the variable with the slot's name
+    /// if the slot is not set.
+    LoadSlot(usize),
+    /// Pushes the value of a variable stored in the environment.
+    LoadEnv(usize),
+    /// Declares a variable with the value on top of the stack and pushes `abyss`.
+    Define(usize),
+    /// Assigns to a variable, possibly through indexes and fields, and pushes `abyss`.
+    /// The right-hand side is below the evaluated indexes on the stack.
+    Assign(usize),
+    /// Applies a binary operator to the two values on top of the stack.
+    Binary(BinaryOp),
+    /// Applies a binary operator to the value on top of the stack and a local variable,
+    /// which is read in place rather than pushed first, as `LoadSlot` then `Binary` would.
+    BinarySlot(BinaryOp, usize),
+    /// Applies a binary operator to the value on top of the stack and a constant arcana, as
+    /// `Constant` then `Binary` would.
+    BinaryConstant(BinaryOp, usize),
+    /// Applies `!` to the value on top of the stack.
+    Not,
+    /// Collects the given number of values into a grimoire.
+    MakeGrimoire(usize),
+    /// Collects the given number of key and value pairs into a codex.
+    MakeCodex(usize),
+    /// Indexes a grimoire or codex.
+    Index,
+    /// Reads the named field of a sigil.
+    Field(usize),
+    /// Reads the element of a variable reached through indexes and fields, whose evaluated
+    /// indexes are on top of the stack, without copying the variable.
+    Access(usize),
+    /// Creates an instance of a sigil from the values of its fields.
+    MakeSigil(usize),
+    /// Casts the value on top of the stack to a type.
+    Trans(usize),
+    /// Casts a curse with the message on top of the stack.
+    Curse,
+    /// Returns the value on top of the stack from the current function, closing the scopes
+    /// opened in its body.
+    Reveal,
+    /// Writes the given number of values to the output.
+    Unveil(usize),
+    /// Joins the given number of values into a rune, converting them as `Unveil` does.
+    Interpolate(usize),
+    /// Reads a value from the input.
+    Summon(usize),
+    /// Jumps unconditionally.
+    Jump(usize),
+    /// Pops a value and jumps unless it is `boon`.
+    JumpUnlessBoon(usize),
+    /// Opens a scope, whose local variables start at the given slot, together with a scope of
+    /// the environment if `env` is set.
+    EnterScope { first_slot: usize, env: bool },
+    /// Closes the innermost scope and clears its local variables.
+    ExitScope,
+    /// Catches errors raised until the matching `TryEnd`, turning them into a curse
+    /// pushed before jumping to the given position.
+    TryBegin(usize),
+    /// Stops catching errors for the innermost `TryBegin`.
+    TryEnd,
+    /// Binds the value of an oracle conditional to its variable and to a hidden slot
+    /// that the patterns of the oracle are matched against.
+    BindConditional { var: Var, hidden: usize },
+    /// Binds the message of the curse held by a hidden slot to a variable,
+    /// or jumps if it does not hold a curse.
+    MatchCurseBind {
+        hidden: usize,
+        var: Var,
+        next: usize,
+    },
+    /// Jumps if the hidden slot does not hold a curse.
+    JumpUnlessCurse { hidden: usize, target: usize },
+    /// Jumps if the hidden slot holds a curse.
+    JumpIfCurse { hidden: usize, target: usize },
+    /// Pops a curse and jumps unless its message is the message of the curse in the hidden slot.
+    MatchCurseMessage { hidden: usize, next: usize },
+    /// Pops a pattern and jumps unless it equals the value in the hidden slot.
+    MatchValue { hidden: usize, next: usize },
+    /// Starts iterating over the range between the two values on top of the stack.
+    IterRange { inclusive: bool, name: usize },
+    /// Starts iterating over the grimoire or codex on top of the stack.
+    IterCollection { pair: bool, name: usize },
+    /// Starts an iteration that only ends by `eject`.
+    IterEndless,
+    /// Opens the scope of the next iteration and binds its variables,
+    /// or jumps when the iteration is over.
+    IterNext(usize),
+    /// Stops the innermost iteration.
+    IterEnd,
+    /// Leaves the scopes, iterations and `attempt`s opened since the iteration `depth` levels
+    /// out from the innermost one started, discarding the values pushed since, and jumps to
+    /// its next iteration for `resume` or to its end for `eject`.
+    LoopJump { depth: usize, target: usize },
+    /// Defines a function.
+    DefineFunction(usize),
+    /// Pushes an anonymous function as a value.
+    MakeEngrave(usize),
+    /// Defines a sigil.
+    DefineSigil(usize),
+    /// Invokes a module.
+    Invoke(usize),
+    /// Jumps if a function with the given name is defined, so that it is called
+    /// instead of the builtin with the same name.
+    JumpIfFunction { name: usize, target: usize },
+    /// Calls a builtin function that does not modify a variable with the given number of arguments.
+    Builtin { name: usize, argc: usize },
+    /// Calls `push`, with the value on top of the stack, or `pop` on a grimoire variable.
+    PushPop { target: usize, push: bool },
+    /// Calls a function with the given number of arguments.
+    Call { name: usize, argc: usize },
+    /// Calls the function value below the given number of arguments on the stack.
+    CallValue(usize),
+    /// Returns the value on top of the stack from the current function, or ends the program.
+    Return,
+    /// Raises an invalid operation error with the given message.
+    Fail(usize),
+}
+
+/// A variable referred to by an instruction: a local slot of the frame, or a variable of the
+/// environment, whose index is in the `env_vars` of the chunk.
+#[derive(Debug, Clone, Copy)]
+pub enum Var {
+    Slot(usize),
+    Env(usize),
+}
+
+/// A variable stored in the environment: a global, a variable of a closure or of code that
+/// engraves functions, or a variable imported by `invoke`. It is read through the slot the
+/// resolver gave it, or looked up by name without one.
+#[derive(Debug, Clone)]
+pub struct EnvVar {
+    pub name: String,
+    pub slot: Option<Slot>,
+}
+
+/// A variable declared with `forge`, stored in a local slot or, at the top level of a program
+/// and in code that engraves functions, in the environment.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub var: Var,
+    pub var_type: Type,
+    pub is_morph: bool,
+}
+
+/// The target of an assignment, of `push` and `pop`, or of a read through indexes and fields.
+#[derive(Debug, Clone)]
+pub struct AssignTarget {
+    pub name: String,
+    pub var: Var,
+    pub accessors: Vec<Accessor>,
+    pub index_count: usize,
+    pub op: AssignmentOp,
+}
+
+/// The variables bound by each iteration of a loop, whether its scope is mirrored by a scope of
+/// the environment, and where to jump once it is over.
+#[derive(Debug, Clone)]
+pub struct IterBinding {
+    pub first_slot: usize,
+    pub env: bool,
+    pub vars: Vec<Var>,
+    pub done: usize,
+}
+
+/// A module invoked with `invoke`.
+#[derive(Debug, Clone)]
+pub struct InvokeTarget {
+    pub path: String,
+    pub names: Option<Vec<String>>,
+}
+
+/// A function compiled to bytecode. `function` describes it to the environment,
+/// and `params` holds the variable each parameter is bound to.
+#[derive(Debug)]
+pub struct CompiledFunction {
+    pub function: Function,
+    pub chunk: Rc<Chunk>,
+    pub params: Vec<Var>,
+}
+
+/// The compiled code of a program or a function, with the pools its instructions refer to.
+/// `lines` holds the source location of each instruction, used for errors.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Instruction>,
+    pub lines: Vec<Option<LineInfo>>,
+    pub constants: Vec<EvalResult>,
+    pub names: Vec<String>,
+    pub types: Vec<Type>,
+    pub definitions: Vec<Definition>,
+    pub assignments: Vec<AssignTarget>,
+    pub sigil_instances: Vec<(String, Vec<String>)>,
+    pub summons: Vec<(String, Type)>,
+    pub iterations: Vec<IterBinding>,
+    pub functions: Vec<Rc<CompiledFunction>>,
+    pub sigils: Vec<Sigil>,
+    pub invokes: Vec<InvokeTarget>,
+    pub env_vars: Vec<EnvVar>,
+    pub slot_names: Vec<String>,
+    pub declared: HashSet<String>,
+}
+
+impl Chunk {
+    /// Returns the number of local slots of a frame running this chunk.
+    pub fn slot_count(&self) -> usize {
+        self.slot_names.len()
+    }
+}
+
+/// Compiles the top-level statements of a program to bytecode.
+/// Variables declared at the top level are globals of the environment, while the variables of
+/// nested scopes and functions are stored in local slots, unless the scope declaring them
+/// engraves functions, which see them through the environment. The slots the resolver gave the
+/// variables decide which local slot or variable of the environment each name refers to.
+///
+/// # Arguments
+/// * `program` - The top-level statements of the program, as resolved by
+///   `resolver::resolve_program`. Variables without a slot are looked up by name.
+///
+/// # Returns
+/// The compiled program, which leaves the result of its last statement on the stack.
+pub fn compile(program: &[AST]) -> Chunk {
+    let compiler = compile_program(program, Vec::new());
+    match compiler.needs_env.contains(&true) {
+        true => compile_program(program, compiler.needs_env).chunk,
+        false => compiler.chunk,
+    }
+}
+
+/// Compiles the top-level statements of a program, keeping the scopes flagged in `env_scopes`
+/// in the environment.
+fn compile_program(program: &[AST], env_scopes: Vec<bool>) -> Compiler {
+    let mut compiler = Compiler::new(false, env_scopes);
+    if program.is_empty() {
+        compiler.emit(Instruction::Constant(0), &None);
+        compiler.chunk.constants.push(EvalResult::Abyss);
+    }
+    for (position, ast) in program.iter().enumerate() {
+        if position > 0 {
+            compiler.drop(&program[position - 1], &None);
+        }
+        compiler.compile(ast);
+    }
+    compiler.emit(Instruction::Return, &None);
+    compiler
+}
+
+/// Compiles the body of a function, binding its parameters first and keeping the scopes
+/// flagged in `env_scopes`, the first being the scope of the parameters, in the environment.
+fn compile_function(
+    params: &[AST],
+    body: &AST,
+    line_info: &Option<LineInfo>,
+    env_scopes: Vec<bool>,
+) -> (Compiler, Vec<Var>) {
+    let mut compiler = Compiler::new(true, env_scopes);
+    compiler.open_scope();
+    let params = params
+        .iter()
+        .filter_map(|param| match param {
+            AST::EngraveParam { name, .. } => Some(name),
+            _ => None,
+        })
+        .enumerate()
+        .map(|(index, name)| compiler.declare(name, Some(Slot::current(index))))
+        .collect();
+    compiler.compile(body);
+    compiler.emit(Instruction::Return, line_info);
+    (compiler, params)
+}
+
+/// A scope being compiled, which maps the slots the resolver gave to its variables to the local
+/// slots of the frame, unless its variables are kept in the environment.
+struct Scope {
+    id: usize,                 // The position of the scope among the scopes the chunk opens
+    slots: Vec<Option<usize>>, // The local slot of each slot of the resolver
+    env_vars: bool,            // Whether the variables of the scope are kept in the environment
+    env_scope: bool,           // Whether the scope is mirrored by a scope of the environment
+}
+
+/// A loop being compiled, which the `resume` and `eject` inside it jump out of.
+struct LoopContext {
+    label: Option<String>, // The loop variable, or `None` for an `orbit` without parameters
+    next: usize,           // The start of the next iteration
+    ejects: Vec<usize>,    // The jumps to the end of the loop, patched once it is compiled
+}
+
+/// Compiles the body of a program or function into a chunk, keeping track of the local
+/// variables in scope and of the loops enclosing the code being compiled.
+///
+/// The scopes of a function engraving other functions, and the scopes enclosing them, keep their
+/// variables in the environment rather than in slots, since the functions see them when called
+/// from elsewhere. So do scopes that define sigils, invoke modules or declare variables by
+/// name, which the environment holds. Other scopes only exist in the frame, so that entering
+/// them costs nothing. Which scopes need the environment is only known once they are compiled,
+/// so a chunk in which some do is compiled again, knowing them.
+struct Compiler {
+    chunk: Chunk,
+    scopes: Vec<Scope>,
+    loops: Vec<LoopContext>,
+    in_function: bool,
+    env_scopes: Vec<bool>, // Whether each scope the chunk opens is kept in the environment
+    needs_env: Vec<bool>,  // Whether each scope opened so far needs the environment
+}
+
+impl Compiler {
+    /// Creates a compiler for a function or for the top level of a program, whose variables are
+    /// globals, keeping the scopes flagged in `env_scopes` in the environment.
+    fn new(in_function: bool, env_scopes: Vec<bool>) -> Self {
+        Compiler {
+            chunk: Chunk::default(),
+            scopes: Vec::new(),
+            loops: Vec::new(),
+            in_function,
+            env_scopes,
+            needs_env: Vec::new(),
+        }
+    }
+
+    /// Appends an instruction and returns its position.
+    fn emit(&mut self, instruction: Instruction, line_info: &Option<LineInfo>) -> usize {
+        self.chunk.code.push(instruction);
+        self.chunk.lines.push(line_info.clone());
+        self.chunk.code.len() - 1
+    }
+
+    /// Returns the position of the next instruction.
+    fn here(&self) -> usize {
+        self.chunk.code.len()
+    }
+
+    /// Points the jump at `position` to the next instruction.
+    fn patch(&mut self, position: usize) {
+        let target = self.here();
+        match &mut self.chunk.code[position] {
+            Instruction::Jump(to)
+            | Instruction::JumpUnlessBoon(to)
+            | Instruction::TryBegin(to)
+            | Instruction::MatchCurseBind { next: to, .. }
+            | Instruction::JumpUnlessCurse { target: to, .. }
+            | Instruction::JumpIfCurse { target: to, .. }
+            | Instruction::MatchCurseMessage { next: to, .. }
+            | Instruction::MatchValue { next: to, .. }
+            | Instruction::JumpIfFunction { target: to, .. }
+            | Instruction::LoopJump { target: to, .. } => *to = target,
+            _ => {}
+        }
+    }
+
+    /// Adds a constant to the pool and emits the instruction pushing it.
+    fn constant(&mut self, value: EvalResult, line_info: &Option<LineInfo>) {
+        self.chunk.constants.push(value);
+        let index = self.chunk.constants.len() - 1;
+        self.emit(Instruction::Constant(index), line_info);
+    }
+
+    /// Adds a name to the pool, reusing it if it is already there, and returns its index.
+    fn name(&mut self, name: &str) -> usize {
+        match self
+            .chunk
+            .names
+            .iter()
+            .position(|existing| existing == name)
+        {
+            Some(index) => index,
+            None => {
+                self.chunk.names.push(name.to_string());
+                self.chunk.names.len() - 1
+            }
+        }
+    }
+
+    /// Emits an instruction raising an invalid operation error with the given message.
+    fn fail(&mut self, message: String, line_info: &Option<LineInfo>) {
+        self.chunk.names.push(message);
+        let index = self.chunk.names.len() - 1;
+        self.emit(Instruction::Fail(index), line_info);
+    }
+
+    /// Allocates a new local slot with the given name.
+    fn new_slot(&mut self, name: &str) -> usize {
+        self.chunk.slot_names.push(name.to_string());
+        self.chunk.declared.insert(name.to_string());
+        self.chunk.slot_names.len() - 1
+    }
+
+    /// Opens a scope at compile time, returning whether it is mirrored by a scope of the
+    /// environment at run time. The scope of the parameters of a function always is.
+    fn open_scope(&mut self) -> bool {
+        let id = self.needs_env.len();
+        let env_vars = self.env_scopes.get(id).copied().unwrap_or(false);
+        let env_scope = env_vars || (self.in_function && id == 0);
+        self.needs_env.push(false);
+        self.scopes.push(Scope {
+            id,
+            slots: Vec::new(),
+            env_vars,
+            env_scope,
+        });
+        env_scope
+    }
+
+    /// Records that the innermost scope, or all the open scopes if `enclosing` is set, need the
+    /// environment.
+    fn need_env(&mut self, enclosing: bool) {
+        let open = match enclosing {
+            true => &self.scopes[..],
+            false => &self.scopes[self.scopes.len().saturating_sub(1)..],
+        };
+        for scope in open {
+            self.needs_env[scope.id] = true;
+        }
+    }
+
+    /// Declares a variable in the innermost scope at the slot the resolver gave it, returning
+    /// its local slot, or the variable of the environment for a global, a variable of a scope
+    /// kept in the environment, or a variable without a slot. Declaring a variable twice in the
+    /// same scope reuses its slot.
+    fn declare(&mut self, name: &str, slot: Option<Slot>) -> Var {
+        let index = match slot {
+            Some(Slot { depth: 0, index }) => index,
+            _ => {
+                self.need_env(false);
+                return self.env_var(name, slot);
+            }
+        };
+        let scope = match self.scopes.last() {
+            Some(scope) if !scope.env_vars => scope,
+            _ => return self.env_var(name, slot),
+        };
+        let local = scope.slots.get(index).copied().flatten();
+        let local = local.unwrap_or_else(|| self.new_slot(name));
+        if let Some(scope) = self.scopes.last_mut() {
+            if scope.slots.len() <= index {
+                scope.slots.resize(index + 1, None);
+            }
+            scope.slots[index] = Some(local);
+        }
+        Var::Slot(local)
+    }
+
+    /// Resolves a variable to the local slot declared at the slot the resolver gave it, or to a
+    /// variable of the environment.
+    fn resolve(&mut self, name: &str, slot: Option<Slot>) -> Var {
+        let local = slot.and_then(|slot| {
+            let scope = &self.scopes[self.scopes.len().checked_sub(slot.depth + 1)?];
+            scope.slots.get(slot.index).copied().flatten()
+        });
+        match local {
+            Some(local) => Var::Slot(local),
+            None => self.env_var(name, slot),
+        }
+    }
+
+    /// Adds a variable of the environment to the pool and returns it. The scopes of the chunk
+    /// that only exist in the frame are left out of the depth of its slot, and a local variable
+    /// of such a scope, which has no local slot yet, is looked up by name.
+    fn env_var(&mut self, name: &str, slot: Option<Slot>) -> Var {
+        let slot = slot.and_then(|slot| {
+            let inner = self.scopes.len().saturating_sub(slot.depth);
+            let depth = self.scopes[inner..]
+                .iter()
+                .filter(|scope| scope.env_scope)
+                .count();
+            match inner.checked_sub(1) {
+                Some(scope) if !self.scopes[scope].env_vars => None,
+                Some(_) => Some(Slot { depth, ..slot }),
+                None => Some(Slot {
+                    depth: depth + slot.depth - self.scopes.len(),
+                    ..slot
+                }),
+            }
+        });
+        self.chunk.env_vars.push(EnvVar {
+            name: name.to_string(),
+            slot,
+        });
+        Var::Env(self.chunk.env_vars.len() - 1)
+    }
+
+    /// Opens a scope both at compile time and at run time.
+    fn enter_scope(&mut self, line_info: &Option<LineInfo>) {
+        let env = self.open_scope();
+        let first_slot = self.chunk.slot_count();
+        self.emit(Instruction::EnterScope { first_slot, env }, line_info);
+    }
+
+    /// Compiles a function to its own chunk, adds it to the pool and returns its index.
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[AST],
+        return_type: &Type,
+        body: &AST,
+        line_info: &Option<LineInfo>,
+    ) -> usize {
+        self.need_env(true);
+        let (mut compiler, mut vars) = compile_function(params, body, line_info, Vec::new());
+        if compiler.needs_env.contains(&true) {
+            let env_scopes = std::mem::take(&mut compiler.needs_env);
+            (compiler, vars) = compile_function(params, body, line_info, env_scopes);
+        }
+        self.chunk.functions.push(Rc::new(CompiledFunction {
+            function: Function {
+                name: name.to_string(),
+                params: params.to_vec(),
+                return_type: return_type.clone(),
+                body: Rc::new(body.clone()),
+                line_info: line_info.clone(),
+                module: None,
+                closure: Closure::default(),
+                native: false,
+            },
+            chunk: Rc::new(compiler.chunk),
+            params: vars,
+        }));
+        self.chunk.functions.len() - 1
+    }
+
+    /// Compiles a chain of indexes and fields on a variable, such as `xs[i].hp`, into an
+    /// `Access` reading the element without copying the variable.
+    ///
+    /// # Returns
+    /// Whether the chain starts from a variable and was compiled.
+    fn var_access(&mut self, ast: &AST, line_info: &Option<LineInfo>) -> bool {
+        let Some((name, slot, accessors)) = place_of(ast) else {
+            return false;
+        };
+        let index_count = self.indexes(&accessors);
+        let var = self.resolve(name, slot);
+        self.chunk.assignments.push(AssignTarget {
+            name: name.clone(),
+            var,
+            accessors,
+            index_count,
+            op: AssignmentOp::Assign,
+        });
+        let index = self.chunk.assignments.len() - 1;
+        self.emit(Instruction::Access(index), line_info);
+        true
+    }
+
+    /// Compiles the indexes among the given accessors, in order.
+    ///
+    /// # Returns
+    /// The number of indexes compiled.
+    fn indexes(&mut self, accessors: &[Accessor]) -> usize {
+        let mut index_count = 0;
+        for accessor in accessors {
+            if let Accessor::Index(index, _) = accessor {
+                self.compile(index);
+                index_count += 1;
+            }
+        }
+        index_count
+    }
+
+    /// Compiles an AST node into instructions leaving its result on the stack.
+    fn compile(&mut self, ast: &AST) {
+        match ast {
+            AST::Statement(node, _) => self.compile(node),
+            AST::Omen(b, line_info) => self.constant(EvalResult::Omen(*b), line_info),
+            AST::Arcana(n, line_info) => self.constant(EvalResult::Arcana(*n), line_info),
+            AST::BigArcana(n, line_info) => {
+                self.chunk.constants.push(EvalResult::BigArcana(n.clone()));
+                let index = self.chunk.constants.len() - 1;
+                self.emit(Instruction::BigConstant(index), line_info);
+            }
+            AST::Aether(n, line_info) => self.constant(EvalResult::Aether(*n), line_info),
+            AST::Rune(s, _, line_info) => self.constant(EvalResult::Rune(s.clone()), line_info),
+            AST::Abyss(line_info) | AST::Comment(_, line_info) => {
+                self.constant(EvalResult::Abyss, line_info)
+            }
+            AST::OracleDontCareItem(line_info) => self.constant(EvalResult::Omen(true), line_info),
+            AST::Resume(label, line_info) => self.loop_jump(false, label, line_info),
+            AST::Eject(label, line_info) => self.loop_jump(true, label, line_info),
+            AST::Grimoire(elements, line_info) => {
+                for element in elements {
+                    self.compile(element);
+                }
+                self.emit(Instruction::MakeGrimoire(elements.len()), line_info);
+            }
+            AST::Codex(entries, line_info) => {
+                for (key, value) in entries {
+                    self.compile(key);
+                    self.compile(value);
+                }
+                self.emit(Instruction::MakeCodex(entries.len()), line_info);
+            }
+            AST::Index(_, _, line_info) | AST::Field(_, _, line_info)
+                if self.var_access(ast, line_info) => {}
+            AST::Index(target, index, line_info) => {
+                self.compile(target);
+                self.compile(index);
+                self.emit(Instruction::Index, line_info);
+            }
+            AST::Field(target, field, line_info) => {
+                self.compile(target);
+                let field = self.name(field);
+                self.emit(Instruction::Field(field), line_info);
+            }
+            AST::Call(callee, args, line_info) => {
+                self.compile(callee);
+                for arg in args {
+                    self.compile(arg);
+                }
+                self.emit(Instruction::CallValue(args.len()), line_info);
+            }
+            AST::Add(left, right, line_info) => self.binary(BinaryOp::Add, left, right, line_info),
+            AST::Sub(left, right, line_info) => self.binary(BinaryOp::Sub, left, right, line_info),
+            AST::Mul(left, right, line_info) => self.binary(BinaryOp::Mul, left, right, line_info),
+            AST::Div(left, right, line_info) => self.binary(BinaryOp::Div, left, right, line_info),
+            AST::Mod(left, right, line_info) => self.binary(BinaryOp::Mod, left, right, line_info),
+            AST::PowArcana(left, right, line_info) => {
+                self.binary(BinaryOp::PowArcana, left, right, line_info)
+            }
+            AST::PowAether(left, right, line_info) => {
+                self.binary(BinaryOp::PowAether, left, right, line_info)
+            }
+            AST::Equal(left, right, line_info) => {
+                self.binary(BinaryOp::Equal, left, right, line_info)
+            }
+            AST::NotEqual(left, right, line_info) => {
+                self.binary(BinaryOp::NotEqual, left, right, line_info)
+            }
+            AST::LessThan(left, right, line_info) => {
+                self.binary(BinaryOp::LessThan, left, right, line_info)
+            }
+            AST::LessThanOrEqual(left, right, line_info) => {
+                self.binary(BinaryOp::LessThanOrEqual, left, right, line_info)
+            }
+            AST::GreaterThan(left, right, line_info) => {
+                self.binary(BinaryOp::GreaterThan, left, right, line_info)
+            }
+            AST::GreaterThanOrEqual(left, right, line_info) => {
+                self.binary(BinaryOp::GreaterThanOrEqual, left, right, line_info)
+            }
+            AST::LogicalAnd(left, right, line_info) => {
+                self.binary(BinaryOp::LogicalAnd, left, right, line_info)
+            }
+            AST::LogicalOr(left, right, line_info) => {
+                self.binary(BinaryOp::LogicalOr, left, right, line_info)
+            }
+            AST::LogicalNot(expr, line_info) => {
+                self.compile(expr);
+                self.emit(Instruction::Not, line_info);
+            }
+            AST::VarAssign {
+                name,
+                value,
+                var_type,
+                is_morph,
+                slot,
+                line_info,
+            } => {
+                // The value is compiled first, so that it still sees an outer variable of the same name.
+                self.compile(value);
+                let var = self.declare(name, *slot);
+                self.chunk.definitions.push(Definition {
+                    var,
+                    var_type: var_type.clone(),
+                    is_morph: *is_morph,
+                });
+                let index = self.chunk.definitions.len() - 1;
+                self.emit(Instruction::Define(index), line_info);
+            }
+            AST::Assignment {
+                name,
+                accessors,
+                value,
+                op,
+                slot,
+                line_info,
+            } => {
+                self.compile(value);
+                let index_count = self.indexes(accessors);
+                let var = self.resolve(name, *slot);
+                self.chunk.assignments.push(AssignTarget {
+                    name: name.clone(),
+                    var,
+                    accessors: accessors.clone(),
+                    index_count,
+                    op: op.clone(),
+                });
+                let index = self.chunk.assignments.len() - 1;
+                self.emit(Instruction::Assign(index), line_info);
+            }
+            AST::Var(name, slot, line_info) => match self.resolve(name, *slot) {
+                Var::Slot(slot) => {
+                    self.emit(Instruction::LoadSlot(slot), line_info);
+                }
+                Var::Env(index) => {
+                    self.emit(Instruction::LoadEnv(index), line_info);
+                }
+            },
+            AST::Unveil(args, line_info) => {
+                for arg in args {
+                    self.compile(arg);
+                }
+                self.emit(Instruction::Unveil(args.len()), line_info);
+            }
+            AST::Interpolation(parts, line_info) => {
+                for part in parts {
+                    match part {
+                        RunePart::Text(text, line_info) => {
+                            self.constant(EvalResult::Rune(text.clone()), line_info)
+                        }
+                        RunePart::Expr(expr) => self.compile(expr),
+                    }
+                }
+                self.emit(Instruction::Interpolate(parts.len()), line_info);
+            }
+            AST::Trans(expr, target_type, line_info) => {
+                self.compile(expr);
+                self.chunk.types.push(target_type.clone());
+                let index = self.chunk.types.len() - 1;
+                self.emit(Instruction::Trans(index), line_info);
+            }
+            AST::Curse(message, line_info) => {
+                self.compile(message);
+                self.emit(Instruction::Curse, line_info);
+            }
+            AST::Attempt(expr, line_info) => {
+                let try_begin = self.emit(Instruction::TryBegin(0), line_info);
+                self.compile(expr);
+                self.emit(Instruction::TryEnd, line_info);
+                self.patch(try_begin);
+            }
+            AST::Reveal(expr, line_info) => {
+                self.compile(expr);
+                match self.in_function {
+                    true => {
+                        self.emit(Instruction::Reveal, line_info);
+                    }
+                    false => self.fail(misplaced_control("reveal", None), line_info),
+                }
+            }
+            AST::Block(statements, line_info) => self.block(statements, line_info),
+            AST::Oracle {
+                is_match,
+                conditionals,
+                branches,
+                line_info,
+            } => {
+                self.enter_scope(line_info);
+                let mut hidden_slots = Vec::new();
+                for (index, conditional) in conditionals.iter().enumerate() {
+                    self.compile(&conditional.expression);
+                    let hidden = self.new_slot("");
+                    let var = self.declare(&conditional.variable, Some(Slot::current(index)));
+                    self.emit(Instruction::BindConditional { var, hidden }, line_info);
+                    hidden_slots.push(hidden);
+                }
+
+                let mut ends = Vec::new();
+                for branch in branches {
+                    if let AST::OracleBranch {
+                        pattern,
+                        body,
+                        line_info,
+                    } = branch
+                    {
+                        let nexts = match is_match {
+                            true => self.match_patterns(pattern, &hidden_slots, line_info),
+                            false => self.boolean_patterns(pattern, line_info),
+                        };
+                        self.compile(body);
+                        self.emit(Instruction::ExitScope, line_info);
+                        ends.push(self.emit(Instruction::Jump(0), line_info));
+                        for next in nexts {
+                            self.patch(next);
+                        }
+                    }
+                }
+                self.emit(Instruction::ExitScope, line_info);
+                self.constant(EvalResult::Abyss, line_info);
+                self.scopes.pop();
+                for end in ends {
+                    self.patch(end);
+                }
+            }
+            AST::Orbit {
+                params,
+                body,
+                line_info,
+            } => {
+                if params.is_empty() {
+                    self.endless_orbit(body, line_info);
+                } else {
+                    self.orbit(params, body, line_info);
+                }
+            }
+            AST::Engrave {
+                name,
+                params,
+                return_type,
+                body,
+                line_info,
+            } => {
+                let index = self.function(name, params, return_type, body, line_info);
+                self.emit(Instruction::DefineFunction(index), line_info);
+            }
+            AST::EngraveExpr {
+                params,
+                return_type,
+                body,
+                line_info,
+            } => {
+                let index = self.function(ANONYMOUS_ENGRAVE, params, return_type, body, line_info);
+                self.emit(Instruction::MakeEngrave(index), line_info);
+            }
+            AST::FuncCall {
+                name,
+                args,
+                line_info,
+            } => self.call(name, args, line_info),
+            AST::Summon(prompt, var_type, line_info) => {
+                self.chunk.summons.push((prompt.clone(), var_type.clone()));
+                let index = self.chunk.summons.len() - 1;
+                self.emit(Instruction::Summon(index), line_info);
+            }
+            AST::Invoke {
+                path,
+                names,
+                line_info,
+            } => {
+                self.need_env(false);
+                self.chunk.invokes.push(InvokeTarget {
+                    path: path.clone(),
+                    names: names.clone(),
+                });
+                let index = self.chunk.invokes.len() - 1;
+                self.emit(Instruction::Invoke(index), line_info);
+            }
+            AST::Sigil {
+                name,
+                fields,
+                line_info,
+            } => {
+                self.need_env(false);
+                self.chunk.sigils.push(Sigil {
+                    name: name.clone(),
+                    fields: fields.clone(),
+                    line_info: line_info.clone(),
+                });
+                let index = self.chunk.sigils.len() - 1;
+                self.emit(Instruction::DefineSigil(index), line_info);
+            }
+            AST::SigilInstance {
+                name,
+                fields,
+                line_info,
+            } => {
+                for (_, value) in fields {
+                    self.compile(value);
+                }
+                let field_names = fields.iter().map(|(field, _)| field.clone()).collect();
+                self.chunk.sigil_instances.push((name.clone(), field_names));
+                let index = self.chunk.sigil_instances.len() - 1;
+                self.emit(Instruction::MakeSigil(index), line_info);
+            }
+            _ => self.fail(format!("Unsupported operation: {:?}", ast), &None),
+        }
+    }
+
+    /// Compiles a binary operation.
+    fn binary(&mut self, op: BinaryOp, left: &AST, right: &AST, line_info: &Option<LineInfo>) {
+        self.compile(left);
+        // A local variable or an arcana literal on the right is read by the operator itself.
+        match right {
+            AST::Var(name, slot, _) => {
+                if let Var::Slot(slot) = self.resolve(name, *slot) {
+                    self.emit(Instruction::BinarySlot(op, slot), line_info);
+                    return;
+                }
+            }
+            AST::Arcana(n, _) => {
+                self.chunk.constants.push(EvalResult::Arcana(*n));
+                let index = self.chunk.constants.len() - 1;
+                self.emit(Instruction::BinaryConstant(op, index), line_info);
+                return;
+            }
+            _ => {}
+        }
+        self.compile(right);
+        self.emit(Instruction::Binary(op), line_info);
+    }
+
+    /// Compiles a block, whose value is the value of its last statement.
+    fn block(&mut self, statements: &[AST], line_info: &Option<LineInfo>) {
+        if statements.is_empty() {
+            self.constant(EvalResult::Abyss, line_info);
+            return;
+        }
+        for (position, statement) in statements.iter().enumerate() {
+            if position > 0 {
+                self.drop(&statements[position - 1], line_info);
+            }
+            self.compile(statement);
+        }
+    }
+
+    /// Compiles the end of a statement whose value is not used: the value is discarded, and
+    /// raised if it is a curse that `attempt` did not catch.
+    fn drop(&mut self, statement: &AST, line_info: &Option<LineInfo>) {
+        match is_caught(statement) {
+            true => self.emit(Instruction::Pop, line_info),
+            false => self.emit(Instruction::Drop, line_info),
+        };
+    }
+
+    /// Compiles a `resume`, or an `eject` when `eject` is set, into a jump out of the innermost
+    /// enclosing loop or of the loop over the variable `label`.
+    fn loop_jump(&mut self, eject: bool, label: &Option<String>, line_info: &Option<LineInfo>) {
+        let keyword = if eject { "eject" } else { "resume" };
+        let target = self
+            .loops
+            .iter()
+            .rposition(|context| label.is_none() || context.label == *label);
+        let Some(index) = target else {
+            self.fail(misplaced_control(keyword, label.as_deref()), line_info);
+            return;
+        };
+        let depth = self.loops.len() - 1 - index;
+        let next = self.loops[index].next;
+        let jump = self.emit(
+            Instruction::LoopJump {
+                depth,
+                target: next,
+            },
+            line_info,
+        );
+        if eject {
+            self.loops[index].ejects.push(jump);
+        }
+    }
+
+    /// Compiles the end of the body of a loop: its value is dropped, the scope of the
+    /// iteration closed and the next iteration started. Then points the `eject`s out of the loop
+    /// to the next instruction, which ends it.
+    fn end_loop(&mut self, body: &AST, line_info: &Option<LineInfo>) {
+        self.drop(body, line_info);
+        self.emit(Instruction::ExitScope, line_info);
+        let context = self.loops.pop();
+        if let Some(context) = context {
+            self.emit(Instruction::Jump(context.next), line_info);
+            for eject in context.ejects {
+                self.patch(eject);
+            }
+        }
+    }
+
+    /// Compiles the patterns of a branch of an oracle matching the values of its conditionals.
+    /// Returns the jumps taken when the branch does not match.
+    fn match_patterns(
+        &mut self,
+        patterns: &[AST],
+        hidden_slots: &[usize],
+        line_info: &Option<LineInfo>,
+    ) -> Vec<usize> {
+        let mut nexts = Vec::new();
+        for (idx, pattern) in patterns.iter().enumerate() {
+            if let AST::OracleDontCareItem(_) = pattern {
+                continue;
+            }
+            let hidden = match hidden_slots.get(idx) {
+                Some(hidden) => *hidden,
+                None => {
+                    self.fail(
+                        "Oracle branch has more patterns than conditionals".to_string(),
+                        line_info,
+                    );
+                    continue;
+                }
+            };
+            match pattern {
+                AST::Curse(message, curse_line_info) => match message.as_ref() {
+                    AST::Var(name, slot, _) => {
+                        let var = self.declare(name, *slot);
+                        nexts.push(self.emit(
+                            Instruction::MatchCurseBind {
+                                hidden,
+                                var,
+                                next: 0,
+                            },
+                            curse_line_info,
+                        ));
+                    }
+                    _ => {
+                        nexts.push(self.emit(
+                            Instruction::JumpUnlessCurse { hidden, target: 0 },
+                            line_info,
+                        ));
+                        self.compile(pattern);
+                        nexts.push(self.emit(
+                            Instruction::MatchCurseMessage { hidden, next: 0 },
+                            line_info,
+                        ));
+                    }
+                },
+                _ => {
+                    nexts
+                        .push(self.emit(Instruction::JumpIfCurse { hidden, target: 0 }, line_info));
+                    self.compile(pattern);
+                    nexts.push(self.emit(Instruction::MatchValue { hidden, next: 0 }, line_info));
+                }
+            }
+        }
+        nexts
+    }
+
+    /// Compiles the patterns of a branch of an oracle made of conditions, which all have to be
+    /// `boon`. A condition that fails counts as not met. Returns the jumps taken when the branch
+    /// does not match.
+    fn boolean_patterns(&mut self, patterns: &[AST], line_info: &Option<LineInfo>) -> Vec<usize> {
+        let mut nexts = Vec::new();
+        for pattern in patterns {
+            let try_begin = self.emit(Instruction::TryBegin(0), line_info);
+            self.compile(pattern);
+            self.emit(Instruction::TryEnd, line_info);
+            self.patch(try_begin);
+            nexts.push(self.emit(Instruction::JumpUnlessBoon(0), line_info));
+        }
+        nexts
+    }
+
+    /// Compiles an `orbit` without parameters, which runs until it is ejected from.
+    fn endless_orbit(&mut self, body: &AST, line_info: &Option<LineInfo>) {
+        self.emit(Instruction::IterEndless, line_info);
+        self.loops.push(LoopContext {
+            label: None,
+            next: self.here(),
+            ejects: Vec::new(),
+        });
+        self.enter_scope(line_info);
+        self.compile(body);
+        self.scopes.pop();
+        self.end_loop(body, line_info);
+        self.emit(Instruction::IterEnd, line_info);
+        self.constant(EvalResult::Abyss, line_info);
+    }
+
+    /// Compiles an `orbit` over the first of `params`, whose body is an `orbit` over the
+    /// remaining parameters, or `body` once none remain.
+    fn orbit(&mut self, params: &[AST], body: &AST, line_info: &Option<LineInfo>) {
+        let (names, label) = match &params[0] {
+            AST::OrbitParam {
+                name,
+                start,
+                end,
+                op,
+                ..
+            } => {
+                self.compile(start);
+                self.compile(end);
+                let index = self.name(name);
+                let inclusive = op == "..=";
+                self.emit(
+                    Instruction::IterRange {
+                        inclusive,
+                        name: index,
+                    },
+                    line_info,
+                );
+                (vec![name.clone()], name)
+            }
+            AST::OrbitCollection {
+                name,
+                value_name,
+                collection,
+                ..
+            } => {
+                self.compile(collection);
+                let index = self.name(name);
+                let pair = value_name.is_some();
+                self.emit(Instruction::IterCollection { pair, name: index }, line_info);
+                let mut names = vec![name.clone()];
+                names.extend(value_name.clone());
+                (names, name)
+            }
+            _ => {
+                self.fail("Expected OrbitParam in Orbit".to_string(), line_info);
+                return;
+            }
+        };
+
+        self.loops.push(LoopContext {
+            label: Some(label.clone()),
+            next: self.here(),
+            ejects: Vec::new(),
+        });
+        let env = self.open_scope();
+        let first_slot = self.chunk.slot_count();
+        let vars = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| self.declare(name, Some(Slot::current(index))))
+            .collect();
+        self.chunk.iterations.push(IterBinding {
+            first_slot,
+            env,
+            vars,
+            done: 0,
+        });
+        let iteration = self.chunk.iterations.len() - 1;
+        self.emit(Instruction::IterNext(iteration), line_info);
+        match params.len() {
+            1 => self.compile(body),
+            _ => self.orbit(&params[1..], body, line_info),
+        }
+        self.scopes.pop();
+        self.end_loop(body, line_info);
+
+        self.chunk.iterations[iteration].done = self.here();
+        self.emit(Instruction::IterEnd, line_info);
+        self.constant(EvalResult::Abyss, line_info);
+    }
+
+    /// Compiles a function call. A call to a builtin is compiled both as a builtin and as a
+    /// call, since a function defined with the same name takes precedence at run time.
+    fn call(&mut self, name: &str, args: &[AST], line_info: &Option<LineInfo>) {
+        let name_index = self.name(name);
+        let arity = match builtin_arity(name) {
+            Some(arity) => arity,
+            None => {
+                for arg in args {
+                    self.compile(arg);
+                }
+                self.emit(
+                    Instruction::Call {
+                        name: name_index,
+                        argc: args.len(),
+                    },
+                    line_info,
+                );
+                return;
+            }
+        };
+
+        let to_function = self.emit(
+            Instruction::JumpIfFunction {
+                name: name_index,
+                target: 0,
+            },
+            line_info,
+        );
+        if args.len() != arity {
+            self.fail(format!("{} expects {} argument(s)", name, arity), line_info);
+        } else if name == "push" || name == "pop" {
+            match place_of(&args[0]) {
+                Some((var_name, slot, accessors)) => {
+                    let index_count = self.indexes(&accessors);
+                    for arg in &args[1..] {
+                        self.compile(arg);
+                    }
+                    let var = self.resolve(var_name, slot);
+                    self.chunk.assignments.push(AssignTarget {
+                        name: var_name.clone(),
+                        var,
+                        accessors,
+                        index_count,
+                        op: AssignmentOp::Assign,
+                    });
+                    let target = self.chunk.assignments.len() - 1;
+                    let push = name == "push";
+                    self.emit(Instruction::PushPop { target, push }, line_info);
+                }
+                None => self.fail(format!("{} requires a grimoire variable", name), line_info),
+            }
+        } else {
+            for arg in args {
+                self.compile(arg);
+            }
+            self.emit(
+                Instruction::Builtin {
+                    name: name_index,
+                    argc: args.len(),
+                },
+                line_info,
+            );
+        }
+        let end = self.emit(Instruction::Jump(0), line_info);
+
+        self.patch(to_function);
+        for arg in args {
+            self.compile(arg);
+        }
+        self.emit(
+            Instruction::Call {
+                name: name_index,
+                argc: args.len(),
+            },
+            line_info,
+        );
+        self.patch(end);
+    }
+}
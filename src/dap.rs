@@ -0,0 +1,705 @@
+use crate::ast::{LineInfo, AST};
+use crate::debugger::{evaluate_expression, EvalHook, StepMode};
+use crate::env::{Environment, Function, VarInfo};
+use crate::eval::{
+    evaluate, format_element, type_of_result, value_to_result, write_error_with_source,
+    write_traceback, EvalError, EvalResult,
+};
+use crate::format::format_type;
+use crate::interpreter::AbyssError;
+use crate::io::IoContext;
+use crate::lsp::{read_message, write_message};
+use crate::parser::{build_ast, parse, Rule};
+use crate::resolver::resolve_program;
+use crate::typeck::scrutinize_in;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, BufRead, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The only thread reported to the client.
+const THREAD_ID: u64 = 1;
+
+/// The connection to a Debug Adapter Protocol client.
+struct Connection {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    seq: u64, // The sequence number of the last message sent
+}
+
+impl Connection {
+    /// Reads the next message from the client, or `None` once the client closed the input.
+    fn receive(&mut self) -> io::Result<Option<Value>> {
+        read_message(&mut self.input)
+    }
+
+    /// Sends a message to the client, numbering it.
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.output, &message)
+    }
+}
+
+/// A stream that forwards what a script writes to the client as `output` events, one event
+/// per line.
+struct OutputEvents {
+    connection: Rc<RefCell<Connection>>,
+    category: &'static str, // `stdout` for `unveil`, `stderr` for errors
+    pending: Vec<u8>,       // The start of a line not sent yet
+}
+
+impl OutputEvents {
+    /// Creates a stream sending output events of a category.
+    fn new(connection: &Rc<RefCell<Connection>>, category: &'static str) -> Self {
+        OutputEvents {
+            connection: Rc::clone(connection),
+            category,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Write for OutputEvents {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if buf.contains(&b'\n') {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let output = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        let body = json!({ "category": self.category, "output": output });
+        self.connection.borrow_mut().send(event("output", body))
+    }
+}
+
+/// What a request asks the session to do besides answering it.
+enum Action {
+    None,
+    Resume(StepMode),
+    ConfigurationDone,
+    Terminate,
+    Disconnect,
+}
+
+/// The script or a function call in progress.
+struct Frame {
+    name: String,
+    line_info: Option<LineInfo>, // The statement being evaluated in the frame
+    path: Option<PathBuf>,       // The file the statement belongs to
+    scope_base: usize,           // The number of local scopes that belong to the frames below
+}
+
+/// A set of variables the client can expand, referred to by its position plus one.
+enum Container {
+    Locals(usize), // The local variables of a frame, by position from the innermost frame
+    Globals,
+    Value(EvalResult), // The elements of a grimoire or codex, or the fields of a sigil
+}
+
+/// A debugging session: the breakpoints set by the client and the state of the script.
+/// While the script is paused, the session answers the client's requests from within
+/// `EvalHook::on_statement`.
+struct Session {
+    connection: Rc<RefCell<Connection>>,
+    breakpoints: HashMap<PathBuf, BTreeSet<usize>>, // The lines to pause at, by canonical path
+    canonical_paths: HashMap<PathBuf, PathBuf>,
+    mode: StepMode,
+    frames: Vec<Frame>,
+    containers: Vec<Container>, // The variables the client can expand while the script is paused
+    lines_start_at_1: bool,
+    columns_start_at_1: bool,
+    stop_on_entry: bool,
+    started: bool,      // Whether the first statement of the script was reached
+    disconnected: bool, // Whether the client ended the session
+}
+
+/// Runs a script under the control of a Debug Adapter Protocol client, reading requests from
+/// `input` and writing responses and events to `output`.
+///
+/// The script starts once the client sends `configurationDone`, and everything it writes is
+/// sent to the client as `output` events. Requests sent while the script runs are answered
+/// when it pauses or ends.
+///
+/// # Arguments
+/// * `script` - The source code of the script.
+/// * `env` - The environment to run the script in, which names the script's path.
+/// * `input` - The stream the client writes requests to.
+/// * `output` - The stream the client reads responses and events from.
+///
+/// # Returns
+/// An error if a message could not be read or written.
+pub fn serve<R, W>(script: &str, mut env: Environment, input: R, output: W) -> io::Result<()>
+where
+    R: BufRead + 'static,
+    W: Write + 'static,
+{
+    let connection = Rc::new(RefCell::new(Connection {
+        input: Box::new(input),
+        output: Box::new(output),
+        seq: 0,
+    }));
+    env.set_io(IoContext::new(
+        OutputEvents::new(&connection, "stdout"),
+        Cursor::new(Vec::new()),
+        OutputEvents::new(&connection, "stderr"),
+    ));
+    let session = Rc::new(RefCell::new(Session::new(Rc::clone(&connection))));
+
+    loop {
+        let Some(request) = connection.borrow_mut().receive()? else {
+            return Ok(());
+        };
+        match session.borrow_mut().handle(&request, None)? {
+            Action::ConfigurationDone => break,
+            Action::Terminate | Action::Disconnect => return Ok(()),
+            _ => {}
+        }
+    }
+
+    env.set_hook(Some(session.clone()));
+    let exit_code = match load_program(script, &env) {
+        Ok(program) => match program
+            .iter()
+            .try_for_each(|ast| evaluate(ast, &mut env).map(|_| ()))
+        {
+            Ok(()) => 0,
+            Err(e) if e.is_halt() => 0,
+            Err(e) => {
+                let _ = write_error_with_source(
+                    &mut *env.io().error(),
+                    script,
+                    e.line_info(),
+                    &e.to_string(),
+                );
+                let _ = write_traceback(&mut *env.io().error(), script, &env.take_trace());
+                1
+            }
+        },
+        Err(errors) => {
+            for (line_info, message) in errors {
+                let _ =
+                    write_error_with_source(&mut *env.io().error(), script, line_info, &message);
+            }
+            1
+        }
+    };
+    env.set_hook(None);
+    flush_output(&env)?;
+
+    let mut session = session.borrow_mut();
+    if session.disconnected {
+        return Ok(());
+    }
+    session.frames.clear();
+    session.send(event("exited", json!({ "exitCode": exit_code })))?;
+    session.send(event("terminated", json!({})))?;
+    loop {
+        let Some(request) = connection.borrow_mut().receive()? else {
+            return Ok(());
+        };
+        if let Action::Disconnect = session.handle(&request, None)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Parses, type checks and resolves a script.
+///
+/// # Returns
+/// The statements of the script, or the errors that prevent it from running.
+fn load_program(
+    script: &str,
+    env: &Environment,
+) -> Result<Vec<AST>, Vec<(Option<LineInfo>, String)>> {
+    let parse_error = |e| {
+        let e = AbyssError::from(e);
+        vec![(e.line_info(), e.to_string())]
+    };
+    let mut program = Vec::new();
+    for pair in parse(script).map_err(parse_error)?.into_inner() {
+        if pair.as_rule() != Rule::EOI {
+            program.push(build_ast(pair).map_err(parse_error)?);
+        }
+    }
+    scrutinize_in(&program, env).map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|error| (error.line_info.clone(), error.to_string()))
+            .collect::<Vec<_>>()
+    })?;
+    resolve_program(&program, env).map_err(|e| vec![(e.line_info(), e.to_string())])
+}
+
+/// Sends what the script wrote to the client, including the start of an unfinished line.
+fn flush_output(env: &Environment) -> io::Result<()> {
+    env.io().output().flush()?;
+    env.io().error().flush()
+}
+
+/// Builds an event.
+fn event(name: &str, body: Value) -> Value {
+    json!({ "type": "event", "event": name, "body": body })
+}
+
+/// Builds the response to a request, which failed if `body` is an error message.
+fn response(request: &Value, body: Result<Value, String>) -> Value {
+    let mut response = json!({
+        "type": "response",
+        "request_seq": request["seq"],
+        "command": request["command"],
+        "success": body.is_ok(),
+    });
+    match body {
+        Ok(body) => response["body"] = body,
+        Err(message) => response["message"] = json!(message),
+    }
+    response
+}
+
+/// Returns the canonical form of a path, or the path itself if it does not exist.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Returns the elements of a value that the client can expand, with their names.
+fn children(result: &EvalResult) -> Vec<(String, EvalResult)> {
+    match result {
+        EvalResult::Grimoire(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (index.to_string(), item.clone()))
+            .collect(),
+        EvalResult::Codex(entries) => entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect(),
+        EvalResult::Sigil(_, fields) => fields.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the lines the statements of a script start on, which are the lines breakpoints can
+/// pause at, or `None` if the script cannot be read or parsed.
+fn statement_lines(path: &Path) -> Option<BTreeSet<usize>> {
+    let source = fs::read_to_string(path).ok()?;
+    let pairs = parse(&source).ok()?.into_inner().flatten();
+    Some(
+        pairs
+            .filter(|pair| pair.as_rule() == Rule::statement)
+            .map(|pair| LineInfo::from_span(&pair.as_span()).line)
+            .collect(),
+    )
+}
+
+impl Session {
+    /// Creates a session that has not received any request yet.
+    fn new(connection: Rc<RefCell<Connection>>) -> Self {
+        Session {
+            connection,
+            breakpoints: HashMap::new(),
+            canonical_paths: HashMap::new(),
+            mode: StepMode::Continue,
+            frames: vec![Frame {
+                name: "<script>".to_string(),
+                line_info: None,
+                path: None,
+                scope_base: 0,
+            }],
+            containers: Vec::new(),
+            lines_start_at_1: true,
+            columns_start_at_1: true,
+            stop_on_entry: false,
+            started: false,
+            disconnected: false,
+        }
+    }
+
+    /// Sends a message to the client.
+    fn send(&self, message: Value) -> io::Result<()> {
+        self.connection.borrow_mut().send(message)
+    }
+
+    /// Returns the canonical path of the file the statements evaluated in an environment
+    /// belong to, caching it.
+    fn path_of(&mut self, env: &Environment) -> Option<PathBuf> {
+        let path = env.script_path()?;
+        let canonical_path = self
+            .canonical_paths
+            .entry(path.to_path_buf())
+            .or_insert_with(|| canonical(path));
+        Some(canonical_path.clone())
+    }
+
+    /// Converts a one-based line from the client's numbering.
+    fn line_from_client(&self, line: u64) -> usize {
+        line as usize + usize::from(!self.lines_start_at_1)
+    }
+
+    /// Converts the line information of a node to a line and column in the client's numbering.
+    fn position_for_client(&self, line_info: &Option<LineInfo>) -> (usize, usize) {
+        let (line, column) = line_info
+            .as_ref()
+            .map_or((1, 1), |info| (info.line, info.column));
+        (
+            line - usize::from(!self.lines_start_at_1),
+            column - usize::from(!self.columns_start_at_1),
+        )
+    }
+
+    /// Registers a set of variables the client can expand.
+    ///
+    /// # Returns
+    /// The reference the client expands the variables with.
+    fn register(&mut self, container: Container) -> usize {
+        self.containers.push(container);
+        self.containers.len()
+    }
+
+    /// Describes a variable or an element of a value as a DAP variable.
+    fn variable(&mut self, name: &str, result: EvalResult, declared: Option<&VarInfo>) -> Value {
+        let var_type = match declared {
+            Some(var) => Some(var.var_type.clone()),
+            None => type_of_result(&result),
+        };
+        let value = match result {
+            EvalResult::Abyss => "abyss".to_string(),
+            _ => format_element(&result),
+        };
+        let reference = match children(&result).is_empty() {
+            true => 0,
+            false => self.register(Container::Value(result)),
+        };
+        json!({
+            "name": name,
+            "value": value,
+            "type": var_type.map(|var_type| format_type(&var_type)),
+            "variablesReference": reference,
+        })
+    }
+
+    /// Lists the variables of a container.
+    fn variables(&mut self, reference: usize, env: &Environment) -> Result<Value, String> {
+        let container = match self.containers.get(reference.wrapping_sub(1)) {
+            Some(Container::Locals(frame)) => Container::Locals(*frame),
+            Some(Container::Globals) => Container::Globals,
+            Some(Container::Value(result)) => Container::Value(result.clone()),
+            None => return Err(format!("Unknown variables reference {}", reference)),
+        };
+        let mut variables = Vec::new();
+        match container {
+            Container::Locals(frame) => {
+                // The local scopes of a frame start where the frame was entered and end where
+                // the frame it called was entered. The innermost declaration of a name wins.
+                let scopes = env.local_vars();
+                let index = self.frames.len().saturating_sub(frame + 1);
+                let start = self.frames.get(index).map_or(0, |f| f.scope_base);
+                let end = self
+                    .frames
+                    .get(index + 1)
+                    .map_or(scopes.len(), |f| f.scope_base);
+                let mut seen = Vec::new();
+                for scope in scopes[start.min(scopes.len())..end.min(scopes.len())]
+                    .iter()
+                    .rev()
+                {
+                    for (name, var) in scope {
+                        if !seen.contains(name) {
+                            seen.push(name.clone());
+                            variables.push(self.variable(
+                                name,
+                                value_to_result(&var.value),
+                                Some(var),
+                            ));
+                        }
+                    }
+                }
+            }
+            Container::Globals => {
+                let mut globals: Vec<_> = env.global_vars().iter().collect();
+                globals.sort_by_key(|(name, _)| *name);
+                for (name, var) in globals {
+                    variables.push(self.variable(name, value_to_result(&var.value), Some(var)));
+                }
+            }
+            Container::Value(result) => {
+                for (name, child) in children(&result) {
+                    variables.push(self.variable(&name, child, None));
+                }
+            }
+        }
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// Describes the frames in progress, from the innermost.
+    fn stack_trace(&self) -> Value {
+        let frames: Vec<Value> = self
+            .frames
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(id, frame)| {
+                let (line, column) = self.position_for_client(&frame.line_info);
+                let source = frame.path.as_ref().map(|path| {
+                    json!({
+                        "name": path.file_name().map(|name| name.to_string_lossy()),
+                        "path": path,
+                    })
+                });
+                json!({
+                    "id": id,
+                    "name": frame.name,
+                    "line": line,
+                    "column": column,
+                    "source": source,
+                })
+            })
+            .collect();
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+
+    /// Answers a request.
+    ///
+    /// # Arguments
+    /// * `request` - The request.
+    /// * `env` - The environment of the paused script, or `None` if the script is not paused.
+    ///
+    /// # Returns
+    /// What the request asks the session to do besides answering it.
+    fn handle(&mut self, request: &Value, env: Option<&mut Environment>) -> io::Result<Action> {
+        let arguments = &request["arguments"];
+        let paused = env.is_some();
+        let mut action = Action::None;
+        let body = match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                self.lines_start_at_1 = arguments["linesStartAt1"].as_bool().unwrap_or(true);
+                self.columns_start_at_1 = arguments["columnsStartAt1"].as_bool().unwrap_or(true);
+                Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsEvaluateForHovers": true,
+                    "supportsTerminateRequest": true,
+                }))
+            }
+            "launch" | "attach" => {
+                self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+                Ok(json!({}))
+            }
+            "setBreakpoints" => {
+                let path = canonical(Path::new(
+                    arguments["source"]["path"].as_str().unwrap_or_default(),
+                ));
+                // A breakpoint on a line without a statement moves to the next statement.
+                let statement_lines = statement_lines(&path);
+                let mut lines = BTreeSet::new();
+                let mut breakpoints = Vec::new();
+                for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+                    let requested = breakpoint["line"].as_u64().unwrap_or(0);
+                    let requested = self.line_from_client(requested);
+                    let message = match &statement_lines {
+                        Some(statement_lines) => match statement_lines.range(requested..).next() {
+                            Some(&line) => {
+                                lines.insert(line);
+                                let line = line - usize::from(!self.lines_start_at_1);
+                                breakpoints.push(json!({ "verified": true, "line": line }));
+                                continue;
+                            }
+                            None => "No statement at or after this line",
+                        },
+                        None => "The script cannot be read or parsed",
+                    };
+                    breakpoints.push(json!({
+                        "verified": false,
+                        "line": breakpoint["line"],
+                        "message": message,
+                    }));
+                }
+                self.breakpoints.insert(path, lines);
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "configurationDone" => {
+                action = Action::ConfigurationDone;
+                Ok(json!({}))
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => {
+                let frame = arguments["frameId"].as_u64().unwrap_or(0) as usize;
+                let locals = self.register(Container::Locals(frame));
+                let globals = self.register(Container::Globals);
+                Ok(json!({
+                    "scopes": [
+                        { "name": "Locals", "variablesReference": locals, "expensive": false },
+                        { "name": "Globals", "variablesReference": globals, "expensive": false },
+                    ],
+                }))
+            }
+            "variables" => match &env {
+                Some(env) => {
+                    let reference = arguments["variablesReference"].as_u64().unwrap_or(0);
+                    self.variables(reference as usize, env)
+                }
+                None => Err("The script is not paused".to_string()),
+            },
+            "evaluate" => match env {
+                Some(env) => {
+                    let expression = arguments["expression"].as_str().unwrap_or_default();
+                    evaluate_expression(expression, env).map(|result| {
+                        let variable = self.variable("", result, None);
+                        json!({
+                            "result": variable["value"],
+                            "type": variable["type"],
+                            "variablesReference": variable["variablesReference"],
+                        })
+                    })
+                }
+                None => Err("The script is not paused".to_string()),
+            },
+            "continue" => {
+                action = Action::Resume(StepMode::Continue);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                action = Action::Resume(StepMode::StepOver(self.frames.len()));
+                Ok(json!({}))
+            }
+            "stepIn" => {
+                action = Action::Resume(StepMode::StepInto);
+                Ok(json!({}))
+            }
+            "stepOut" => {
+                action = Action::Resume(StepMode::StepOut(self.frames.len()));
+                Ok(json!({}))
+            }
+            "pause" => Ok(json!({})),
+            "terminate" => {
+                action = Action::Terminate;
+                Ok(json!({}))
+            }
+            "disconnect" => {
+                action = Action::Disconnect;
+                Ok(json!({}))
+            }
+            command => Err(format!("Unsupported request: {}", command)),
+        };
+        self.send(response(request, body))?;
+        if let ("initialize", Action::None) =
+            (request["command"].as_str().unwrap_or_default(), &action)
+        {
+            self.send(event("initialized", json!({})))?;
+        }
+        if !paused {
+            if let Action::Resume(_) = action {
+                action = Action::None;
+            }
+        }
+        Ok(action)
+    }
+
+    /// Tells the client the script paused, then answers its requests until one resumes or
+    /// stops the script.
+    fn pause(&mut self, reason: &str, env: &mut Environment) -> Result<(), EvalError> {
+        let io_error = |e: io::Error| EvalError::IoError(e.to_string(), None);
+        self.containers.clear();
+        flush_output(env).map_err(io_error)?;
+        self.send(event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        ))
+        .map_err(io_error)?;
+        loop {
+            let request = self.connection.borrow_mut().receive().map_err(io_error)?;
+            let Some(request) = request else {
+                self.disconnected = true;
+                return Err(EvalError::Halted(
+                    "the debugger disconnected".to_string(),
+                    None,
+                ));
+            };
+            match self.handle(&request, Some(env)).map_err(io_error)? {
+                Action::Resume(mode) => {
+                    self.mode = mode;
+                    self.send(event(
+                        "continued",
+                        json!({ "threadId": THREAD_ID, "allThreadsContinued": true }),
+                    ))
+                    .map_err(io_error)?;
+                    return Ok(());
+                }
+                Action::Terminate => {
+                    return Err(EvalError::Halted(
+                        "terminated by the debugger".to_string(),
+                        None,
+                    ));
+                }
+                Action::Disconnect => {
+                    self.disconnected = true;
+                    return Err(EvalError::Halted(
+                        "the debugger disconnected".to_string(),
+                        None,
+                    ));
+                }
+                Action::None | Action::ConfigurationDone => {}
+            }
+        }
+    }
+}
+
+impl EvalHook for Session {
+    fn on_statement(&mut self, statement: &AST, env: &mut Environment) -> Result<(), EvalError> {
+        let AST::Statement(_, line_info) = statement else {
+            return Ok(());
+        };
+        let path = self.path_of(env);
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line_info = line_info.clone();
+            frame.path = path.clone();
+        }
+
+        let line = line_info.as_ref().map_or(0, |info| info.line);
+        let at_breakpoint = path
+            .and_then(|path| self.breakpoints.get(&path))
+            .is_some_and(|lines| lines.contains(&line));
+        let reason = match std::mem::replace(&mut self.started, true) {
+            false if self.stop_on_entry => "entry",
+            _ if self.mode.pauses_at(self.frames.len()) => "step",
+            _ if at_breakpoint => "breakpoint",
+            _ => return Ok(()),
+        };
+        self.mode = StepMode::Continue;
+        self.pause(reason, env)
+    }
+
+    fn on_call(
+        &mut self,
+        function: &Function,
+        line_info: &Option<LineInfo>,
+        env: &mut Environment,
+    ) -> Result<(), EvalError> {
+        // The scope of the function was pushed before it is entered.
+        let scope_base = env.scope_depth().saturating_sub(2);
+        let path = self.path_of(env);
+        self.frames.push(Frame {
+            name: function.name.clone(),
+            line_info: line_info.clone(),
+            path,
+            scope_base,
+        });
+        Ok(())
+    }
+
+    fn on_return(&mut self, _function: &Function, _env: &mut Environment) {
+        if self.frames.len() > 1 {
+            self.frames.pop();
+        }
+    }
+}
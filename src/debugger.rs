@@ -0,0 +1,420 @@
+use crate::ast::{LineInfo, AST};
+use crate::env::{Environment, Function, VarInfo};
+use crate::eval::{
+    evaluate, format_element, result_to_value, value_to_result, EvalError, EvalResult,
+};
+use crate::format::{format_ast, format_type};
+use crate::parser::{build_ast, parse, Rule};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Receives the progress of an evaluation by `eval::evaluate`, so that a front-end such as a
+/// debugger can follow it, inspect the environment and pause it.
+/// A hook is set on an environment with `Environment::set_hook`.
+pub trait EvalHook {
+    /// Called before a statement is evaluated.
+    ///
+    /// # Arguments
+    /// * `statement` - The `Statement` node about to be evaluated.
+    /// * `env` - The environment the statement is evaluated in.
+    ///
+    /// # Returns
+    /// An error to stop the evaluation, such as `EvalError::Halted`.
+    fn on_statement(&mut self, statement: &AST, env: &mut Environment) -> Result<(), EvalError>;
+
+    /// Called when a function defined by `engrave` is entered, after its arguments are bound.
+    ///
+    /// # Arguments
+    /// * `function` - The function being called.
+    /// * `line_info` - The line information of the call.
+    /// * `env` - The environment the body of the function is evaluated in.
+    ///
+    /// # Returns
+    /// An error to stop the evaluation.
+    fn on_call(
+        &mut self,
+        _function: &Function,
+        _line_info: &Option<LineInfo>,
+        _env: &mut Environment,
+    ) -> Result<(), EvalError> {
+        Ok(())
+    }
+
+    /// Called when a function defined by `engrave` is left, whether or not its body succeeded.
+    ///
+    /// # Arguments
+    /// * `function` - The function being left.
+    /// * `env` - The environment the body of the function was evaluated in.
+    fn on_return(&mut self, _function: &Function, _env: &mut Environment) {}
+}
+
+/// How far a debugger lets the script run before pausing again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum StepMode {
+    Continue,        // Until a breakpoint
+    StepInto,        // Until the next statement
+    StepOver(usize), // Until the next statement in a frame at most this deep
+    StepOut(usize),  // Until the next statement in a frame less deep than this
+}
+
+impl StepMode {
+    /// Checks whether a debugger in this mode pauses before a statement evaluated in a frame
+    /// `depth` frames deep, breakpoints aside.
+    pub(crate) fn pauses_at(self, depth: usize) -> bool {
+        match self {
+            StepMode::Continue => false,
+            StepMode::StepInto => true,
+            StepMode::StepOver(max_depth) => depth <= max_depth,
+            StepMode::StepOut(max_depth) => depth < max_depth,
+        }
+    }
+}
+
+/// The script or a function call in progress, as shown by the `backtrace` command.
+#[derive(Debug, Clone)]
+struct Frame {
+    name: String,
+    line: usize,          // The line of the statement being evaluated in the frame
+    file: Option<String>, // The module the statement belongs to, if it is not the script
+}
+
+/// An interactive debugger for the tree-walking evaluator.
+///
+/// The debugger pauses before the first statement, at breakpoints and after each step, and
+/// then reads commands from the input stream of the environment until one resumes the script.
+/// Everything it prints is written to the output stream of the environment.
+#[derive(Debug)]
+pub struct Debugger {
+    breakpoints: BTreeSet<usize>,
+    mode: StepMode,
+    frames: Vec<Frame>,
+    script_path: Option<PathBuf>,
+}
+
+/// The commands understood by the debugger.
+const HELP: &str = "\
+Commands:
+  break <line>         Pause before the statements on a line (b)
+  delete <line>        Remove the breakpoint on a line (d)
+  continue             Run until the next breakpoint (c)
+  step                 Run to the next statement, entering function calls (s)
+  next                 Run to the next statement, stepping over function calls (n)
+  finish               Run until the current function returns (f)
+  print <expression>   Evaluate an expression and print its value (p)
+  set <name> = <expression>
+                       Change the value of a variable
+  vars                 List the variables in scope (v)
+  backtrace            Show the calls of engrave functions in progress (bt)
+  quit                 Stop the script (q)
+";
+
+impl Debugger {
+    /// Creates a debugger that pauses before the first statement of a script.
+    ///
+    /// # Arguments
+    /// * `script_path` - The path of the script being debugged. Breakpoints apply to the
+    ///   statements of this script, not to those of the modules it invokes.
+    pub fn new(script_path: Option<PathBuf>) -> Self {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            mode: StepMode::StepInto,
+            frames: vec![Frame {
+                name: "<script>".to_string(),
+                line: 0,
+                file: None,
+            }],
+            script_path,
+        }
+    }
+
+    /// Sets a breakpoint, so that the debugger pauses before the statements on a line.
+    pub fn add_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    /// Removes the breakpoint on a line, returning true if there was one.
+    pub fn remove_breakpoint(&mut self, line: usize) -> bool {
+        self.breakpoints.remove(&line)
+    }
+
+    /// Returns the module a statement evaluated in an environment belongs to, or `None` if it
+    /// belongs to the script being debugged.
+    fn module_of(&self, env: &Environment) -> Option<String> {
+        match env.script_path() {
+            path if path == self.script_path.as_deref() => None,
+            Some(path) => Some(path.display().to_string()),
+            None => None,
+        }
+    }
+
+    /// Checks whether the debugger pauses before a statement on a line.
+    fn should_pause(&self, line: usize, in_script: bool) -> bool {
+        self.mode.pauses_at(self.frames.len()) || in_script && self.breakpoints.contains(&line)
+    }
+
+    /// Reads and runs commands until one of them resumes the script.
+    ///
+    /// # Returns
+    /// An error if the script is stopped.
+    fn prompt(&mut self, env: &mut Environment) -> Result<(), EvalError> {
+        loop {
+            write_output(env, "(debug) ")?;
+            let mut line = String::new();
+            let read = env
+                .io()
+                .input()
+                .read_line(&mut line)
+                .map_err(|e| EvalError::IoError(e.to_string(), None))?;
+            if read == 0 {
+                // Without more commands, the script runs to its end.
+                write_output(env, "\n")?;
+                self.breakpoints.clear();
+                self.mode = StepMode::Continue;
+                return Ok(());
+            }
+
+            let line = line.trim();
+            let (command, argument) = line
+                .split_once(char::is_whitespace)
+                .map_or((line, ""), |(command, argument)| (command, argument.trim()));
+            let depth = self.frames.len();
+            let mode = match command {
+                "c" | "continue" => StepMode::Continue,
+                "s" | "step" => StepMode::StepInto,
+                "n" | "next" => StepMode::StepOver(depth),
+                "f" | "finish" => StepMode::StepOut(depth),
+                "q" | "quit" => {
+                    return Err(EvalError::Halted(
+                        "stopped by the debugger".to_string(),
+                        None,
+                    ))
+                }
+                _ => {
+                    let message = self.run_command(command, argument, env);
+                    write_output(env, &format!("{}\n", message))?;
+                    continue;
+                }
+            };
+            self.mode = mode;
+            return Ok(());
+        }
+    }
+
+    /// Runs a command that does not resume the script.
+    ///
+    /// # Returns
+    /// The text printed in answer to the command.
+    fn run_command(&mut self, command: &str, argument: &str, env: &mut Environment) -> String {
+        match command {
+            "" => String::new(),
+            "h" | "help" => HELP.trim_end().to_string(),
+            "b" | "break" => match argument.parse::<usize>() {
+                Ok(line) => {
+                    self.add_breakpoint(line);
+                    format!("Breakpoint set at line {}", line)
+                }
+                Err(_) if argument.is_empty() && self.breakpoints.is_empty() => {
+                    "No breakpoints".to_string()
+                }
+                Err(_) if argument.is_empty() => {
+                    let lines: Vec<String> = self
+                        .breakpoints
+                        .iter()
+                        .map(|line| line.to_string())
+                        .collect();
+                    format!("Breakpoints at lines {}", lines.join(", "))
+                }
+                Err(_) => format!("Invalid line number: {}", argument),
+            },
+            "d" | "delete" => match argument.parse::<usize>() {
+                Ok(line) if self.remove_breakpoint(line) => {
+                    format!("Breakpoint removed from line {}", line)
+                }
+                Ok(line) => format!("No breakpoint at line {}", line),
+                Err(_) => format!("Invalid line number: {}", argument),
+            },
+            "p" | "print" => match evaluate_expression(argument, env) {
+                Ok(result) => format_element(&result),
+                Err(message) => message,
+            },
+            "set" => self.set_variable(argument, env),
+            "v" | "vars" => describe_vars(env),
+            "bt" | "backtrace" => self
+                .frames
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(index, frame)| {
+                    format!(
+                        "#{} {} at {}",
+                        index,
+                        frame.name,
+                        describe_line(frame.line, &frame.file)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => format!(
+                "Unknown command: {}. Type 'help' for the commands.",
+                command
+            ),
+        }
+    }
+
+    /// Changes the value of a variable, whether or not it is mutable. The new value must be
+    /// of the declared type of the variable.
+    ///
+    /// # Arguments
+    /// * `argument` - The argument of the command, `<name> = <expression>`.
+    /// * `env` - The environment the variable is looked up in.
+    ///
+    /// # Returns
+    /// The text printed in answer to the command.
+    fn set_variable(&mut self, argument: &str, env: &mut Environment) -> String {
+        let Some((name, expression)) = argument.split_once('=') else {
+            return "Usage: set <name> = <expression>".to_string();
+        };
+        let name = name.trim();
+        let Some(var_type) = env.get_var(name).map(|var| var.var_type.clone()) else {
+            return format!("Variable {} is not defined!", name);
+        };
+        let result = match evaluate_expression(expression, env) {
+            Ok(result) => result,
+            Err(message) => return message,
+        };
+        let Some(value) = result_to_value(result, &var_type) else {
+            return format!(
+                "Type mismatch: {} is of type {}",
+                name,
+                format_type(&var_type)
+            );
+        };
+        let described = format_element(&value_to_result(&value));
+        env.modify_var(name, |var| var.value = value);
+        format!("{} = {}", name, described)
+    }
+}
+
+impl EvalHook for Debugger {
+    fn on_statement(&mut self, statement: &AST, env: &mut Environment) -> Result<(), EvalError> {
+        let AST::Statement(_, Some(line_info)) = statement else {
+            return Ok(());
+        };
+        let file = self.module_of(env);
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = line_info.line;
+            frame.file = file.clone();
+        }
+        if !self.should_pause(line_info.line, file.is_none()) {
+            return Ok(());
+        }
+
+        let code = format_ast(statement, 0);
+        let code = code.lines().next().unwrap_or_default();
+        let location = describe_line(line_info.line, &file);
+        write_output(env, &format!("Paused at {}: {}\n", location, code))?;
+        self.prompt(env)
+    }
+
+    fn on_call(
+        &mut self,
+        function: &Function,
+        line_info: &Option<LineInfo>,
+        env: &mut Environment,
+    ) -> Result<(), EvalError> {
+        self.frames.push(Frame {
+            name: function.name.clone(),
+            line: line_info.as_ref().map_or(0, |info| info.line),
+            file: self.module_of(env),
+        });
+        Ok(())
+    }
+
+    fn on_return(&mut self, _function: &Function, _env: &mut Environment) {
+        if self.frames.len() > 1 {
+            self.frames.pop();
+        }
+    }
+}
+
+/// Describes a line of the script, or of a module.
+fn describe_line(line: usize, file: &Option<String>) -> String {
+    match file {
+        Some(file) => {
+            let name = Path::new(file)
+                .file_name()
+                .map(|name| name.to_string_lossy());
+            format!("line {} of {}", line, name.unwrap_or_default())
+        }
+        None => format!("line {}", line),
+    }
+}
+
+/// Writes text to the output stream of an environment.
+fn write_output(env: &Environment, text: &str) -> Result<(), EvalError> {
+    let mut output = env.io().output();
+    write!(output, "{}", text)
+        .and_then(|_| output.flush())
+        .map_err(|e| EvalError::IoError(e.to_string(), None))
+}
+
+/// Evaluates an expression typed at a debugger's prompt in an environment.
+///
+/// # Returns
+/// The value of the expression, or the message of the error that prevented its evaluation.
+pub(crate) fn evaluate_expression(
+    expression: &str,
+    env: &mut Environment,
+) -> Result<EvalResult, String> {
+    let source = format!("{};", expression.trim());
+    let statement = parse(&source)
+        .map_err(|e| format!("Parse error: {}", e))?
+        .into_inner()
+        .find(|pair| pair.as_rule() != Rule::EOI)
+        .ok_or_else(|| "Usage: print <expression>".to_string())?;
+    let ast = build_ast(statement).map_err(|e| format!("Parse error: {}", e))?;
+    let AST::Statement(expression, _) = ast else {
+        return Err("Usage: print <expression>".to_string());
+    };
+
+    let depth = env.scope_depth();
+    evaluate(&expression, env).map_err(|e| {
+        env.unwind_scopes(depth);
+        format!("Error: {}", e)
+    })
+}
+
+/// Describes the variables visible from the current scope of an environment, from the
+/// innermost scope to the global scope. Inside a function, these are the scopes of the call and
+/// of the function's closure, not those of its callers.
+fn describe_vars(env: &Environment) -> String {
+    let describe = |name: &str, var: &VarInfo| {
+        format!(
+            "  {}{}: {} = {}",
+            if var.is_morph { "morph " } else { "" },
+            name,
+            format_type(&var.var_type),
+            format_element(&value_to_result(&var.value))
+        )
+    };
+
+    let mut lines = Vec::new();
+    let scopes = env.visible_vars();
+    for (depth, vars) in scopes.iter().enumerate().rev() {
+        if vars.is_empty() {
+            continue;
+        }
+        lines.push(format!("Scope {}:", depth + 1));
+        lines.extend(vars.iter().map(|(name, var)| describe(name, var)));
+    }
+    let mut globals: Vec<_> = env.global_vars().iter().collect();
+    globals.sort_by_key(|(name, _)| *name);
+    if !globals.is_empty() {
+        lines.push("Globals:".to_string());
+        lines.extend(globals.iter().map(|(name, var)| describe(name, var)));
+    }
+    match lines.is_empty() {
+        true => "No variables".to_string(),
+        false => lines.join("\n"),
+    }
+}
@@ -1,6 +1,16 @@
-use crate::ast::{LineInfo, Type, AST};
-use crate::eval::EvalError;
-use std::collections::HashMap;
+use crate::ast::{LineInfo, Slot, Type, AST};
+use crate::debugger::EvalHook;
+use crate::eval::{BinaryOp, EvalError, EvalResult};
+use crate::io::IoContext;
+use crate::limits::{Budget, Limits};
+use crate::stdlib::register_core_library;
+use num_bigint::BigInt;
+use std::cell::{Ref, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::ops::{Deref, Range};
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
 
 /// Stores information about a variable, including its value, type, and mutability.
 #[derive(Debug, Clone)]
@@ -11,43 +21,1049 @@ pub struct VarInfo {
     pub line_info: Option<LineInfo>,
 }
 
+impl VarInfo {
+    /// Updates the value of the variable if it is mutable and the types match.
+    /// Returns an error if the variable is immutable or the types do not match.
+    pub fn update(
+        &mut self,
+        name: &str,
+        value: Value,
+        var_type: Type,
+        line_info: Option<LineInfo>,
+    ) -> Result<(), EvalError> {
+        if !self.is_morph {
+            return Err(EvalError::InvalidOperation(
+                format!("Cannot reassign to immutable variable {}", name),
+                line_info,
+            ));
+        }
+
+        if self.var_type != var_type {
+            return Err(EvalError::InvalidOperation(
+                format!(
+                    "Type mismatch: cannot assign {:?} to variable {} of type {:?}",
+                    var_type, name, self.var_type
+                ),
+                line_info,
+            ));
+        }
+
+        self.value = value;
+        Ok(())
+    }
+}
+
+/// The variables of a scope. A variable the resolver assigned a slot to is stored in that slot,
+/// so that it is read by its position; any other variable, such as one exposed by `invoke` or
+/// declared by code that was not resolved, is stored apart, so that it never takes the slot of a
+/// variable that has not been declared yet.
+#[derive(Debug, Clone, Default)]
+pub struct ScopeVars {
+    slots: Vec<Option<(String, VarInfo)>>, // The variables stored in slots, with their names
+    named: Vec<(String, VarInfo)>,         // The variables declared by name
+    positions: HashMap<String, Position>,  // Where each variable is stored
+}
+
+/// Where a variable of a scope is stored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Position {
+    Slot(usize),
+    Named(usize),
+}
+
+impl ScopeVars {
+    /// Returns the variable with the given name.
+    pub fn get(&self, name: &str) -> Option<&VarInfo> {
+        self.at(self.position(name)?)
+    }
+
+    /// Returns where the variable with the given name is stored.
+    fn position(&self, name: &str) -> Option<Position> {
+        self.positions.get(name).copied()
+    }
+
+    /// Returns the variable stored at a position.
+    fn at(&self, position: Position) -> Option<&VarInfo> {
+        match position {
+            Position::Slot(index) => self.slot(index),
+            Position::Named(index) => self.named.get(index).map(|(_, var_info)| var_info),
+        }
+    }
+
+    /// Returns the variable stored at a position for modification.
+    fn at_mut(&mut self, position: Position) -> Option<&mut VarInfo> {
+        match position {
+            Position::Slot(index) => self.slot_mut(index),
+            Position::Named(index) => self.named.get_mut(index).map(|(_, var_info)| var_info),
+        }
+    }
+
+    /// Returns the variable stored in a slot.
+    pub fn slot(&self, index: usize) -> Option<&VarInfo> {
+        self.slots
+            .get(index)?
+            .as_ref()
+            .map(|(_, var_info)| var_info)
+    }
+
+    /// Returns the variable stored in a slot for modification.
+    fn slot_mut(&mut self, index: usize) -> Option<&mut VarInfo> {
+        self.slots
+            .get_mut(index)?
+            .as_mut()
+            .map(|(_, var_info)| var_info)
+    }
+
+    /// Returns the slot of the variable with the given name, if it is stored in one.
+    pub fn slot_of(&self, name: &str) -> Option<usize> {
+        match self.positions.get(name) {
+            Some(Position::Slot(index)) => Some(*index),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of slots of the scope, which is the slot of the next variable the
+    /// resolver declares in it.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the variables of the scope with their names, those stored in slots first,
+    /// in the order of their slots.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &VarInfo)> {
+        self.slots
+            .iter()
+            .flatten()
+            .chain(&self.named)
+            .map(|(name, var_info)| (name, var_info))
+    }
+
+    /// Stores a variable in the place of the variable with the same name, or apart.
+    fn declare(&mut self, name: String, var_info: VarInfo) {
+        match self.positions.get(&name) {
+            Some(Position::Slot(index)) => self.slots[*index] = Some((name, var_info)),
+            Some(Position::Named(index)) => self.named[*index].1 = var_info,
+            None => {
+                self.positions
+                    .insert(name.clone(), Position::Named(self.named.len()));
+                self.named.push((name, var_info));
+            }
+        }
+    }
+
+    /// Stores a variable in the given slot, replacing the variable with the same name
+    /// wherever it is stored.
+    fn declare_at(&mut self, index: usize, name: String, var_info: VarInfo) {
+        match self.positions.get(&name).copied() {
+            Some(Position::Slot(existing)) if existing == index => {}
+            Some(Position::Slot(existing)) => self.slots[existing] = None,
+            Some(Position::Named(existing)) => {
+                self.named.swap_remove(existing);
+                if let Some((moved, _)) = self.named.get(existing) {
+                    self.positions
+                        .insert(moved.clone(), Position::Named(existing));
+                }
+            }
+            None => {}
+        }
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+        if let Some((occupant, _)) = &self.slots[index] {
+            if *occupant != name {
+                self.positions.remove(occupant);
+            }
+        }
+        self.positions.insert(name.clone(), Position::Slot(index));
+        self.slots[index] = Some((name, var_info));
+    }
+}
+
+/// The variables, functions and sigils of a local scope.
+#[derive(Debug, Default)]
+pub struct LocalScope {
+    vars: ScopeVars,
+    functions: HashMap<String, Rc<Function>>,
+    sigils: HashMap<String, Sigil>,
+}
+
+/// A local scope, shared with the functions engraved while it is visible, which still see it
+/// when they are called from elsewhere.
+type SharedScope = Rc<RefCell<LocalScope>>;
+
+/// Returns the variables of a shared local scope, those stored in slots first.
+fn shared_vars(scope: &SharedScope) -> Vec<(String, VarInfo)> {
+    let scope = scope.borrow();
+    scope
+        .vars
+        .iter()
+        .map(|(name, var_info)| (name.clone(), var_info.clone()))
+        .collect()
+}
+
+/// A local scope on the stack. Its variables are stored in the local variables of the
+/// environment, from `base` on, until a function engraved in it needs to hold it, or it gets a
+/// function, a sigil or a variable that is not stored in a slot: from then on it is `shared`,
+/// and stores them itself.
+#[derive(Debug, Clone)]
+struct StackScope {
+    base: usize,
+    shared: Option<SharedScope>,
+}
+
+/// A local scope found by a lookup: a scope on the stack, by its position, or a scope of the
+/// closure of the current call.
+#[derive(Clone, Copy)]
+enum ScopeRef<'a> {
+    Stack(usize),
+    Closure(&'a SharedScope),
+}
+
+/// The scope a slot refers to: a local scope, or the global scope.
+enum SlotScope<'a> {
+    Local(ScopeRef<'a>),
+    Global,
+}
+
+/// Where a variable is stored: at a position of the local variables of the environment, in a
+/// shared local scope, or in the global scope.
+enum Place<'a> {
+    Local(usize),
+    Shared(&'a SharedScope, Position),
+    Global(Position),
+}
+
 /// Represents a function in the environment, including its name, parameters, return type, body, and line information.
+/// A function imported with `invoke` keeps the environment of the module that defined it in `module`,
+/// so that its body runs against that module's globals. A function engraved in a local scope
+/// keeps the scopes visible there in `closure`, so that its body sees their variables.
+/// A native function taken as a value is marked by `native`: it has no body, and calling it
+/// calls the native function with its name.
 #[derive(Debug, Clone)]
 pub struct Function {
     pub name: String,
     pub params: Vec<AST>,
     pub return_type: Type,
-    pub body: Box<AST>,
+    pub body: Rc<AST>,
     pub line_info: Option<LineInfo>,
+    pub module: Option<Rc<RefCell<Environment>>>,
+    pub closure: Closure,
+    pub native: bool,
+}
+
+impl Function {
+    /// Returns the type of the function as a value, such as `engrave(arcana) -> rune`.
+    pub fn signature(&self) -> Type {
+        let param_types = self
+            .params
+            .iter()
+            .filter_map(|param| match param {
+                AST::EngraveParam { param_type, .. } => Some(param_type.clone()),
+                _ => None,
+            })
+            .collect();
+        Type::Engrave(param_types, Box::new(self.return_type.clone()))
+    }
+}
+
+/// The local scopes visible where a function was engraved, from the outermost. A function
+/// defined by `engrave` holds them weakly, since it is itself stored in one of them: it can only
+/// be looked up while they are alive. A function value holds them, so that it can still be
+/// called once they are left.
+#[derive(Clone)]
+pub enum Closure {
+    Engraved(Vec<Weak<RefCell<LocalScope>>>),
+    Held(Vec<SharedScope>),
+}
+
+impl Closure {
+    /// Returns a closure holding the scopes of this one that are still alive.
+    pub fn held(&self) -> Closure {
+        Closure::Held(self.scopes())
+    }
+
+    /// Returns the scopes of the closure that are still alive, from the outermost.
+    fn scopes(&self) -> Vec<SharedScope> {
+        match self {
+            Closure::Engraved(scopes) => scopes.iter().filter_map(Weak::upgrade).collect(),
+            Closure::Held(scopes) => scopes.clone(),
+        }
+    }
+}
+
+impl Default for Closure {
+    fn default() -> Self {
+        Closure::Engraved(Vec::new())
+    }
+}
+
+// The scopes are not shown, since they may hold the function itself.
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, count) = match self {
+            Closure::Engraved(scopes) => ("Engraved", scopes.len()),
+            Closure::Held(scopes) => ("Held", scopes.len()),
+        };
+        write!(f, "{}({} scopes)", kind, count)
+    }
+}
+
+/// The scopes seen by a call to a function defined by `engrave` that is in progress: its own,
+/// from `base` on, and those of its closure.
+#[derive(Debug, Clone)]
+struct CallScopes {
+    base: usize,
+    closure: Vec<SharedScope>,
 }
 
-/// Manages variable and function scopes in the execution environment.
+/// A variable found in the environment. A variable of a local scope is borrowed from the
+/// scope, which cannot be modified while the variable is held; any other is a plain reference.
+pub enum VarRef<'a> {
+    Scoped(Ref<'a, VarInfo>),
+    Direct(&'a VarInfo),
+}
+
+impl Deref for VarRef<'_> {
+    type Target = VarInfo;
+
+    fn deref(&self) -> &VarInfo {
+        match self {
+            VarRef::Scoped(var_info) => var_info,
+            VarRef::Direct(var_info) => var_info,
+        }
+    }
+}
+
+/// The body of a native function: receives the evaluated arguments, which already conform to
+/// the declared parameter types, and returns its result or a message describing the failure.
+pub type NativeBody = Rc<dyn Fn(Vec<EvalResult>) -> Result<EvalResult, String>>;
+
+/// Represents a function implemented in Rust, with the parameter types and return type
+/// that its arguments and result are checked against.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    pub params: Vec<Type>,
+    pub return_type: Type,
+    pub body: NativeBody,
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .field("return_type", &self.return_type)
+            .finish()
+    }
+}
+
+/// Represents a sigil (user-defined struct type) in the environment, including its name, fields, and line information.
+#[derive(Debug, Clone)]
+pub struct Sigil {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+    pub line_info: Option<LineInfo>,
+}
+
+impl Sigil {
+    /// Returns the declared type of a field, or `None` if the sigil has no such field.
+    pub fn field_type(&self, field: &str) -> Option<&Type> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, field_type)| field_type)
+    }
+}
+
+/// The number of calls that can be in progress at once unless set otherwise with
+/// `Environment::set_max_call_depth`.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// A call to a function defined by `engrave` in progress: the name of the function and the
+/// location of the call. A call made from an invoked module names the module's file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame {
+    pub function: String,
+    pub line_info: Option<LineInfo>,
+}
+
+/// The calls in progress, shared by an environment and the environments of the modules it
+/// invokes, together with the calls that were in progress when the last uncaught error occurred.
+#[derive(Debug, Default)]
+struct CallStack {
+    frames: Vec<CallFrame>,
+    trace: Option<Vec<CallFrame>>,
+}
+
+/// Manages variable, function and sigil scopes in the execution environment.
 /// This includes handling both global and local scopes.
+///
+/// Scoping is lexical: a function defined by `engrave` sees its own scopes, then the scopes
+/// visible where it was engraved, then the global scope, but not the scopes of its callers.
 #[derive(Debug, Clone)]
 pub struct Environment {
-    scopes: Vec<HashMap<String, VarInfo>>, // Variable scopes
-    function_scopes: Vec<HashMap<String, Function>>, // Function scopes
+    globals: ScopeVars,                              // Global variables
+    global_functions: HashMap<String, Rc<Function>>, // Global functions
+    global_sigils: HashMap<String, Sigil>,           // Global sigils
+    scopes: Vec<StackScope>, // Local scopes of the calls in progress, innermost last
+    locals: Vec<Option<VarInfo>>, // The variables of the local scopes that are not shared
+    local_names: Vec<String>, // The names of the local variables, kept for the next scopes
+    call_scopes: Vec<CallScopes>, // The scopes seen by each call in progress
+    scope_warnings: bool, // Whether scoping stays dynamic, with warnings where lexical scoping differs
+    warned: Rc<RefCell<HashSet<String>>>, // The scope warnings already reported
+    natives: HashMap<String, Vec<NativeFunction>>, // Native functions, with their overloads
+    script_path: Option<PathBuf>, // The script file being evaluated, if any
+    module_chain: Vec<PathBuf>, // The chain of modules being invoked, for cycle detection
+    module_root: Option<PathBuf>, // The directory invoked modules must lie in without file access
+    file_access: bool,    // Whether the file I/O builtins may touch the disk
+    bigint: bool,         // Whether arcana values are unbounded
+    io: IoContext,        // The streams used by unveil and summon
+    hook: Option<SharedHook>, // The hook notified of the progress of the evaluation
+    calls: Rc<RefCell<CallStack>>, // The calls in progress
+    max_call_depth: usize, // The number of calls that can be in progress at once
+    budget: Option<Rc<RefCell<Budget>>>, // The resources the script may use, if limited
+    in_module: bool,      // Whether this is the environment of an invoked module
+}
+
+/// A hook shared by an environment and the environments of the modules it invokes.
+#[derive(Clone)]
+struct SharedHook(Rc<RefCell<dyn EvalHook>>);
+
+impl fmt::Debug for SharedHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedHook").finish_non_exhaustive()
+    }
 }
 
 impl Environment {
-    /// Creates a new environment with an initial global scope.
+    /// Creates a new environment with an initial global scope and the core library of
+    /// native functions.
     pub fn new() -> Self {
-        Environment {
-            scopes: vec![HashMap::new()],
-            function_scopes: vec![HashMap::new()],
+        let mut env = Environment {
+            globals: ScopeVars::default(),
+            global_functions: HashMap::new(),
+            global_sigils: HashMap::new(),
+            scopes: Vec::new(),
+            locals: Vec::new(),
+            local_names: Vec::new(),
+            call_scopes: Vec::new(),
+            scope_warnings: false,
+            warned: Rc::default(),
+            natives: HashMap::new(),
+            script_path: None,
+            module_chain: Vec::new(),
+            module_root: None,
+            file_access: false,
+            bigint: false,
+            io: IoContext::stdio(),
+            hook: None,
+            calls: Rc::default(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            budget: None,
+            in_module: false,
+        };
+        register_core_library(&mut env);
+        env
+    }
+
+    /// Creates a new environment for evaluating the script at the given path.
+    /// Modules invoked from the script are resolved relative to its directory.
+    pub fn with_script_path(path: PathBuf) -> Self {
+        let mut env = Environment::new();
+        env.set_script_path(Some(path));
+        env
+    }
+
+    /// Sets the path of the script being evaluated, which modules invoked from it are resolved
+    /// against. `None` resolves them against the current directory. Unless file access is
+    /// allowed, the modules must lie in that directory.
+    pub fn set_script_path(&mut self, path: Option<PathBuf>) {
+        self.module_chain = match &path {
+            Some(path) => vec![path.canonicalize().unwrap_or_else(|_| path.clone())],
+            None => Vec::new(),
+        };
+        self.module_root = self
+            .module_chain
+            .first()
+            .and_then(|path| path.parent())
+            .map(Path::to_path_buf);
+        self.script_path = path;
+    }
+
+    /// Creates the environment of a module invoked from this environment.
+    /// The module's canonical path is appended to the chain of modules being invoked,
+    /// and the module inherits the directory modules must lie in, the permission to access files,
+    /// the bigint mode, the I/O streams,
+    /// the hook, the calls in progress, the maximum call depth, the resource limits and the
+    /// scoping mode.
+    pub fn for_module(&self, path: PathBuf, canonical_path: PathBuf) -> Self {
+        let mut env = Environment::new();
+        env.module_chain = self.module_chain.clone();
+        env.module_chain.push(canonical_path);
+        env.script_path = Some(path);
+        env.module_root = self.module_root.clone();
+        env.file_access = self.file_access;
+        env.bigint = self.bigint;
+        env.io = self.io.clone();
+        env.hook = self.hook.clone();
+        env.calls = Rc::clone(&self.calls);
+        env.max_call_depth = self.max_call_depth;
+        env.budget = self.budget.clone();
+        env.in_module = true;
+        env.scope_warnings = self.scope_warnings;
+        env.warned = Rc::clone(&self.warned);
+        env.natives = self.natives.clone();
+        env
+    }
+
+    /// Allows or forbids the file I/O builtins (`read`, `read_lines`, `write`, `append` and
+    /// `exists`) to access the disk. File access is forbidden by default.
+    pub fn set_file_access(&mut self, allowed: bool) {
+        self.file_access = allowed;
+    }
+
+    /// Returns true if the file I/O builtins may access the disk.
+    pub fn file_access(&self) -> bool {
+        self.file_access
+    }
+
+    /// Enables or disables bigint mode, in which `arcana` values are unbounded: a result that does
+    /// not fit in 64 bits is kept as an arbitrary-precision integer instead of raising an
+    /// overflow. Bigint mode is disabled by default.
+    pub fn set_bigint(&mut self, enabled: bool) {
+        self.bigint = enabled;
+    }
+
+    /// Returns true if `arcana` values are unbounded.
+    pub fn bigint(&self) -> bool {
+        self.bigint
+    }
+
+    /// Sets the streams used by `unveil` and `summon`. The standard streams are used by default.
+    pub fn set_io(&mut self, io: IoContext) {
+        self.io = io;
+    }
+
+    /// Returns the streams used by `unveil` and `summon`.
+    pub fn io(&self) -> &IoContext {
+        &self.io
+    }
+
+    /// Sets the hook notified before each statement is evaluated and when a function is
+    /// entered and left, such as a debugger. `None` removes the hook.
+    pub fn set_hook(&mut self, hook: Option<Rc<RefCell<dyn EvalHook>>>) {
+        self.hook = hook.map(SharedHook);
+    }
+
+    /// Returns the hook notified of the progress of the evaluation, if any.
+    pub fn hook(&self) -> Option<Rc<RefCell<dyn EvalHook>>> {
+        self.hook.as_ref().map(|hook| Rc::clone(&hook.0))
+    }
+
+    /// Enables or disables the scoping migration mode, in which a function still sees the
+    /// variables and functions of its callers, as before lexical scoping, and a warning is
+    /// reported to the error stream wherever lexical scoping would see another one, or none.
+    /// The mode is disabled by default.
+    pub fn set_scope_warnings(&mut self, enabled: bool) {
+        self.scope_warnings = enabled;
+    }
+
+    /// Returns true if the scoping migration mode is enabled.
+    pub fn scope_warnings(&self) -> bool {
+        self.scope_warnings
+    }
+
+    /// Sets the limits on the resources the script may use. The time limit starts running now,
+    /// and the steps are counted from zero.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.budget = match limits.is_unlimited() {
+            true => None,
+            false => Some(Rc::new(RefCell::new(Budget::new(limits)))),
+        };
+    }
+
+    /// Gives the script its full resources again: restarts the time limit and counts the steps
+    /// from zero, e.g. before each evaluation of an embedded interpreter.
+    pub fn restart_limits(&mut self) {
+        if let Some(budget) = &self.budget {
+            let limits = budget.borrow().limits().clone();
+            *budget.borrow_mut() = Budget::new(limits);
+        }
+    }
+
+    /// Returns the limits on the resources the script may use.
+    pub fn limits(&self) -> Limits {
+        self.budget
+            .as_ref()
+            .map(|budget| budget.borrow().limits().clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns whether the resources the script may use are limited.
+    pub fn is_limited(&self) -> bool {
+        self.budget.is_some()
+    }
+
+    /// Counts a step of the evaluation against the limits.
+    ///
+    /// # Arguments
+    /// * `line_info` - The location of the node or instruction evaluated.
+    ///
+    /// # Returns
+    /// An error if the script ran out of steps or time.
+    pub fn step(&self, line_info: Option<&LineInfo>) -> Result<(), EvalError> {
+        self.steps(1, line_info)
+    }
+
+    /// Counts several steps of the evaluation against the limits at once, such as the
+    /// instructions the VM ran since it last checked them.
+    ///
+    /// # Arguments
+    /// * `count` - The number of steps.
+    /// * `line_info` - The location of the instruction checking the limits.
+    ///
+    /// # Returns
+    /// An error if the script ran out of steps or time.
+    #[inline]
+    pub fn steps(&self, count: u64, line_info: Option<&LineInfo>) -> Result<(), EvalError> {
+        match &self.budget {
+            Some(budget) => budget
+                .borrow_mut()
+                .step(count)
+                .map_err(|message| EvalError::LimitExceeded(message, line_info.cloned())),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the size of a value produced by the script against the limits.
+    ///
+    /// # Returns
+    /// An error if the value is a rune or collection larger than allowed.
+    #[inline]
+    pub fn check_result(
+        &self,
+        result: &EvalResult,
+        line_info: Option<&LineInfo>,
+    ) -> Result<(), EvalError> {
+        match &self.budget {
+            Some(budget) => budget
+                .borrow()
+                .check_result(result)
+                .map_err(|message| EvalError::LimitExceeded(message, line_info.cloned())),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks an arithmetic operation on two arcana against the limit on their number of bits,
+    /// before it is computed.
+    ///
+    /// # Returns
+    /// An error if the result would be larger than allowed.
+    #[inline]
+    pub fn check_arcana_operation(
+        &self,
+        op: BinaryOp,
+        left: &EvalResult,
+        right: &EvalResult,
+        line_info: &Option<LineInfo>,
+    ) -> Result<(), EvalError> {
+        match &self.budget {
+            Some(budget) if self.bigint => budget
+                .borrow()
+                .check_arcana_operation(op, left, right)
+                .map_err(|message| EvalError::LimitExceeded(message, line_info.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the size of a value about to be stored in a variable against the limits.
+    ///
+    /// # Returns
+    /// An error if the value is a rune or collection larger than allowed.
+    #[inline]
+    pub fn check_value(
+        &self,
+        value: &Value,
+        line_info: Option<&LineInfo>,
+    ) -> Result<(), EvalError> {
+        match &self.budget {
+            Some(budget) => budget
+                .borrow()
+                .check_value(value)
+                .map_err(|message| EvalError::LimitExceeded(message, line_info.cloned())),
+            None => Ok(()),
         }
     }
 
-    /// Pushes a new scope onto the stack, creating a new local environment for variables and functions.
+    /// Sets the number of calls to functions defined by `engrave` that can be in progress at
+    /// once. A call beyond it raises a stack overflow error, which `attempt` can catch.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    /// Returns the number of calls that can be in progress at once.
+    pub fn max_call_depth(&self) -> usize {
+        self.max_call_depth
+    }
+
+    /// Records that a function defined by `engrave` is entered. A call made when no other call
+    /// is in progress starts a new chain of calls, so the calls recorded for an earlier error
+    /// are forgotten.
+    ///
+    /// # Arguments
+    /// * `function` - The name of the function.
+    /// * `line_info` - The location of the call, as returned by `locate`.
+    ///
+    /// # Returns
+    /// A stack overflow error if the maximum call depth is reached, in which case the call is
+    /// not recorded.
+    pub fn push_call(
+        &mut self,
+        function: &str,
+        line_info: &Option<LineInfo>,
+    ) -> Result<(), EvalError> {
+        let mut calls = self.calls.borrow_mut();
+        if calls.frames.is_empty() {
+            calls.trace = None;
+        }
+        if calls.frames.len() >= self.max_call_depth {
+            return Err(EvalError::StackOverflow(
+                format!(
+                    "calling {} exceeds the maximum call depth of {}",
+                    function, self.max_call_depth
+                ),
+                line_info.clone(),
+            ));
+        }
+        calls.frames.push(CallFrame {
+            function: function.to_string(),
+            line_info: line_info.clone(),
+        });
+        Ok(())
+    }
+
+    /// Records that the innermost function in progress returned.
+    pub fn pop_call(&mut self) {
+        self.calls.borrow_mut().frames.pop();
+    }
+
+    /// Returns the calls in progress, from the outermost.
+    pub fn call_stack(&self) -> Vec<CallFrame> {
+        self.calls.borrow().frames.clone()
+    }
+
+    /// Returns the number of calls in progress.
+    pub fn call_depth(&self) -> usize {
+        self.calls.borrow().frames.len()
+    }
+
+    /// Forgets the innermost calls until only `depth` remain, discarding the calls left behind
+    /// by an evaluation that was interrupted by an error.
+    pub fn unwind_calls(&mut self, depth: usize) {
+        self.calls.borrow_mut().frames.truncate(depth);
+    }
+
+    /// Keeps the calls in progress as the trace of the error being raised, unless the error
+    /// already has a trace because it was raised by a deeper call.
+    pub fn record_trace(&mut self) {
+        let mut calls = self.calls.borrow_mut();
+        if calls.trace.is_none() {
+            calls.trace = Some(calls.frames.clone());
+        }
+    }
+
+    /// Forgets the trace of an error that was caught.
+    pub fn clear_trace(&mut self) {
+        self.calls.borrow_mut().trace = None;
+    }
+
+    /// Returns the calls that were in progress when the last uncaught error occurred, from the
+    /// outermost, and forgets them.
+    pub fn take_trace(&self) -> Vec<CallFrame> {
+        self.calls.borrow_mut().trace.take().unwrap_or_default()
+    }
+
+    /// Returns the given line information, naming the file of the module when this is the
+    /// environment of an invoked module.
+    pub fn locate(&self, line_info: &Option<LineInfo>) -> Option<LineInfo> {
+        let mut line_info = line_info.clone();
+        if let Some(info) = line_info.as_mut().filter(|info| info.file.is_none()) {
+            if self.in_module {
+                info.file = self.script_path().map(|path| path.display().to_string());
+            }
+        }
+        line_info
+    }
+
+    /// Returns the path of the script being evaluated, if any.
+    pub fn script_path(&self) -> Option<&Path> {
+        self.script_path.as_deref()
+    }
+
+    /// Returns the canonical directory of the script that started the evaluation, which invoked
+    /// modules must lie in unless file access is allowed. `None` stands for the current directory.
+    pub fn module_root(&self) -> Option<&Path> {
+        self.module_root.as_deref()
+    }
+
+    /// Returns true if the module at the given canonical path is already being invoked.
+    pub fn is_invoking(&self, canonical_path: &Path) -> bool {
+        self.module_chain.iter().any(|path| path == canonical_path)
+    }
+
+    /// Returns the functions defined in the global scope.
+    pub fn global_functions(&self) -> &HashMap<String, Rc<Function>> {
+        &self.global_functions
+    }
+
+    /// Returns the variables defined in the global scope.
+    pub fn global_vars(&self) -> &ScopeVars {
+        &self.globals
+    }
+
+    /// Returns the sigils defined in the global scope.
+    pub fn global_sigils(&self) -> &HashMap<String, Sigil> {
+        &self.global_sigils
+    }
+
+    /// Pushes a new scope onto the stack, creating a new local environment for variables, functions and sigils.
+    /// The variables of the scope are stored in the local variables of the environment, so that
+    /// entering and leaving it allocates nothing, until it has to be shared.
     pub fn push_scope(&mut self) {
-        self.scopes.push(HashMap::new());
-        self.function_scopes.push(HashMap::new());
+        self.scopes.push(StackScope {
+            base: self.locals.len(),
+            shared: None,
+        });
+    }
+
+    /// Pushes the scope of a call to a function defined by `engrave`, which sees the scopes of
+    /// its closure instead of those of its caller until the scope is popped.
+    pub fn push_function_scope(&mut self, function: &Function) {
+        self.call_scopes.push(CallScopes {
+            base: self.scopes.len(),
+            closure: function.closure.scopes(),
+        });
+        self.push_scope();
     }
 
     /// Pops the most recent scope off the stack, discarding the current local environment.
+    /// Popping the scope of a call ends the call.
     pub fn pop_scope(&mut self) {
-        self.scopes.pop();
-        self.function_scopes.pop();
+        if let Some(scope) = self.scopes.pop() {
+            self.locals.truncate(scope.base);
+        }
+        while self
+            .call_scopes
+            .last()
+            .is_some_and(|call| call.base >= self.scopes.len())
+        {
+            self.call_scopes.pop();
+        }
+    }
+
+    /// Returns the number of scopes currently on the stack, including the global scope.
+    pub fn scope_depth(&self) -> usize {
+        self.scopes.len() + 1
+    }
+
+    /// Returns the local scopes visible where a function is engraved now, which the function
+    /// sees when it is called. The scopes of the current call are shared from then on.
+    pub fn capture_scopes(&mut self) -> Closure {
+        let (own, _) = self.visible_scopes(true);
+        self.share_scopes(own.start);
+        let (own, closure) = self.visible_scopes(true);
+        let own = self.scopes[own]
+            .iter()
+            .filter_map(|scope| scope.shared.as_ref());
+        Closure::Engraved(closure.iter().chain(own).map(Rc::downgrade).collect())
+    }
+
+    /// Moves the variables of the scopes on the stack from position `from` on out of the local
+    /// variables, into scopes of their own that functions can hold.
+    fn share_scopes(&mut self, from: usize) {
+        for index in (from..self.scopes.len()).rev() {
+            if self.scopes[index].shared.is_some() {
+                continue;
+            }
+            let base = self.scopes[index].base;
+            let mut vars = ScopeVars::default();
+            for (index, var_info) in self.locals.drain(base..).enumerate() {
+                if let Some(var_info) = var_info {
+                    vars.declare_at(index, self.local_names[base + index].clone(), var_info);
+                }
+            }
+            self.scopes[index].shared = Some(Rc::new(RefCell::new(LocalScope {
+                vars,
+                ..LocalScope::default()
+            })));
+        }
+        // The shared scopes no longer hold any local variable.
+        let end = self.locals.len();
+        for scope in &mut self.scopes[from..] {
+            scope.base = end;
+        }
+    }
+
+    /// Returns the innermost local scope, shared so that it can store functions, sigils and
+    /// variables declared by name, or `None` at the top level.
+    fn shared_scope_mut(&mut self) -> Option<&SharedScope> {
+        let innermost = self.scopes.len().checked_sub(1)?;
+        self.share_scopes(innermost);
+        self.scopes[innermost].shared.as_ref()
+    }
+
+    /// Returns the positions in the local variables of the variables of the scope at the given
+    /// position of the stack.
+    fn region(&self, index: usize) -> Range<usize> {
+        let end = match self.scopes.get(index + 1) {
+            Some(next) => next.base,
+            None => self.locals.len(),
+        };
+        self.scopes[index].base..end
+    }
+
+    /// Returns the local scopes a lookup by name searches, as the positions on the stack of the
+    /// scopes of the current call and the scopes of its closure, from the outermost. Without
+    /// lexical scoping, every scope is searched, including those of the callers.
+    fn visible_scopes(&self, lexical: bool) -> (Range<usize>, &[SharedScope]) {
+        match self.call_scopes.last() {
+            Some(call) if lexical => (call.base..self.scopes.len(), &call.closure),
+            _ => (0..self.scopes.len(), &[]),
+        }
+    }
+
+    /// Returns the shared scope behind a local scope, if it has one.
+    fn shared_scope<'a>(&'a self, scope: ScopeRef<'a>) -> Option<&'a SharedScope> {
+        match scope {
+            ScopeRef::Stack(index) => self.scopes[index].shared.as_ref(),
+            ScopeRef::Closure(scope) => Some(scope),
+        }
+    }
+
+    /// Returns where a local scope stores the variable with the given name.
+    fn scope_place<'a>(&'a self, scope: ScopeRef<'a>, name: &str) -> Option<Place<'a>> {
+        match self.shared_scope(scope) {
+            Some(shared) => {
+                let position = shared.borrow().vars.position(name)?;
+                Some(Place::Shared(shared, position))
+            }
+            None => match scope {
+                ScopeRef::Stack(index) => self
+                    .region(index)
+                    .rev()
+                    .find(|&at| self.locals[at].is_some() && self.local_names[at] == name)
+                    .map(Place::Local),
+                ScopeRef::Closure(_) => None,
+            },
+        }
+    }
+
+    /// Returns the innermost local scope defining a variable, or a function if `function` is
+    /// set, searching the scopes seen with lexical scoping or, if `lexical` is unset, every scope.
+    fn defining_scope(&self, name: &str, function: bool, lexical: bool) -> Option<ScopeRef<'_>> {
+        let (own, closure) = self.visible_scopes(lexical);
+        own.rev()
+            .map(ScopeRef::Stack)
+            .chain(closure.iter().rev().map(ScopeRef::Closure))
+            .find(|scope| match function {
+                true => self
+                    .shared_scope(*scope)
+                    .is_some_and(|scope| scope.borrow().functions.contains_key(name)),
+                false => self.scope_place(*scope, name).is_some(),
+            })
+    }
+
+    /// Returns the innermost local scope defining a variable, or a function if `function` is
+    /// set, among the scopes a lookup by name searches.
+    fn lookup_scope(&self, name: &str, function: bool) -> Option<ScopeRef<'_>> {
+        self.defining_scope(name, function, !self.scope_warnings)
+    }
+
+    /// Returns whether two local scopes found by a lookup are the same scope.
+    fn same_scope(&self, a: ScopeRef<'_>, b: ScopeRef<'_>) -> bool {
+        match (a, b) {
+            (ScopeRef::Stack(a), ScopeRef::Stack(b)) if a == b => true,
+            _ => match (self.shared_scope(a), self.shared_scope(b)) {
+                (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+                _ => false,
+            },
+        }
+    }
+
+    /// In the scoping migration mode, warns if looking up a variable, or a function if
+    /// `function` is set, finds one of a caller of the current function that lexical scoping
+    /// would not see.
+    ///
+    /// # Arguments
+    /// * `name` - The name looked up.
+    /// * `function` - Whether a function is looked up rather than a variable.
+    /// * `line_info` - The location of the lookup.
+    pub fn check_scoping(&self, name: &str, function: bool, line_info: &Option<LineInfo>) {
+        if !self.scope_warnings || self.call_scopes.is_empty() {
+            return;
+        }
+        let dynamic = self.defining_scope(name, function, false);
+        let lexical = self.defining_scope(name, function, true);
+        let same = match (dynamic, lexical) {
+            (Some(dynamic), Some(lexical)) => self.same_scope(dynamic, lexical),
+            (dynamic, _) => dynamic.is_none(),
+        };
+        if !same {
+            self.warn_caller_lookup(name, function, line_info);
+        }
+    }
+
+    /// Warns that the current function uses a variable, or a function if `function` is set, of
+    /// its caller, describing what lexical scoping would find instead. Each warning is reported
+    /// once.
+    pub fn warn_caller_lookup(&self, name: &str, function: bool, line_info: &Option<LineInfo>) {
+        let kind = if function { "function" } else { "variable" };
+        let current = self
+            .calls
+            .borrow()
+            .frames
+            .last()
+            .map(|frame| frame.function.clone())
+            .unwrap_or_default();
+        let found = match function {
+            true => {
+                self.defining_scope(name, true, true).is_some()
+                    || self.global_functions.contains_key(name)
+            }
+            false => {
+                self.defining_scope(name, false, true).is_some() || self.globals.get(name).is_some()
+            }
+        };
+        let consequence = match found {
+            true => format!(
+                "the {} {} visible where {} is engraved",
+                kind, name, current
+            ),
+            false => format!(
+                "no {}, as {} is not visible where {} is engraved",
+                kind, name, current
+            ),
+        };
+        let location = match self.locate(line_info) {
+            Some(info) => format!(" at line {}, column {}", info.line, info.column),
+            None => String::new(),
+        };
+        let warning = format!(
+            "Warning{}: {} uses the {} {} of its caller; with lexical scoping it would use {}",
+            location, current, kind, name, consequence
+        );
+        if self.warned.borrow_mut().insert(warning.clone()) {
+            let _ = writeln!(self.io.error(), "{}", warning);
+        }
+    }
+
+    /// Pops scopes until only `depth` remain, discarding the scopes left behind by an
+    /// evaluation that was interrupted by an error.
+    pub fn unwind_scopes(&mut self, depth: usize) {
+        while self.scope_depth() > depth {
+            self.pop_scope();
+        }
     }
 
     /// Sets a variable in the current scope, specifying its name, value, type, and whether it's mutable.
@@ -59,27 +1075,224 @@ impl Environment {
         is_morph: bool,
         line_info: Option<LineInfo>,
     ) {
-        if let Some(current_scope) = self.scopes.last_mut() {
-            current_scope.insert(
-                name,
-                VarInfo {
+        let var_info = VarInfo {
+            value,
+            var_type,
+            is_morph,
+            line_info,
+        };
+        match self.shared_scope_mut() {
+            Some(current_scope) => current_scope.borrow_mut().vars.declare(name, var_info),
+            None => self.globals.declare(name, var_info),
+        }
+    }
+
+    /// Sets a variable in the current scope, storing it in the slot the resolver assigned to it.
+    /// A variable without a slot is set by name, like `set_var`.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the variable.
+    /// * `slot` - The slot of the variable in the current scope, at depth 0, which is the global
+    ///   scope when no local scope is open.
+    /// * `value` - The value of the variable.
+    /// * `var_type` - The declared type of the variable.
+    /// * `is_morph` - Whether the variable is mutable.
+    /// * `line_info` - The location of the declaration.
+    pub fn set_resolved_var(
+        &mut self,
+        name: &str,
+        slot: Option<Slot>,
+        value: Value,
+        var_type: Type,
+        is_morph: bool,
+        line_info: Option<LineInfo>,
+    ) {
+        let var_info = VarInfo {
+            value,
+            var_type,
+            is_morph,
+            line_info,
+        };
+        let slot = match slot {
+            Some(slot) if slot.depth == 0 => slot,
+            _ => {
+                let VarInfo {
                     value,
                     var_type,
                     is_morph,
                     line_info,
+                } = var_info;
+                return self.set_var(name.to_string(), value, var_type, is_morph, line_info);
+            }
+        };
+        match self.scopes.last() {
+            Some(StackScope {
+                shared: Some(current_scope),
+                ..
+            }) => {
+                current_scope
+                    .borrow_mut()
+                    .vars
+                    .declare_at(slot.index, name.to_string(), var_info)
+            }
+            Some(StackScope { base, shared: None }) => {
+                self.declare_local(base + slot.index, name, var_info)
+            }
+            None => self
+                .globals
+                .declare_at(slot.index, name.to_string(), var_info),
+        }
+    }
+
+    /// Stores a variable of the innermost scope, which is not shared, at the given position of
+    /// the local variables. The name is copied into the buffer a previous scope left there.
+    fn declare_local(&mut self, at: usize, name: &str, var_info: VarInfo) {
+        if at >= self.locals.len() {
+            self.locals.resize_with(at + 1, || None);
+        }
+        if at >= self.local_names.len() {
+            self.local_names.resize_with(at + 1, String::new);
+        }
+        let local_name = &mut self.local_names[at];
+        if local_name != name {
+            local_name.clear();
+            local_name.push_str(name);
+        }
+        self.locals[at] = Some(var_info);
+    }
+
+    /// Retrieves a variable from the environment by searching the visible scopes from the most
+    /// recent to the global scope.
+    pub fn get_var(&self, name: &str) -> Option<VarRef<'_>> {
+        self.get_resolved_var(name, None)
+    }
+
+    /// Modifies a variable found like `get_var` with the given function, returning its result,
+    /// or `None` if the variable is not defined. Unlike `update_var`, this ignores whether the
+    /// variable is mutable.
+    pub fn modify_var<R>(
+        &mut self,
+        name: &str,
+        modify: impl FnOnce(&mut VarInfo) -> R,
+    ) -> Option<R> {
+        self.modify_resolved_var(name, None, modify)
+    }
+
+    /// Returns the variables of each local scope, from the outermost scope to the innermost,
+    /// those stored in slots first. The global variables are returned by `global_vars`.
+    pub fn local_vars(&self) -> Vec<Vec<(String, VarInfo)>> {
+        (0..self.scopes.len())
+            .map(|index| self.scope_vars(ScopeRef::Stack(index)))
+            .collect()
+    }
+
+    /// Returns the variables of each local scope a lookup by name searches, from the outermost:
+    /// those of the closure of the current call and then its own, or every local scope, including
+    /// those of the callers, without lexical scoping.
+    pub fn visible_vars(&self) -> Vec<Vec<(String, VarInfo)>> {
+        let (own, closure) = self.visible_scopes(!self.scope_warnings);
+        closure
+            .iter()
+            .map(ScopeRef::Closure)
+            .chain(own.map(ScopeRef::Stack))
+            .map(|scope| self.scope_vars(scope))
+            .collect()
+    }
+
+    /// Returns the variables of a local scope, those stored in slots first.
+    fn scope_vars(&self, scope: ScopeRef<'_>) -> Vec<(String, VarInfo)> {
+        match (self.shared_scope(scope), scope) {
+            (Some(shared), _) => shared_vars(shared),
+            (None, ScopeRef::Stack(index)) => self
+                .region(index)
+                .filter_map(|at| {
+                    let var_info = self.locals[at].as_ref()?;
+                    Some((self.local_names[at].clone(), var_info.clone()))
+                })
+                .collect(),
+            (None, ScopeRef::Closure(_)) => Vec::new(),
+        }
+    }
+
+    /// Retrieves a variable from the slot the resolver assigned to it, or by name, searching
+    /// the visible scopes from the most recent to the global scope, when it has no slot.
+    pub fn get_resolved_var(&self, name: &str, slot: Option<Slot>) -> Option<VarRef<'_>> {
+        match self.place(name, slot)? {
+            Place::Local(at) => self.locals[at].as_ref().map(VarRef::Direct),
+            Place::Shared(scope, position) => {
+                Ref::filter_map(scope.borrow(), |scope| scope.vars.at(position))
+                    .ok()
+                    .map(VarRef::Scoped)
+            }
+            Place::Global(position) => self.globals.at(position).map(VarRef::Direct),
+        }
+    }
+
+    /// Returns where the variable in a slot is stored, or where the visible variable with the
+    /// given name is when there is no slot. A slot that does not hold a variable, as when it was
+    /// forged on a path that did not run, has no place.
+    fn place(&self, name: &str, slot: Option<Slot>) -> Option<Place<'_>> {
+        let slot = match slot {
+            Some(slot) => slot,
+            None => {
+                let (own, closure) = self.visible_scopes(!self.scope_warnings);
+                return own
+                    .rev()
+                    .map(ScopeRef::Stack)
+                    .chain(closure.iter().rev().map(ScopeRef::Closure))
+                    .find_map(|scope| self.scope_place(scope, name))
+                    .or_else(|| self.globals.position(name).map(Place::Global));
+            }
+        };
+        let position = Position::Slot(slot.index);
+        match self.slot_scope(slot)? {
+            SlotScope::Local(scope) => match self.shared_scope(scope) {
+                Some(shared) => {
+                    let defined = shared.borrow().vars.at(position).is_some();
+                    defined.then_some(Place::Shared(shared, position))
+                }
+                None => match scope {
+                    ScopeRef::Stack(index) => {
+                        let region = self.region(index);
+                        let at = region.start + slot.index;
+                        (region.contains(&at) && self.locals[at].is_some())
+                            .then_some(Place::Local(at))
+                    }
+                    ScopeRef::Closure(_) => None,
                 },
-            );
+            },
+            SlotScope::Global => self.globals.at(position).map(|_| Place::Global(position)),
         }
     }
 
-    /// Retrieves a variable from the environment by searching the scopes from the most recent to the global scope.
-    pub fn get_var(&self, name: &str) -> Option<&VarInfo> {
-        for scope in self.scopes.iter().rev() {
-            if let Some(var_info) = scope.get(name) {
-                return Some(var_info);
+    /// Returns the scope a slot refers to. The depth of a slot counts the scopes of the current
+    /// call, then those of its closure, from the innermost; the global scope comes last.
+    fn slot_scope(&self, slot: Slot) -> Option<SlotScope<'_>> {
+        let (own, closure) = self.visible_scopes(true);
+        let scopes = own.len() + closure.len();
+        match slot.depth {
+            depth if depth < own.len() => {
+                Some(SlotScope::Local(ScopeRef::Stack(own.end - 1 - depth)))
             }
+            depth if depth < scopes => Some(SlotScope::Local(ScopeRef::Closure(
+                &closure[scopes - 1 - depth],
+            ))),
+            depth if depth == scopes => Some(SlotScope::Global),
+            _ => None,
         }
-        None
+    }
+
+    /// Sets a variable in the global scope, whatever the current scope is.
+    pub fn set_global_var(&mut self, name: String, value: Value, var_type: Type, is_morph: bool) {
+        self.globals.declare(
+            name,
+            VarInfo {
+                value,
+                var_type,
+                is_morph,
+                line_info: None,
+            },
+        );
     }
 
     /// Updates an existing variable's value in the environment if it is mutable and the types match.
@@ -91,56 +1304,198 @@ impl Environment {
         var_type: Type,
         line_info: Option<LineInfo>,
     ) -> Result<(), EvalError> {
-        for scope in self.scopes.iter_mut().rev() {
-            if let Some(var_info) = scope.get_mut(name) {
-                if !var_info.is_morph {
-                    return Err(EvalError::InvalidOperation(
-                        format!("Cannot reassign to immutable variable {}", name),
-                        line_info,
-                    ));
-                }
+        let error_line_info = line_info.clone();
+        self.modify_var(name, |var_info| {
+            var_info.update(name, value, var_type, line_info)
+        })
+        .unwrap_or_else(|| {
+            Err(EvalError::UndefinedVariable(
+                name.to_string(),
+                error_line_info,
+            ))
+        })
+    }
 
-                if var_info.var_type != var_type {
-                    return Err(EvalError::InvalidOperation(
-                        format!(
-                            "Type mismatch: cannot assign {:?} to variable {} of type {:?}",
-                            var_type, name, var_info.var_type
-                        ),
-                        line_info,
-                    ));
-                }
+    /// Modifies a variable found like `get_resolved_var` with the given function, returning its
+    /// result, or `None` if the variable is not defined.
+    fn modify_resolved_var<R>(
+        &mut self,
+        name: &str,
+        slot: Option<Slot>,
+        modify: impl FnOnce(&mut VarInfo) -> R,
+    ) -> Option<R> {
+        match self.place(name, slot)? {
+            Place::Local(at) => self.locals[at].as_mut().map(modify),
+            Place::Shared(scope, position) => {
+                let mut scope = scope.borrow_mut();
+                scope.vars.at_mut(position).map(modify)
+            }
+            Place::Global(position) => self.globals.at_mut(position).map(modify),
+        }
+    }
 
-                var_info.value = value;
-                return Ok(());
+    /// Lends a variable found like `get_resolved_var` to the given function, which can modify
+    /// its value in place while reading the environment, such as the sigils or the limits.
+    /// The variable is taken out of its scope for the duration of the call.
+    ///
+    /// # Returns
+    /// The result of the function, or `None` if the variable is not defined.
+    pub fn lend_resolved_var<R>(
+        &mut self,
+        name: &str,
+        slot: Option<Slot>,
+        lend: impl FnOnce(&mut VarInfo, &Environment) -> R,
+    ) -> Option<R> {
+        let placeholder = VarInfo {
+            value: Value::Omen(false),
+            var_type: Type::Abyss,
+            is_morph: false,
+            line_info: None,
+        };
+        match self.place(name, slot)? {
+            Place::Local(at) => {
+                let mut var_info = self.locals[at].take()?;
+                let result = lend(&mut var_info, self);
+                self.locals[at] = Some(var_info);
+                Some(result)
+            }
+            Place::Shared(scope, position) => {
+                let var_info = scope
+                    .borrow_mut()
+                    .vars
+                    .at_mut(position)
+                    .map(|var_info| std::mem::replace(var_info, placeholder));
+                let mut var_info = var_info?;
+                let result = lend(&mut var_info, self);
+                if let Some(lent) = scope.borrow_mut().vars.at_mut(position) {
+                    *lent = var_info;
+                }
+                Some(result)
+            }
+            Place::Global(position) => {
+                let mut var_info = std::mem::replace(self.globals.at_mut(position)?, placeholder);
+                let result = lend(&mut var_info, self);
+                if let Some(lent) = self.globals.at_mut(position) {
+                    *lent = var_info;
+                }
+                Some(result)
             }
         }
-        Err(EvalError::UndefinedVariable(name.to_string(), line_info))
     }
 
     /// Registers a function in the current scope, associating it with its name.
     pub fn set_function(&mut self, name: String, function: Function) {
-        if let Some(current_scope) = self.function_scopes.last_mut() {
-            current_scope.insert(name, function);
-        }
+        let function = Rc::new(function);
+        match self.shared_scope_mut() {
+            Some(current_scope) => current_scope.borrow_mut().functions.insert(name, function),
+            None => self.global_functions.insert(name, function),
+        };
     }
 
-    /// Retrieves a function by name from the environment, searching from the most recent scope to the global scope.
-    pub fn get_function(&self, name: &str) -> Option<&Function> {
-        for scope in self.function_scopes.iter().rev() {
-            if let Some(function) = scope.get(name) {
-                return Some(function);
-            }
+    /// Retrieves a function by name from the environment, searching from the most recent visible scope to the global scope.
+    pub fn get_function(&self, name: &str) -> Option<Rc<Function>> {
+        match self
+            .lookup_scope(name, true)
+            .and_then(|scope| self.shared_scope(scope))
+        {
+            Some(scope) => scope.borrow().functions.get(name).cloned(),
+            None => self.global_functions.get(name).cloned(),
         }
-        None
+    }
+
+    /// Registers a native function, so that scripts can call it like a function defined by `engrave`.
+    /// Registering several functions under the same name overloads it: a call runs the first
+    /// registered function whose parameter types accept the arguments. An `arcana` too large for
+    /// 64 bits can be returned as a `BigArcana`: outside bigint mode, it raises an overflow, or is
+    /// returned as a curse if the return type is `cursed`.
+    ///
+    /// # Arguments
+    /// * `name` - The name scripts call the function by.
+    /// * `params` - The types of the parameters.
+    /// * `return_type` - The type of the result.
+    /// * `body` - The Rust closure implementing the function.
+    pub fn register_native<F>(&mut self, name: &str, params: Vec<Type>, return_type: Type, body: F)
+    where
+        F: Fn(Vec<EvalResult>) -> Result<EvalResult, String> + 'static,
+    {
+        self.natives
+            .entry(name.to_string())
+            .or_default()
+            .push(NativeFunction {
+                name: name.to_string(),
+                params,
+                return_type,
+                body: Rc::new(body),
+            });
+    }
+
+    /// Retrieves the overloads of a native function by name.
+    pub fn get_native(&self, name: &str) -> Option<&[NativeFunction]> {
+        self.natives.get(name).map(Vec::as_slice)
+    }
+
+    /// Returns every registered native function, keyed by name.
+    pub fn natives(&self) -> &HashMap<String, Vec<NativeFunction>> {
+        &self.natives
+    }
+
+    /// Registers a sigil in the current scope, associating it with its name.
+    pub fn set_sigil(&mut self, name: String, sigil: Sigil) {
+        match self.shared_scope_mut() {
+            Some(current_scope) => current_scope.borrow_mut().sigils.insert(name, sigil),
+            None => self.global_sigils.insert(name, sigil),
+        };
+    }
+
+    /// Retrieves a sigil by name from the environment, searching from the most recent visible scope to the global scope.
+    pub fn get_sigil(&self, name: &str) -> Option<Sigil> {
+        let (own, closure) = self.visible_scopes(!self.scope_warnings);
+        own.rev()
+            .filter_map(|index| self.scopes[index].shared.as_ref())
+            .chain(closure.iter().rev())
+            .find_map(|scope| scope.borrow().sigils.get(name).cloned())
+            .or_else(|| self.global_sigils.get(name).cloned())
     }
 }
 
-/// Represents the value stored in a variable, which can be a boolean (Omen), integer (Arcana),
-/// floating-point number (Aether), or string (Rune).
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Represents the value stored in a variable, which can be a boolean (Omen), integer (Arcana,
+/// or BigArcana for an integer that does not fit in 64 bits), floating-point number (Aether), string (Rune), list of values (Grimoire),
+/// map from keys to values (Codex), instance of a sigil with its fields in declaration order,
+/// a curse held by a `cursed` variable, with its message and the location where it was cast,
+/// or a function (Engrave).
 #[derive(Debug, Clone)]
 pub enum Value {
     Omen(bool),
     Arcana(i64),
+    BigArcana(BigInt),
     Aether(f64),
     Rune(String),
+    Grimoire(Vec<Value>),
+    Codex(BTreeMap<CodexKey, Value>),
+    Sigil(String, Vec<(String, Value)>),
+    Curse(String, Option<LineInfo>),
+    Engrave(Rc<Function>),
+}
+
+/// Represents a key of a codex, which can be an integer (Arcana) or a string (Rune).
+/// Keys are ordered so that codex entries are always visited in a stable order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CodexKey {
+    Arcana(i64),
+    Rune(String),
+}
+
+impl fmt::Display for CodexKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodexKey::Arcana(n) => write!(f, "{}", n),
+            CodexKey::Rune(s) => write!(f, "\"{}\"", s),
+        }
+    }
 }
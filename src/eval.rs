@@ -1,21 +1,40 @@
-use crate::ast::{AssignmentOp, ConditionalAssignment, LineInfo, Type, AST};
-use crate::env::{Environment, Function, Value};
+use crate::ast::{
+    Accessor, AssignmentOp, ConditionalAssignment, LineInfo, RunePart, Slot, Type, AST,
+};
+use crate::env::{CallFrame, Closure, CodexKey, Environment, Function, Sigil, Value, VarInfo};
+use crate::format::format_type;
+use crate::module::{check_module_access, load_module, resolve_module_path};
+use crate::resolver::Resolver;
 use colored::*;
-use std::{fmt, io::Write};
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+use std::{fmt, fs};
 
 /// Represents the result of an evaluation in the interpreter.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum EvalResult {
     Omen(bool),
     Arcana(i64),
+    BigArcana(BigInt), // An `arcana` that does not fit in 64 bits, only produced in bigint mode
     Aether(f64),
     Rune(String),
     Abyss,
-    Revealed(Box<EvalResult>),
-    Resume(Option<String>),
-    Eject(Option<String>),
+    Grimoire(Vec<EvalResult>),
+    Codex(BTreeMap<CodexKey, EvalResult>),
+    Sigil(String, Vec<(String, EvalResult)>),
+    Curse(String, Option<LineInfo>),
+    Engrave(Rc<Function>), // A function value, defined anonymously or referred to by name
 }
 
+/// The name of a function defined by an `engrave` expression, shown in call stack traces.
+pub const ANONYMOUS_ENGRAVE: &str = "<engrave>";
+
 /// Represents possible errors that can occur during evaluation.
 #[derive(Debug)]
 pub enum EvalError {
@@ -23,6 +42,81 @@ pub enum EvalError {
     InvalidOperation(String, Option<LineInfo>),
     NegativeExponent(Option<LineInfo>),
     TypeError(String, Option<LineInfo>),
+    KeyNotFound(String, Option<LineInfo>),
+    ModuleError(String, Option<LineInfo>),
+    Curse(String, Option<LineInfo>),
+    IoError(String, Option<LineInfo>),
+    ArithmeticOverflow(String, Option<LineInfo>),
+    DivisionByZero(Option<LineInfo>),
+    Halted(String, Option<LineInfo>),
+    StackOverflow(String, Option<LineInfo>),
+    LimitExceeded(String, Option<LineInfo>),
+}
+
+impl EvalError {
+    /// Returns the line information of the source location where the error occurred.
+    pub fn line_info(&self) -> Option<LineInfo> {
+        match self {
+            EvalError::UndefinedVariable(_, line_info)
+            | EvalError::InvalidOperation(_, line_info)
+            | EvalError::NegativeExponent(line_info)
+            | EvalError::TypeError(_, line_info)
+            | EvalError::KeyNotFound(_, line_info)
+            | EvalError::ModuleError(_, line_info)
+            | EvalError::Curse(_, line_info)
+            | EvalError::IoError(_, line_info)
+            | EvalError::ArithmeticOverflow(_, line_info)
+            | EvalError::DivisionByZero(line_info)
+            | EvalError::Halted(_, line_info)
+            | EvalError::StackOverflow(_, line_info)
+            | EvalError::LimitExceeded(_, line_info) => line_info.clone(),
+        }
+    }
+
+    /// Returns true if the error stops the script whatever happens, like a debugger stopping
+    /// it or a resource limit being exceeded, so that `attempt` must not turn it into a curse.
+    pub fn is_halt(&self) -> bool {
+        matches!(
+            self,
+            EvalError::Halted(_, _) | EvalError::LimitExceeded(_, _)
+        )
+    }
+
+    /// Marks the error as having occurred in the given file, unless it already names a file.
+    /// Used when an error escapes a module invoked from another script.
+    pub fn in_file(mut self, file: Option<String>) -> Self {
+        let line_info = match &mut self {
+            EvalError::UndefinedVariable(_, line_info)
+            | EvalError::InvalidOperation(_, line_info)
+            | EvalError::NegativeExponent(line_info)
+            | EvalError::TypeError(_, line_info)
+            | EvalError::KeyNotFound(_, line_info)
+            | EvalError::ModuleError(_, line_info)
+            | EvalError::Curse(_, line_info)
+            | EvalError::IoError(_, line_info)
+            | EvalError::ArithmeticOverflow(_, line_info)
+            | EvalError::DivisionByZero(line_info)
+            | EvalError::Halted(_, line_info)
+            | EvalError::StackOverflow(_, line_info)
+            | EvalError::LimitExceeded(_, line_info) => line_info,
+        };
+        if let Some(line_info) = line_info {
+            if line_info.file.is_none() {
+                line_info.file = file;
+            }
+        }
+        self
+    }
+
+    /// Converts the error into a curse that can be caught by `attempt`.
+    /// A curse raised by the script keeps its own message.
+    pub fn into_curse(self) -> EvalResult {
+        let line_info = self.line_info();
+        match self {
+            EvalError::Curse(message, _) => EvalResult::Curse(message, line_info),
+            e => EvalResult::Curse(e.to_string(), line_info),
+        }
+    }
 }
 
 impl fmt::Display for EvalError {
@@ -34,817 +128,3202 @@ impl fmt::Display for EvalError {
                 write!(f, "PowArcana operation requires a non-negative exponent!")
             }
             EvalError::TypeError(var_type, _) => write!(f, "Type error: {}", var_type),
+            EvalError::KeyNotFound(key, _) => write!(f, "Key {} is not found in codex!", key),
+            EvalError::ModuleError(message, _) => write!(f, "Module error: {}", message),
+            EvalError::Curse(message, _) => write!(f, "Curse: {}", message),
+            EvalError::IoError(message, _) => write!(f, "I/O error: {}", message),
+            EvalError::ArithmeticOverflow(operation, _) => {
+                write!(
+                    f,
+                    "Arithmetic overflow: {} does not fit in arcana!",
+                    operation
+                )
+            }
+            EvalError::DivisionByZero(_) => write!(f, "Division by zero!"),
+            EvalError::Halted(reason, _) => write!(f, "Execution halted: {}", reason),
+            EvalError::StackOverflow(message, _) => write!(f, "Stack overflow: {}", message),
+            EvalError::LimitExceeded(message, _) => write!(f, "Limit exceeded: {}", message),
         }
     }
 }
 impl std::error::Error for EvalError {}
 
-/// Displays an error message along with the relevant source code and line information, if available.
-pub fn display_error_with_source(script: &str, line_info: Option<LineInfo>, error_message: &str) {
-    if let Some(info) = line_info {
-        let lines: Vec<&str> = script.lines().collect();
-        if let Some(source_line) = lines.get(info.line - 1) {
-            // Line numbers start from 1, so we subtract 1
-            eprintln!(
-                "{}",
+/// A transfer of control raised by `reveal`, `resume` or `eject`. It travels apart from the
+/// values of expressions and leaves every node up to the one that handles it: the call of the
+/// enclosing function for `reveal`, and the enclosing `orbit` (or the `orbit` over the named
+/// variable) for `resume` and `eject`.
+#[derive(Debug)]
+enum ControlFlow {
+    Reveal(EvalResult),
+    Resume(Option<String>),
+    Eject(Option<String>),
+}
+
+impl ControlFlow {
+    /// Returns true if the control is handled by the `orbit` over the variable `name`,
+    /// or by an `orbit` without parameters when `name` is `None`.
+    fn is_for_loop(&self, name: Option<&str>) -> bool {
+        match self {
+            ControlFlow::Reveal(_) => false,
+            ControlFlow::Resume(label) | ControlFlow::Eject(label) => {
+                label.is_none() || label.as_deref() == name
+            }
+        }
+    }
+}
+
+/// Why the evaluation of a node stopped before producing a value: an error, or a transfer of
+/// control located at the statement that raised it.
+#[derive(Debug)]
+enum Unwind {
+    Error(EvalError),
+    Control(ControlFlow, Option<LineInfo>),
+}
+
+impl From<EvalError> for Unwind {
+    fn from(error: EvalError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+impl Unwind {
+    /// Converts a transfer of control that left the function or loop it may be used in
+    /// into the error it amounts to.
+    fn into_error(self) -> EvalError {
+        match self {
+            Unwind::Error(e) => e,
+            Unwind::Control(control, line_info) => {
+                let message = match &control {
+                    ControlFlow::Reveal(_) => misplaced_control("reveal", None),
+                    ControlFlow::Resume(label) => misplaced_control("resume", label.as_deref()),
+                    ControlFlow::Eject(label) => misplaced_control("eject", label.as_deref()),
+                };
+                EvalError::InvalidOperation(message, line_info)
+            }
+        }
+    }
+}
+
+/// Describes a `reveal` outside of any function, or a `resume` or `eject` outside of the
+/// `orbit` it refers to. The resolver rejects them before a script runs, and both engines
+/// raise the same error if one is evaluated anyway.
+///
+/// # Arguments
+/// * `keyword` - `reveal`, `resume` or `eject`.
+/// * `label` - The loop variable named by `resume` or `eject`, if any.
+pub fn misplaced_control(keyword: &str, label: Option<&str>) -> String {
+    match (keyword, label) {
+        ("reveal", _) => "reveal can only be used inside an engrave function".to_string(),
+        (keyword, None) => format!("{} can only be used inside an orbit", keyword),
+        (keyword, Some(label)) => {
+            format!("{} {} does not name an enclosing orbit", keyword, label)
+        }
+    }
+}
+
+impl fmt::Display for EvalResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalResult::Omen(b) => write!(f, "{}", if *b { "boon" } else { "hex" }),
+            EvalResult::Arcana(n) => write!(f, "{}", n),
+            EvalResult::BigArcana(n) => write!(f, "{}", n),
+            EvalResult::Aether(n) => write!(f, "{}", n),
+            EvalResult::Rune(s) => write!(f, "{}", s),
+            EvalResult::Grimoire(items) => {
+                let items: Vec<String> = items.iter().map(format_element).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            EvalResult::Codex(entries) if entries.is_empty() => write!(f, "[:]"),
+            EvalResult::Codex(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, format_element(value)))
+                    .collect();
+                write!(f, "[{}]", entries.join(", "))
+            }
+            EvalResult::Sigil(name, fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, value)| format!("{}: {}", field, format_element(value)))
+                    .collect();
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
+            }
+            EvalResult::Curse(message, _) => write!(f, "curse(\"{}\")", message),
+            EvalResult::Engrave(function) => write!(f, "{}", format_type(&function.signature())),
+            EvalResult::Abyss => Ok(()),
+        }
+    }
+}
+
+/// Formats an element of a collection, quoting runes so that they stand apart from other values.
+pub fn format_element(result: &EvalResult) -> String {
+    match result {
+        EvalResult::Rune(s) => format!("\"{}\"", s),
+        _ => result.to_string(),
+    }
+}
+
+/// Converts a stored variable value into an evaluation result.
+pub fn value_to_result(value: &Value) -> EvalResult {
+    match value {
+        Value::Omen(b) => EvalResult::Omen(*b),
+        Value::Arcana(n) => EvalResult::Arcana(*n),
+        Value::BigArcana(n) => EvalResult::BigArcana(n.clone()),
+        Value::Aether(n) => EvalResult::Aether(*n),
+        Value::Rune(s) => EvalResult::Rune(s.clone()),
+        Value::Grimoire(items) => EvalResult::Grimoire(items.iter().map(value_to_result).collect()),
+        Value::Codex(entries) => EvalResult::Codex(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), value_to_result(value)))
+                .collect(),
+        ),
+        Value::Sigil(name, fields) => EvalResult::Sigil(
+            name.clone(),
+            fields
+                .iter()
+                .map(|(field, value)| (field.clone(), value_to_result(value)))
+                .collect(),
+        ),
+        Value::Curse(message, line_info) => EvalResult::Curse(message.clone(), line_info.clone()),
+        Value::Engrave(function) => EvalResult::Engrave(Rc::clone(function)),
+    }
+}
+
+/// Converts an evaluation result into a value of the expected type.
+/// Returns `None` if the result does not conform to the expected type.
+pub fn result_to_value(result: EvalResult, expected: &Type) -> Option<Value> {
+    match (result, expected) {
+        (EvalResult::Curse(message, line_info), Type::Cursed(_)) => {
+            Some(Value::Curse(message, line_info))
+        }
+        (result, Type::Cursed(value_type)) => result_to_value(result, value_type),
+        (EvalResult::Omen(b), Type::Omen) => Some(Value::Omen(b)),
+        (EvalResult::Arcana(n), Type::Arcana) => Some(Value::Arcana(n)),
+        (EvalResult::BigArcana(n), Type::Arcana) => Some(Value::BigArcana(n)),
+        (EvalResult::Aether(n), Type::Aether) => Some(Value::Aether(n)),
+        (EvalResult::Rune(s), Type::Rune) => Some(Value::Rune(s)),
+        (EvalResult::Grimoire(items), Type::Grimoire(element_type)) => items
+            .into_iter()
+            .map(|item| result_to_value(item, element_type))
+            .collect::<Option<Vec<Value>>>()
+            .map(Value::Grimoire),
+        (EvalResult::Codex(entries), Type::Codex(key_type, value_type)) => entries
+            .into_iter()
+            .map(|(key, value)| {
+                if key_type_of(&key) != **key_type {
+                    return None;
+                }
+                result_to_value(value, value_type).map(|value| (key, value))
+            })
+            .collect::<Option<BTreeMap<CodexKey, Value>>>()
+            .map(Value::Codex),
+        (EvalResult::Sigil(name, fields), Type::Sigil(expected)) if name == *expected => fields
+            .into_iter()
+            .map(|(field, value)| typed_value(value).map(|(value, _)| (field, value)))
+            .collect::<Option<Vec<(String, Value)>>>()
+            .map(|fields| Value::Sigil(name, fields)),
+        (EvalResult::Engrave(function), Type::Engrave(_, _))
+            if function.signature() == *expected =>
+        {
+            Some(Value::Engrave(function))
+        }
+        _ => None,
+    }
+}
+
+/// Raises a curse that is about to be stored where a `cursed` type is not declared,
+/// so that it propagates as an error instead of being stored.
+fn raise_curse(result: EvalResult, expected: &Type) -> Result<EvalResult, EvalError> {
+    match (result, expected) {
+        (result, Type::Cursed(_)) => Ok(result),
+        (EvalResult::Curse(message, line_info), _) => Err(EvalError::Curse(message, line_info)),
+        (result, _) => Ok(result),
+    }
+}
+
+/// Drops the value of a statement that nothing uses, raising it if it is a curse that was not
+/// caught, since a curse that is neither stored nor caught stops the script.
+pub fn drop_result(result: EvalResult, statement: &AST) -> Result<(), EvalError> {
+    match result {
+        EvalResult::Curse(message, line_info) if !is_caught(statement) => {
+            Err(EvalError::Curse(message, line_info))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Returns true if a curse the value of a statement holds was already caught: by `attempt`, or
+/// when it was stored in the variable, element or field the statement reads. This looks through
+/// the last statement of a block and of the branches of an oracle.
+pub fn is_caught(statement: &AST) -> bool {
+    match statement {
+        AST::Attempt(_, _) | AST::Var(_, _, _) | AST::Index(_, _, _) | AST::Field(_, _, _) => true,
+        AST::Statement(node, _) => is_caught(node),
+        AST::Block(statements, _) => statements.last().is_some_and(is_caught),
+        AST::Oracle { branches, .. } => branches.iter().any(|branch| match branch {
+            AST::OracleBranch { body, .. } => is_caught(body),
+            _ => false,
+        }),
+        _ => false,
+    }
+}
+
+/// Converts an evaluation result into a value together with its derived type.
+fn typed_value(result: EvalResult) -> Option<(Value, Type)> {
+    let var_type = type_of_result(&result)?;
+    result_to_value(result, &var_type).map(|value| (value, var_type))
+}
+
+/// Converts a codex key into a value that can be bound to a variable.
+fn value_of_key(key: &CodexKey) -> Value {
+    match key {
+        CodexKey::Arcana(n) => Value::Arcana(*n),
+        CodexKey::Rune(s) => Value::Rune(s.clone()),
+    }
+}
+
+/// Returns the type of a codex key.
+fn key_type_of(key: &CodexKey) -> Type {
+    match key {
+        CodexKey::Arcana(_) => Type::Arcana,
+        CodexKey::Rune(_) => Type::Rune,
+    }
+}
+
+/// Converts an evaluation result into a codex key of the expected key type.
+fn result_to_key(
+    result: EvalResult,
+    key_type: &Type,
+    line_info: &Option<LineInfo>,
+) -> Result<CodexKey, EvalError> {
+    match (result, key_type) {
+        (EvalResult::Arcana(n), Type::Arcana | Type::Abyss) => Ok(CodexKey::Arcana(n)),
+        (EvalResult::BigArcana(n), Type::Arcana | Type::Abyss) => Err(EvalError::InvalidOperation(
+            format!("Codex key {} does not fit in 64 bits", n),
+            line_info.clone(),
+        )),
+        (EvalResult::Rune(s), Type::Rune | Type::Abyss) => Ok(CodexKey::Rune(s)),
+        (result, _) => {
+            let expected = match key_type {
+                Type::Abyss => "Arcana or Rune".to_string(),
+                _ => format!("{:?}", key_type),
+            };
+            Err(EvalError::TypeError(
                 format!(
-                    "Error at line {}, column {}: {}",
-                    info.line, info.column, error_message
-                )
-                .red()
-            );
-            eprintln!("  {}", source_line.red());
-            eprintln!("  {}{}", " ".repeat(info.column - 1).red(), "^".red());
-        } else {
-            eprintln!("{}", format!("Error: {}", error_message).red());
+                    "Codex key must be of type {}, found {:?}",
+                    expected,
+                    type_of_result(&result)
+                ),
+                line_info.clone(),
+            ))
         }
-    } else {
-        eprintln!("{}", format!("Error: {}", error_message).red());
     }
 }
 
-/// Evaluates an abstract syntax tree (AST) node in the given environment.
+/// Returns the type of an evaluation result that can be stored in a variable.
+/// The element type of an empty grimoire or codex is unknown and reported as `abyss`.
+pub fn type_of_result(result: &EvalResult) -> Option<Type> {
+    match result {
+        EvalResult::Omen(_) => Some(Type::Omen),
+        EvalResult::Arcana(_) | EvalResult::BigArcana(_) => Some(Type::Arcana),
+        EvalResult::Aether(_) => Some(Type::Aether),
+        EvalResult::Rune(_) => Some(Type::Rune),
+        EvalResult::Grimoire(items) => {
+            let element_type = match items.first() {
+                Some(item) => type_of_result(item)?,
+                None => Type::Abyss,
+            };
+            Some(Type::Grimoire(Box::new(element_type)))
+        }
+        EvalResult::Codex(entries) => {
+            let (key_type, value_type) = match entries.iter().next() {
+                Some((key, value)) => (key_type_of(key), type_of_result(value)?),
+                None => (Type::Abyss, Type::Abyss),
+            };
+            Some(Type::Codex(Box::new(key_type), Box::new(value_type)))
+        }
+        EvalResult::Sigil(name, _) => Some(Type::Sigil(name.clone())),
+        EvalResult::Curse(_, _) => Some(Type::Cursed(Box::new(Type::Abyss))),
+        EvalResult::Engrave(function) => Some(function.signature()),
+        _ => None,
+    }
+}
+
+/// Represents a binary operator, applied to two already evaluated operands by `binary_op`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    PowArcana,
+    PowAether,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LogicalAnd,
+    LogicalOr,
+}
+
+/// Returns whether a result is an `arcana` value, in either of its representations.
+fn is_arcana(result: &EvalResult) -> bool {
+    matches!(result, EvalResult::Arcana(_) | EvalResult::BigArcana(_))
+}
+
+/// Converts an `arcana` result into an unbounded integer.
+fn to_big(result: &EvalResult) -> Option<BigInt> {
+    match result {
+        EvalResult::Arcana(n) => Some(BigInt::from(*n)),
+        EvalResult::BigArcana(n) => Some(n.clone()),
+        _ => None,
+    }
+}
+
+/// Returns the `arcana` result holding an unbounded integer, which keeps the 64-bit
+/// representation whenever the integer fits in it.
+pub fn big_arcana(n: BigInt) -> EvalResult {
+    match n.to_i64() {
+        Some(n) => EvalResult::Arcana(n),
+        None => EvalResult::BigArcana(n),
+    }
+}
+
+/// Returns the `arcana` result for an integer literal that does not fit in 64 bits, which is
+/// only allowed in bigint mode.
 ///
 /// # Arguments
+/// * `n` - The value of the literal.
+/// * `bigint` - Whether `arcana` values are unbounded.
+/// * `line_info` - The location of the literal, used for errors.
+pub fn big_arcana_literal(
+    n: &BigInt,
+    bigint: bool,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    match bigint {
+        true => Ok(big_arcana(n.clone())),
+        false => Err(EvalError::ArithmeticOverflow(
+            n.to_string(),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Converts an aether to an arcana, dropping its fractional part. A value that does not fit in
+/// 64 bits is returned as an unbounded integer, which only bigint mode keeps.
 ///
-/// * `ast` - The AST node to be evaluated.
-/// * `env` - The environment containing variable and function bindings.
+/// # Returns
+/// The arcana, or `None` for NaN and infinities.
+pub fn aether_to_arcana(n: f64) -> Option<EvalResult> {
+    if (i64::MIN as f64..i64::MAX as f64).contains(&n) {
+        return Some(EvalResult::Arcana(n as i64));
+    }
+    BigInt::from_f64(n).map(big_arcana)
+}
+
+/// Parses an `arcana` value from a rune. Integers that do not fit in 64 bits are only parsed
+/// in bigint mode.
+pub fn parse_arcana(s: &str, bigint: bool) -> Option<EvalResult> {
+    match s.parse::<i64>() {
+        Ok(n) => Some(EvalResult::Arcana(n)),
+        Err(_) if bigint => s.parse::<BigInt>().ok().map(big_arcana),
+        Err(_) => None,
+    }
+}
+
+/// Compares two `arcana` results, in either of their representations.
+pub fn arcana_cmp(left: &EvalResult, right: &EvalResult) -> Ordering {
+    match (left, right) {
+        (EvalResult::Arcana(l), EvalResult::Arcana(r)) => l.cmp(r),
+        _ => to_big(left).cmp(&to_big(right)),
+    }
+}
+
+/// Applies an arithmetic operator to two `arcana` results. The 64-bit representation is used
+/// while the operands and the result fit in it; in bigint mode, a result that does not fit is
+/// computed on unbounded integers instead of raising an overflow.
+///
+/// # Arguments
+/// * `op` - The operator: `Add`, `Sub`, `Mul`, `Div`, `Mod` or `PowArcana`.
+/// * `left` - The left operand.
+/// * `right` - The right operand.
+/// * `bigint` - Whether `arcana` values are unbounded.
+/// * `line_info` - The location of the operator, used for errors.
 ///
 /// # Returns
+/// The result of the operation, or the error raised by it.
+pub fn arcana_arithmetic(
+    op: BinaryOp,
+    left: &EvalResult,
+    right: &EvalResult,
+    bigint: bool,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    if let (EvalResult::Arcana(l), EvalResult::Arcana(r)) = (left, right) {
+        match checked_arcana(op, *l, *r, line_info) {
+            Err(EvalError::ArithmeticOverflow(_, _)) if bigint => {}
+            result => return result.map(EvalResult::Arcana),
+        }
+    }
+    match (to_big(left), to_big(right)) {
+        (Some(l), Some(r)) => big_arithmetic(op, l, r, line_info),
+        _ => Err(EvalError::InvalidOperation(
+            format!("{:?} operation requires two Arcana!", op),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Applies an arithmetic operator to two unbounded integers.
+fn big_arithmetic(
+    op: BinaryOp,
+    l: BigInt,
+    r: BigInt,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let result = match op {
+        BinaryOp::Add => l + r,
+        BinaryOp::Sub => l - r,
+        BinaryOp::Mul => l * r,
+        BinaryOp::Div | BinaryOp::Mod if r.is_zero() => {
+            return Err(EvalError::DivisionByZero(line_info.clone()))
+        }
+        BinaryOp::Div => l / r,
+        BinaryOp::Mod => l % r,
+        BinaryOp::PowArcana if r.is_negative() => {
+            return Err(EvalError::NegativeExponent(line_info.clone()))
+        }
+        BinaryOp::PowArcana => match r.to_u32() {
+            Some(r) => l.pow(r),
+            None if l.is_zero() || l.is_one() => l,
+            None if l == BigInt::from(-1) && (&r % 2u32).is_zero() => BigInt::one(),
+            None if l == BigInt::from(-1) => l,
+            None => {
+                return Err(EvalError::ArithmeticOverflow(
+                    format!("{} ^ {}", l, r),
+                    line_info.clone(),
+                ))
+            }
+        },
+        _ => {
+            return Err(EvalError::InvalidOperation(
+                format!("{:?} is not an arithmetic operation!", op),
+                line_info.clone(),
+            ))
+        }
+    };
+    Ok(big_arcana(result))
+}
+
+/// Applies an arithmetic operator to two `arcana` values, detecting the results that do not
+/// fit in 64 bits and divisions by zero.
 ///
-/// The result of the evaluation, or an `EvalError` if an error occurs.
-pub fn evaluate(ast: &AST, env: &mut Environment) -> Result<EvalResult, EvalError> {
-    match ast {
-        AST::Statement(node, _line_info) => evaluate(node, env),
-        AST::Omen(b, _line_info) => Ok(EvalResult::Omen(*b)),
-        AST::Arcana(n, _line_info) => Ok(EvalResult::Arcana(*n)),
-        AST::Aether(n, _line_info) => Ok(EvalResult::Aether(*n)),
-        AST::Rune(s, _line_info) => Ok(EvalResult::Rune(s.clone())),
-        AST::Abyss(_line_info) => Ok(EvalResult::Abyss),
-        AST::Add(left, right, line_info) => match (evaluate(left, env)?, evaluate(right, env)?) {
-            (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Arcana(l + r)),
+/// # Arguments
+/// * `op` - The operator: `Add`, `Sub`, `Mul`, `Div`, `Mod` or `PowArcana`.
+/// * `l` - The left operand.
+/// * `r` - The right operand.
+/// * `line_info` - The location of the operator, used for errors.
+///
+/// # Returns
+/// The result of the operation, or an `ArithmeticOverflow`, `DivisionByZero` or
+/// `NegativeExponent` error.
+pub fn checked_arcana(
+    op: BinaryOp,
+    l: i64,
+    r: i64,
+    line_info: &Option<LineInfo>,
+) -> Result<i64, EvalError> {
+    let (result, symbol) = match op {
+        BinaryOp::Add => (l.checked_add(r), "+"),
+        BinaryOp::Sub => (l.checked_sub(r), "-"),
+        BinaryOp::Mul => (l.checked_mul(r), "*"),
+        BinaryOp::Div | BinaryOp::Mod if r == 0 => {
+            return Err(EvalError::DivisionByZero(line_info.clone()))
+        }
+        BinaryOp::Div => (l.checked_div(r), "/"),
+        BinaryOp::Mod => (l.checked_rem(r), "%"),
+        BinaryOp::PowArcana if r < 0 => return Err(EvalError::NegativeExponent(line_info.clone())),
+        BinaryOp::PowArcana => {
+            let result = match u32::try_from(r) {
+                Ok(r) => l.checked_pow(r),
+                // Only 0, 1 and -1 can be raised to such a power without overflowing.
+                Err(_) => match l {
+                    0 | 1 => Some(l),
+                    -1 => Some(if r % 2 == 0 { 1 } else { -1 }),
+                    _ => None,
+                },
+            };
+            (result, "^")
+        }
+        _ => {
+            return Err(EvalError::InvalidOperation(
+                format!("{:?} is not an arithmetic operation!", op),
+                line_info.clone(),
+            ))
+        }
+    };
+    result.ok_or_else(|| {
+        EvalError::ArithmeticOverflow(format!("{} {} {}", l, symbol, r), line_info.clone())
+    })
+}
+
+/// Applies a binary operator to two evaluated operands.
+///
+/// # Arguments
+/// * `op` - The operator.
+/// * `left` - The value of the left operand.
+/// * `right` - The value of the right operand.
+/// * `line_info` - The location of the operation, used for errors.
+///
+/// # Returns
+/// The result of the operation, or an error if the operands do not support it.
+pub fn binary_op(
+    op: BinaryOp,
+    left: EvalResult,
+    right: EvalResult,
+    bigint: bool,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let invalid = |message: &str| {
+        Err(EvalError::InvalidOperation(
+            message.to_string(),
+            line_info.clone(),
+        ))
+    };
+    match op {
+        BinaryOp::Add => match (left, right) {
+            (l, r) if is_arcana(&l) && is_arcana(&r) => {
+                arcana_arithmetic(op, &l, &r, bigint, line_info)
+            }
             (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Aether(l + r)),
             (EvalResult::Rune(l), EvalResult::Rune(r)) => {
                 Ok(EvalResult::Rune(format!("{}{}", l, r)))
             }
-            _ => Err(EvalError::InvalidOperation(
-                "Add operation requires either two Arcana, two Aether, or two Rune!".to_string(),
-                line_info.clone(),
-            )),
+            _ => invalid("Add operation requires either two Arcana, two Aether, or two Rune!"),
         },
-        AST::Sub(left, right, line_info) => match (evaluate(left, env)?, evaluate(right, env)?) {
-            (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Arcana(l - r)),
+        BinaryOp::Sub => match (left, right) {
+            (l, r) if is_arcana(&l) && is_arcana(&r) => {
+                arcana_arithmetic(op, &l, &r, bigint, line_info)
+            }
             (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Aether(l - r)),
-            _ => Err(EvalError::InvalidOperation(
-                "Subtract operation requires either two Arcana or two Aether!".to_string(),
-                line_info.clone(),
-            )),
+            _ => invalid("Subtract operation requires either two Arcana or two Aether!"),
         },
-        AST::Mul(left, right, line_info) => match (evaluate(left, env)?, evaluate(right, env)?) {
-            (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Arcana(l * r)),
+        BinaryOp::Mul => match (left, right) {
+            (l, r) if is_arcana(&l) && is_arcana(&r) => {
+                arcana_arithmetic(op, &l, &r, bigint, line_info)
+            }
             (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Aether(l * r)),
-            _ => Err(EvalError::InvalidOperation(
-                "Multiply operation requires either two Arcana or two Aether!".to_string(),
-                line_info.clone(),
-            )),
+            _ => invalid("Multiply operation requires either two Arcana or two Aether!"),
         },
-        AST::Div(left, right, line_info) => match (evaluate(left, env)?, evaluate(right, env)?) {
-            (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Arcana(l / r)),
+        BinaryOp::Div => match (left, right) {
+            (l, r) if is_arcana(&l) && is_arcana(&r) => {
+                arcana_arithmetic(op, &l, &r, bigint, line_info)
+            }
             (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Aether(l / r)),
-            _ => Err(EvalError::InvalidOperation(
-                "Divide operation requires either two Arcana or two Aether!".to_string(),
-                line_info.clone(),
-            )),
+            _ => invalid("Divide operation requires either two Arcana or two Aether!"),
         },
-        AST::Mod(left, right, line_info) => match (evaluate(left, env)?, evaluate(right, env)?) {
-            (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Arcana(l % r)),
+        BinaryOp::Mod => match (left, right) {
+            (l, r) if is_arcana(&l) && is_arcana(&r) => {
+                arcana_arithmetic(op, &l, &r, bigint, line_info)
+            }
             (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Aether(l % r)),
-            _ => Err(EvalError::InvalidOperation(
-                "Modulo operation requires either two Arcana or two Aether!".to_string(),
-                line_info.clone(),
-            )),
+            _ => invalid("Modulo operation requires either two Arcana or two Aether!"),
         },
-        AST::PowArcana(left, right, line_info) => {
-            match (evaluate(left, env)?, evaluate(right, env)?) {
-                (EvalResult::Arcana(l), EvalResult::Arcana(r)) => {
-                    if r < 0 {
-                        return Err(EvalError::NegativeExponent(line_info.clone()));
-                    }
-                    Ok(EvalResult::Arcana(l.pow(r as u32)))
-                }
-                _ => Err(EvalError::InvalidOperation(
-                    "PowArcana operation requires two Arcana!".to_string(),
-                    line_info.clone(),
-                )),
+        BinaryOp::PowArcana => match (left, right) {
+            (l, r) if is_arcana(&l) && is_arcana(&r) => {
+                arcana_arithmetic(op, &l, &r, bigint, line_info)
             }
+            _ => invalid("PowArcana operation requires two Arcana!"),
+        },
+        BinaryOp::PowAether => match (left, right) {
+            (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Aether(l.powf(r))),
+            _ => invalid("PowAether operation requires two Aether!"),
+        },
+        BinaryOp::Equal | BinaryOp::NotEqual => {
+            let equal = match (left, right) {
+                (l, r) if is_arcana(&l) && is_arcana(&r) => arcana_cmp(&l, &r).is_eq(),
+                (EvalResult::Aether(l), EvalResult::Aether(r)) => (l - r).abs() < f64::EPSILON,
+                (EvalResult::Rune(l), EvalResult::Rune(r)) => l == r,
+                _ => return invalid("Comparison requires compatible types!"),
+            };
+            Ok(EvalResult::Omen(equal == (op == BinaryOp::Equal)))
         }
-        AST::PowAether(left, right, line_info) => {
-            match (evaluate(left, env)?, evaluate(right, env)?) {
-                (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Aether(l.powf(r))),
-                _ => Err(EvalError::InvalidOperation(
-                    "PowAether operation requires two Aether!".to_string(),
-                    line_info.clone(),
-                )),
-            }
+        BinaryOp::LessThan
+        | BinaryOp::LessThanOrEqual
+        | BinaryOp::GreaterThan
+        | BinaryOp::GreaterThanOrEqual => {
+            let ordering = match (left, right) {
+                (l, r) if is_arcana(&l) && is_arcana(&r) => Some(arcana_cmp(&l, &r)),
+                (EvalResult::Aether(l), EvalResult::Aether(r)) => l.partial_cmp(&r),
+                _ => return invalid("Comparison requires numeric types!"),
+            };
+            let holds = match (op, ordering) {
+                (_, None) => false,
+                (BinaryOp::LessThan, Some(ordering)) => ordering.is_lt(),
+                (BinaryOp::LessThanOrEqual, Some(ordering)) => ordering.is_le(),
+                (BinaryOp::GreaterThan, Some(ordering)) => ordering.is_gt(),
+                (_, Some(ordering)) => ordering.is_ge(),
+            };
+            Ok(EvalResult::Omen(holds))
         }
-        AST::Equal(left, right, line_info) => match (evaluate(left, env)?, evaluate(right, env)?) {
-            (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Omen(l == r)),
-            (EvalResult::Aether(l), EvalResult::Aether(r)) => {
-                Ok(EvalResult::Omen((l - r).abs() < std::f64::EPSILON))
-            }
-            (EvalResult::Rune(l), EvalResult::Rune(r)) => Ok(EvalResult::Omen(l == r)),
-            _ => Err(EvalError::InvalidOperation(
-                "Comparison requires compatible types!".to_string(),
+        BinaryOp::LogicalAnd => match (left, right) {
+            (EvalResult::Omen(l), EvalResult::Omen(r)) => Ok(EvalResult::Omen(l && r)),
+            _ => invalid("LogicalAnd operation requires two Omen!"),
+        },
+        BinaryOp::LogicalOr => match (left, right) {
+            (EvalResult::Omen(l), EvalResult::Omen(r)) => Ok(EvalResult::Omen(l || r)),
+            _ => invalid("LogicalOr operation requires two Omen!"),
+        },
+    }
+}
+
+/// Applies `!` to an evaluated operand.
+pub fn logical_not(
+    result: EvalResult,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    match result {
+        EvalResult::Omen(value) => Ok(EvalResult::Omen(!value)),
+        _ => Err(EvalError::InvalidOperation(
+            "LogicalNot operation requires Omen!".to_string(),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Applies an assignment operator to the current value of a variable (or of an element of it).
+fn apply_assignment_op(
+    current: &Value,
+    target_type: &Type,
+    value: EvalResult,
+    op: &AssignmentOp,
+    name: &str,
+    env: &Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<Value, EvalError> {
+    let unsupported = || {
+        EvalError::InvalidOperation(
+            format!("Unsupported operation for variable {}", name),
+            line_info.clone(),
+        )
+    };
+
+    if let AssignmentOp::Assign = op {
+        return result_to_value(raise_curse(value, target_type)?, target_type).ok_or_else(|| {
+            EvalError::InvalidOperation(
+                format!(
+                    "Type mismatch or unsupported operation for variable {}",
+                    name
+                ),
                 line_info.clone(),
-            )),
+            )
+        });
+    }
+
+    match (value, current) {
+        (v, Value::Arcana(_) | Value::BigArcana(_)) if is_arcana(&v) => {
+            let op = match op {
+                AssignmentOp::AddAssign => BinaryOp::Add,
+                AssignmentOp::SubAssign => BinaryOp::Sub,
+                AssignmentOp::MulAssign => BinaryOp::Mul,
+                AssignmentOp::DivAssign => BinaryOp::Div,
+                AssignmentOp::ModAssign => BinaryOp::Mod,
+                AssignmentOp::PowArcanaAssign => BinaryOp::PowArcana,
+                _ => return Err(unsupported()),
+            };
+            let current = value_to_result(current);
+            env.check_arcana_operation(op, &current, &v, line_info)?;
+            let new_value = arcana_arithmetic(op, &current, &v, env.bigint(), line_info)?;
+            Ok(result_to_value(new_value, &Type::Arcana).ok_or_else(unsupported)?)
+        }
+        (EvalResult::Aether(v), Value::Aether(current)) => {
+            let new_value = match op {
+                AssignmentOp::AddAssign => current + v,
+                AssignmentOp::SubAssign => current - v,
+                AssignmentOp::MulAssign => current * v,
+                AssignmentOp::DivAssign => current / v,
+                AssignmentOp::ModAssign => current % v,
+                AssignmentOp::PowAetherAssign => current.powf(v),
+                _ => return Err(unsupported()),
+            };
+            Ok(Value::Aether(new_value))
+        }
+        (EvalResult::Rune(v), Value::Rune(current)) => match op {
+            AssignmentOp::AddAssign => Ok(Value::Rune(format!("{}{}", current, v))),
+            _ => Err(unsupported()),
         },
-        AST::NotEqual(left, right, line_info) => {
-            match (evaluate(left, env)?, evaluate(right, env)?) {
-                (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Omen(l != r)),
-                (EvalResult::Aether(l), EvalResult::Aether(r)) => {
-                    Ok(EvalResult::Omen((l - r).abs() >= std::f64::EPSILON))
-                }
-                (EvalResult::Rune(l), EvalResult::Rune(r)) => Ok(EvalResult::Omen(l != r)),
-                _ => Err(EvalError::InvalidOperation(
-                    "Comparison requires compatible types!".to_string(),
-                    line_info.clone(),
-                )),
-            }
+        _ => Err(EvalError::InvalidOperation(
+            format!(
+                "Type mismatch or unsupported operation for variable {}",
+                name
+            ),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Converts the evaluated initial value of a variable declared with `forge` into a value of its
+/// declared type.
+pub fn declared_value(
+    result: EvalResult,
+    var_type: &Type,
+    line_info: &Option<LineInfo>,
+) -> Result<Value, EvalError> {
+    let result = raise_curse(result, var_type)?;
+    result_to_value(result, var_type).ok_or_else(|| {
+        EvalError::InvalidOperation(
+            "VarAssign operation requires a valid type!".to_string(),
+            line_info.clone(),
+        )
+    })
+}
+
+/// Creates an instance of a sigil from the evaluated values of its fields, given in any order.
+///
+/// # Arguments
+/// * `name` - The name of the sigil.
+/// * `fields` - The name and evaluated value of each field.
+/// * `env` - The environment the sigil is defined in.
+/// * `line_info` - The location of the instance, used for errors.
+///
+/// # Returns
+/// The instance with its fields in declaration order, or an error if a field is missing,
+/// unknown, given twice or of the wrong type.
+pub fn make_sigil(
+    name: &str,
+    fields: Vec<(String, EvalResult)>,
+    env: &Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let sigil = env.get_sigil(name).ok_or_else(|| {
+        EvalError::TypeError(format!("Sigil {} is not defined", name), line_info.clone())
+    })?;
+
+    let mut values: Vec<(String, Value)> = Vec::new();
+    for (field, value) in fields {
+        let field_type = sigil.field_type(&field).ok_or_else(|| {
+            EvalError::TypeError(
+                format!("Sigil {} has no field {}", name, field),
+                line_info.clone(),
+            )
+        })?;
+        if values.iter().any(|(existing, _)| *existing == field) {
+            return Err(EvalError::TypeError(
+                format!("Field {} of sigil {} is given twice", field, name),
+                line_info.clone(),
+            ));
         }
-        AST::LessThan(left, right, line_info) => {
-            match (evaluate(left, env)?, evaluate(right, env)?) {
-                (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Omen(l < r)),
-                (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Omen(l < r)),
-                _ => Err(EvalError::InvalidOperation(
-                    "Comparison requires numeric types!".to_string(),
+        let value = raise_curse(value, field_type)?;
+        let value = result_to_value(value, field_type).ok_or_else(|| {
+            EvalError::TypeError(
+                format!(
+                    "Field {} of sigil {} expects a value of type {:?}",
+                    field, name, field_type
+                ),
+                line_info.clone(),
+            )
+        })?;
+        values.push((field, value));
+    }
+
+    let mut ordered = Vec::new();
+    for (field, _) in &sigil.fields {
+        let position = values
+            .iter()
+            .position(|(given, _)| given == field)
+            .ok_or_else(|| {
+                EvalError::TypeError(
+                    format!("Field {} of sigil {} is missing", field, name),
                     line_info.clone(),
-                )),
+                )
+            })?;
+        ordered.push(values.swap_remove(position));
+    }
+    Ok(value_to_result(&Value::Sigil(name.to_string(), ordered)))
+}
+
+/// Assigns to a variable with `=` or a compound assignment operator, possibly through indexes
+/// and fields such as `xs[0].hp`, modifying its value in place.
+///
+/// # Arguments
+/// * `name` - The name of the variable.
+/// * `var_info` - The variable, which must be mutable.
+/// * `accessors` - The indexes and fields the assignment goes through.
+/// * `indexes` - The evaluated index of each `Accessor::Index`, in order.
+/// * `value` - The evaluated right-hand side.
+/// * `op` - The assignment operator.
+/// * `env` - The environment, used to look up the field types of sigils.
+/// * `line_info` - The location of the assignment, used for errors.
+///
+/// # Returns
+/// An error if the assignment fails, in which case the variable is left unchanged.
+#[allow(clippy::too_many_arguments)]
+pub fn assign_value(
+    name: &str,
+    var_info: &mut VarInfo,
+    accessors: &[Accessor],
+    indexes: Vec<EvalResult>,
+    value: EvalResult,
+    op: &AssignmentOp,
+    env: &Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<(), EvalError> {
+    if !var_info.is_morph {
+        return Err(EvalError::InvalidOperation(
+            format!("Cannot reassign to immutable variable {}", name),
+            line_info.clone(),
+        ));
+    }
+    if accessors.is_empty() {
+        let new_value = apply_assignment_op(
+            &var_info.value,
+            &var_info.var_type,
+            value,
+            op,
+            name,
+            env,
+            line_info,
+        )?;
+        env.check_value(&new_value, line_info.as_ref())?;
+        var_info.value = new_value;
+        return Ok(());
+    }
+
+    let inserts = matches!(op, AssignmentOp::Assign);
+    let place = borrow_place(
+        name,
+        &mut var_info.value,
+        &var_info.var_type,
+        accessors,
+        indexes,
+        |value_type| match inserts {
+            true => apply_assignment_op(
+                &Value::Omen(false),
+                value_type,
+                value.clone(),
+                op,
+                name,
+                env,
+                line_info,
+            )
+            .map(Some),
+            false => Ok(None),
+        },
+        env,
+        line_info,
+    )?;
+    *place.value = apply_assignment_op(
+        place.value,
+        &place.value_type,
+        value,
+        op,
+        &place.name,
+        env,
+        line_info,
+    )?;
+    let inserted = place.inserted;
+    if let Err(error) = env.check_value(&var_info.value, line_info.as_ref()) {
+        if let (Some(key), Value::Codex(entries)) = (inserted, &mut var_info.value) {
+            entries.remove(&key);
+        }
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// An element of a variable reached through indexes and fields, such as `xs[0].hp`, borrowed to
+/// be modified in place.
+struct Place<'a> {
+    value: &'a mut Value,
+    value_type: Type,
+    name: String, // The variable and the fields leading to the element, used in errors
+    inserted: Option<CodexKey>, // A key inserted into the variable itself to reach the element
+}
+
+/// Borrows the element of a variable reached through indexes and fields, the target of an
+/// assignment or of `push` and `pop`.
+///
+/// # Arguments
+/// * `name` - The name of the variable.
+/// * `value` - The value of the variable.
+/// * `var_type` - The type of the variable.
+/// * `accessors` - The indexes and fields to go through.
+/// * `indexes` - The evaluated index of each `Accessor::Index`, in order.
+/// * `insert` - Returns the value to insert when the last access is a key missing from a codex,
+///   given the type of the values of the codex, or `None` to report the key as not found.
+/// * `env` - The environment, used to look up the field types of sigils.
+/// * `line_info` - The location of the access, used for errors.
+#[allow(clippy::too_many_arguments)]
+fn borrow_place<'a>(
+    name: &str,
+    value: &'a mut Value,
+    var_type: &Type,
+    accessors: &[Accessor],
+    indexes: Vec<EvalResult>,
+    mut insert: impl FnMut(&Type) -> Result<Option<Value>, EvalError>,
+    env: &Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<Place<'a>, EvalError> {
+    let mut indexes = indexes.into_iter();
+    let mut inserted = None;
+    let mut target = value;
+    let mut target_type = var_type.clone();
+    let mut target_name = name.to_string();
+    for (position, accessor) in accessors.iter().enumerate() {
+        target_type = match accessor {
+            Accessor::Index(_, index_line_info) => {
+                let index = indexes.next().ok_or_else(|| {
+                    EvalError::InvalidOperation(
+                        format!("Missing index for variable {}", name),
+                        index_line_info.clone(),
+                    )
+                })?;
+                match (target, &target_type) {
+                    (Value::Grimoire(items), Type::Grimoire(element_type)) => {
+                        let position = grimoire_position(index, items.len(), index_line_info)?;
+                        target = &mut items[position];
+                        element_type.as_ref().clone()
+                    }
+                    (Value::Codex(entries), Type::Codex(key_type, value_type)) => {
+                        let key = result_to_key(index, key_type, index_line_info)?;
+                        // A missing key may be inserted by the final access.
+                        if !entries.contains_key(&key) && position + 1 == accessors.len() {
+                            if let Some(value) = insert(value_type)? {
+                                entries.insert(key.clone(), value);
+                                // Only an insertion into the variable itself changes its size.
+                                if position == 0 {
+                                    inserted = Some(key.clone());
+                                }
+                            }
+                        }
+                        target = entries.get_mut(&key).ok_or_else(|| {
+                            EvalError::KeyNotFound(key.to_string(), index_line_info.clone())
+                        })?;
+                        value_type.as_ref().clone()
+                    }
+                    _ => {
+                        return Err(EvalError::TypeError(
+                            format!("Variable {} cannot be indexed", name),
+                            line_info.clone(),
+                        ))
+                    }
+                }
             }
+            Accessor::Field(field, field_line_info) => match target {
+                Value::Sigil(sigil_name, fields) => {
+                    let field_type = env
+                        .get_sigil(sigil_name)
+                        .and_then(|sigil| sigil.field_type(field).cloned());
+                    let value = fields
+                        .iter_mut()
+                        .find(|(name, _)| name == field)
+                        .map(|(_, value)| value);
+                    match (value, field_type) {
+                        (Some(value), Some(field_type)) => {
+                            target = value;
+                            target_name = format!("{}.{}", target_name, field);
+                            field_type
+                        }
+                        _ => {
+                            return Err(EvalError::TypeError(
+                                format!("Sigil {} has no field {}", sigil_name, field),
+                                field_line_info.clone(),
+                            ))
+                        }
+                    }
+                }
+                _ => {
+                    return Err(EvalError::TypeError(
+                        format!("Variable {} has no field {}", name, field),
+                        field_line_info.clone(),
+                    ))
+                }
+            },
+        };
+    }
+    Ok(Place {
+        value: target,
+        value_type: target_type,
+        name: target_name,
+        inserted,
+    })
+}
+
+/// Converts an index into a position within a grimoire of the given length.
+fn grimoire_position(
+    index: EvalResult,
+    len: usize,
+    line_info: &Option<LineInfo>,
+) -> Result<usize, EvalError> {
+    match index {
+        EvalResult::Arcana(i) if i >= 0 && (i as usize) < len => Ok(i as usize),
+        EvalResult::Arcana(_) | EvalResult::BigArcana(_) => Err(EvalError::InvalidOperation(
+            format!(
+                "Index {} is out of bounds for grimoire of length {}",
+                index, len
+            ),
+            line_info.clone(),
+        )),
+        _ => Err(EvalError::TypeError(
+            "Grimoire index must be of type Arcana".to_string(),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Returns the number of arguments taken by a builtin function, or `None` if `name` is not a
+/// builtin: the collection functions `len`, `has`, `push` and `pop`, and the file I/O functions
+/// `read`, `read_lines`, `exists`, `write` and `append`. Unlike the native functions of the
+/// core library, the builtins accept collections of any element type, `push` and `pop` modify
+/// the variable passed to them, and the file I/O functions depend on the file access allowed in
+/// the environment, which a native signature cannot express.
+pub fn builtin_arity(name: &str) -> Option<usize> {
+    match name {
+        "len" | "pop" | "read" | "read_lines" | "exists" => Some(1),
+        "has" | "push" | "write" | "append" => Some(2),
+        _ => None,
+    }
+}
+
+/// Evaluates a call to a builtin function. `push` and `pop` modify the grimoire variable
+/// given as their first argument; the other builtins are applied by `apply_builtin`.
+/// Returns `None` if `name` is not a builtin function.
+fn evaluate_builtin(
+    name: &str,
+    args: &[AST],
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Option<Result<EvalResult, Unwind>> {
+    let arity = builtin_arity(name)?;
+    let result = (|| {
+        if args.len() != arity {
+            return Err(EvalError::InvalidOperation(
+                format!("{} expects {} argument(s)", name, arity),
+                line_info.clone(),
+            )
+            .into());
         }
-        AST::LessThanOrEqual(left, right, line_info) => {
-            match (evaluate(left, env)?, evaluate(right, env)?) {
-                (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Omen(l <= r)),
-                (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Omen(l <= r)),
-                _ => Err(EvalError::InvalidOperation(
-                    "Comparison requires numeric types!".to_string(),
+        if name == "push" || name == "pop" {
+            let Some((var_name, slot, accessors)) = place_of(&args[0]) else {
+                return Err(EvalError::InvalidOperation(
+                    format!("{} requires a grimoire variable", name),
                     line_info.clone(),
-                )),
+                )
+                .into());
+            };
+            let mut indexes = Vec::new();
+            for accessor in &accessors {
+                if let Accessor::Index(index, _) = accessor {
+                    indexes.push(evaluate_flow(index, env)?);
+                }
             }
+            let pushed = match args.get(1) {
+                Some(arg) => Some(evaluate_flow(arg, env)?),
+                None => None,
+            };
+            let result = env
+                .lend_resolved_var(var_name, slot, |var_info, env| {
+                    push_or_pop(
+                        var_name, var_info, &accessors, indexes, pushed, env, line_info,
+                    )
+                })
+                .ok_or_else(|| EvalError::UndefinedVariable(var_name.clone(), line_info.clone()))?;
+            return Ok(result?);
         }
-        AST::GreaterThan(left, right, line_info) => {
-            match (evaluate(left, env)?, evaluate(right, env)?) {
-                (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Omen(l > r)),
-                (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Omen(l > r)),
-                _ => Err(EvalError::InvalidOperation(
-                    "Comparison requires numeric types!".to_string(),
-                    line_info.clone(),
-                )),
+
+        let evaluated_args = args
+            .iter()
+            .map(|arg| evaluate_flow(arg, env))
+            .collect::<Result<Vec<EvalResult>, Unwind>>()?;
+        Ok(apply_builtin(name, evaluated_args, env, line_info)?)
+    })();
+    Some(result)
+}
+
+/// Returns the variable a chain of indexes and fields such as `p.xs[0]` starts from, with the
+/// accessors it goes through in order, so that the element it reaches can be modified like the
+/// target of an assignment.
+///
+/// # Returns
+/// The name and slot of the variable and the accessors, or `None` if the chain does not start
+/// from a variable.
+pub fn place_of(ast: &AST) -> Option<(&String, Option<Slot>, Vec<Accessor>)> {
+    let mut accessors = Vec::new();
+    let mut target = ast;
+    let (name, slot) = loop {
+        target = match target {
+            AST::Index(inner, index, line_info) => {
+                accessors.push(Accessor::Index(index.clone(), line_info.clone()));
+                inner
+            }
+            AST::Field(inner, field, line_info) => {
+                accessors.push(Accessor::Field(field.clone(), line_info.clone()));
+                inner
             }
+            AST::Var(name, slot, _) => break (name, *slot),
+            _ => return None,
+        };
+    };
+    accessors.reverse();
+    Some((name, slot, accessors))
+}
+
+/// Pushes a value onto a grimoire, or pops its last element when `pushed` is `None`, modifying
+/// the grimoire in place. The grimoire is a variable, or an element of one reached through
+/// indexes and fields such as `p.xs`.
+///
+/// # Arguments
+/// * `var_name` - The name of the variable.
+/// * `var_info` - The variable, which must be mutable.
+/// * `accessors` - The indexes and fields leading to the grimoire.
+/// * `indexes` - The evaluated index of each `Accessor::Index`, in order.
+/// * `pushed` - The evaluated value to push, if any.
+/// * `env` - The environment, whose limits the grown grimoire is checked against.
+/// * `line_info` - The location of the call, used for errors.
+///
+/// # Returns
+/// The result of the call: `abyss` for `push`, the popped element for `pop`.
+pub fn push_or_pop(
+    var_name: &str,
+    var_info: &mut VarInfo,
+    accessors: &[Accessor],
+    indexes: Vec<EvalResult>,
+    pushed: Option<EvalResult>,
+    env: &Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let name = if pushed.is_some() { "push" } else { "pop" };
+    let is_morph = var_info.is_morph;
+    // A push beyond the limits is undone by reaching the grimoire again.
+    let undo_indexes = pushed.as_ref().map(|_| indexes.clone());
+    let no_insert = |_: &Type| Ok(None);
+    let var_type = &var_info.var_type;
+    let place = borrow_place(
+        var_name,
+        &mut var_info.value,
+        var_type,
+        accessors,
+        indexes,
+        no_insert,
+        env,
+        line_info,
+    )?;
+    let (items, element_type) = match (place.value, &place.value_type) {
+        (Value::Grimoire(items), Type::Grimoire(element_type)) => (items, element_type.as_ref()),
+        _ => {
+            return Err(EvalError::TypeError(
+                format!("{} requires a grimoire variable", name),
+                line_info.clone(),
+            ))
         }
-        AST::GreaterThanOrEqual(left, right, line_info) => {
-            match (evaluate(left, env)?, evaluate(right, env)?) {
-                (EvalResult::Arcana(l), EvalResult::Arcana(r)) => Ok(EvalResult::Omen(l >= r)),
-                (EvalResult::Aether(l), EvalResult::Aether(r)) => Ok(EvalResult::Omen(l >= r)),
-                _ => Err(EvalError::InvalidOperation(
-                    "Comparison requires numeric types!".to_string(),
+    };
+    if !is_morph {
+        return Err(EvalError::InvalidOperation(
+            format!("Cannot reassign to immutable variable {}", var_name),
+            line_info.clone(),
+        ));
+    }
+    match pushed {
+        Some(pushed) => {
+            let pushed = raise_curse(pushed, element_type)?;
+            let value = result_to_value(pushed, element_type).ok_or_else(|| {
+                EvalError::TypeError(
+                    format!(
+                        "Cannot push a value into grimoire {} of type {:?}",
+                        place.name, place.value_type
+                    ),
                     line_info.clone(),
-                )),
+                )
+            })?;
+            items.push(value);
+            if let Err(error) = env.check_value(&var_info.value, line_info.as_ref()) {
+                let indexes = undo_indexes.unwrap_or_default();
+                let place = borrow_place(
+                    var_name,
+                    &mut var_info.value,
+                    &var_info.var_type,
+                    accessors,
+                    indexes,
+                    no_insert,
+                    env,
+                    line_info,
+                )?;
+                if let Value::Grimoire(items) = place.value {
+                    items.pop();
+                }
+                return Err(error);
             }
+            Ok(EvalResult::Abyss)
         }
-        AST::LogicalAnd(left, right, line_info) => {
-            let left_result = evaluate(left, env)?;
-            let right_result = evaluate(right, env)?;
-            match (left_result, right_result) {
-                (EvalResult::Omen(l), EvalResult::Omen(r)) => Ok(EvalResult::Omen(l && r)),
-                _ => Err(EvalError::InvalidOperation(
-                    "LogicalAnd operation requires two Omen!".to_string(),
+        None => match items.pop() {
+            Some(value) => Ok(value_to_result(&value)),
+            None => Err(EvalError::InvalidOperation(
+                format!("Cannot pop from empty grimoire {}", place.name),
+                line_info.clone(),
+            )),
+        },
+    }
+}
+
+/// Applies a builtin function that does not modify a variable to its evaluated arguments:
+/// `len`, `has`, and the file I/O builtins handled by `apply_file_builtin`.
+pub fn apply_builtin(
+    name: &str,
+    args: Vec<EvalResult>,
+    env: &Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let mut args = args.into_iter();
+    match name {
+        "len" => match args.next() {
+            Some(EvalResult::Grimoire(items)) => Ok(EvalResult::Arcana(items.len() as i64)),
+            Some(EvalResult::Codex(entries)) => Ok(EvalResult::Arcana(entries.len() as i64)),
+            Some(EvalResult::Rune(s)) => Ok(EvalResult::Arcana(s.chars().count() as i64)),
+            _ => Err(EvalError::TypeError(
+                "len requires a Grimoire, Codex or Rune".to_string(),
+                line_info.clone(),
+            )),
+        },
+        "has" => match (args.next(), args.next()) {
+            (Some(EvalResult::Codex(entries)), Some(key)) => {
+                let key = result_to_key(key, &Type::Abyss, line_info)?;
+                Ok(EvalResult::Omen(entries.contains_key(&key)))
+            }
+            _ => Err(EvalError::TypeError(
+                "has requires a Codex".to_string(),
+                line_info.clone(),
+            )),
+        },
+        _ => apply_file_builtin(name, args.collect(), env, line_info),
+    }
+}
+
+/// Applies the file I/O builtins, whose first argument is the path of a file:
+/// `read` returns the whole file as a rune, `read_lines` returns its lines as a grimoire of runes,
+/// `write` and `append` write a rune to it, and `exists` tells whether it exists.
+/// Fails unless file access has been allowed in the environment.
+fn apply_file_builtin(
+    name: &str,
+    args: Vec<EvalResult>,
+    env: &Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let mut runes = Vec::new();
+    for (idx, arg) in args.into_iter().enumerate() {
+        match arg {
+            EvalResult::Rune(s) => runes.push(s),
+            _ => {
+                return Err(EvalError::TypeError(
+                    format!("Argument {} of function {} must be a Rune", idx + 1, name),
                     line_info.clone(),
-                )),
+                ))
             }
         }
-        AST::LogicalOr(left, right, line_info) => {
-            let left_result = evaluate(left, env)?;
-            let right_result = evaluate(right, env)?;
-            match (left_result, right_result) {
-                (EvalResult::Omen(l), EvalResult::Omen(r)) => Ok(EvalResult::Omen(l || r)),
-                _ => Err(EvalError::InvalidOperation(
-                    "LogicalOr operation requires two Omen!".to_string(),
+    }
+    if !env.file_access() {
+        return Err(EvalError::IoError(
+            format!(
+                "{} cannot access files unless file access is allowed (--allow-files)",
+                name
+            ),
+            line_info.clone(),
+        ));
+    }
+
+    let path = &runes[0];
+    let text = runes.get(1).map(String::as_str);
+    let io_error =
+        |e: std::io::Error| EvalError::IoError(format!("{}: {}", path, e), line_info.clone());
+    match name {
+        "read" => fs::read_to_string(path)
+            .map(EvalResult::Rune)
+            .map_err(io_error),
+        "read_lines" => fs::read_to_string(path)
+            .map(|contents| {
+                EvalResult::Grimoire(
+                    contents
+                        .lines()
+                        .map(|line| EvalResult::Rune(line.to_string()))
+                        .collect(),
+                )
+            })
+            .map_err(io_error),
+        "write" => fs::write(path, text.unwrap_or_default())
+            .map(|_| EvalResult::Abyss)
+            .map_err(io_error),
+        "append" => fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(text.unwrap_or_default().as_bytes()))
+            .map(|_| EvalResult::Abyss)
+            .map_err(io_error),
+        _ => Ok(EvalResult::Omen(Path::new(path).exists())),
+    }
+}
+
+/// Calls a function defined by `engrave` (possibly in an invoked module) or a native function
+/// with already evaluated arguments.
+///
+/// # Arguments
+/// * `name` - The name of the function.
+/// * `evaluated_args` - The values of the arguments.
+/// * `env` - The environment to look the function up in and call it from.
+/// * `line_info` - The location of the call, used for errors.
+///
+/// # Returns
+/// The result of the function, or an error if it is not defined or fails.
+pub fn apply_function(
+    name: &str,
+    evaluated_args: Vec<EvalResult>,
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    match env.get_function(name) {
+        Some(function) => call_engrave(&function, evaluated_args, env, line_info),
+        None => call_native(name, evaluated_args, env, line_info),
+    }
+}
+
+/// Calls a function defined by `engrave`, such as one held by a variable, with already
+/// evaluated arguments. An imported function runs in the environment of its module.
+///
+/// # Arguments
+/// * `function` - The function to call.
+/// * `evaluated_args` - The values of the arguments.
+/// * `env` - The environment to call the function from.
+/// * `line_info` - The location of the call, used for errors.
+///
+/// # Returns
+/// The result of the function, or an error if it fails.
+pub fn call_engrave(
+    function: &Function,
+    evaluated_args: Vec<EvalResult>,
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let name = &function.name;
+    if function.native {
+        return call_native(name, evaluated_args, env, line_info);
+    }
+    // The function may run in the environment of another module, so the call names its file.
+    let line_info = &env.locate(line_info);
+
+    match &function.module {
+        Some(module) => {
+            let mut module_env = module.try_borrow_mut().map_err(|_| {
+                EvalError::ModuleError(
+                    format!("Function {} cannot re-enter its module", name),
                     line_info.clone(),
-                )),
+                )
+            })?;
+            let file = module_env
+                .script_path()
+                .map(|path| path.display().to_string());
+            let depth = module_env.scope_depth();
+            call_function(function, evaluated_args, &mut module_env, line_info).map_err(|e| {
+                module_env.unwind_scopes(depth);
+                e.in_file(file)
+            })
+        }
+        None => call_function(function, evaluated_args, env, line_info),
+    }
+}
+
+/// Returns the function held by a variable, if the variable holds one.
+pub fn function_in_var(name: &str, env: &Environment) -> Option<Rc<Function>> {
+    match env.get_var(name)?.value {
+        Value::Engrave(ref function) => Some(Rc::clone(function)),
+        _ => None,
+    }
+}
+
+/// Returns a function defined by `engrave` as a value, which keeps the scopes the function
+/// sees alive.
+pub fn function_value(function: &Function) -> EvalResult {
+    EvalResult::Engrave(Rc::new(Function {
+        closure: function.closure.held(),
+        ..function.clone()
+    }))
+}
+
+/// Returns a native function registered in the environment as a value, so that it can be
+/// stored and passed like a function defined by `engrave`. A native function with several
+/// overloads, such as `abs`, takes the signature of the first one.
+pub fn native_value(name: &str, env: &Environment) -> Option<EvalResult> {
+    let native = env.get_native(name)?.first()?;
+    let params = native
+        .params
+        .iter()
+        .enumerate()
+        .map(|(index, param_type)| AST::EngraveParam {
+            name: format!("arg{}", index + 1),
+            param_type: param_type.clone(),
+            line_info: None,
+        })
+        .collect();
+    Some(EvalResult::Engrave(Rc::new(Function {
+        name: name.to_string(),
+        params,
+        return_type: native.return_type.clone(),
+        body: Rc::new(AST::Abyss(None)),
+        line_info: None,
+        module: None,
+        closure: Closure::default(),
+        native: true,
+    })))
+}
+
+/// Returns the error for a call to a variable that does not hold a function.
+pub fn not_a_function(name: &str, line_info: &Option<LineInfo>) -> EvalError {
+    EvalError::TypeError(format!("{} is not a function", name), line_info.clone())
+}
+
+/// Calls a native function registered in the environment. The arguments are checked against
+/// the signatures of its overloads, and the first overload that accepts them is called.
+/// An unbounded `arcana` returned outside bigint mode raises an overflow.
+fn call_native(
+    name: &str,
+    evaluated_args: Vec<EvalResult>,
+    env: &Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let overloads = match env.get_native(name) {
+        Some(overloads) => overloads.to_vec(),
+        None => {
+            return Err(EvalError::UndefinedVariable(
+                name.to_string(),
+                line_info.clone(),
+            ))
+        }
+    };
+
+    // A curse is accepted by any parameter here, and raised below unless the parameter is cursed.
+    let accepts = |arg: &EvalResult, param_type: &Type| {
+        matches!(arg, EvalResult::Curse(_, _)) || result_to_value(arg.clone(), param_type).is_some()
+    };
+    let native = overloads
+        .iter()
+        .find(|native| {
+            native.params.len() == evaluated_args.len()
+                && evaluated_args
+                    .iter()
+                    .zip(&native.params)
+                    .all(|(arg, param_type)| accepts(arg, param_type))
+        })
+        .ok_or_else(|| {
+            let arg_types: Vec<String> = evaluated_args
+                .iter()
+                .map(|arg| match type_of_result(arg) {
+                    Some(arg_type) => format_type(&arg_type),
+                    None => "abyss".to_string(),
+                })
+                .collect();
+            EvalError::TypeError(
+                format!(
+                    "No signature of function {} accepts ({})",
+                    name,
+                    arg_types.join(", ")
+                ),
+                line_info.clone(),
+            )
+        })?;
+
+    let evaluated_args = evaluated_args
+        .into_iter()
+        .zip(&native.params)
+        .map(|(arg, param_type)| raise_curse(arg, param_type))
+        .collect::<Result<Vec<EvalResult>, EvalError>>()?;
+    let result = (native.body)(evaluated_args).map_err(|message| {
+        EvalError::InvalidOperation(format!("{}: {}", name, message), line_info.clone())
+    })?;
+    // An arcana beyond 64 bits overflows outside bigint mode, as a curse if the result is cursed.
+    let result = match result {
+        EvalResult::BigArcana(n) if !env.bigint() => {
+            let error = EvalError::ArithmeticOverflow(n.to_string(), line_info.clone());
+            match native.return_type {
+                Type::Cursed(_) => error.into_curse(),
+                _ => return Err(error),
+            }
+        }
+        result => result,
+    };
+    let returns_abyss =
+        matches!(result, EvalResult::Abyss) && matches!(native.return_type, Type::Abyss);
+    if !returns_abyss && result_to_value(result.clone(), &native.return_type).is_none() {
+        return Err(EvalError::TypeError(
+            format!(
+                "Native function {} returned a value that is not of type {}",
+                name,
+                format_type(&native.return_type)
+            ),
+            line_info.clone(),
+        ));
+    }
+    match result {
+        // A curse cast by a native function is located at the call.
+        EvalResult::Curse(message, None) => Ok(EvalResult::Curse(message, line_info.clone())),
+        result => Ok(result),
+    }
+}
+
+/// Evaluates an `orbit` loop over the given parameters. Each iteration runs in its own scope,
+/// holding the loop variables of the first parameter; the remaining parameters are looped
+/// over inside it. A `resume` or `eject` labeled with another loop's variable ends this loop
+/// and is passed on to the enclosing one, like a `reveal`.
+fn evaluate_orbit(
+    params: &[AST],
+    body: &AST,
+    line_info: &Option<LineInfo>,
+    env: &mut Environment,
+) -> Result<EvalResult, Unwind> {
+    if params.is_empty() {
+        loop {
+            env.step(line_info.as_ref())?;
+            let depth = env.scope_depth();
+            env.push_scope();
+            let result = evaluate_flow(body, env);
+            if !end_iteration(result, body, None, depth, env)? {
+                break;
             }
         }
-        AST::LogicalNot(expr, line_info) => {
-            let result = evaluate(expr, env)?;
-            match result {
-                EvalResult::Omen(value) => Ok(EvalResult::Omen(!value)),
-                _ => Err(EvalError::InvalidOperation(
-                    "LogicalNot operation requires Omen!".to_string(),
+        return Ok(EvalResult::Abyss);
+    }
+
+    let (names, values): (Vec<&String>, Bindings) = match &params[0] {
+        AST::OrbitParam {
+            name,
+            start,
+            end,
+            op,
+            ..
+        } => {
+            let start = evaluate_flow(start, env)?;
+            let end = evaluate_flow(end, env)?;
+            // A number of the range is bound without collecting it with the values of the
+            // iteration.
+            for value in orbit_range(start, end, op == "..=", name, line_info)? {
+                let bindings = [(Value::Arcana(value), Type::Arcana)];
+                if !orbit_iteration(&[name], bindings, true, params, body, line_info, env)? {
+                    break;
+                }
+            }
+            return Ok(EvalResult::Abyss);
+        }
+        AST::OrbitCollection {
+            name,
+            value_name,
+            collection,
+            ..
+        } => {
+            let mut names = vec![name];
+            names.extend(value_name);
+            let collection = evaluate_flow(collection, env)?;
+            let values = orbit_bindings(collection, value_name.is_some(), name, line_info)?;
+            (names, values)
+        }
+        _ => {
+            return Err(EvalError::InvalidOperation(
+                "Expected OrbitParam in Orbit".to_string(),
+                line_info.clone(),
+            )
+            .into())
+        }
+    };
+
+    for bindings in values {
+        if !orbit_iteration(&names, bindings, false, params, body, line_info, env)? {
+            break;
+        }
+    }
+    Ok(EvalResult::Abyss)
+}
+
+/// Runs an iteration of an `orbit` in a new scope, binding its values to the variables of the
+/// first parameter, and loops over the remaining parameters inside it.
+///
+/// # Returns
+/// Whether the loop goes on, as `end_iteration` tells.
+fn orbit_iteration(
+    names: &[&String],
+    bindings: impl IntoIterator<Item = (Value, Type)>,
+    is_morph: bool,
+    params: &[AST],
+    body: &AST,
+    line_info: &Option<LineInfo>,
+    env: &mut Environment,
+) -> Result<bool, Unwind> {
+    env.step(line_info.as_ref())?;
+    let depth = env.scope_depth();
+    env.push_scope();
+
+    for (index, (name, (value, var_type))) in names.iter().zip(bindings).enumerate() {
+        env.set_resolved_var(
+            name,
+            Some(Slot::current(index)),
+            value,
+            var_type,
+            is_morph,
+            line_info.clone(),
+        );
+    }
+
+    let result = match params.len() {
+        1 => evaluate_flow(body, env),
+        _ => evaluate_orbit(&params[1..], body, line_info, env),
+    };
+    end_iteration(result, body, Some(names[0]), depth, env)
+}
+
+/// Ends an iteration of the `orbit` over the variable `name`, or of an `orbit` without
+/// parameters when `name` is `None`, closing the scopes opened since `depth`. The value of the
+/// `body` is dropped.
+///
+/// # Returns
+/// Whether the loop goes on: `false` once the iteration ejects from it. A transfer of control
+/// aimed at an enclosing loop or function is passed on, and so is a curse the body results in.
+fn end_iteration(
+    result: Result<EvalResult, Unwind>,
+    body: &AST,
+    name: Option<&str>,
+    depth: usize,
+    env: &mut Environment,
+) -> Result<bool, Unwind> {
+    let go_on = match result {
+        Ok(result) => drop_result(result, body).map(|_| true),
+        Err(Unwind::Control(control, _)) if control.is_for_loop(name) => {
+            Ok(matches!(control, ControlFlow::Resume(_)))
+        }
+        Err(unwind) => return Err(unwind),
+    };
+    env.unwind_scopes(depth);
+    Ok(go_on?)
+}
+
+/// Calls a function with already evaluated arguments in the given environment.
+/// The hook of the environment, if any, is notified when the function is entered and left.
+fn call_function(
+    function: &Function,
+    evaluated_args: Vec<EvalResult>,
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let depth = env.scope_depth();
+    env.push_function_scope(function);
+    bind_arguments(function, evaluated_args, env, line_info)?;
+
+    env.push_call(&function.name, line_info)?;
+    // The body runs on a stack that grows on the heap as needed, so deep recursion is stopped
+    // by the maximum call depth rather than by the end of the native stack.
+    let result = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || {
+        evaluate_body(function, line_info, env)
+    });
+    if result.is_err() {
+        env.record_trace();
+    }
+    env.pop_call();
+    let result = result?;
+
+    // A `reveal` may leave scopes of the body open.
+    env.unwind_scopes(depth);
+
+    return_value(result, function, line_info)
+}
+
+/// Binds the arguments of a call to the parameters of a function defined by `engrave`, in the
+/// scope of the call.
+#[inline(never)]
+fn bind_arguments(
+    function: &Function,
+    evaluated_args: Vec<EvalResult>,
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<(), EvalError> {
+    let params = evaluated_args.into_iter().zip(function.params.iter());
+    for (index, (evaluated_arg, param)) in params.enumerate() {
+        let (name, param_type) = param_of(function, param, line_info)?;
+        let value = bind_argument(evaluated_arg, name, param_type, line_info)?;
+        env.set_resolved_var(
+            name,
+            Some(Slot::current(index)),
+            value,
+            param_type.clone(),
+            false,
+            line_info.clone(),
+        );
+    }
+    Ok(())
+}
+
+/// The native stack space below which a call to a function defined by `engrave` continues on a
+/// new stack segment.
+const STACK_RED_ZONE: usize = 1024 * 1024;
+
+/// The size of the stack segments allocated for calls to functions defined by `engrave`.
+const STACK_SEGMENT_SIZE: usize = 8 * 1024 * 1024;
+
+/// Evaluates the body of a function defined by `engrave` whose arguments are bound, notifying
+/// the hook when the function is entered and left. The function results in the value it
+/// reveals, or else in the value of its body.
+fn evaluate_body(
+    function: &Function,
+    line_info: &Option<LineInfo>,
+    env: &mut Environment,
+) -> Result<EvalResult, EvalError> {
+    let hook = env.hook();
+    if let Some(Ok(mut hook)) = hook.as_ref().map(|hook| hook.try_borrow_mut()) {
+        hook.on_call(function, line_info, env)?;
+    }
+    let result = match evaluate_flow(&function.body, env) {
+        Err(Unwind::Control(ControlFlow::Reveal(result), _)) => Ok(result),
+        result => result.map_err(Unwind::into_error),
+    };
+    if let Some(Ok(mut hook)) = hook.as_ref().map(|hook| hook.try_borrow_mut()) {
+        hook.on_return(function, env);
+    }
+    result
+}
+
+/// Returns the name and type of a parameter of a function defined by `engrave`.
+pub fn param_of<'a>(
+    function: &Function,
+    param: &'a AST,
+    line_info: &Option<LineInfo>,
+) -> Result<(&'a String, &'a Type), EvalError> {
+    match param {
+        AST::EngraveParam {
+            name, param_type, ..
+        } => Ok((name, param_type)),
+        _ => Err(EvalError::InvalidOperation(
+            format!(
+                "Expected EngraveParam in function definition: {}",
+                function.name
+            ),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Converts an evaluated argument into a value of the type of the parameter it is bound to.
+pub fn bind_argument(
+    arg: EvalResult,
+    name: &str,
+    param_type: &Type,
+    line_info: &Option<LineInfo>,
+) -> Result<Value, EvalError> {
+    let arg = raise_curse(arg, param_type)?;
+    result_to_value(arg, param_type).ok_or_else(|| {
+        EvalError::TypeError(
+            format!("Type mismatch for parameter {}", name),
+            line_info.clone(),
+        )
+    })
+}
+
+/// Converts the result of the body of a function into the value it returns, which must be of
+/// its return type. A value of another type is reported at the call, given by `line_info`.
+pub fn return_value(
+    result: EvalResult,
+    function: &Function,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    match (
+        raise_curse(result, &function.return_type)?,
+        &function.return_type,
+    ) {
+        (EvalResult::Abyss, Type::Abyss) => Ok(EvalResult::Abyss),
+        (result, return_type) => match result_to_value(result, return_type) {
+            Some(value) => Ok(value_to_result(&value)),
+            None => Err(EvalError::TypeError(
+                format!(
+                    "Type mismatch for return value of function {}",
+                    function.name
+                ),
+                line_info.clone(),
+            )),
+        },
+    }
+}
+
+/// The values bound to the variables of an `orbit` over a collection, one entry per iteration.
+pub type Bindings = Box<dyn Iterator<Item = Vec<(Value, Type)>>>;
+
+/// Returns the values taken by the variable of an `orbit` over the range `start..end`
+/// (or `start..=end` when `inclusive`).
+pub fn orbit_range(
+    start: EvalResult,
+    end: EvalResult,
+    inclusive: bool,
+    name: &str,
+    line_info: &Option<LineInfo>,
+) -> Result<std::ops::Range<i64>, EvalError> {
+    match (start, end) {
+        (EvalResult::Arcana(start), EvalResult::Arcana(end)) if !inclusive => Ok(start..end),
+        (EvalResult::Arcana(start), EvalResult::Arcana(end)) => match end.checked_add(1) {
+            Some(end) => Ok(start..end),
+            None => Err(EvalError::ArithmeticOverflow(
+                format!("{} + 1", end),
+                line_info.clone(),
+            )),
+        },
+        (EvalResult::BigArcana(_), _) | (_, EvalResult::BigArcana(_)) => {
+            Err(EvalError::InvalidOperation(
+                format!("Orbit range of {} does not fit in 64 bits", name),
+                line_info.clone(),
+            ))
+        }
+        _ => Err(EvalError::TypeError(
+            format!("Orbit parameter must be of type Arcana: {}", name),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Returns the values bound to the variables of an `orbit` over a grimoire or codex.
+/// Each iteration binds the element of a grimoire or the key of a codex, preceded by the
+/// position of the element or followed by the value of the key when `pair` is set.
+pub fn orbit_bindings(
+    collection: EvalResult,
+    pair: bool,
+    name: &str,
+    line_info: &Option<LineInfo>,
+) -> Result<Bindings, EvalError> {
+    match collection {
+        EvalResult::Grimoire(items) => Ok(Box::new(items.into_iter().enumerate().filter_map(
+            move |(position, item)| {
+                let item = typed_value(item)?;
+                Some(match pair {
+                    true => vec![(Value::Arcana(position as i64), Type::Arcana), item],
+                    false => vec![item],
+                })
+            },
+        ))),
+        EvalResult::Codex(entries) => Ok(Box::new(entries.into_iter().filter_map(
+            move |(key, value)| {
+                let key = (value_of_key(&key), key_type_of(&key));
+                Some(match pair {
+                    true => vec![key, typed_value(value)?],
+                    false => vec![key],
+                })
+            },
+        ))),
+        _ => Err(EvalError::TypeError(
+            format!(
+                "Orbit collection must be of type Grimoire or Codex: {}",
+                name
+            ),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Converts the value of an oracle conditional into the value bound to its variable,
+/// together with its derived type.
+pub fn conditional_value(
+    result: &EvalResult,
+    line_info: &Option<LineInfo>,
+) -> Result<(Value, Type), EvalError> {
+    typed_value(result.clone()).ok_or_else(|| {
+        EvalError::InvalidOperation(
+            format!("Unsupported type in oracle conditional: {:?}", result),
+            line_info.clone(),
+        )
+    })
+}
+
+/// Returns the error reported when a branch of an oracle has more patterns than the oracle
+/// has conditionals.
+pub fn missing_conditional(line_info: &Option<LineInfo>) -> EvalError {
+    EvalError::InvalidOperation(
+        "Oracle branch has more patterns than conditionals".to_string(),
+        line_info.clone(),
+    )
+}
+
+/// Compares the value of an oracle conditional with the value of a pattern of a branch.
+/// Fails if they are not of the same type.
+pub fn pattern_matches(
+    conditional: EvalResult,
+    pattern: EvalResult,
+    line_info: &Option<LineInfo>,
+) -> Result<bool, EvalError> {
+    match (conditional, pattern) {
+        (cond_n, pat_n) if is_arcana(&cond_n) && is_arcana(&pat_n) => {
+            Ok(arcana_cmp(&cond_n, &pat_n).is_eq())
+        }
+        (EvalResult::Aether(cond_n), EvalResult::Aether(pat_n)) => {
+            Ok((cond_n - pat_n).abs() < f64::EPSILON)
+        }
+        (EvalResult::Rune(cond_s), EvalResult::Rune(pat_s)) => Ok(cond_s == pat_s),
+        (EvalResult::Omen(cond_b), EvalResult::Omen(pat_b)) => Ok(cond_b == pat_b),
+        _ => Err(EvalError::InvalidOperation(
+            "Oracle branch pattern type must match conditional type".to_string(),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Matches a conditional value against the curse part of an oracle pattern.
+/// A `curse(name)` pattern matches any curse and binds its message to `name`, while other
+/// `curse(...)` patterns match a curse with the same message. A curse never matches a pattern
+/// that is not a curse pattern, and vice versa.
+fn match_curse_pattern(
+    pattern: &AST,
+    conditional_result: &EvalResult,
+    env: &mut Environment,
+) -> Result<bool, Unwind> {
+    match (pattern, conditional_result) {
+        (AST::Curse(message_pattern, line_info), EvalResult::Curse(message, _)) => {
+            match message_pattern.as_ref() {
+                AST::Var(name, slot, _) => {
+                    env.set_resolved_var(
+                        name,
+                        *slot,
+                        Value::Rune(message.clone()),
+                        Type::Rune,
+                        false,
+                        line_info.clone(),
+                    );
+                    Ok(true)
+                }
+                _ => match evaluate_flow(pattern, env)? {
+                    EvalResult::Curse(expected, _) => Ok(expected == *message),
+                    _ => Ok(false),
+                },
+            }
+        }
+        (AST::Curse(_, _), _) | (_, EvalResult::Curse(_, _)) => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// Evaluates an `invoke` statement: loads the module in its own environment and exposes its
+/// functions, immutable globals and sigils (or only the selected `names`) to `env`.
+pub fn evaluate_invoke(
+    path: &str,
+    names: &Option<Vec<String>>,
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let module_error = |message: String| EvalError::ModuleError(message, line_info.clone());
+
+    let module_path = resolve_module_path(env.script_path(), path);
+    let canonical_path = module_path
+        .canonicalize()
+        .map_err(|e| module_error(format!("Cannot read module {}: {}", path, e)))?;
+    check_module_access(&canonical_path, env.module_root(), env.file_access())
+        .map_err(module_error)?;
+    if env.is_invoking(&canonical_path) {
+        return Err(module_error(format!("Cyclic invoke of module {}", path)));
+    }
+
+    let program = load_module(&module_path).map_err(module_error)?;
+    let file = Some(module_path.display().to_string());
+    let mut module_env = env.for_module(module_path, canonical_path);
+    let mut resolver = Resolver::new(&program, &module_env);
+    for ast in &program {
+        resolver
+            .resolve(ast)
+            .and_then(|ast| evaluate(&ast, &mut module_env))
+            .map_err(|e| e.in_file(file.clone()))?;
+    }
+
+    let module = Rc::new(RefCell::new(module_env));
+    let module_env = module.borrow();
+    let export_function = |function: &Function| {
+        let mut function = function.clone();
+        if function.module.is_none() {
+            function.module = Some(Rc::clone(&module));
+        }
+        function
+    };
+    let exported_names: Vec<String> = match names {
+        Some(names) => names.clone(),
+        None => module_env
+            .global_functions()
+            .keys()
+            .chain(module_env.global_sigils().keys())
+            .chain(
+                module_env
+                    .global_vars()
+                    .iter()
+                    .filter(|(_, var_info)| !var_info.is_morph)
+                    .map(|(name, _)| name),
+            )
+            .cloned()
+            .collect(),
+    };
+
+    for name in exported_names {
+        if let Some(function) = module_env.global_functions().get(&name) {
+            env.set_function(name, export_function(function));
+        } else if let Some(sigil) = module_env.global_sigils().get(&name) {
+            env.set_sigil(name, sigil.clone());
+        } else if let Some(var_info) = module_env
+            .global_vars()
+            .get(&name)
+            .filter(|var_info| !var_info.is_morph)
+        {
+            env.set_var(
+                name,
+                var_info.value.clone(),
+                var_info.var_type.clone(),
+                false,
+                var_info.line_info.clone(),
+            );
+        } else {
+            return Err(module_error(format!(
+                "Module {} does not export {}",
+                path, name
+            )));
+        }
+    }
+    Ok(EvalResult::Abyss)
+}
+
+/// Displays an error message along with the relevant source code and line information, if available.
+/// When the line information names another file (such as an invoked module), that file's source is shown.
+pub fn display_error_with_source(script: &str, line_info: Option<LineInfo>, error_message: &str) {
+    let _ = write_error_with_source(&mut std::io::stderr(), script, line_info, error_message);
+}
+
+/// Writes an error message along with the relevant source code and line information, if available,
+/// to the given stream. When the line information names another file (such as an invoked module),
+/// that file's source is shown.
+///
+/// # Arguments
+/// * `out` - The stream the error is written to.
+/// * `script` - The source code of the script the error occurred in.
+/// * `line_info` - The location of the error, if known.
+/// * `error_message` - The message describing the error.
+pub fn write_error_with_source(
+    out: &mut dyn Write,
+    script: &str,
+    line_info: Option<LineInfo>,
+    error_message: &str,
+) -> std::io::Result<()> {
+    match line_info.map(|info| (source_line(script, &info), info)) {
+        Some(((location, Some(source_line)), info)) => {
+            writeln!(
+                out,
+                "{}",
+                format!(
+                    "Error at {}, column {}: {}",
+                    location, info.column, error_message
+                )
+                .red()
+            )?;
+            writeln!(out, "  {}", source_line.red())?;
+            writeln!(out, "  {}{}", " ".repeat(info.column - 1).red(), "^".red())
+        }
+        _ => writeln!(out, "{}", format!("Error: {}", error_message).red()),
+    }
+}
+
+/// Displays the calls that were in progress when an error occurred on stderr, from the
+/// innermost, along with the line of each call.
+///
+/// # Arguments
+/// * `script` - The source code of the script the error occurred in.
+/// * `trace` - The calls in progress, from the outermost, as returned by `Environment::take_trace`.
+pub fn display_traceback(script: &str, trace: &[CallFrame]) {
+    let _ = write_traceback(&mut std::io::stderr(), script, trace);
+}
+
+/// Writes the calls that were in progress when an error occurred to the given stream, from the
+/// innermost, along with the line of each call. Nothing is written if no call was in progress.
+/// Consecutive calls of the same function from the same place are written once, with the
+/// number of times they are repeated.
+///
+/// # Arguments
+/// * `out` - The stream the calls are written to.
+/// * `script` - The source code of the script the error occurred in.
+/// * `trace` - The calls in progress, from the outermost, as returned by `Environment::take_trace`.
+pub fn write_traceback(
+    out: &mut dyn Write,
+    script: &str,
+    trace: &[CallFrame],
+) -> std::io::Result<()> {
+    if trace.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "{}", "Traceback (most recent call first):".red())?;
+    // A call repeated from the same place, as in a recursion, is shown once.
+    let mut frames = trace.iter().rev().peekable();
+    while let Some(frame) = frames.next() {
+        let mut repeated = 0;
+        while frames.next_if_eq(&frame).is_some() {
+            repeated += 1;
+        }
+        match &frame.line_info {
+            Some(info) => {
+                let (location, source_line) = source_line(script, info);
+                writeln!(
+                    out,
+                    "{}",
+                    format!(
+                        "  in {}, called at {}, column {}",
+                        frame.function, location, info.column
+                    )
+                    .red()
+                )?;
+                if let Some(source_line) = source_line {
+                    writeln!(out, "    {}", source_line.red())?;
+                    writeln!(
+                        out,
+                        "    {}{}",
+                        " ".repeat(info.column - 1).red(),
+                        "^".red()
+                    )?;
+                }
+            }
+            None => writeln!(out, "{}", format!("  in {}", frame.function).red())?,
+        }
+        if repeated > 0 {
+            let message = format!("  ... the call above is repeated {} more times", repeated);
+            writeln!(out, "{}", message.red())?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns a description of the location given by line information, and the line of source
+/// code it points at. When the line information names another file (such as an invoked module),
+/// the line is read from that file.
+fn source_line(script: &str, info: &LineInfo) -> (String, Option<String>) {
+    let (script, location) = match &info.file {
+        Some(file) => (
+            fs::read_to_string(file).unwrap_or_default(),
+            format!("{}: line {}", file, info.line),
+        ),
+        None => (script.to_string(), format!("line {}", info.line)),
+    };
+    // Line numbers start from 1, so we subtract 1
+    let line = info
+        .line
+        .checked_sub(1)
+        .and_then(|index| script.lines().nth(index));
+    (location, line.map(str::to_string))
+}
+
+/// Creates a codex from evaluated key and value pairs, failing if a key is listed twice.
+pub fn make_codex(
+    entries: Vec<(EvalResult, EvalResult)>,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let mut codex = BTreeMap::new();
+    for (key, value) in entries {
+        let key = result_to_key(key, &Type::Abyss, line_info)?;
+        if codex.insert(key.clone(), value).is_some() {
+            return Err(duplicate_key(&key, line_info));
+        }
+    }
+    Ok(EvalResult::Codex(codex))
+}
+
+/// Returns the error raised when a codex literal lists the same key twice.
+fn duplicate_key(key: &CodexKey, line_info: &Option<LineInfo>) -> EvalError {
+    EvalError::InvalidOperation(
+        format!("Duplicate key {} in codex literal", key),
+        line_info.clone(),
+    )
+}
+
+/// Reads the element of an evaluated grimoire or codex at an evaluated index.
+pub fn index_value(
+    target: EvalResult,
+    index: EvalResult,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    match target {
+        EvalResult::Grimoire(mut items) => {
+            let position = grimoire_position(index, items.len(), line_info)?;
+            Ok(items.swap_remove(position))
+        }
+        EvalResult::Codex(mut entries) => {
+            let key = result_to_key(index, &Type::Abyss, line_info)?;
+            entries
+                .remove(&key)
+                .ok_or_else(|| EvalError::KeyNotFound(key.to_string(), line_info.clone()))
+        }
+        _ => Err(EvalError::TypeError(
+            "Only a Grimoire or Codex can be indexed".to_string(),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Reads a field of an evaluated sigil.
+pub fn field_value(
+    target: EvalResult,
+    field: &str,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    match target {
+        EvalResult::Sigil(name, fields) => fields
+            .into_iter()
+            .find(|(field_name, _)| field_name == field)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                EvalError::TypeError(
+                    format!("Sigil {} has no field {}", name, field),
+                    line_info.clone(),
+                )
+            }),
+        _ => Err(EvalError::TypeError(
+            format!("Only a sigil has fields, cannot read field {}", field),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Returns the function held by an evaluated callee, such as `fs[0]` in `fs[0](5)`.
+pub fn callee_function(
+    callee: EvalResult,
+    line_info: &Option<LineInfo>,
+) -> Result<Rc<Function>, EvalError> {
+    match callee {
+        EvalResult::Engrave(function) => Ok(function),
+        _ => Err(EvalError::TypeError(
+            "Only a function can be called".to_string(),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// An index or field access into a stored value, such as `[i]` or `.hp` in `xs[i].hp`, with the
+/// location used for its errors.
+pub enum Access<'a> {
+    Index(EvalResult, &'a Option<LineInfo>),
+    Field(&'a str, &'a Option<LineInfo>),
+}
+
+/// Reads the element of a stored value reached through indexes and fields, without copying
+/// the collections it goes through. Fails like `index_value` and `field_value`.
+///
+/// # Arguments
+/// * `value` - The stored value, such as the value of a variable.
+/// * `accesses` - The accesses to go through, from the outermost value.
+///
+/// # Returns
+/// The element reached by the last access.
+pub fn accessed_value<'v, 'a>(
+    value: &'v Value,
+    accesses: impl IntoIterator<Item = Access<'a>>,
+) -> Result<&'v Value, EvalError> {
+    let mut target = value;
+    for access in accesses {
+        target = match (access, target) {
+            (Access::Index(index, line_info), Value::Grimoire(items)) => {
+                &items[grimoire_position(index, items.len(), line_info)?]
+            }
+            (Access::Index(index, line_info), Value::Codex(entries)) => {
+                let key = result_to_key(index, &Type::Abyss, line_info)?;
+                entries
+                    .get(&key)
+                    .ok_or_else(|| EvalError::KeyNotFound(key.to_string(), line_info.clone()))?
+            }
+            (Access::Index(_, line_info), _) => {
+                return Err(EvalError::TypeError(
+                    "Only a Grimoire or Codex can be indexed".to_string(),
+                    line_info.clone(),
+                ))
+            }
+            (Access::Field(field, line_info), Value::Sigil(name, fields)) => fields
+                .iter()
+                .find(|(field_name, _)| field_name == field)
+                .map(|(_, value)| value)
+                .ok_or_else(|| {
+                    EvalError::TypeError(
+                        format!("Sigil {} has no field {}", name, field),
+                        line_info.clone(),
+                    )
+                })?,
+            (Access::Field(field, line_info), _) => {
+                return Err(EvalError::TypeError(
+                    format!("Only a sigil has fields, cannot read field {}", field),
+                    line_info.clone(),
+                ))
+            }
+        };
+    }
+    Ok(target)
+}
+
+/// Casts an evaluated value to the target type with `trans`.
+/// In bigint mode, a rune holding an integer or an aether that does not fit in 64 bits is cast
+/// to an unbounded `arcana`; otherwise such an aether raises an overflow.
+pub fn trans(
+    value: EvalResult,
+    target_type: &Type,
+    bigint: bool,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    match target_type {
+        Type::Arcana => match value {
+            EvalResult::Aether(n) => match aether_to_arcana(n) {
+                Some(EvalResult::BigArcana(n)) => big_arcana_literal(&n, bigint, line_info),
+                Some(result) => Ok(result),
+                None => Err(EvalError::InvalidOperation(
+                    format!("Cannot convert {} to Arcana", n),
                     line_info.clone(),
                 )),
+            },
+            EvalResult::Rune(s) => parse_arcana(&s, bigint).ok_or_else(|| {
+                EvalError::InvalidOperation(
+                    "Failed to convert Rune to Arcana".to_string(),
+                    line_info.clone(),
+                )
+            }),
+            _ => Err(EvalError::InvalidOperation(
+                "Invalid cast to Arcana".to_string(),
+                line_info.clone(),
+            )),
+        },
+        Type::Aether => match value {
+            EvalResult::Arcana(n) => Ok(EvalResult::Aether(n as f64)),
+            EvalResult::BigArcana(n) => Ok(EvalResult::Aether(n.to_f64().unwrap_or(f64::NAN))),
+            EvalResult::Rune(s) => s.parse::<f64>().map(EvalResult::Aether).map_err(|_| {
+                EvalError::InvalidOperation(
+                    "Failed to convert Rune to Aether".to_string(),
+                    line_info.clone(),
+                )
+            }),
+            _ => Err(EvalError::InvalidOperation(
+                "Invalid cast to Aether".to_string(),
+                line_info.clone(),
+            )),
+        },
+        Type::Rune => match value {
+            EvalResult::Arcana(n) => Ok(EvalResult::Rune(n.to_string())),
+            EvalResult::BigArcana(n) => Ok(EvalResult::Rune(n.to_string())),
+            EvalResult::Aether(n) => Ok(EvalResult::Rune(n.to_string())),
+            _ => Err(EvalError::InvalidOperation(
+                "Invalid cast to Rune".to_string(),
+                line_info.clone(),
+            )),
+        },
+        Type::Omen => Err(EvalError::InvalidOperation(
+            "Casting to Omen is not supported".to_string(),
+            line_info.clone(),
+        )),
+        _ => Err(EvalError::InvalidOperation(
+            format!("Unsupported cast to type {:?}", target_type),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Casts a curse with an evaluated message.
+pub fn make_curse(
+    message: EvalResult,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    match message {
+        EvalResult::Rune(message) => Ok(EvalResult::Curse(message, line_info.clone())),
+        _ => Err(EvalError::TypeError(
+            "Curse message must be of type Rune".to_string(),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Converts evaluated values to the text `unveil` writes for them, one after another.
+/// An interpolated rune converts its embedded values the same way.
+pub fn unveil_text(results: &[EvalResult]) -> String {
+    results.iter().map(EvalResult::to_string).collect()
+}
+
+/// Writes evaluated values to the output stream of the environment, followed by a newline.
+pub fn unveil(results: &[EvalResult], env: &Environment) -> Result<EvalResult, EvalError> {
+    let output_str = unveil_text(results);
+    writeln!(env.io().output(), "{}", output_str)
+        .map_err(|e| EvalError::IoError(format!("Failed to write output: {}", e), None))?;
+    Ok(EvalResult::Abyss)
+}
+
+/// Writes a prompt to the output stream of the environment and reads a value of the given type
+/// from its input stream.
+pub fn summon(
+    prompt: &str,
+    var_type: &Type,
+    env: &Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, EvalError> {
+    let io_error = |e: std::io::Error| EvalError::IoError(e.to_string(), line_info.clone());
+    {
+        let mut output = env.io().output();
+        write!(output, "{}", prompt).map_err(io_error)?;
+        output.flush().map_err(io_error)?;
+    }
+    let mut input = String::new();
+    env.io().input().read_line(&mut input).map_err(io_error)?;
+    match var_type {
+        Type::Arcana => parse_arcana(input.trim(), env.bigint()).ok_or_else(|| {
+            EvalError::InvalidOperation(
+                "Failed to parse input as Arcana".to_string(),
+                line_info.clone(),
+            )
+        }),
+        Type::Aether => input
+            .trim()
+            .parse::<f64>()
+            .map(EvalResult::Aether)
+            .map_err(|_| {
+                EvalError::InvalidOperation(
+                    "Failed to parse input as Aether".to_string(),
+                    line_info.clone(),
+                )
+            }),
+        Type::Rune => Ok(EvalResult::Rune(input.trim().to_string())),
+        _ => Err(EvalError::InvalidOperation(
+            "Unsupported type for summon".to_string(),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Evaluates an abstract syntax tree (AST) node in the given environment.
+/// Each node evaluated counts as a step against the resource limits of the environment,
+/// and the size of its result is checked against them.
+///
+/// # Arguments
+///
+/// * `ast` - The AST node to be evaluated.
+/// * `env` - The environment containing variable and function bindings.
+///
+/// # Returns
+///
+/// The result of the evaluation, or an `EvalError` if an error occurs. A `reveal`, `resume` or
+/// `eject` that would leave the node is an error, since nothing outside of it can handle it,
+/// and so is a curse a top-level statement results in, unless it was caught (see `is_caught`).
+pub fn evaluate(ast: &AST, env: &mut Environment) -> Result<EvalResult, EvalError> {
+    match evaluate_flow(ast, env).map_err(Unwind::into_error)? {
+        EvalResult::Curse(message, line_info)
+            if matches!(ast, AST::Statement(_, _)) && !is_caught(ast) =>
+        {
+            Err(EvalError::Curse(message, line_info))
+        }
+        result => Ok(result),
+    }
+}
+
+/// Evaluates an AST node like `evaluate`, letting a transfer of control leave it.
+fn evaluate_flow(ast: &AST, env: &mut Environment) -> Result<EvalResult, Unwind> {
+    // Without limits, there is nothing to count, nor to locate.
+    if !env.is_limited() {
+        return evaluate_node(ast, env);
+    }
+    env.step(ast.line_info())?;
+    let result = evaluate_node(ast, env)?;
+    env.check_result(&result, ast.line_info())?;
+    Ok(result)
+}
+
+/// Evaluates a chain of indexes and fields on a variable, such as `xs[i].hp`, reading the
+/// element from the variable without copying the collections it goes through. The indexes are
+/// evaluated before the variable is read.
+///
+/// # Returns
+/// The element read, or `None` if the chain does not start from a variable.
+fn evaluate_var_access(ast: &AST, env: &mut Environment) -> Option<Result<EvalResult, Unwind>> {
+    let mut chain = Vec::new();
+    let mut target = ast;
+    let (name, slot, var_line_info) = loop {
+        target = match target {
+            AST::Index(inner, _, _) | AST::Field(inner, _, _) => {
+                chain.push(target);
+                inner
+            }
+            AST::Var(name, slot, line_info) => break (name, *slot, line_info),
+            _ => return None,
+        };
+    };
+    env.get_resolved_var(name, slot)?;
+    if slot.is_none() {
+        env.check_scoping(name, false, var_line_info);
+    }
+    let result = (|| {
+        let mut accesses = Vec::with_capacity(chain.len());
+        for node in chain.into_iter().rev() {
+            match node {
+                AST::Index(_, index, line_info) => {
+                    accesses.push(Access::Index(evaluate_flow(index, env)?, line_info))
+                }
+                AST::Field(_, field, line_info) => accesses.push(Access::Field(field, line_info)),
+                _ => {}
             }
         }
+        let var_info = env
+            .get_resolved_var(name, slot)
+            .ok_or_else(|| EvalError::UndefinedVariable(name.clone(), var_line_info.clone()))?;
+        Ok(value_to_result(accessed_value(&var_info.value, accesses)?))
+    })();
+    Some(result)
+}
+
+/// Evaluates a binary operation. An operation on two arcana is checked against the limit on
+/// their number of bits before it is computed.
+fn evaluate_binary(
+    op: BinaryOp,
+    left: &AST,
+    right: &AST,
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    let left = evaluate_flow(left, env)?;
+    let right = evaluate_flow(right, env)?;
+    env.check_arcana_operation(op, &left, &right, line_info)?;
+    Ok(binary_op(op, left, right, env.bigint(), line_info)?)
+}
+
+/// Evaluates an AST node, without counting it against the resource limits.
+///
+/// Recursion goes through this function several times per call of a function defined by
+/// `engrave`, so nodes that need much stack space are evaluated by functions that are never
+/// inlined into it, keeping its frame small.
+fn evaluate_node(ast: &AST, env: &mut Environment) -> Result<EvalResult, Unwind> {
+    match ast {
+        AST::Statement(node, _line_info) => {
+            notify_statement(ast, env)?;
+            evaluate_flow(node, env)
+        }
+        AST::Omen(b, _line_info) => Ok(EvalResult::Omen(*b)),
+        AST::Arcana(n, _line_info) => Ok(EvalResult::Arcana(*n)),
+        AST::Aether(n, _line_info) => Ok(EvalResult::Aether(*n)),
+        AST::Rune(s, _, _line_info) => Ok(EvalResult::Rune(s.clone())),
+        AST::Abyss(_line_info) => Ok(EvalResult::Abyss),
+        AST::Grimoire(elements, _line_info) => evaluate_grimoire(elements, env),
+        AST::Codex(entries, line_info) => evaluate_codex(entries, env, line_info),
+        AST::Index(..) | AST::Field(..) => evaluate_access(ast, env),
+        AST::Call(callee, args, line_info) => evaluate_call(callee, args, env, line_info),
+        AST::Add(left, right, line_info) => {
+            evaluate_binary(BinaryOp::Add, left, right, env, line_info)
+        }
+        AST::Sub(left, right, line_info) => {
+            evaluate_binary(BinaryOp::Sub, left, right, env, line_info)
+        }
+        AST::Mul(left, right, line_info) => {
+            evaluate_binary(BinaryOp::Mul, left, right, env, line_info)
+        }
+        AST::Div(left, right, line_info) => {
+            evaluate_binary(BinaryOp::Div, left, right, env, line_info)
+        }
+        AST::Mod(left, right, line_info) => {
+            evaluate_binary(BinaryOp::Mod, left, right, env, line_info)
+        }
+        AST::PowArcana(left, right, line_info) => {
+            evaluate_binary(BinaryOp::PowArcana, left, right, env, line_info)
+        }
+        AST::PowAether(left, right, line_info) => {
+            evaluate_binary(BinaryOp::PowAether, left, right, env, line_info)
+        }
+        AST::Equal(left, right, line_info) => {
+            evaluate_binary(BinaryOp::Equal, left, right, env, line_info)
+        }
+        AST::NotEqual(left, right, line_info) => {
+            evaluate_binary(BinaryOp::NotEqual, left, right, env, line_info)
+        }
+        AST::LessThan(left, right, line_info) => {
+            evaluate_binary(BinaryOp::LessThan, left, right, env, line_info)
+        }
+        AST::LessThanOrEqual(left, right, line_info) => {
+            evaluate_binary(BinaryOp::LessThanOrEqual, left, right, env, line_info)
+        }
+        AST::GreaterThan(left, right, line_info) => {
+            evaluate_binary(BinaryOp::GreaterThan, left, right, env, line_info)
+        }
+        AST::GreaterThanOrEqual(left, right, line_info) => {
+            evaluate_binary(BinaryOp::GreaterThanOrEqual, left, right, env, line_info)
+        }
+        AST::LogicalAnd(left, right, line_info) => {
+            evaluate_binary(BinaryOp::LogicalAnd, left, right, env, line_info)
+        }
+        AST::LogicalOr(left, right, line_info) => {
+            evaluate_binary(BinaryOp::LogicalOr, left, right, env, line_info)
+        }
+        AST::LogicalNot(..) | AST::Trans(..) | AST::Curse(..) => evaluate_unary(ast, env),
         AST::VarAssign {
             name,
             value,
             var_type,
             is_morph,
+            slot,
             line_info,
-        } => {
-            let value = match evaluate(value, env)? {
-                EvalResult::Omen(b) if *var_type == Type::Omen => Value::Omen(b),
-                EvalResult::Arcana(n) if *var_type == Type::Arcana => Value::Arcana(n),
-                EvalResult::Aether(n) if *var_type == Type::Aether => Value::Aether(n),
-                EvalResult::Rune(s) if *var_type == Type::Rune => Value::Rune(s),
-                _ => {
-                    return Err(EvalError::InvalidOperation(
-                        "VarAssign operation requires a valid type!".to_string(),
-                        line_info.clone(),
-                    ))
-                }
-            };
-            env.set_var(
-                name.clone(),
-                value,
-                var_type.clone(),
-                *is_morph,
-                line_info.clone(),
-            );
-            Ok(EvalResult::Abyss)
-        }
+        } => evaluate_var_assign(name, value, var_type, *is_morph, *slot, env, line_info),
         AST::Assignment {
             name,
+            accessors,
             value,
             op,
+            slot,
             line_info,
-        } => {
-            let evaluated_value = evaluate(value, env)?;
-
-            if let Some(var_info) = env.get_var(name) {
-                if !var_info.is_morph {
-                    return Err(EvalError::InvalidOperation(
-                        format!("Cannot reassign to immutable variable {}", name),
-                        line_info.clone(),
-                    ));
-                }
-
-                let result = match (evaluated_value, &var_info.value, op) {
-                    (EvalResult::Arcana(v), Value::Arcana(current), op) => {
-                        let new_value = match op {
-                            AssignmentOp::AddAssign => current + v,
-                            AssignmentOp::SubAssign => current - v,
-                            AssignmentOp::MulAssign => current * v,
-                            AssignmentOp::DivAssign => current / v,
-                            AssignmentOp::ModAssign => current % v,
-                            AssignmentOp::PowArcanaAssign => {
-                                if v < 0 {
-                                    return Err(EvalError::NegativeExponent(line_info.clone()));
-                                }
-                                current.pow(v as u32)
-                            }
-                            AssignmentOp::Assign => v,
-                            _ => {
-                                return Err(EvalError::InvalidOperation(
-                                    format!("Unsupported operation for variable {}", name),
-                                    line_info.clone(),
-                                ))
-                            }
-                        };
-                        env.update_var(
-                            name,
-                            Value::Arcana(new_value),
-                            Type::Arcana,
-                            line_info.clone(),
-                        )
-                    }
-                    (EvalResult::Aether(v), Value::Aether(current), op) => {
-                        let new_value = match op {
-                            AssignmentOp::AddAssign => current + v,
-                            AssignmentOp::SubAssign => current - v,
-                            AssignmentOp::MulAssign => current * v,
-                            AssignmentOp::DivAssign => current / v,
-                            AssignmentOp::ModAssign => current % v,
-                            AssignmentOp::PowAetherAssign => current.powf(v),
-                            AssignmentOp::Assign => v,
-                            _ => {
-                                return Err(EvalError::InvalidOperation(
-                                    format!("Unsupported operation for variable {}", name),
-                                    line_info.clone(),
-                                ))
-                            }
-                        };
-                        env.update_var(
-                            name,
-                            Value::Aether(new_value),
-                            Type::Aether,
-                            line_info.clone(),
-                        )
-                    }
-                    (EvalResult::Rune(v), Value::Rune(current), op) => {
-                        let new_value = match op {
-                            AssignmentOp::AddAssign => format!("{}{}", current, v),
-                            AssignmentOp::Assign => v,
-                            _ => {
-                                return Err(EvalError::InvalidOperation(
-                                    format!("Unsupported operation for variable {}", name),
-                                    line_info.clone(),
-                                ))
-                            }
-                        };
-                        env.update_var(name, Value::Rune(new_value), Type::Rune, line_info.clone())
-                    }
-                    (EvalResult::Omen(v), _, AssignmentOp::Assign) => {
-                        env.update_var(name, Value::Omen(v), Type::Omen, line_info.clone())
-                    }
-                    _ => Err(EvalError::InvalidOperation(
-                        format!(
-                            "Type mismatch or unsupported operation for variable {}",
-                            name
-                        ),
-                        line_info.clone(),
-                    )),
-                };
-
-                result.map(|_| EvalResult::Abyss)
-            } else {
-                Err(EvalError::UndefinedVariable(
-                    name.clone(),
-                    line_info.clone(),
-                ))
-            }
-        }
-        AST::Var(name, line_info) => match env.get_var(name) {
-            Some(var_info) => match &var_info.value {
-                Value::Omen(b) => Ok(EvalResult::Omen(*b)),
-                Value::Arcana(n) => Ok(EvalResult::Arcana(*n)),
-                Value::Aether(n) => Ok(EvalResult::Aether(*n)),
-                Value::Rune(s) => Ok(EvalResult::Rune(s.clone())),
-            },
-            None => Err(EvalError::UndefinedVariable(
-                name.clone(),
-                line_info.clone(),
-            )),
-        },
-        AST::Unveil(args, _line_info) => {
-            let outputs: Result<Vec<String>, EvalError> = args
-                .iter()
-                .map(|arg| evaluate(arg, env))
-                .collect::<Result<Vec<EvalResult>, EvalError>>()?
-                .iter()
-                .map(|result| match result {
-                    EvalResult::Omen(b) => match b {
-                        true => Ok("boon".to_string()),
-                        false => Ok("hex".to_string()),
-                    },
-                    EvalResult::Arcana(n) => Ok(n.to_string()),
-                    EvalResult::Aether(n) => Ok(n.to_string()),
-                    EvalResult::Rune(s) => Ok(s.replace("\\n", "\n")),
-                    EvalResult::Abyss => Ok("".to_string()),
-                    _ => Err(EvalError::InvalidOperation(
-                        "Unsupported type in unveil statement".to_string(),
-                        None,
-                    )),
-                })
-                .collect();
-            let output_str = outputs?.join("");
-            println!("{}", output_str);
-            Ok(EvalResult::Abyss)
-        }
-        AST::Trans(expr, target_type, line_info) => {
-            let value = evaluate(expr, env)?;
-            match target_type {
-                Type::Arcana => match value {
-                    EvalResult::Aether(n) => Ok(EvalResult::Arcana(n as i64)),
-                    EvalResult::Rune(s) => s.parse::<i64>().map(EvalResult::Arcana).map_err(|_| {
-                        EvalError::InvalidOperation(
-                            "Failed to convert Rune to Arcana".to_string(),
-                            line_info.clone(),
-                        )
-                    }),
-                    _ => Err(EvalError::InvalidOperation(
-                        "Invalid cast to Arcana".to_string(),
-                        line_info.clone(),
-                    )),
-                },
-                Type::Aether => match value {
-                    EvalResult::Arcana(n) => Ok(EvalResult::Aether(n as f64)),
-                    EvalResult::Rune(s) => s.parse::<f64>().map(EvalResult::Aether).map_err(|_| {
-                        EvalError::InvalidOperation(
-                            "Failed to convert Rune to Aether".to_string(),
-                            line_info.clone(),
-                        )
-                    }),
-                    _ => Err(EvalError::InvalidOperation(
-                        "Invalid cast to Aether".to_string(),
-                        line_info.clone(),
-                    )),
-                },
-                Type::Rune => match value {
-                    EvalResult::Arcana(n) => Ok(EvalResult::Rune(n.to_string())),
-                    EvalResult::Aether(n) => Ok(EvalResult::Rune(n.to_string())),
-                    _ => Err(EvalError::InvalidOperation(
-                        "Invalid cast to Rune".to_string(),
-                        line_info.clone(),
-                    )),
-                },
-                Type::Omen => Err(EvalError::InvalidOperation(
-                    "Casting to Omen is not supported".to_string(),
-                    line_info.clone(),
-                )),
-                _ => Err(EvalError::InvalidOperation(
-                    format!("Unsupported cast to type {:?}", target_type),
-                    line_info.clone(),
-                )),
-            }
-        }
+        } => evaluate_assignment(name, accessors, value, op, *slot, env, line_info),
+        AST::Var(name, slot, line_info) => evaluate_var(name, *slot, env, line_info),
+        AST::Unveil(args, _line_info) => evaluate_unveil(args, env),
+        AST::Interpolation(parts, _line_info) => evaluate_interpolation(parts, env),
+        AST::Attempt(expr, _) => evaluate_attempt(expr, env),
         AST::Oracle {
             is_match,
             conditionals,
             branches,
             line_info,
-        } => {
-            env.push_scope();
-
-            let mut evaluate_and_set_var =
-                |conditional: &ConditionalAssignment| -> Result<(), EvalError> {
-                    let result = evaluate(&conditional.expression, env)?;
-                    match result {
-                        EvalResult::Arcana(n) => env.set_var(
-                            conditional.variable.clone(),
-                            Value::Arcana(n),
-                            Type::Arcana,
-                            false,
-                            line_info.clone(),
-                        ),
-                        EvalResult::Aether(n) => env.set_var(
-                            conditional.variable.clone(),
-                            Value::Aether(n),
-                            Type::Aether,
-                            false,
-                            line_info.clone(),
-                        ),
-                        EvalResult::Rune(ref s) => env.set_var(
-                            conditional.variable.clone(),
-                            Value::Rune(s.clone()),
-                            Type::Rune,
-                            false,
-                            line_info.clone(),
-                        ),
-                        EvalResult::Omen(b) => env.set_var(
-                            conditional.variable.clone(),
-                            Value::Omen(b),
-                            Type::Omen,
-                            false,
-                            line_info.clone(),
-                        ),
-                        _ => {
-                            return Err(EvalError::InvalidOperation(
-                                format!("Unsupported type in oracle conditional: {:?}", result),
-                                line_info.clone(),
-                            ))
-                        }
-                    }
-                    Ok(())
-                };
-
-            for conditional in conditionals {
-                evaluate_and_set_var(conditional)?;
-            }
-
-            for branch in branches {
-                if let AST::Comment(_, _) = branch {
-                    continue;
-                }
-
-                if let AST::OracleBranch {
-                    pattern,
-                    body,
-                    line_info,
-                } = branch
-                {
-                    let matched = if pattern.is_empty() {
-                        true
-                    } else if *is_match {
-                        let mut matched = true;
-                        for (idx, pattern) in pattern.iter().enumerate() {
-                            if let AST::OracleDontCareItem(_) = pattern {
-                                continue;
-                            }
-                            let pattern_result = evaluate(pattern, env)?;
-                            let conditional_result = evaluate(&conditionals[idx].expression, env)?;
-
-                            match (conditional_result, pattern_result) {
-                                (EvalResult::Arcana(cond_n), EvalResult::Arcana(pat_n)) => {
-                                    if cond_n != pat_n {
-                                        matched = false;
-                                        break;
-                                    }
-                                }
-                                (EvalResult::Aether(cond_n), EvalResult::Aether(pat_n)) => {
-                                    if (cond_n - pat_n).abs() >= std::f64::EPSILON {
-                                        matched = false;
-                                        break;
-                                    }
-                                }
-                                (EvalResult::Rune(cond_s), EvalResult::Rune(pat_s)) => {
-                                    if cond_s != pat_s {
-                                        matched = false;
-                                        break;
-                                    }
-                                }
-                                (EvalResult::Omen(cond_b), EvalResult::Omen(pat_b)) => {
-                                    if cond_b != pat_b {
-                                        matched = false;
-                                        break;
-                                    }
-                                }
-                                _ => {
-                                    return Err(EvalError::InvalidOperation(
-                                        "Oracle branch pattern type must match conditional type"
-                                            .to_string(),
-                                        line_info.clone(),
-                                    ))
-                                }
-                            }
-                        }
-                        matched
-                    } else {
-                        pattern.iter().all(|pattern| {
-                            matches!(evaluate(pattern, env), Ok(EvalResult::Omen(true)))
-                        })
-                    };
-
-                    if matched {
-                        let result = match evaluate(&body, env) {
-                            Ok(result) => match result {
-                                EvalResult::Revealed(revealed) => *revealed,
-                                _ => result,
-                            },
-                            Err(e) => return Err(e),
-                        };
-                        env.pop_scope();
-                        return Ok(result);
-                    }
-                }
-            }
-
-            env.pop_scope();
-            Ok(EvalResult::Abyss)
-        }
-        AST::Reveal(expr, _line_info) => {
-            let result = evaluate(expr, env)?;
-            Ok(EvalResult::Revealed(Box::new(result)))
-        }
-        AST::Block(statements, _line_info) => {
-            let mut last_result = EvalResult::Abyss;
-            for statement in statements {
-                let result = evaluate(statement, env)?;
-
-                match result {
-                    EvalResult::Revealed(revealed) => return Ok(*revealed),
-                    EvalResult::Resume(_) | EvalResult::Eject(_) => return Ok(result),
-                    _ => {}
-                }
-
-                last_result = result;
-            }
-            Ok(last_result)
-        }
+        } => evaluate_oracle(*is_match, conditionals, branches, env, line_info),
+        AST::Block(statements, _line_info) => evaluate_block(statements, env),
         AST::OracleDontCareItem(_line_info) => Ok(EvalResult::Omen(true)),
         AST::Orbit {
             params,
             body,
             line_info,
-        } => {
-            if params.is_empty() {
-                loop {
-                    env.push_scope();
-
-                    let result = evaluate(body, env)?;
-
-                    match result {
-                        EvalResult::Resume(_) => continue,
-                        EvalResult::Eject(_) => break,
-                        _ => {}
-                    }
-
-                    env.pop_scope();
-                }
-
-                Ok(EvalResult::Abyss)
-            } else {
-                if let AST::OrbitParam {
-                    name,
-                    start,
-                    end,
-                    op,
-                    ..
-                } = &params[0]
-                {
-                    let start_value = evaluate(start, env)?;
-                    let end_value = evaluate(end, env)?;
-
-                    if let (EvalResult::Arcana(start_num), EvalResult::Arcana(end_num)) =
-                        (start_value, end_value)
-                    {
-                        let range = start_num..end_num + if op == ".." { 0 } else { 1 };
-
-                        for value in range {
-                            env.push_scope();
-
-                            env.set_var(
-                                name.clone(),
-                                Value::Arcana(value),
-                                Type::Arcana,
-                                true,
-                                line_info.clone(),
-                            );
-
-                            let remaining_params = params[1..].to_vec();
-                            let result = match remaining_params.len() == 0 {
-                                true => evaluate(body, env)?,
-                                false => evaluate(
-                                    &AST::Orbit {
-                                        params: remaining_params,
-                                        body: body.clone(),
-                                        line_info: line_info.clone(),
-                                    },
-                                    env,
-                                )?,
-                            };
-
-                            match result {
-                                EvalResult::Resume(identifier) => {
-                                    if let Some(id) = identifier {
-                                        if id == *name {
-                                            continue;
-                                        } else {
-                                            env.pop_scope();
-                                            return Ok(EvalResult::Resume(Some(id)));
-                                        }
-                                    }
-                                    continue;
-                                }
-                                EvalResult::Eject(identifier) => {
-                                    if let Some(id) = identifier {
-                                        if id == *name {
-                                            break;
-                                        } else {
-                                            env.pop_scope();
-                                            return Ok(EvalResult::Eject(Some(id)));
-                                        }
-                                    }
-                                    break;
-                                }
-                                _ => {}
-                            }
-
-                            env.pop_scope();
-                        }
-                        Ok(EvalResult::Abyss)
-                    } else {
-                        Err(EvalError::TypeError(
-                            format!("Orbit parameter must be of type Arcana: {}", name),
-                            line_info.clone(),
-                        ))
-                    }
-                } else {
-                    Err(EvalError::InvalidOperation(
-                        "Expected OrbitParam in Orbit".to_string(),
-                        line_info.clone(),
-                    ))
-                }
-            }
-        }
-        AST::Resume(identifier, _line_info) => Ok(EvalResult::Resume(identifier.clone())),
-        AST::Eject(identifier, _line_info) => Ok(EvalResult::Eject(identifier.clone())),
+        } => evaluate_orbit(params, body, line_info, env),
+        AST::Reveal(expr, line_info) => evaluate_reveal(expr, env, line_info),
+        AST::BigArcana(..)
+        | AST::Invoke { .. }
+        | AST::Summon(..)
+        | AST::Resume(..)
+        | AST::Eject(..) => evaluate_uncommon(ast, env),
         AST::Engrave {
             name,
             params,
             return_type,
             body,
             line_info,
-        } => {
-            let function = Function {
-                name: name.clone(),
-                params: params.clone(),
-                return_type: return_type.clone(),
-                body: body.clone(),
-                line_info: line_info.clone(),
-            };
-            env.set_function(name.clone(), function);
-            Ok(EvalResult::Abyss)
-        }
+        } => evaluate_engrave(name, params, return_type, body, line_info, env),
+        AST::EngraveExpr {
+            params,
+            return_type,
+            body,
+            line_info,
+        } => evaluate_engrave_expr(params, return_type, body, line_info, env),
         AST::FuncCall {
             name,
             args,
             line_info,
-        } => {
-            let function = {
-                env.get_function(name)
-                    .ok_or_else(|| EvalError::UndefinedVariable(name.clone(), line_info.clone()))?
-            }
-            .clone();
+        } => evaluate_func_call(name, args, env, line_info),
+        AST::Sigil {
+            name,
+            fields,
+            line_info,
+        } => evaluate_sigil(name, fields, env, line_info),
+        AST::SigilInstance {
+            name,
+            fields,
+            line_info,
+        } => evaluate_sigil_instance(name, fields, env, line_info),
+        AST::Comment(_, _) => Ok(EvalResult::Abyss),
+        _ => Err(unsupported(ast).into()),
+    }
+}
 
-            let params = function.params.clone();
+/// Notifies the hook of the environment of a statement about to run. A hook that is already
+/// running, such as a debugger evaluating an expression, is not notified of the statements it
+/// runs.
+#[inline(never)]
+fn notify_statement(statement: &AST, env: &mut Environment) -> Result<(), EvalError> {
+    if let Some(hook) = env.hook() {
+        if let Ok(mut hook) = hook.try_borrow_mut() {
+            hook.on_statement(statement, env)?;
+        }
+    }
+    Ok(())
+}
 
-            let mut evaluated_args = Vec::new();
-            for arg in args {
-                let evaluated_arg = evaluate(arg, env)?;
-                evaluated_args.push(evaluated_arg);
-            }
+/// Evaluates the nodes that are rare in loops and recursion: an `arcana` literal too large for
+/// 64 bits, an `invoke`, a `summon`, a `resume` and an `eject`.
+#[inline(never)]
+fn evaluate_uncommon(ast: &AST, env: &mut Environment) -> Result<EvalResult, Unwind> {
+    match ast {
+        AST::BigArcana(n, line_info) => Ok(big_arcana_literal(n, env.bigint(), line_info)?),
+        AST::Invoke {
+            path,
+            names,
+            line_info,
+        } => Ok(evaluate_invoke(path, names, env, line_info)?),
+        AST::Summon(prompt, var_type, line_info) => Ok(summon(prompt, var_type, env, line_info)?),
+        AST::Resume(identifier, line_info) => Err(Unwind::Control(
+            ControlFlow::Resume(identifier.clone()),
+            line_info.clone(),
+        )),
+        AST::Eject(identifier, line_info) => Err(Unwind::Control(
+            ControlFlow::Eject(identifier.clone()),
+            line_info.clone(),
+        )),
+        _ => Err(unsupported(ast).into()),
+    }
+}
 
-            env.push_scope();
+/// Evaluates a `reveal`, which transfers its value to the end of the function it is in.
+#[inline(never)]
+fn evaluate_reveal(
+    expr: &AST,
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    let result = evaluate_flow(expr, env)?;
+    Err(Unwind::Control(
+        ControlFlow::Reveal(result),
+        line_info.clone(),
+    ))
+}
 
-            for (evaluated_arg, param) in evaluated_args.into_iter().zip(params.iter()) {
-                let (name, param_type) = match param {
-                    AST::EngraveParam {
-                        name, param_type, ..
-                    } => (name, param_type),
-                    _ => {
-                        return Err(EvalError::InvalidOperation(
-                            format!("Expected EngraveParam in function definition: {}", name),
-                            line_info.clone(),
-                        ))
-                    }
-                };
-                let value = match (evaluated_arg, param_type) {
-                    (EvalResult::Arcana(n), Type::Arcana) => Value::Arcana(n),
-                    (EvalResult::Aether(n), Type::Aether) => Value::Aether(n),
-                    (EvalResult::Rune(s), Type::Rune) => Value::Rune(s),
-                    (EvalResult::Omen(b), Type::Omen) => Value::Omen(b),
-                    _ => {
-                        return Err(EvalError::TypeError(
-                            format!("Type mismatch for parameter {}", name),
-                            line_info.clone(),
-                        ))
-                    }
-                };
-                env.set_var(
-                    name.to_string(),
-                    value,
-                    param_type.clone(),
-                    false,
-                    line_info.clone(),
-                );
+/// Evaluates a block, which results in the value of its last statement. The values of the
+/// other statements are dropped.
+#[inline(never)]
+fn evaluate_block(statements: &[AST], env: &mut Environment) -> Result<EvalResult, Unwind> {
+    let Some((last, statements)) = statements.split_last() else {
+        return Ok(EvalResult::Abyss);
+    };
+    for statement in statements {
+        drop_result(evaluate_flow(statement, env)?, statement)?;
+    }
+    evaluate_flow(last, env)
+}
+
+/// Evaluates a codex literal, whose keys must be distinct.
+#[inline(never)]
+fn evaluate_codex(
+    entries: &[(AST, AST)],
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    let mut evaluated = BTreeMap::new();
+    for (key, value) in entries {
+        let key = result_to_key(evaluate_flow(key, env)?, &Type::Abyss, line_info)?;
+        if evaluated.contains_key(&key) {
+            return Err(duplicate_key(&key, line_info).into());
+        }
+        evaluated.insert(key, evaluate_flow(value, env)?);
+    }
+    Ok(EvalResult::Codex(evaluated))
+}
+
+/// Evaluates a call to the function a `callee` expression results in.
+#[inline(never)]
+fn evaluate_call(
+    callee: &AST,
+    args: &[AST],
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    let function = callee_function(evaluate_flow(callee, env)?, line_info)?;
+    let evaluated_args = evaluate_args(args, env)?;
+    Ok(call_engrave(&function, evaluated_args, env, line_info)?)
+}
+
+/// Evaluates the declaration of a variable in the current scope.
+#[inline(never)]
+fn evaluate_var_assign(
+    name: &str,
+    value: &AST,
+    var_type: &Type,
+    is_morph: bool,
+    slot: Option<Slot>,
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    let value = declared_value(evaluate_flow(value, env)?, var_type, line_info)?;
+    env.set_resolved_var(
+        name,
+        slot,
+        value,
+        var_type.clone(),
+        is_morph,
+        line_info.clone(),
+    );
+    Ok(EvalResult::Abyss)
+}
+
+/// Evaluates an assignment to a variable, or to an element of it reached through `accessors`.
+#[inline(never)]
+fn evaluate_assignment(
+    name: &str,
+    accessors: &[Accessor],
+    value: &AST,
+    op: &AssignmentOp,
+    slot: Option<Slot>,
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    let evaluated_value = evaluate_flow(value, env)?;
+
+    if slot.is_none() {
+        env.check_scoping(name, false, line_info);
+    }
+    if env.get_resolved_var(name, slot).is_none() {
+        return Err(EvalError::UndefinedVariable(name.to_string(), line_info.clone()).into());
+    }
+    let mut indexes = Vec::new();
+    for accessor in accessors {
+        if let Accessor::Index(index, _) = accessor {
+            indexes.push(evaluate_flow(index, env)?);
+        }
+    }
+    env.lend_resolved_var(name, slot, |var_info, env| {
+        assign_value(
+            name,
+            var_info,
+            accessors,
+            indexes,
+            evaluated_value,
+            op,
+            env,
+            line_info,
+        )
+    })
+    .ok_or_else(|| EvalError::UndefinedVariable(name.to_string(), line_info.clone()))??;
+    Ok(EvalResult::Abyss)
+}
+
+/// Evaluates a variable, or a function named like it, as a value.
+#[inline(never)]
+fn evaluate_var(
+    name: &str,
+    slot: Option<Slot>,
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    if slot.is_none() {
+        env.check_scoping(name, false, line_info);
+    }
+    if let Some(var_info) = env.get_resolved_var(name, slot) {
+        return Ok(value_to_result(&var_info.value));
+    }
+    match env.get_function(name) {
+        Some(function) => Ok(function_value(&function)),
+        None => native_value(name, env).ok_or_else(|| {
+            EvalError::UndefinedVariable(name.to_string(), line_info.clone()).into()
+        }),
+    }
+}
+
+/// Evaluates an `attempt`, turning an error of the expression into a curse.
+#[inline(never)]
+fn evaluate_attempt(expr: &AST, env: &mut Environment) -> Result<EvalResult, Unwind> {
+    let depth = env.scope_depth();
+    // Only errors are caught: a transfer of control passes through.
+    match evaluate_flow(expr, env) {
+        Err(Unwind::Error(e)) if !e.is_halt() => {
+            env.unwind_scopes(depth);
+            env.clear_trace();
+            Ok(e.into_curse())
+        }
+        result => result,
+    }
+}
+
+/// Evaluates an `oracle` in a scope of its own holding its conditional variables. The oracle
+/// results in the value of the first branch whose pattern matches.
+#[inline(never)]
+fn evaluate_oracle(
+    is_match: bool,
+    conditionals: &[ConditionalAssignment],
+    branches: &[AST],
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    env.push_scope();
+    let conditional_results = evaluate_conditionals(conditionals, env, line_info)?;
+
+    for branch in branches {
+        if let AST::OracleBranch {
+            pattern,
+            body,
+            line_info,
+        } = branch
+        {
+            if branch_matches(pattern, is_match, &conditional_results, env, line_info)? {
+                // The oracle's value is the value of the branch taken.
+                let result = evaluate_flow(body, env)?;
+                env.pop_scope();
+                return Ok(result);
             }
+        }
+    }
 
-            let result = evaluate(&function.body, env)?;
+    env.pop_scope();
+    Ok(EvalResult::Abyss)
+}
 
-            env.pop_scope();
+/// Evaluates the conditionals of an `oracle`, binding the variable of each to its value in the
+/// scope of the oracle. Each conditional is evaluated once, so that patterns never re-run its
+/// side effects.
+///
+/// # Returns
+/// The values of the conditionals.
+#[inline(never)]
+fn evaluate_conditionals(
+    conditionals: &[ConditionalAssignment],
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<Vec<EvalResult>, Unwind> {
+    let mut conditional_results = Vec::new();
+    for (index, conditional) in conditionals.iter().enumerate() {
+        let result = evaluate_flow(&conditional.expression, env)?;
+        let (value, var_type) = conditional_value(&result, line_info)?;
+        env.set_resolved_var(
+            &conditional.variable,
+            Some(Slot::current(index)),
+            value,
+            var_type,
+            false,
+            line_info.clone(),
+        );
+        conditional_results.push(result);
+    }
+    Ok(conditional_results)
+}
 
-            match (result, function.return_type) {
-                (EvalResult::Arcana(n), Type::Arcana) => Ok(EvalResult::Arcana(n)),
-                (EvalResult::Aether(n), Type::Aether) => Ok(EvalResult::Aether(n)),
-                (EvalResult::Rune(s), Type::Rune) => Ok(EvalResult::Rune(s)),
-                (EvalResult::Omen(b), Type::Omen) => Ok(EvalResult::Omen(b)),
-                (EvalResult::Abyss, Type::Abyss) => Ok(EvalResult::Abyss),
-                _ => Err(EvalError::TypeError(
-                    format!("Type mismatch for return value of function {}", name),
-                    function.line_info.clone(),
-                )),
+/// Returns whether the pattern of a branch of an `oracle` matches. A branch without a pattern
+/// always matches. In a match, each item of the pattern is compared with the value of the
+/// conditional at its position; otherwise every item must evaluate to `boon`.
+#[inline(never)]
+fn branch_matches(
+    pattern: &[AST],
+    is_match: bool,
+    conditional_results: &[EvalResult],
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<bool, Unwind> {
+    if is_match {
+        for (idx, pattern) in pattern.iter().enumerate() {
+            if let AST::OracleDontCareItem(_) = pattern {
+                continue;
+            }
+            let conditional_result = conditional_results
+                .get(idx)
+                .cloned()
+                .ok_or_else(|| missing_conditional(line_info))?;
+            if !match_curse_pattern(pattern, &conditional_result, env)? {
+                return Ok(false);
+            }
+            if let AST::Curse(_, _) = pattern {
+                continue;
+            }
+            let pattern_result = evaluate_flow(pattern, env)?;
+            if !pattern_matches(conditional_result, pattern_result, line_info)? {
+                return Ok(false);
             }
         }
-        AST::Summon(prompt, var_type, line_info) => {
-            print!("{}", prompt.trim_matches('"'));
-            std::io::stdout().flush().map_err(|_| {
-                EvalError::InvalidOperation("Failed to flush stdout".to_string(), line_info.clone())
-            })?;
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).map_err(|_| {
-                EvalError::InvalidOperation("Failed to read input".to_string(), line_info.clone())
-            })?;
-            match var_type {
-                Type::Arcana => input
-                    .trim()
-                    .parse::<i64>()
-                    .map(EvalResult::Arcana)
-                    .map_err(|_| {
-                        EvalError::InvalidOperation(
-                            "Failed to parse input as Arcana".to_string(),
-                            line_info.clone(),
-                        )
-                    }),
-                Type::Aether => input
-                    .trim()
-                    .parse::<f64>()
-                    .map(EvalResult::Aether)
-                    .map_err(|_| {
-                        EvalError::InvalidOperation(
-                            "Failed to parse input as Aether".to_string(),
-                            line_info.clone(),
-                        )
-                    }),
-                Type::Rune => Ok(EvalResult::Rune(input.trim().to_string())),
-                _ => Err(EvalError::InvalidOperation(
-                    "Unsupported type for summon".to_string(),
-                    line_info.clone(),
-                )),
+        return Ok(true);
+    }
+    // A pattern that fails to evaluate does not match; the scopes it left behind are
+    // discarded.
+    let depth = env.scope_depth();
+    for pattern in pattern {
+        match evaluate_flow(pattern, env) {
+            Ok(EvalResult::Omen(true)) => {}
+            Ok(_) => return Ok(false),
+            Err(Unwind::Error(e)) if !e.is_halt() => {
+                env.unwind_scopes(depth);
+                return Ok(false);
             }
+            Err(unwind) => return Err(unwind),
         }
-        AST::Comment(_, _) => Ok(EvalResult::Abyss),
-        _ => Err(EvalError::InvalidOperation(
-            format!("Unsupported operation: {:?}", ast),
-            None,
-        )),
     }
+    Ok(true)
+}
+
+/// Evaluates the definition of a function by `engrave`. The function sees the scopes open where
+/// it is defined.
+#[inline(never)]
+fn evaluate_engrave(
+    name: &str,
+    params: &[AST],
+    return_type: &Type,
+    body: &AST,
+    line_info: &Option<LineInfo>,
+    env: &mut Environment,
+) -> Result<EvalResult, Unwind> {
+    let function = Function {
+        name: name.to_string(),
+        params: params.to_vec(),
+        return_type: return_type.clone(),
+        body: Rc::new(body.clone()),
+        line_info: line_info.clone(),
+        module: None,
+        closure: env.capture_scopes(),
+        native: false,
+    };
+    env.set_function(name.to_string(), function);
+    Ok(EvalResult::Abyss)
+}
+
+/// Evaluates an anonymous function, which keeps the scopes open where it is defined alive.
+#[inline(never)]
+fn evaluate_engrave_expr(
+    params: &[AST],
+    return_type: &Type,
+    body: &AST,
+    line_info: &Option<LineInfo>,
+    env: &mut Environment,
+) -> Result<EvalResult, Unwind> {
+    Ok(EvalResult::Engrave(Rc::new(Function {
+        name: ANONYMOUS_ENGRAVE.to_string(),
+        params: params.to_vec(),
+        return_type: return_type.clone(),
+        body: Rc::new(body.clone()),
+        line_info: line_info.clone(),
+        module: None,
+        closure: env.capture_scopes().held(),
+        native: false,
+    })))
+}
+
+/// Evaluates a grimoire literal.
+#[inline(never)]
+fn evaluate_grimoire(elements: &[AST], env: &mut Environment) -> Result<EvalResult, Unwind> {
+    let items = elements
+        .iter()
+        .map(|element| evaluate_flow(element, env))
+        .collect::<Result<Vec<EvalResult>, Unwind>>()?;
+    Ok(EvalResult::Grimoire(items))
+}
+
+/// Evaluates an index or a field, reading it from a variable in place when it accesses one.
+#[inline(never)]
+fn evaluate_access(ast: &AST, env: &mut Environment) -> Result<EvalResult, Unwind> {
+    if let Some(result) = evaluate_var_access(ast, env) {
+        return result;
+    }
+    match ast {
+        AST::Index(target, index, line_info) => {
+            let target = evaluate_flow(target, env)?;
+            Ok(index_value(target, evaluate_flow(index, env)?, line_info)?)
+        }
+        AST::Field(target, field, line_info) => {
+            Ok(field_value(evaluate_flow(target, env)?, field, line_info)?)
+        }
+        _ => Err(unsupported(ast).into()),
+    }
+}
+
+/// Evaluates a `!`, a `trans` or a `curse` on the value of an expression.
+#[inline(never)]
+fn evaluate_unary(ast: &AST, env: &mut Environment) -> Result<EvalResult, Unwind> {
+    match ast {
+        AST::LogicalNot(expr, line_info) => Ok(logical_not(evaluate_flow(expr, env)?, line_info)?),
+        AST::Trans(expr, target_type, line_info) => Ok(trans(
+            evaluate_flow(expr, env)?,
+            target_type,
+            env.bigint(),
+            line_info,
+        )?),
+        AST::Curse(message, line_info) => Ok(make_curse(evaluate_flow(message, env)?, line_info)?),
+        _ => Err(unsupported(ast).into()),
+    }
+}
+
+/// Evaluates an `unveil` of the values of its arguments.
+#[inline(never)]
+fn evaluate_unveil(args: &[AST], env: &mut Environment) -> Result<EvalResult, Unwind> {
+    let results = args
+        .iter()
+        .map(|arg| evaluate_flow(arg, env))
+        .collect::<Result<Vec<EvalResult>, Unwind>>()?;
+    Ok(unveil(&results, env)?)
+}
+
+/// Evaluates a rune with interpolated expressions.
+#[inline(never)]
+fn evaluate_interpolation(parts: &[RunePart], env: &mut Environment) -> Result<EvalResult, Unwind> {
+    let results = parts
+        .iter()
+        .map(|part| match part {
+            RunePart::Text(text, _) => Ok(EvalResult::Rune(text.clone())),
+            RunePart::Expr(expr) => evaluate_flow(expr, env),
+        })
+        .collect::<Result<Vec<EvalResult>, Unwind>>()?;
+    Ok(EvalResult::Rune(unveil_text(&results)))
+}
+
+/// Evaluates the definition of a sigil.
+#[inline(never)]
+fn evaluate_sigil(
+    name: &str,
+    fields: &[(String, Type)],
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    let sigil = Sigil {
+        name: name.to_string(),
+        fields: fields.to_vec(),
+        line_info: line_info.clone(),
+    };
+    env.set_sigil(name.to_string(), sigil);
+    Ok(EvalResult::Abyss)
+}
+
+/// Returns the error for a node that cannot be evaluated.
+#[inline(never)]
+fn unsupported(ast: &AST) -> EvalError {
+    EvalError::InvalidOperation(format!("Unsupported operation: {:?}", ast), None)
+}
+
+/// Evaluates a call to a function by name: a function defined by `engrave`, a function held
+/// by a variable, a builtin or a native function.
+#[inline(never)]
+fn evaluate_func_call(
+    name: &str,
+    args: &[AST],
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    env.check_scoping(name, true, line_info);
+    if env.get_function(name).is_none() {
+        return evaluate_other_call(name, args, env, line_info);
+    }
+    let evaluated_args = evaluate_args(args, env)?;
+    Ok(apply_function(name, evaluated_args, env, line_info)?)
+}
+
+/// Evaluates a call by name to a function that is not defined by `engrave`: a function held
+/// by a variable, a builtin or a native function.
+#[inline(never)]
+fn evaluate_other_call(
+    name: &str,
+    args: &[AST],
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    if let Some(function) = function_in_var(name, env) {
+        let evaluated_args = evaluate_args(args, env)?;
+        return Ok(call_engrave(&function, evaluated_args, env, line_info)?);
+    }
+    if let Some(result) = evaluate_builtin(name, args, env, line_info) {
+        return result;
+    }
+    if env.get_native(name).is_none() {
+        return Err(match env.get_var(name) {
+            Some(_) => not_a_function(name, line_info),
+            None => EvalError::UndefinedVariable(name.to_string(), line_info.clone()),
+        }
+        .into());
+    }
+    let evaluated_args = evaluate_args(args, env)?;
+    Ok(call_native(name, evaluated_args, env, line_info)?)
+}
+
+/// Evaluates the arguments of a call, in order.
+fn evaluate_args(args: &[AST], env: &mut Environment) -> Result<Vec<EvalResult>, Unwind> {
+    args.iter().map(|arg| evaluate_flow(arg, env)).collect()
+}
+
+/// Evaluates an instance of a sigil from the values of its fields.
+#[inline(never)]
+fn evaluate_sigil_instance(
+    name: &str,
+    fields: &[(String, AST)],
+    env: &mut Environment,
+    line_info: &Option<LineInfo>,
+) -> Result<EvalResult, Unwind> {
+    let mut values = Vec::new();
+    for (field, value) in fields {
+        values.push((field.clone(), evaluate_flow(value, env)?));
+    }
+    Ok(make_sigil(name, values, env, line_info)?)
 }
@@ -1,4 +1,4 @@
-use crate::ast::{AssignmentOp, Type, AST};
+use crate::ast::{Accessor, AssignmentOp, RunePart, RuneStyle, Type, AST};
 
 /// Formats an AST node into a readable string with appropriate indentation.
 /// This function handles various types of AST nodes, applying formatting rules based on node type.
@@ -97,48 +97,49 @@ pub fn format_ast(ast: &AST, indent_level: usize) -> String {
             is_morph,
             ..
         } => {
-            let type_str = match var_type {
-                Type::Arcana => "arcana",
-                Type::Aether => "aether",
-                Type::Rune => "rune",
-                _ => "",
-            };
             format!(
                 "forge {}{}: {} = {}",
                 if *is_morph { "morph " } else { "" },
                 name,
-                type_str,
+                format_type(var_type),
                 format_ast(value, indent_level)
             )
         }
         AST::Assignment {
-            name, value, op, ..
-        } => match op {
-            AssignmentOp::Assign => format!("{} = {}", name, format_ast(value, indent_level)),
-            AssignmentOp::AddAssign => {
-                format!("{} += {}", name, format_ast(value, indent_level))
-            }
-            AssignmentOp::SubAssign => {
-                format!("{} -= {}", name, format_ast(value, indent_level))
-            }
-            AssignmentOp::MulAssign => {
-                format!("{} *= {}", name, format_ast(value, indent_level))
-            }
-            AssignmentOp::DivAssign => {
-                format!("{} /= {}", name, format_ast(value, indent_level))
-            }
-            AssignmentOp::ModAssign => {
-                format!("{} %= {}", name, format_ast(value, indent_level))
-            }
-            AssignmentOp::PowArcanaAssign => {
-                format!("{} ^= {}", name, format_ast(value, indent_level))
-            }
-            AssignmentOp::PowAetherAssign => {
-                format!("{} **= {}", name, format_ast(value, indent_level))
-            }
-        },
-        AST::Var(name, _) => name.clone(),
+            name,
+            accessors,
+            value,
+            op,
+            ..
+        } => {
+            let target = accessors
+                .iter()
+                .fold(name.clone(), |target, accessor| match accessor {
+                    Accessor::Index(index, _) => {
+                        format!("{}[{}]", target, format_ast(index, indent_level))
+                    }
+                    Accessor::Field(field, _) => format!("{}.{}", target, field),
+                });
+            let operator = match op {
+                AssignmentOp::Assign => "=",
+                AssignmentOp::AddAssign => "+=",
+                AssignmentOp::SubAssign => "-=",
+                AssignmentOp::MulAssign => "*=",
+                AssignmentOp::DivAssign => "/=",
+                AssignmentOp::ModAssign => "%=",
+                AssignmentOp::PowArcanaAssign => "^=",
+                AssignmentOp::PowAetherAssign => "**=",
+            };
+            format!(
+                "{} {} {}",
+                target,
+                operator,
+                format_ast(value, indent_level)
+            )
+        }
+        AST::Var(name, _, _) => name.clone(),
         AST::Arcana(value, _) => format!("{}", value),
+        AST::BigArcana(value, _) => format!("{}", value),
         AST::Aether(value, _) => {
             if value.fract() == 0.0 {
                 format!("{:.1}", value)
@@ -146,12 +147,51 @@ pub fn format_ast(ast: &AST, indent_level: usize) -> String {
                 format!("{}", value)
             }
         }
-        AST::Rune(value, _) => format!("\"{}\"", value),
+        AST::Rune(value, style, _) => format_rune(value, *style),
         AST::Omen(value, _) => match value {
             true => "boon".to_string(),
             false => "hex".to_string(),
         },
         AST::Abyss(_) => "abyss".to_string(),
+        AST::Grimoire(elements, _) => format!(
+            "[{}]",
+            elements
+                .iter()
+                .map(|element| format_ast(element, indent_level))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        AST::Codex(entries, _) if entries.is_empty() => "[:]".to_string(),
+        AST::Codex(entries, _) => format!(
+            "[{}]",
+            entries
+                .iter()
+                .map(|(key, value)| format!(
+                    "{}: {}",
+                    format_ast(key, indent_level),
+                    format_ast(value, indent_level)
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        AST::Field(target, field, _) => format!(
+            "{}.{}",
+            format_with_parentheses(target, current_precedence),
+            field
+        ),
+        AST::Call(callee, args, _) => format!(
+            "{}({})",
+            format_with_parentheses(callee, current_precedence),
+            args.iter()
+                .map(|arg| format_ast(arg, indent_level))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        AST::Index(target, index, _) => format!(
+            "{}[{}]",
+            format_with_parentheses(target, current_precedence),
+            format_ast(index, indent_level)
+        ),
         AST::Unveil(args, _) => format!(
             "unveil({})",
             args.iter()
@@ -159,14 +199,24 @@ pub fn format_ast(ast: &AST, indent_level: usize) -> String {
                 .collect::<Vec<_>>()
                 .join(", ")
         ),
+        AST::Interpolation(parts, _) => {
+            let parts: String = parts
+                .iter()
+                .map(|part| match part {
+                    RunePart::Text(text, _) => escape_rune(text, false, true),
+                    RunePart::Expr(expr) => format!("{{{}}}", format_ast(expr, indent_level)),
+                })
+                .collect();
+            format!("$\"{}\"", parts)
+        }
+        AST::Curse(message, _) => format!("curse({})", format_ast(message, indent_level)),
+        AST::Attempt(expr, _) => format!("attempt({})", format_ast(expr, indent_level)),
         AST::Trans(value, var_type, _) => {
-            let type_str = match var_type {
-                Type::Arcana => "arcana",
-                Type::Aether => "aether",
-                Type::Rune => "rune",
-                _ => "",
-            };
-            format!("trans({} as {})", format_ast(value, indent_level), type_str)
+            format!(
+                "trans({} as {})",
+                format_ast(value, indent_level),
+                format_type(var_type)
+            )
         }
         AST::Reveal(value, _) => {
             let val = format_ast(value, indent_level);
@@ -196,7 +246,7 @@ pub fn format_ast(ast: &AST, indent_level: usize) -> String {
                     .iter()
                     .map(|cond| {
                         if *is_match {
-                            format!("{}", format_ast(&cond.expression, indent_level))
+                            format_ast(&cond.expression, indent_level)
                         } else {
                             format!(
                                 "{} = {}",
@@ -225,19 +275,19 @@ pub fn format_ast(ast: &AST, indent_level: usize) -> String {
                     result.push_str(&format!(
                         "{}{} => {}\n",
                         "    ".repeat(indent_level + 1),
-                        if pattern == "" {
+                        if pattern.is_empty() {
                             "_".to_string()
                         } else {
                             format!("({})", pattern)
                         },
-                        format_ast(&body, indent_level + 1).trim()
+                        format_ast(body, indent_level + 1).trim()
                     ));
                 }
             }
             result.push_str(&format!("{}}}", indent));
             result
         }
-        AST::OracleDontCareItem(_) => format!("_"),
+        AST::OracleDontCareItem(_) => "_".to_string(),
         AST::Orbit { params, body, .. } => {
             let mut result = "orbit".to_string();
             if !params.is_empty() {
@@ -248,7 +298,7 @@ pub fn format_ast(ast: &AST, indent_level: usize) -> String {
                     .join(", ");
                 result.push_str(&format!(" ({})", params_str));
             }
-            result.push_str(&format_ast(body, indent_level).trim());
+            result.push_str(format_ast(body, indent_level).trim());
             result
         }
         AST::OrbitParam {
@@ -262,6 +312,17 @@ pub fn format_ast(ast: &AST, indent_level: usize) -> String {
             let end_expr = format_ast(end, 0);
             format!("{} = {}{}{}", name, start_expr, op, end_expr)
         }
+        AST::OrbitCollection {
+            name,
+            value_name,
+            collection,
+            ..
+        } => match value_name {
+            Some(value_name) => {
+                format!("({}, {}) = {}", name, value_name, format_ast(collection, 0))
+            }
+            None => format!("{} = {}", name, format_ast(collection, 0)),
+        },
         AST::Resume(value, _) => match value {
             Some(idendifier) => format!("resume {}", idendifier),
             None => "resume".to_string(),
@@ -276,45 +337,23 @@ pub fn format_ast(ast: &AST, indent_level: usize) -> String {
             return_type,
             body,
             ..
-        } => {
-            let return_type_str = match return_type {
-                Type::Arcana => "arcana",
-                Type::Aether => "aether",
-                Type::Rune => "rune",
-                Type::Omen => "omen",
-                Type::Abyss => "",
-            };
-            let params_str = params
-                .iter()
-                .map(|param| format_ast(param, indent_level))
-                .collect::<Vec<_>>()
-                .join(", ");
-            match return_type_str {
-                "" => format!(
-                    "engrave {}({}) {}",
-                    name,
-                    params_str,
-                    format_ast(body, indent_level)
-                ),
-                _ => format!(
-                    "engrave {}({}) -> {} {}",
-                    name,
-                    params_str,
-                    return_type_str,
-                    format_ast(body, indent_level)
-                ),
-            }
-        }
+        } => format_function(
+            &format!("engrave {}", name),
+            params,
+            return_type,
+            body,
+            indent_level,
+        ),
+        AST::EngraveExpr {
+            params,
+            return_type,
+            body,
+            ..
+        } => format_function("engrave", params, return_type, body, indent_level),
         AST::EngraveParam {
             name, param_type, ..
         } => {
-            let type_str = match param_type {
-                Type::Arcana => "arcana",
-                Type::Aether => "aether",
-                Type::Rune => "rune",
-                _ => "",
-            };
-            format!("{}: {}", name, type_str)
+            format!("{}: {}", name, format_type(param_type))
         }
         AST::FuncCall { name, args, .. } => {
             let args_str = args
@@ -325,15 +364,150 @@ pub fn format_ast(ast: &AST, indent_level: usize) -> String {
             format!("{}({})", name, args_str)
         }
         AST::Summon(prompt, var_type, _) => {
-            let type_str = match var_type {
-                Type::Arcana => "arcana",
-                Type::Aether => "aether",
-                Type::Rune => "rune",
-                _ => "",
-            };
-            format!("summon({}, {})", prompt, type_str)
+            format!(
+                "summon({}, {})",
+                format_rune(prompt, RuneStyle::Plain),
+                format_type(var_type)
+            )
         }
+        AST::Sigil { name, fields, .. } => {
+            let mut result = format!("sigil {} {{\n", name);
+            for (field, field_type) in fields {
+                result.push_str(&format!(
+                    "{}{}: {},\n",
+                    "    ".repeat(indent_level + 1),
+                    field,
+                    format_type(field_type)
+                ));
+            }
+            result.push_str(&format!("{}}}", indent));
+            result
+        }
+        AST::SigilInstance { name, fields, .. } if fields.is_empty() => format!("{} {{}}", name),
+        AST::SigilInstance { name, fields, .. } => format!(
+            "{} {{ {} }}",
+            name,
+            fields
+                .iter()
+                .map(|(field, value)| format!("{}: {}", field, format_ast(value, indent_level)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        AST::Invoke { path, names, .. } => match names {
+            Some(names) => format!(
+                "invoke {} {{ {} }}",
+                format_rune(path, RuneStyle::Plain),
+                names.join(", ")
+            ),
+            None => format!("invoke {}", format_rune(path, RuneStyle::Plain)),
+        },
         AST::Comment(text, _) => text.clone(),
         _ => format!("Not implemented: {:?}", ast),
     }
 }
+
+/// Formats a type as it is written in AbySS source code.
+///
+/// # Arguments
+/// * `var_type` - The type to format.
+///
+/// # Returns
+/// The type name, such as `arcana`, `grimoire<rune>` or `codex<rune, arcana>`.
+pub fn format_type(var_type: &Type) -> String {
+    match var_type {
+        Type::Omen => "omen".to_string(),
+        Type::Arcana => "arcana".to_string(),
+        Type::Aether => "aether".to_string(),
+        Type::Rune => "rune".to_string(),
+        Type::Abyss => "abyss".to_string(),
+        Type::Grimoire(element_type) => format!("grimoire<{}>", format_type(element_type)),
+        Type::Sigil(name) => name.clone(),
+        Type::Codex(key_type, value_type) => format!(
+            "codex<{}, {}>",
+            format_type(key_type),
+            format_type(value_type)
+        ),
+        Type::Cursed(value_type) => format!("cursed<{}>", format_type(value_type)),
+        Type::Engrave(param_types, return_type) => {
+            let params = param_types
+                .iter()
+                .map(format_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            match return_type.as_ref() {
+                Type::Abyss => format!("engrave({})", params),
+                _ => format!("engrave({}) -> {}", params, format_type(return_type)),
+            }
+        }
+    }
+}
+
+/// Formats a function definition or an anonymous function, omitting an `abyss` return type.
+fn format_function(
+    head: &str,
+    params: &[AST],
+    return_type: &Type,
+    body: &AST,
+    indent_level: usize,
+) -> String {
+    let params_str = params
+        .iter()
+        .map(|param| format_ast(param, indent_level))
+        .collect::<Vec<_>>()
+        .join(", ");
+    match return_type {
+        Type::Abyss => format!(
+            "{}({}) {}",
+            head,
+            params_str,
+            format_ast(body, indent_level).trim()
+        ),
+        _ => format!(
+            "{}({}) -> {} {}",
+            head,
+            params_str,
+            format_type(return_type),
+            format_ast(body, indent_level).trim()
+        ),
+    }
+}
+
+/// Formats a rune literal in the given style. Raw runes are written as they are, while plain and
+/// multi-line runes are escaped. A multi-line rune starts on the line after its opening quotes.
+fn format_rune(value: &str, style: RuneStyle) -> String {
+    match style {
+        RuneStyle::Plain => format!("\"{}\"", escape_rune(value, false, false)),
+        RuneStyle::Multi => format!("\"\"\"\n{}\"\"\"", escape_rune(value, true, false)),
+        RuneStyle::Raw(hashes) => {
+            let hashes = "#".repeat(hashes);
+            format!("r{}\"{}\"{}", hashes, value, hashes)
+        }
+    }
+}
+
+/// Escapes backslashes, control characters and the quotes that would end the text of a rune.
+/// The text of a multi-line rune keeps its newlines, and the text of an interpolated rune also
+/// escapes braces.
+fn escape_rune(value: &str, multi: bool, braces: bool) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' if multi => escaped.push('\n'),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            // Inside a multi-line rune, only a quote that could start the closing quotes is escaped.
+            '"' if multi && !matches!(chars.peek(), Some('"') | None) => escaped.push('"'),
+            '"' => escaped.push_str("\\\""),
+            '{' | '}' if braces => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,370 @@
+use crate::ast::{LineInfo, Type, AST};
+use crate::env::{Environment, Value};
+use crate::eval::{
+    apply_builtin, apply_function, builtin_arity, evaluate, result_to_value, type_of_result,
+    value_to_result, write_error_with_source, write_traceback, EvalError, EvalResult,
+};
+use crate::io::IoContext;
+use crate::limits::Limits;
+use crate::parser::{build_ast, parse, Rule};
+use crate::resolver::Resolver;
+use crate::typeck::{scrutinize_in, TypeCheckError};
+use pest::error::{Error, LineColLocation};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Represents any error reported by the `Interpreter`: a script that cannot be read,
+/// a script that cannot be parsed, the type errors found by the static checker,
+/// an error raised while evaluating it, or a call to a function that does not exist
+/// or with the wrong number of arguments.
+#[derive(Debug)]
+pub enum AbyssError {
+    Io(std::io::Error),
+    Parse(Box<Error<Rule>>),
+    Type(Vec<TypeCheckError>),
+    Eval(EvalError),
+    UndefinedFunction(String),
+    /// The name of the function, the number of parameters it takes and the number of
+    /// arguments it was given.
+    ArityMismatch(String, usize, usize),
+}
+
+impl AbyssError {
+    /// Returns the line information of the source location where the error occurred, if known.
+    pub fn line_info(&self) -> Option<LineInfo> {
+        match self {
+            AbyssError::Io(_)
+            | AbyssError::UndefinedFunction(_)
+            | AbyssError::ArityMismatch(_, _, _) => None,
+            AbyssError::Parse(e) => {
+                let (line, column) = match e.line_col {
+                    LineColLocation::Pos(pos) | LineColLocation::Span(pos, _) => pos,
+                };
+                Some(LineInfo {
+                    line,
+                    column,
+                    file: None,
+                })
+            }
+            AbyssError::Type(errors) => errors.first().and_then(|e| e.line_info.clone()),
+            AbyssError::Eval(e) => e.line_info(),
+        }
+    }
+}
+
+impl fmt::Display for AbyssError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbyssError::Io(e) => write!(f, "Cannot read script: {}", e),
+            AbyssError::Parse(e) => write!(f, "Parse error: {}", e),
+            AbyssError::Type(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            AbyssError::Eval(e) => write!(f, "{}", e),
+            AbyssError::UndefinedFunction(name) => write!(f, "Function {} is not defined!", name),
+            AbyssError::ArityMismatch(name, expected, found) => write!(
+                f,
+                "Function {} takes {} argument(s) but was given {}",
+                name, expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AbyssError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AbyssError::Io(e) => Some(e),
+            AbyssError::Parse(e) => Some(e.as_ref()),
+            AbyssError::Type(errors) => errors
+                .first()
+                .map(|e| e as &(dyn std::error::Error + 'static)),
+            AbyssError::Eval(e) => Some(e),
+            AbyssError::UndefinedFunction(_) | AbyssError::ArityMismatch(_, _, _) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AbyssError {
+    fn from(e: std::io::Error) -> Self {
+        AbyssError::Io(e)
+    }
+}
+
+impl From<Error<Rule>> for AbyssError {
+    fn from(e: Error<Rule>) -> Self {
+        AbyssError::Parse(Box::new(e))
+    }
+}
+
+impl From<EvalError> for AbyssError {
+    fn from(e: EvalError) -> Self {
+        AbyssError::Eval(e)
+    }
+}
+
+/// Runs AbySS code on behalf of a host Rust application.
+/// Globals and functions defined by one call stay available to the following calls.
+pub struct Interpreter {
+    env: Environment,
+    type_check: bool,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    /// Creates an interpreter with a fresh environment holding the core library.
+    pub fn new() -> Self {
+        Interpreter {
+            env: Environment::new(),
+            type_check: false,
+        }
+    }
+
+    /// Creates an interpreter that evaluates code in the given environment.
+    pub fn with_environment(env: Environment) -> Self {
+        Interpreter {
+            env,
+            type_check: false,
+        }
+    }
+
+    /// Returns the environment, e.g. to inspect the functions defined by scripts.
+    pub fn env(&self) -> &Environment {
+        &self.env
+    }
+
+    /// Returns the environment mutably, e.g. to register native functions or allow file access.
+    pub fn env_mut(&mut self) -> &mut Environment {
+        &mut self.env
+    }
+
+    /// Sets the streams used by `unveil` and `summon` and by `report_error`.
+    pub fn set_io(&mut self, io: IoContext) {
+        self.env.set_io(io);
+    }
+
+    /// Sets whether `eval_str` and `eval_file` run the static type checker before evaluating
+    /// code, as `abyss invoke` does. It is disabled by default, so that each statement is
+    /// evaluated until one fails. The checker knows the globals, functions and sigils defined
+    /// by earlier calls.
+    pub fn set_type_check(&mut self, enabled: bool) {
+        self.type_check = enabled;
+    }
+
+    /// Sets the limits on the resources scripts may use. Each call to `eval_str`, `eval_file`
+    /// or `call_function` gets the full limits, e.g. its own step budget and timeout.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.env.set_limits(limits);
+    }
+
+    /// Writes an error to the error stream, along with the line of `source` it occurred at and
+    /// the calls that were in progress.
+    ///
+    /// # Arguments
+    /// * `source` - The AbySS code that was evaluated when the error occurred.
+    /// * `error` - The error to report.
+    pub fn report_error(&self, source: &str, error: &AbyssError) {
+        let mut out = self.env.io().error();
+        if let AbyssError::Type(errors) = error {
+            for e in errors {
+                let _ =
+                    write_error_with_source(&mut *out, source, e.line_info.clone(), &e.to_string());
+            }
+            return;
+        }
+        let message = match error {
+            AbyssError::Parse(e) => format!("Parse error: {}", e.variant.message()),
+            e => e.to_string(),
+        };
+        let _ = write_error_with_source(&mut *out, source, error.line_info(), &message);
+        if let AbyssError::Eval(_) = error {
+            let _ = write_traceback(&mut *out, source, &self.env.take_trace());
+        }
+    }
+
+    /// Parses and evaluates AbySS source code statement by statement.
+    ///
+    /// # Arguments
+    /// * `source` - The AbySS code to evaluate.
+    ///
+    /// # Returns
+    /// The result of the last statement, or the first error. Statements before the error
+    /// keep their effects, but with the type checker enabled, code with type errors is not
+    /// evaluated at all.
+    pub fn eval_str(&mut self, source: &str) -> Result<EvalResult, AbyssError> {
+        let pair = parse(source)?;
+        let program = pair
+            .into_inner()
+            .filter(|inner_pair| inner_pair.as_rule() != Rule::EOI)
+            .map(build_ast)
+            .collect::<Result<Vec<AST>, _>>()?;
+        if self.type_check {
+            scrutinize_in(&program, &self.env).map_err(AbyssError::Type)?;
+        }
+        self.env.restart_limits();
+        let mut resolver = Resolver::new(&program, &self.env);
+        let mut last_result = EvalResult::Abyss;
+        for ast in &program {
+            let ast = resolver.resolve(ast)?;
+            let depth = self.env.scope_depth();
+            last_result = evaluate(&ast, &mut self.env).inspect_err(|_| {
+                self.env.unwind_scopes(depth);
+            })?;
+        }
+        Ok(last_result)
+    }
+
+    /// Reads and evaluates a script file. Modules it invokes are resolved relative to its directory,
+    /// and errors name the file in their line information.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the script file.
+    ///
+    /// # Returns
+    /// The result of the last statement of the script, or the first error.
+    pub fn eval_file<P: AsRef<Path>>(&mut self, path: P) -> Result<EvalResult, AbyssError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        let previous_path = self.env.script_path().map(Path::to_path_buf);
+        self.env.set_script_path(Some(path.to_path_buf()));
+        let result = self.eval_str(&source);
+        self.env.set_script_path(previous_path);
+
+        let file = Some(path.display().to_string());
+        result.map_err(|e| match e {
+            AbyssError::Eval(e) => AbyssError::Eval(e.in_file(file)),
+            e => e,
+        })
+    }
+
+    /// Calls a function defined by `engrave`, a native function, or one of the builtins that
+    /// take values, such as `len`, `has` and `read`. `push` and `pop` modify a script variable,
+    /// so they cannot be called by the host.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the function.
+    /// * `args` - The arguments, which must conform to the function's parameter types.
+    ///
+    /// # Returns
+    /// The value revealed by the function, or `None` if it reveals nothing.
+    /// `UndefinedFunction` if there is no such function, and `ArityMismatch` if it takes
+    /// another number of arguments.
+    pub fn call_function(
+        &mut self,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>, AbyssError> {
+        let arities = self.arities(name);
+        let expected = *arities
+            .first()
+            .ok_or_else(|| AbyssError::UndefinedFunction(name.to_string()))?;
+        if !arities.contains(&args.len()) {
+            return Err(AbyssError::ArityMismatch(
+                name.to_string(),
+                expected,
+                args.len(),
+            ));
+        }
+        if name == "push" || name == "pop" {
+            return Err(EvalError::InvalidOperation(
+                format!("{} modifies a grimoire variable of a script", name),
+                None,
+            )
+            .into());
+        }
+
+        let args = args.iter().map(value_to_result).collect();
+        self.env.restart_limits();
+        let depth = self.env.scope_depth();
+        let result = match builtin_arity(name) {
+            Some(_) if self.env.get_function(name).is_none() => {
+                apply_builtin(name, args, &self.env, &None)
+            }
+            _ => apply_function(name, args, &mut self.env, &None),
+        }
+        .inspect_err(|_| {
+            self.env.unwind_scopes(depth);
+        })?;
+        Ok(to_value(result))
+    }
+
+    /// Returns the numbers of arguments accepted by the function `name`, which is empty if
+    /// there is no such function. A function defined by `engrave` hides the native functions
+    /// and builtins of the same name, as it does for calls made by scripts.
+    fn arities(&self, name: &str) -> Vec<usize> {
+        if let Some(function) = self.env.get_function(name) {
+            return vec![function.params.len()];
+        }
+        if let Some(arity) = builtin_arity(name) {
+            return vec![arity];
+        }
+        self.env
+            .get_native(name)
+            .map(|overloads| overloads.iter().map(|native| native.params.len()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of a global variable, or `None` if it is not defined.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.env
+            .global_vars()
+            .get(name)
+            .map(|var_info| var_info.value.clone())
+    }
+
+    /// Sets a global variable. An existing global keeps its type and mutability, so the value
+    /// must be of the same type. A new global is defined as immutable, like a `forge` without `morph`.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the global variable.
+    /// * `value` - Its new value.
+    pub fn set_global(&mut self, name: &str, value: Value) -> Result<(), AbyssError> {
+        let (var_type, is_morph) = match self.env.global_vars().get(name) {
+            Some(var_info) => (var_info.var_type.clone(), var_info.is_morph),
+            None => match type_of_value(&value) {
+                Some(var_type) => (var_type, false),
+                None => {
+                    return Err(EvalError::TypeError(
+                        format!("Cannot determine the type of global {}", name),
+                        None,
+                    )
+                    .into())
+                }
+            },
+        };
+        let value = result_to_value(value_to_result(&value), &var_type).ok_or_else(|| {
+            EvalError::TypeError(
+                format!("Global {} must be of type {:?}", name, var_type),
+                None,
+            )
+        })?;
+        self.env
+            .set_global_var(name.to_string(), value, var_type, is_morph);
+        Ok(())
+    }
+}
+
+/// Returns the type of a value, or `None` if it holds an empty collection whose element
+/// type cannot be known.
+fn type_of_value(value: &Value) -> Option<Type> {
+    let var_type = type_of_result(&value_to_result(value))?;
+    let known = match &var_type {
+        Type::Grimoire(element_type) => **element_type != Type::Abyss,
+        Type::Codex(key_type, _) => **key_type != Type::Abyss,
+        _ => true,
+    };
+    known.then_some(var_type)
+}
+
+/// Converts the result of a function into a value, or `None` if it is `abyss`.
+fn to_value(result: EvalResult) -> Option<Value> {
+    let var_type = type_of_result(&result)?;
+    result_to_value(result, &var_type)
+}
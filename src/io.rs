@@ -0,0 +1,121 @@
+use std::cell::{RefCell, RefMut};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Cursor, Write};
+use std::rc::Rc;
+
+/// The streams a script talks to: `unveil` writes to the output, `summon` writes its prompt to
+/// the output and reads from the input, and errors are reported to the error stream.
+/// Clones share the same streams, so modules invoked from a script write to the same output.
+#[derive(Clone)]
+pub struct IoContext {
+    output: Rc<RefCell<dyn Write>>,
+    input: Rc<RefCell<dyn BufRead>>,
+    error: Rc<RefCell<dyn Write>>,
+}
+
+impl IoContext {
+    /// Creates an I/O context that uses the standard output, input and error streams of the process.
+    pub fn stdio() -> Self {
+        IoContext::new(io::stdout(), BufReader::new(io::stdin()), io::stderr())
+    }
+
+    /// Creates an I/O context from the given streams.
+    ///
+    /// # Arguments
+    /// * `output` - The stream `unveil` and the prompts of `summon` are written to.
+    /// * `input` - The stream `summon` reads lines from.
+    /// * `error` - The stream errors are reported to.
+    pub fn new<W, R, E>(output: W, input: R, error: E) -> Self
+    where
+        W: Write + 'static,
+        R: BufRead + 'static,
+        E: Write + 'static,
+    {
+        IoContext {
+            output: Rc::new(RefCell::new(output)),
+            input: Rc::new(RefCell::new(input)),
+            error: Rc::new(RefCell::new(error)),
+        }
+    }
+
+    /// Creates an I/O context that captures everything written to it in memory and answers
+    /// `summon` with the given lines, e.g. to test scripts.
+    ///
+    /// # Arguments
+    /// * `input` - The text `summon` reads from, one line per call.
+    ///
+    /// # Returns
+    /// The I/O context, and the buffers capturing its output and error streams.
+    pub fn scripted(input: &str) -> (Self, SharedBuffer, SharedBuffer) {
+        let output = SharedBuffer::new();
+        let error = SharedBuffer::new();
+        let io = IoContext::new(
+            output.clone(),
+            Cursor::new(input.as_bytes().to_vec()),
+            error.clone(),
+        );
+        (io, output, error)
+    }
+
+    /// Returns the output stream.
+    pub fn output(&self) -> RefMut<'_, dyn Write> {
+        self.output.borrow_mut()
+    }
+
+    /// Returns the input stream.
+    pub fn input(&self) -> RefMut<'_, dyn BufRead> {
+        self.input.borrow_mut()
+    }
+
+    /// Returns the error stream.
+    pub fn error(&self) -> RefMut<'_, dyn Write> {
+        self.error.borrow_mut()
+    }
+}
+
+impl Default for IoContext {
+    fn default() -> Self {
+        IoContext::stdio()
+    }
+}
+
+impl fmt::Debug for IoContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IoContext").finish_non_exhaustive()
+    }
+}
+
+/// An in-memory stream that can be handed to an `IoContext` while a clone of it
+/// is kept to read back what was written.
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer {
+    buffer: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SharedBuffer {
+    /// Creates an empty buffer.
+    pub fn new() -> Self {
+        SharedBuffer::default()
+    }
+
+    /// Returns everything written to the buffer so far.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buffer.borrow()).into_owned()
+    }
+
+    /// Discards everything written to the buffer so far.
+    pub fn clear(&self) {
+        self.buffer.borrow_mut().clear();
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
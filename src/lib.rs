@@ -1,5 +1,17 @@
 pub mod ast;
+pub mod compiler;
+pub mod dap;
+pub mod debugger;
 pub mod env;
 pub mod eval;
 pub mod format;
+pub mod interpreter;
+pub mod io;
+pub mod limits;
+pub mod lsp;
+pub mod module;
 pub mod parser;
+pub mod resolver;
+pub mod stdlib;
+pub mod typeck;
+pub mod vm;
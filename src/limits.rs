@@ -0,0 +1,187 @@
+use crate::env::Value;
+use crate::eval::{BinaryOp, EvalResult};
+use std::time::{Duration, Instant};
+
+/// The number of steps between two checks of the clock, which is slower to read than the
+/// step counter.
+const CLOCK_INTERVAL: u64 = 256;
+
+/// Limits on the resources a script may use, for running scripts that cannot be trusted.
+/// Both engines check them, and a script exceeding one is stopped with
+/// `EvalError::LimitExceeded`, which `attempt` cannot catch. No limit is set by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    pub max_steps: Option<u64>, // The number of AST nodes, loop iterations and instructions evaluated
+    pub timeout: Option<Duration>, // The wall-clock time the script may run for
+    pub max_rune_length: Option<usize>, // The length of a rune, in bytes
+    pub max_collection_size: Option<usize>, // The number of elements of a grimoire or codex
+    pub max_arcana_bits: Option<u64>, // The number of bits of an arcana in bigint mode
+}
+
+impl Limits {
+    /// Returns true if no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        *self == Limits::default()
+    }
+}
+
+/// The resources used by a script since its limits were set.
+#[derive(Debug)]
+pub(crate) struct Budget {
+    limits: Limits,
+    steps: u64,
+    deadline: Option<Instant>,
+    next_clock_check: u64, // The step count at which the clock is read next
+}
+
+impl Budget {
+    /// Creates a budget for the given limits, whose time starts running now.
+    pub(crate) fn new(limits: Limits) -> Self {
+        let deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+        Budget {
+            limits,
+            steps: 0,
+            deadline,
+            next_clock_check: CLOCK_INTERVAL,
+        }
+    }
+
+    /// Returns the limits the budget enforces.
+    pub(crate) fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Counts steps of the evaluation, such as the instructions run since the last count.
+    ///
+    /// # Returns
+    /// A message describing the limit exceeded, if the script ran out of steps or time.
+    pub(crate) fn step(&mut self, count: u64) -> Result<(), String> {
+        self.steps += count;
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Err(format!(
+                    "the script evaluated more than {} steps",
+                    max_steps
+                ));
+            }
+        }
+        match self.deadline {
+            Some(deadline) if self.steps >= self.next_clock_check => {
+                self.next_clock_check = self.steps + CLOCK_INTERVAL;
+                match Instant::now() >= deadline {
+                    true => {
+                        let timeout = self.limits.timeout.unwrap_or_default();
+                        Err(format!("the script ran for more than {:?}", timeout))
+                    }
+                    false => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the size of a value produced by the script.
+    ///
+    /// # Returns
+    /// A message describing the limit exceeded, if the value is too large.
+    pub(crate) fn check_result(&self, result: &EvalResult) -> Result<(), String> {
+        match result {
+            EvalResult::Rune(s) => self.check_rune(s.len()),
+            EvalResult::Grimoire(items) => self.check_collection("grimoire", items.len()),
+            EvalResult::Codex(entries) => self.check_collection("codex", entries.len()),
+            EvalResult::BigArcana(n) => self.check_arcana(n.bits()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks an arithmetic operation on two arcana before it is computed, since a single
+    /// operation on unbounded integers, such as `3 ^ 300000000`, can run for longer than the
+    /// timeout. The size of the result is estimated from below, so that no operation whose
+    /// result fits is rejected.
+    ///
+    /// # Returns
+    /// A message describing the limit exceeded, if the result would be too large.
+    pub(crate) fn check_arcana_operation(
+        &self,
+        op: BinaryOp,
+        left: &EvalResult,
+        right: &EvalResult,
+    ) -> Result<(), String> {
+        if self.limits.max_arcana_bits.is_none() {
+            return Ok(());
+        }
+        let (Some(l), Some(r)) = (arcana_bits(left), arcana_bits(right)) else {
+            return Ok(());
+        };
+        let bits = match op {
+            BinaryOp::Mul => (l + r).saturating_sub(1),
+            BinaryOp::PowArcana => {
+                let exponent = match right {
+                    EvalResult::Arcana(n) => u64::try_from(*n).unwrap_or(0),
+                    _ => u64::MAX,
+                };
+                (l.saturating_sub(1))
+                    .saturating_mul(exponent)
+                    .saturating_add(1)
+            }
+            _ => l.max(r),
+        };
+        self.check_arcana(bits)
+    }
+
+    /// Checks the size of a value stored in a variable.
+    ///
+    /// # Returns
+    /// A message describing the limit exceeded, if the value is too large.
+    pub(crate) fn check_value(&self, value: &Value) -> Result<(), String> {
+        match value {
+            Value::Rune(s) => self.check_rune(s.len()),
+            Value::Grimoire(items) => self.check_collection("grimoire", items.len()),
+            Value::Codex(entries) => self.check_collection("codex", entries.len()),
+            Value::BigArcana(n) => self.check_arcana(n.bits()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the length of a rune, in bytes.
+    fn check_rune(&self, length: usize) -> Result<(), String> {
+        match self.limits.max_rune_length {
+            Some(max) if length > max => Err(format!(
+                "a rune of {} bytes exceeds the maximum rune length of {}",
+                length, max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the number of elements of a grimoire or codex.
+    fn check_collection(&self, kind: &str, size: usize) -> Result<(), String> {
+        match self.limits.max_collection_size {
+            Some(max) if size > max => Err(format!(
+                "a {} of {} elements exceeds the maximum collection size of {}",
+                kind, size, max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the number of bits of an arcana.
+    fn check_arcana(&self, bits: u64) -> Result<(), String> {
+        match self.limits.max_arcana_bits {
+            Some(max) if bits > max => Err(format!(
+                "an arcana of {} bits exceeds the maximum of {} bits",
+                bits, max
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the number of bits of the magnitude of an arcana, or `None` for another value.
+fn arcana_bits(value: &EvalResult) -> Option<u64> {
+    match value {
+        EvalResult::Arcana(n) => Some(u64::from(64 - n.unsigned_abs().leading_zeros())),
+        EvalResult::BigArcana(n) => Some(n.bits()),
+        _ => None,
+    }
+}
@@ -0,0 +1,934 @@
+use crate::ast::{Accessor, LineInfo, RunePart, Type, AST};
+use crate::format::{format_ast, format_type};
+use crate::parser::{build_ast, parse, Rule};
+use crate::typeck::scrutinize_with_path;
+use pest::error::{Error, LineColLocation};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+/// A position in a document: a zero-based line and the byte offset of a character in that line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+/// The namespaces names are declared in. A variable, a function and a sigil can share a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Namespace {
+    Variable,
+    Function,
+    Sigil,
+}
+
+/// A name declared in a document, such as a `forge` variable or an `engrave` function.
+#[derive(Debug, Clone)]
+struct Definition {
+    name: String,
+    namespace: Namespace,
+    detail: String,  // The declaration shown on hover, such as `forge morph x: arcana`
+    start: Position, // The start of the declaring statement
+    position: Position, // The start of the declared name
+    children: Vec<usize>, // The listed definitions declared in the body of a function
+}
+
+/// A name written in a document and the definition it refers to.
+#[derive(Debug, Clone)]
+struct Reference {
+    position: Position,
+    definition: usize,
+}
+
+/// A scope of the indexer, mirroring the scopes pushed by the evaluator.
+type Scope = HashMap<(Namespace, String), usize>;
+
+/// The definitions of a document and the references to them.
+#[derive(Debug, Default)]
+struct SymbolIndex {
+    definitions: Vec<Definition>,
+    references: Vec<Reference>,
+    roots: Vec<usize>, // The listed definitions that are not declared in a function
+}
+
+/// Walks the AST of a document, declaring names in scopes like the evaluator does and
+/// recording which definition each name refers to.
+///
+/// As in the type checker, the bodies of functions are walked at the end of the enclosing
+/// block, so that they can refer to the functions and variables declared after them.
+struct Indexer<'a> {
+    lines: &'a [&'a str],
+    index: SymbolIndex,
+    scopes: Vec<Scope>,
+    parent: Option<usize>, // The function whose body is being walked
+    pending: Vec<(usize, &'a [AST], &'a AST)>, // The functions whose bodies are not walked yet
+}
+
+/// An open document and what the server knows about it.
+struct Document {
+    text: String,
+    program: Option<Vec<AST>>, // The statements of the document, if it parses
+    index: SymbolIndex,
+    diagnostics: Vec<Value>,
+}
+
+/// The state of the language server: the documents opened by the client.
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+}
+
+/// Runs the language server, reading Language Server Protocol messages from `input` and
+/// writing responses and notifications to `output` until the client sends `exit` or closes
+/// the input.
+///
+/// The server publishes syntax and type errors as diagnostics, and answers go-to-definition,
+/// hover, document symbol and formatting requests.
+///
+/// # Arguments
+/// * `input` - The stream the client writes messages to.
+/// * `output` - The stream the client reads messages from.
+///
+/// # Returns
+/// An error if a message could not be read or written.
+pub fn serve<R: BufRead, W: Write>(mut input: R, mut output: W) -> io::Result<()> {
+    let mut server = Server::default();
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        if method == "exit" {
+            break;
+        }
+        let responses = match message.get("id") {
+            Some(id) => vec![server.respond(id.clone(), method, &message["params"])],
+            None => server.notify(method, &message["params"]),
+        };
+        for response in responses {
+            write_message(&mut output, &response)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a message framed by a `Content-Length` header, as used by the Language Server
+/// Protocol and the Debug Adapter Protocol.
+///
+/// # Arguments
+/// * `input` - The stream to read from.
+///
+/// # Returns
+/// The message, `None` at the end of the stream, or an error if the message is malformed.
+pub(crate) fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Writes a message framed by a `Content-Length` header.
+///
+/// # Arguments
+/// * `output` - The stream to write to.
+/// * `message` - The message to write.
+pub(crate) fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+impl Server {
+    /// Answers a request.
+    ///
+    /// # Arguments
+    /// * `id` - The id of the request, repeated in the response.
+    /// * `method` - The method of the request.
+    /// * `params` - The parameters of the request.
+    ///
+    /// # Returns
+    /// The response to the request.
+    fn respond(&mut self, id: Value, method: &str, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self.documents.get(uri);
+        let result = match (method, document) {
+            ("initialize", _) => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "documentFormattingProvider": true,
+                },
+                "serverInfo": {
+                    "name": "familiar-lsp",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            }),
+            ("shutdown", _) => Value::Null,
+            ("textDocument/definition", Some(document)) => document
+                .definition_at(&params["position"])
+                .map_or(Value::Null, |definition| {
+                    json!({
+                        "uri": uri,
+                        "range": document.name_range(definition.position, &definition.name),
+                    })
+                }),
+            ("textDocument/hover", Some(document)) => document
+                .definition_at(&params["position"])
+                .map_or(Value::Null, |definition| {
+                    json!({
+                        "contents": {
+                            "kind": "markdown",
+                            "value": format!("```abyss\n{}\n```", definition.detail),
+                        },
+                    })
+                }),
+            ("textDocument/documentSymbol", Some(document)) => document.symbols(),
+            ("textDocument/formatting", Some(document)) => document.formatting(),
+            (
+                "textDocument/definition"
+                | "textDocument/hover"
+                | "textDocument/documentSymbol"
+                | "textDocument/formatting",
+                None,
+            ) => Value::Null,
+            _ => {
+                return json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": -32601,
+                        "message": format!("Unknown method: {}", method),
+                    },
+                })
+            }
+        };
+        json!({ "jsonrpc": "2.0", "id": id, "result": result })
+    }
+
+    /// Handles a notification.
+    ///
+    /// # Arguments
+    /// * `method` - The method of the notification.
+    /// * `params` - The parameters of the notification.
+    ///
+    /// # Returns
+    /// The notifications sent back to the client, which publish the diagnostics of the
+    /// documents that were opened, changed or closed.
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // The server asks for full synchronization, so the last change holds the whole text.
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, Vec::new())];
+            }
+            _ => None,
+        };
+        let Some(text) = text else {
+            return Vec::new();
+        };
+        let document = Document::analyze(text, file_path(&uri));
+        let notification = publish_diagnostics(&uri, document.diagnostics.clone());
+        self.documents.insert(uri, document);
+        vec![notification]
+    }
+}
+
+/// Builds a notification publishing the diagnostics of a document.
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// Converts a `file://` URI to a path, decoding its percent-encoded characters.
+///
+/// # Arguments
+/// * `uri` - The URI of a document.
+///
+/// # Returns
+/// The path of the document, or `None` if the URI does not name a file.
+fn file_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let escaped = encoded
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (encoded[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+/// Splits a text into lines, without their line terminators.
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect()
+}
+
+/// Checks whether a character can be part of an identifier.
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Converts a one-based line and character column, as reported by the parser, to a position.
+fn position_of(lines: &[&str], line: usize, column: usize) -> Position {
+    let line = line.saturating_sub(1);
+    let text = lines.get(line).copied().unwrap_or_default();
+    let column = text
+        .char_indices()
+        .nth(column.saturating_sub(1))
+        .map_or(text.len(), |(offset, _)| offset);
+    Position { line, column }
+}
+
+/// Converts the line information of an AST node to a position.
+fn line_info_position(lines: &[&str], line_info: &Option<LineInfo>) -> Position {
+    match line_info {
+        Some(info) => position_of(lines, info.line, info.column),
+        None => Position { line: 0, column: 0 },
+    }
+}
+
+/// Converts a position to a Language Server Protocol position, whose character is counted
+/// in UTF-16 code units.
+fn lsp_position(lines: &[&str], position: Position) -> Value {
+    let text = lines.get(position.line).copied().unwrap_or_default();
+    let prefix = text.get(..position.column).unwrap_or(text);
+    json!({ "line": position.line, "character": prefix.encode_utf16().count() })
+}
+
+/// Converts a range between two positions to a Language Server Protocol range.
+fn lsp_range(lines: &[&str], start: Position, end: Position) -> Value {
+    json!({ "start": lsp_position(lines, start), "end": lsp_position(lines, end) })
+}
+
+/// Returns the end of the token starting at a position: the end of an identifier or number,
+/// or the next character.
+fn token_end(lines: &[&str], start: Position) -> Position {
+    let text = lines.get(start.line).copied().unwrap_or_default();
+    let rest = text.get(start.column..).unwrap_or_default();
+    let length = match rest.find(|c: char| !is_identifier_char(c)) {
+        Some(0) => rest.chars().next().map_or(0, char::len_utf8),
+        Some(length) => length,
+        None => rest.len(),
+    };
+    Position {
+        column: start.column + length,
+        ..start
+    }
+}
+
+/// Finds the first occurrence of a name as a whole word at or after a position, such as the
+/// name declared by a `forge` statement that starts at the position.
+fn find_name(lines: &[&str], from: Position, name: &str) -> Position {
+    for (line, text) in lines.iter().enumerate().skip(from.line) {
+        let mut column = if line == from.line { from.column } else { 0 };
+        while let Some(offset) = text.get(column..).and_then(|rest| rest.find(name)) {
+            let start = column + offset;
+            let end = start + name.len();
+            let before = text[..start].chars().next_back();
+            let after = text[end..].chars().next();
+            if !before.is_some_and(is_identifier_char) && !after.is_some_and(is_identifier_char) {
+                return Position {
+                    line,
+                    column: start,
+                };
+            }
+            column = end;
+        }
+    }
+    from
+}
+
+/// Builds a diagnostic for a syntax error reported by the parser.
+fn syntax_diagnostic(lines: &[&str], error: &Error<Rule>) -> Value {
+    let (start, end) = match error.line_col {
+        LineColLocation::Pos((line, column)) => {
+            let start = position_of(lines, line, column);
+            (start, token_end(lines, start))
+        }
+        LineColLocation::Span((line, column), (end_line, end_column)) => (
+            position_of(lines, line, column),
+            position_of(lines, end_line, end_column),
+        ),
+    };
+    json!({
+        "range": lsp_range(lines, start, end),
+        "severity": 1,
+        "source": "abyss",
+        "message": format!("Syntax error: {}", error.variant.message()),
+    })
+}
+
+impl Document {
+    /// Parses, type checks and indexes the text of a document.
+    ///
+    /// # Arguments
+    /// * `text` - The text of the document.
+    /// * `path` - The path of the document, used to check the modules it invokes.
+    fn analyze(text: &str, path: Option<PathBuf>) -> Self {
+        let lines = split_lines(text);
+        let mut diagnostics = Vec::new();
+        let program = match parse(text) {
+            Ok(pair) => {
+                let mut program = Vec::new();
+                for inner_pair in pair.into_inner() {
+                    if inner_pair.as_rule() != Rule::EOI {
+                        match build_ast(inner_pair) {
+                            Ok(ast) => program.push(ast),
+                            Err(e) => diagnostics.push(syntax_diagnostic(&lines, &e)),
+                        }
+                    }
+                }
+                Some(program)
+            }
+            Err(e) => {
+                diagnostics.push(syntax_diagnostic(&lines, &e));
+                None
+            }
+        };
+
+        // Type errors are only meaningful once every statement could be built.
+        let parsed = diagnostics.is_empty();
+        let mut index = SymbolIndex::default();
+        if let Some(program) = &program {
+            if parsed {
+                if let Err(errors) = scrutinize_with_path(program, path.as_deref()) {
+                    diagnostics.extend(errors.iter().map(|error| {
+                        // Errors in invoked modules are reported at the start of the document.
+                        let (start, message) = match &error.line_info {
+                            Some(info) if info.file.is_some() => (
+                                Position { line: 0, column: 0 },
+                                format!(
+                                    "{}:{}:{}: {}",
+                                    info.file.as_deref().unwrap_or_default(),
+                                    info.line,
+                                    info.column,
+                                    error
+                                ),
+                            ),
+                            _ => (
+                                line_info_position(&lines, &error.line_info),
+                                error.to_string(),
+                            ),
+                        };
+                        json!({
+                            "range": lsp_range(&lines, start, token_end(&lines, start)),
+                            "severity": 1,
+                            "source": "abyss",
+                            "message": message,
+                        })
+                    }));
+                }
+            }
+            index = Indexer::index(&lines, program);
+        }
+
+        Document {
+            text: text.to_string(),
+            program: program.filter(|_| parsed),
+            index,
+            diagnostics,
+        }
+    }
+
+    /// Finds the definition of the name at a Language Server Protocol position.
+    fn definition_at(&self, position: &Value) -> Option<&Definition> {
+        let lines = split_lines(&self.text);
+        let line = position["line"].as_u64()? as usize;
+        let text = lines.get(line)?;
+
+        // Converts the UTF-16 character to a byte offset, then finds the start of the word.
+        let mut units = position["character"].as_u64()? as usize;
+        let mut column = text.len();
+        for (offset, c) in text.char_indices() {
+            if units == 0 {
+                column = offset;
+                break;
+            }
+            units = units.saturating_sub(c.len_utf16());
+        }
+        let start = text[..column]
+            .rfind(|c: char| !is_identifier_char(c))
+            .map_or(0, |offset| offset + 1);
+
+        self.index
+            .references
+            .iter()
+            .find(|reference| {
+                reference.position
+                    == Position {
+                        line,
+                        column: start,
+                    }
+            })
+            .map(|reference| &self.index.definitions[reference.definition])
+    }
+
+    /// Builds the Language Server Protocol range of a name written at a position.
+    fn name_range(&self, position: Position, name: &str) -> Value {
+        let lines = split_lines(&self.text);
+        let end = Position {
+            column: position.column + name.len(),
+            ..position
+        };
+        lsp_range(&lines, position, end)
+    }
+
+    /// Lists the symbols of the document, nesting the declarations in a function under it.
+    fn symbols(&self) -> Value {
+        let lines = split_lines(&self.text);
+        let symbols = self.index.roots.iter();
+        Value::Array(symbols.map(|&root| self.symbol(&lines, root)).collect())
+    }
+
+    /// Builds the document symbol of a definition and of the definitions declared in it.
+    fn symbol(&self, lines: &[&str], index: usize) -> Value {
+        let definition = &self.index.definitions[index];
+        let name_end = Position {
+            column: definition.position.column + definition.name.len(),
+            ..definition.position
+        };
+        let kind = match definition.namespace {
+            Namespace::Variable => 13,
+            Namespace::Function => 12,
+            Namespace::Sigil => 23,
+        };
+        json!({
+            "name": definition.name,
+            "detail": definition.detail,
+            "kind": kind,
+            "range": lsp_range(lines, definition.start, name_end),
+            "selectionRange": lsp_range(lines, definition.position, name_end),
+            "children": definition
+                .children
+                .iter()
+                .map(|&child| self.symbol(lines, child))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// Formats the document with `format::format_ast`.
+    ///
+    /// # Returns
+    /// An edit replacing the whole document with its formatted text, or `null` if the
+    /// document has syntax errors.
+    fn formatting(&self) -> Value {
+        let Some(program) = &self.program else {
+            return Value::Null;
+        };
+        let mut formatted = String::new();
+        for ast in program {
+            formatted.push_str(&format_ast(ast, 0));
+            formatted.push('\n');
+        }
+        let lines = split_lines(&self.text);
+        let last = lines.len() - 1;
+        let end = Position {
+            line: last,
+            column: lines[last].len(),
+        };
+        json!([{
+            "range": lsp_range(&lines, Position { line: 0, column: 0 }, end),
+            "newText": formatted,
+        }])
+    }
+}
+
+impl<'a> Indexer<'a> {
+    /// Indexes the definitions and references of a program.
+    fn index(lines: &'a [&'a str], program: &'a [AST]) -> SymbolIndex {
+        let mut indexer = Indexer {
+            lines,
+            index: SymbolIndex::default(),
+            scopes: vec![Scope::new()],
+            parent: None,
+            pending: Vec::new(),
+        };
+        indexer.walk_block(program);
+        indexer.index
+    }
+
+    /// Declares a name in the current scope.
+    ///
+    /// # Arguments
+    /// * `namespace` - The namespace the name is declared in.
+    /// * `name` - The declared name.
+    /// * `detail` - The declaration shown on hover.
+    /// * `line_info` - The line information of the declaring node.
+    /// * `listed` - Whether the definition is listed among the document's symbols.
+    ///
+    /// # Returns
+    /// The index of the new definition.
+    fn define(
+        &mut self,
+        namespace: Namespace,
+        name: &str,
+        detail: String,
+        line_info: &Option<LineInfo>,
+        listed: bool,
+    ) -> usize {
+        let start = line_info_position(self.lines, line_info);
+        let position = find_name(self.lines, start, name);
+        let index = self.index.definitions.len();
+        self.index.definitions.push(Definition {
+            name: name.to_string(),
+            namespace,
+            detail,
+            start,
+            position,
+            children: Vec::new(),
+        });
+        // A definition refers to itself, so that hovering a declaration shows it.
+        self.index.references.push(Reference {
+            position,
+            definition: index,
+        });
+        if listed {
+            match self.parent {
+                Some(parent) => self.index.definitions[parent].children.push(index),
+                None => self.index.roots.push(index),
+            }
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert((namespace, name.to_string()), index);
+        }
+        index
+    }
+
+    /// Records a reference to a name written at the position of `line_info`, if the name is
+    /// declared in an enclosing scope.
+    fn refer(&mut self, namespace: Namespace, name: &str, line_info: &Option<LineInfo>) {
+        let key = (namespace, name.to_string());
+        let Some(&definition) = self.scopes.iter().rev().find_map(|scope| scope.get(&key)) else {
+            return;
+        };
+        let position = line_info_position(self.lines, line_info);
+        self.index.references.push(Reference {
+            position,
+            definition,
+        });
+    }
+
+    /// Walks the statements of a block, then the bodies of the functions declared in it.
+    fn walk_block(&mut self, statements: &'a [AST]) {
+        let pending = self.pending.len();
+        for statement in statements {
+            self.walk(statement);
+        }
+        let bodies = self.pending.split_off(pending);
+        for (function, params, body) in bodies {
+            let parent = self.parent.replace(function);
+            self.scopes.push(Scope::new());
+            for param in params {
+                self.walk(param);
+            }
+            self.walk(body);
+            self.scopes.pop();
+            self.parent = parent;
+        }
+    }
+
+    /// Walks a list of nodes.
+    fn walk_all(&mut self, asts: &'a [AST]) {
+        for ast in asts {
+            self.walk(ast);
+        }
+    }
+
+    /// Walks a node, declaring the names it declares and recording the names it refers to.
+    fn walk(&mut self, ast: &'a AST) {
+        match ast {
+            AST::Statement(inner, _)
+            | AST::Field(inner, _, _)
+            | AST::LogicalNot(inner, _)
+            | AST::Trans(inner, _, _)
+            | AST::Curse(inner, _)
+            | AST::Attempt(inner, _)
+            | AST::Reveal(inner, _) => self.walk(inner),
+            AST::Index(l, r, _)
+            | AST::Add(l, r, _)
+            | AST::Sub(l, r, _)
+            | AST::Mul(l, r, _)
+            | AST::Div(l, r, _)
+            | AST::Mod(l, r, _)
+            | AST::PowArcana(l, r, _)
+            | AST::PowAether(l, r, _)
+            | AST::Equal(l, r, _)
+            | AST::NotEqual(l, r, _)
+            | AST::LessThan(l, r, _)
+            | AST::LessThanOrEqual(l, r, _)
+            | AST::GreaterThan(l, r, _)
+            | AST::GreaterThanOrEqual(l, r, _)
+            | AST::LogicalAnd(l, r, _)
+            | AST::LogicalOr(l, r, _) => {
+                self.walk(l);
+                self.walk(r);
+            }
+            AST::Grimoire(items, _) | AST::Unveil(items, _) => self.walk_all(items),
+            AST::Call(callee, args, _) => {
+                self.walk(callee);
+                self.walk_all(args);
+            }
+            AST::Interpolation(parts, _) => {
+                for part in parts {
+                    if let RunePart::Expr(expr) = part {
+                        self.walk(expr);
+                    }
+                }
+            }
+            AST::Codex(entries, _) => {
+                for (key, value) in entries {
+                    self.walk(key);
+                    self.walk(value);
+                }
+            }
+            AST::VarAssign {
+                name,
+                value,
+                var_type,
+                is_morph,
+                line_info,
+                ..
+            } => {
+                // The value is walked first: a variable cannot be read in its own declaration.
+                self.walk(value);
+                let morph = if *is_morph { "morph " } else { "" };
+                let detail = format!("forge {}{}: {}", morph, name, format_type(var_type));
+                self.define(Namespace::Variable, name, detail, line_info, true);
+            }
+            AST::Assignment {
+                name,
+                accessors,
+                value,
+                line_info,
+                ..
+            } => {
+                self.refer(Namespace::Variable, name, line_info);
+                for accessor in accessors {
+                    if let Accessor::Index(index, _) = accessor {
+                        self.walk(index);
+                    }
+                }
+                self.walk(value);
+            }
+            AST::Var(name, _, line_info) => self.refer(Namespace::Variable, name, line_info),
+            AST::Oracle {
+                is_match,
+                conditionals,
+                branches,
+                ..
+            } => {
+                self.scopes.push(Scope::new());
+                for conditional in conditionals {
+                    self.walk(&conditional.expression);
+                    let name = &conditional.variable;
+                    self.define(
+                        Namespace::Variable,
+                        name,
+                        name.clone(),
+                        &conditional.line_info,
+                        false,
+                    );
+                }
+                for branch in branches {
+                    let AST::OracleBranch { pattern, body, .. } = branch else {
+                        continue;
+                    };
+                    for item in pattern {
+                        // In a matching oracle, a `curse(name)` pattern declares `name`.
+                        match item {
+                            AST::Curse(message, _) if *is_match => match message.as_ref() {
+                                AST::Var(name, _, line_info) => {
+                                    self.define(
+                                        Namespace::Variable,
+                                        name,
+                                        format!("{}: rune", name),
+                                        line_info,
+                                        false,
+                                    );
+                                }
+                                _ => self.walk(item),
+                            },
+                            _ => self.walk(item),
+                        }
+                    }
+                    self.walk(body);
+                }
+                self.scopes.pop();
+            }
+            AST::Block(statements, _) => self.walk_block(statements),
+            AST::Orbit { params, body, .. } => {
+                let depth = self.scopes.len();
+                if params.is_empty() {
+                    self.scopes.push(Scope::new());
+                }
+                // Each parameter is declared in a new scope, which the next parameter's range
+                // and the body are walked in.
+                for param in params {
+                    match param {
+                        AST::OrbitParam {
+                            name,
+                            start,
+                            end,
+                            line_info,
+                            ..
+                        } => {
+                            self.walk(start);
+                            self.walk(end);
+                            self.scopes.push(Scope::new());
+                            let detail = format!("{}: {}", name, format_type(&Type::Arcana));
+                            self.define(Namespace::Variable, name, detail, line_info, false);
+                        }
+                        AST::OrbitCollection {
+                            name,
+                            value_name,
+                            collection,
+                            line_info,
+                        } => {
+                            self.walk(collection);
+                            self.scopes.push(Scope::new());
+                            for name in std::iter::once(name).chain(value_name) {
+                                self.define(
+                                    Namespace::Variable,
+                                    name,
+                                    name.clone(),
+                                    line_info,
+                                    false,
+                                );
+                            }
+                        }
+                        _ => self.scopes.push(Scope::new()),
+                    }
+                }
+                self.walk(body);
+                self.scopes.truncate(depth);
+            }
+            AST::Engrave {
+                name,
+                params,
+                return_type,
+                body,
+                line_info,
+            } => {
+                let params_str = params
+                    .iter()
+                    .map(|param| format_ast(param, 0))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let detail = match return_type {
+                    Type::Abyss => format!("engrave {}({})", name, params_str),
+                    _ => format!(
+                        "engrave {}({}) -> {}",
+                        name,
+                        params_str,
+                        format_type(return_type)
+                    ),
+                };
+                let function = self.define(Namespace::Function, name, detail, line_info, true);
+                self.pending.push((function, params, body));
+            }
+            AST::EngraveExpr { params, body, .. } => {
+                self.scopes.push(Scope::new());
+                self.walk_all(params);
+                self.walk(body);
+                self.scopes.pop();
+            }
+            AST::EngraveParam {
+                name,
+                param_type,
+                line_info,
+            } => {
+                let detail = format!("{}: {}", name, format_type(param_type));
+                self.define(Namespace::Variable, name, detail, line_info, true);
+            }
+            AST::FuncCall {
+                name,
+                args,
+                line_info,
+            } => {
+                self.refer(Namespace::Function, name, line_info);
+                self.walk_all(args);
+            }
+            AST::Sigil {
+                name, line_info, ..
+            } => {
+                let detail = format_ast(ast, 0);
+                self.define(Namespace::Sigil, name, detail, line_info, true);
+            }
+            AST::SigilInstance {
+                name,
+                fields,
+                line_info,
+            } => {
+                self.refer(Namespace::Sigil, name, line_info);
+                for (_, value) in fields {
+                    self.walk(value);
+                }
+            }
+            AST::OracleBranch { pattern, body, .. } => {
+                self.walk_all(pattern);
+                self.walk(body);
+            }
+            AST::OrbitParam { start, end, .. } => {
+                self.walk(start);
+                self.walk(end);
+            }
+            AST::OrbitCollection { collection, .. } => self.walk(collection),
+            AST::Omen(_, _)
+            | AST::Arcana(_, _)
+            | AST::BigArcana(_, _)
+            | AST::Aether(_, _)
+            | AST::Rune(_, _, _)
+            | AST::Abyss(_)
+            | AST::OracleDontCareItem(_)
+            | AST::Comment(_, _)
+            | AST::Resume(_, _)
+            | AST::Eject(_, _)
+            | AST::Summon(_, _, _)
+            | AST::Invoke { .. } => {}
+        }
+    }
+}
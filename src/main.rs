@@ -1,18 +1,29 @@
 use abyss_lang::{
-    env::Environment,
-    eval::{display_error_with_source, evaluate, EvalError, EvalResult},
+    ast::AST,
+    dap,
+    debugger::Debugger,
+    env::{Environment, DEFAULT_MAX_CALL_DEPTH},
+    eval::{display_error_with_source, display_traceback, evaluate, EvalResult},
     format::format_ast,
+    limits::Limits,
+    lsp,
     parser::{build_ast, parse, Rule},
+    resolver::{resolve, resolve_program},
+    typeck::scrutinize_in,
+    vm,
 };
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use colored::*;
-use dirs;
 use rustyline::config::Configurer;
 use rustyline::error::ReadlineError;
 use rustyline::history::FileHistory;
 use rustyline::Editor;
+use std::cell::RefCell;
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "abyss")]
@@ -29,18 +40,51 @@ enum Commands {
     Invoke {
         /// The path to the script file
         script: String,
+        #[command(flatten)]
+        options: ScriptOptions,
+        /// The engine that executes the script
+        #[arg(long, value_enum, default_value_t = Engine::Tree)]
+        engine: Engine,
+        /// Run the script in the step debugger
+        #[arg(long)]
+        debug: bool,
+        /// Run the script under a Debug Adapter Protocol client over stdio
+        #[arg(long, conflicts_with = "debug")]
+        dap: bool,
     },
     /// Start the interactive interpreter
     Cast {
         /// Enable debug mode
         #[arg(long)]
         debug: bool,
+        /// Allow the interpreter to read and write files
+        #[arg(long)]
+        allow_files: bool,
+        /// Make arcana values unbounded instead of raising an overflow
+        #[arg(long)]
+        bigint: bool,
     },
     /// Format the input script file
     Align {
         /// The path to the script file
         script: String,
     },
+    /// Check the input script file for type errors without executing it
+    Scrutinize {
+        /// The path to the script file
+        script: String,
+    },
+    /// Start the language server, which talks the Language Server Protocol over stdio
+    FamiliarLsp,
+}
+
+/// The engines that can execute a script.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Engine {
+    /// Evaluate the syntax tree directly
+    Tree,
+    /// Compile the script to bytecode and run it on the stack machine
+    Vm,
 }
 
 /// Sets up the AbySS configuration directory in the user's home directory.
@@ -68,37 +112,22 @@ fn get_history_file_path() -> PathBuf {
     abyss_dir.join("abyss_history.log")
 }
 
-/// Executes a given AbySS script by parsing and evaluating it in a new environment.
+/// Parses a given AbySS script and builds the AST of each top-level statement.
 ///
 /// # Arguments
-/// * `script` - A string containing the AbySS script to be executed.
-fn execute_script(script: &str) {
-    let mut env = Environment::new();
+/// * `script` - A string containing the AbySS script to be parsed.
+///
+/// # Returns
+/// A `Vec<AST>` containing the top-level statements of the script.
+fn build_program(script: &str) -> Vec<AST> {
+    let mut program = Vec::new();
 
     match parse(script) {
         Ok(pair) => {
             for inner_pair in pair.into_inner() {
                 if inner_pair.as_rule() != Rule::EOI {
                     match build_ast(inner_pair) {
-                        Ok(ast) => match evaluate(&ast, &mut env) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                let error_message = e.to_string();
-                                match e {
-                                    EvalError::UndefinedVariable(_, line_info)
-                                    | EvalError::InvalidOperation(_, line_info)
-                                    | EvalError::NegativeExponent(line_info)
-                                    | EvalError::TypeError(_, line_info) => {
-                                        display_error_with_source(
-                                            script,
-                                            line_info,
-                                            &error_message,
-                                        );
-                                        return;
-                                    }
-                                }
-                            }
-                        },
+                        Ok(ast) => program.push(ast),
                         Err(e) => panic!("Error: {}", e),
                     }
                 }
@@ -106,6 +135,151 @@ fn execute_script(script: &str) {
         }
         Err(e) => panic!("Error: {}", e),
     }
+
+    program
+}
+
+/// Statically checks a given AbySS script for type errors and reports each of them.
+///
+/// # Arguments
+/// * `script` - A string containing the AbySS script.
+/// * `program` - The top-level statements of the script.
+/// * `env` - The environment the script will be evaluated in.
+///
+/// # Returns
+/// `true` if no type errors were found.
+fn check_program(script: &str, program: &[AST], env: &Environment) -> bool {
+    match scrutinize_in(program, env) {
+        Ok(()) => true,
+        Err(errors) => {
+            for error in errors {
+                display_error_with_source(script, error.line_info.clone(), &error.to_string());
+            }
+            false
+        }
+    }
+}
+
+/// The options of `abyss invoke` that configure the environment a script runs in.
+#[derive(Args)]
+struct ScriptOptions {
+    /// Allow the script to read and write files
+    #[arg(long)]
+    allow_files: bool,
+    /// Make arcana values unbounded instead of raising an overflow
+    #[arg(long)]
+    bigint: bool,
+    /// The number of engrave calls that can be in progress at once
+    #[arg(long, default_value_t = DEFAULT_MAX_CALL_DEPTH)]
+    max_call_depth: usize,
+    /// Stop the script after evaluating this many steps
+    #[arg(long)]
+    max_steps: Option<u64>,
+    /// Stop the script after running for this many seconds
+    #[arg(long, value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+    /// Stop the script when an arcana in bigint mode would have more than this many bits
+    #[arg(long)]
+    max_arcana_bits: Option<u64>,
+    /// Let functions see the variables of their callers, warning where lexical scoping differs
+    #[arg(long)]
+    scope_warnings: bool,
+}
+
+impl ScriptOptions {
+    /// Creates the environment for running the script at the given path with these options.
+    /// The timeout starts running now.
+    fn environment(&self, path: &Path) -> Environment {
+        let mut env = Environment::with_script_path(path.to_path_buf());
+        env.set_file_access(self.allow_files);
+        env.set_bigint(self.bigint);
+        env.set_max_call_depth(self.max_call_depth);
+        env.set_scope_warnings(self.scope_warnings);
+        env.set_limits(Limits {
+            max_steps: self.max_steps,
+            timeout: self.timeout,
+            max_arcana_bits: self.max_arcana_bits,
+            ..Limits::default()
+        });
+        env
+    }
+}
+
+/// Parses a timeout given in seconds, such as `2` or `0.5`.
+fn parse_timeout(seconds: &str) -> Result<Duration, String> {
+    let seconds: f64 = seconds.parse().map_err(|e| format!("{}", e))?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+/// Executes a given AbySS script by parsing and evaluating it in a new environment.
+/// The script is type-checked before any statement is executed, except with scope warnings,
+/// where functions may still read variables of their callers that the type checker cannot see.
+///
+/// # Arguments
+/// * `script` - A string containing the AbySS script to be executed.
+/// * `path` - The path of the script, used to resolve invoked modules.
+/// * `env` - The environment to run the script in.
+/// * `engine` - The engine that executes the script.
+/// * `debug` - Whether the script runs in the step debugger.
+fn execute_script(script: &str, path: &Path, mut env: Environment, engine: Engine, debug: bool) {
+    if debug && engine == Engine::Vm {
+        eprintln!("Error: The debugger requires the tree engine.");
+        return;
+    }
+    let program = build_program(script);
+    if debug {
+        println!(
+            "Debugging {}. Type 'help' for the commands.",
+            path.display()
+        );
+        let debugger = Debugger::new(Some(path.to_path_buf()));
+        env.set_hook(Some(Rc::new(RefCell::new(debugger))));
+    }
+    if !env.scope_warnings() && !check_program(script, &program, &env) {
+        return;
+    }
+    let program = match resolve_program(&program, &env) {
+        Ok(program) => program,
+        Err(e) => {
+            display_error_with_source(script, e.line_info(), &e.to_string());
+            return;
+        }
+    };
+
+    if engine == Engine::Vm {
+        if let Err(e) = vm::run(&program, &mut env) {
+            display_error_with_source(script, e.line_info(), &e.to_string());
+            display_traceback(script, &env.take_trace());
+        }
+        return;
+    }
+
+    for ast in &program {
+        match evaluate(ast, &mut env) {
+            Ok(_) => {}
+            Err(e) => {
+                display_error_with_source(script, e.line_info(), &e.to_string());
+                display_traceback(script, &env.take_trace());
+                return;
+            }
+        }
+    }
+}
+
+/// Runs a script under the control of a Debug Adapter Protocol client talking over stdio.
+///
+/// # Arguments
+/// * `script` - The source code of the script.
+/// * `env` - The environment to run the script in, which names the script's path.
+/// * `engine` - The engine that executes the script.
+fn serve_debug_adapter(script: &str, env: Environment, engine: Engine) {
+    if engine == Engine::Vm {
+        eprintln!("Error: The debugger requires the tree engine.");
+        return;
+    }
+    if let Err(e) = dap::serve(script, env, io::stdin().lock(), io::stdout().lock()) {
+        eprintln!("Error: {}", e);
+    }
 }
 
 /// Formats the provided AbySS script by parsing and reconstructing it with proper indentation.
@@ -135,13 +309,17 @@ fn execute_format(script: &str) {
 ///
 /// # Arguments
 /// * `debug` - A boolean flag to enable debug mode, which prints the AST of the parsed code.
-fn start_interpreter(debug: bool) {
+/// * `allow_files` - Whether the evaluated code may read and write files.
+/// * `bigint` - Whether `arcana` values are unbounded.
+fn start_interpreter(debug: bool, allow_files: bool, bigint: bool) {
     println!("Starting AbySS interpreter...");
     println!("Type 'exit' or press Ctrl+D to exit the interpreter.\n");
 
     let mut current_session_code = String::new();
     let mut current_statement = String::new();
     let mut env = Environment::new();
+    env.set_file_access(allow_files);
+    env.set_bigint(bigint);
 
     let history_path = get_history_file_path();
     let mut rl = Editor::<(), FileHistory>::new().expect("Error: Failed to create editor");
@@ -200,7 +378,9 @@ fn start_interpreter(debug: bool) {
                                             if debug {
                                                 println!("{}", format!("AST: {:?}", ast).yellow());
                                             }
-                                            match evaluate(&ast, &mut env) {
+                                            match resolve(&ast, &env)
+                                                .and_then(|ast| evaluate(&ast, &mut env))
+                                            {
                                                 Ok(result) => {
                                                     current_session_code
                                                         .push_str(&format_ast(&ast, 0));
@@ -213,12 +393,26 @@ fn start_interpreter(debug: bool) {
                                                         EvalResult::Arcana(n) => {
                                                             println!("{}", format!("{}", n).green())
                                                         }
+                                                        EvalResult::BigArcana(n) => {
+                                                            println!("{}", format!("{}", n).green())
+                                                        }
                                                         EvalResult::Aether(n) => {
                                                             println!("{}", format!("{}", n).green())
                                                         }
                                                         EvalResult::Rune(s) => {
                                                             println!("{}", s.green())
                                                         }
+                                                        EvalResult::Grimoire(_)
+                                                        | EvalResult::Codex(_)
+                                                        | EvalResult::Sigil(_, _) => {
+                                                            println!(
+                                                                "{}",
+                                                                result.to_string().green()
+                                                            )
+                                                        }
+                                                        EvalResult::Curse(_, _) => {
+                                                            println!("{}", result.to_string().red())
+                                                        }
                                                         _ => {}
                                                     }
                                                 }
@@ -242,6 +436,8 @@ fn start_interpreter(debug: bool) {
                 current_session_code.clear();
                 current_statement.clear();
                 env = Environment::new();
+                env.set_file_access(allow_files);
+                env.set_bigint(bigint);
             }
             Err(ReadlineError::Eof) => {
                 println!("CTRL-D: Exiting interpreter...");
@@ -262,15 +458,31 @@ fn main() {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::Invoke { script } => {
+        Commands::Invoke {
+            script,
+            options,
+            engine,
+            debug,
+            dap,
+        } => {
             if let Ok(contents) = fs::read_to_string(script) {
-                execute_script(&contents);
+                let path = Path::new(script);
+                let env = options.environment(path);
+                if *dap {
+                    serve_debug_adapter(&contents, env, *engine);
+                    return;
+                }
+                execute_script(&contents, path, env, *engine, *debug);
             } else {
                 eprintln!("Error: Could not read the script file.");
             }
         }
-        Commands::Cast { debug } => {
-            start_interpreter(*debug);
+        Commands::Cast {
+            debug,
+            allow_files,
+            bigint,
+        } => {
+            start_interpreter(*debug, *allow_files, *bigint);
         }
         Commands::Align { script } => {
             if let Ok(contents) = fs::read_to_string(script) {
@@ -279,5 +491,21 @@ fn main() {
                 eprintln!("Error: Could not read the script file.");
             }
         }
+        Commands::Scrutinize { script } => {
+            if let Ok(contents) = fs::read_to_string(script) {
+                let program = build_program(&contents);
+                let env = Environment::with_script_path(PathBuf::from(script));
+                if check_program(&contents, &program, &env) {
+                    println!("{}", "No type errors found.".green());
+                }
+            } else {
+                eprintln!("Error: Could not read the script file.");
+            }
+        }
+        Commands::FamiliarLsp => {
+            if let Err(e) = lsp::serve(io::stdin().lock(), io::stdout().lock()) {
+                eprintln!("Error: {}", e);
+            }
+        }
     }
 }
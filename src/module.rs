@@ -0,0 +1,94 @@
+use crate::ast::AST;
+use crate::parser::{build_ast, parse, Rule};
+use pest::error::{Error, LineColLocation};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Resolves the path of a module invoked from a script.
+/// Relative paths are resolved against the directory of the invoking script,
+/// or against the current directory when there is no invoking script (e.g. in the interpreter).
+///
+/// # Arguments
+/// * `script_path` - The path of the invoking script, if any.
+/// * `path` - The path written in the `invoke` statement.
+///
+/// # Returns
+/// The path of the module file.
+pub fn resolve_module_path(script_path: Option<&Path>, path: &str) -> PathBuf {
+    match script_path.and_then(Path::parent) {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Checks that a module may be invoked. Unless file access is allowed, a module must lie in the
+/// directory of the script that started the evaluation, or in the current directory when there
+/// is no such script, so that `invoke` cannot read arbitrary files.
+///
+/// # Arguments
+/// * `canonical_path` - The canonical path of the module file.
+/// * `module_root` - The canonical directory of the script that started the evaluation, if any.
+/// * `file_access` - Whether the script may access files.
+///
+/// # Returns
+/// `Ok(())` if the module may be invoked, or a message describing why it may not.
+pub fn check_module_access(
+    canonical_path: &Path,
+    module_root: Option<&Path>,
+    file_access: bool,
+) -> Result<(), String> {
+    if file_access {
+        return Ok(());
+    }
+    let root = match module_root {
+        Some(root) => root.to_path_buf(),
+        None => env::current_dir()
+            .and_then(|dir| dir.canonicalize())
+            .map_err(|e| format!("Cannot read the current directory: {}", e))?,
+    };
+    if canonical_path.starts_with(&root) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Cannot invoke module {} outside {} unless file access is allowed (--allow-files)",
+            canonical_path.display(),
+            root.display()
+        ))
+    }
+}
+
+/// Reads and parses a module file into its top-level statements.
+///
+/// # Arguments
+/// * `path` - The path of the module file.
+///
+/// # Returns
+/// The top-level AST nodes of the module, or a message describing why it could not be loaded.
+/// The message names the place of a syntax error without quoting the file.
+pub fn load_module(path: &Path) -> Result<Vec<AST>, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read module {}: {}", path.display(), e))?;
+    let pair = parse(&source).map_err(|e| parse_error(path, &e))?;
+
+    let mut program = Vec::new();
+    for inner_pair in pair.into_inner() {
+        if inner_pair.as_rule() != Rule::EOI {
+            let ast = build_ast(inner_pair).map_err(|e| parse_error(path, &e))?;
+            program.push(ast);
+        }
+    }
+    Ok(program)
+}
+
+/// Describes a syntax error in a module by its line and column.
+fn parse_error(path: &Path, error: &Error<Rule>) -> String {
+    let (LineColLocation::Pos((line, column)) | LineColLocation::Span((line, column), _)) =
+        error.line_col;
+    format!(
+        "Failed to parse module {} at line {}, column {}",
+        path.display(),
+        line,
+        column
+    )
+}
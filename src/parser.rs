@@ -1,9 +1,13 @@
+#![allow(clippy::result_large_err)]
+
 use pest::error::{Error, ErrorVariant};
-use pest::iterators::Pair;
-use pest::Parser;
+use pest::iterators::{Pair, Pairs};
+use pest::{Parser, Span};
 use pest_derive::Parser;
 
-use crate::ast::{AssignmentOp, ConditionalAssignment, LineInfo, Type, AST};
+use crate::ast::{
+    Accessor, AssignmentOp, ConditionalAssignment, LineInfo, RunePart, RuneStyle, Type, AST,
+};
 
 /// The AbyssParser struct, generated using Pest, handles the parsing of the AbySS grammar.
 #[derive(Parser)]
@@ -17,7 +21,7 @@ pub struct AbyssParser;
 ///
 /// # Returns
 /// A `Result` containing a `Pair<Rule>` on success or a `pest::error::Error` on failure.
-pub fn parse(input: &str) -> Result<Pair<Rule>, Error<Rule>> {
+pub fn parse(input: &str) -> Result<Pair<'_, Rule>, Error<Rule>> {
     match AbyssParser::parse(Rule::statements, input) {
         Ok(mut pairs) => Ok(pairs.next().unwrap()),
         Err(e) => Err(e),
@@ -34,6 +38,8 @@ pub fn parse(input: &str) -> Result<Pair<Rule>, Error<Rule>> {
 pub fn build_ast(pair: Pair<Rule>) -> Result<AST, Error<Rule>> {
     let line_info = Some(LineInfo::from_span(&pair.as_span()));
 
+    // Larger rules are built in their own functions so that the stack frame of this
+    // recursive function stays small for deeply nested expressions.
     match pair.as_rule() {
         Rule::statement => {
             let mut inner = pair.into_inner();
@@ -41,155 +47,14 @@ pub fn build_ast(pair: Pair<Rule>) -> Result<AST, Error<Rule>> {
             Ok(AST::Statement(Box::new(expression), line_info))
         }
         Rule::expression => build_ast(pair.into_inner().next().unwrap()),
-        Rule::or_expr => {
-            let mut inner = pair.into_inner();
-            let left = build_ast(inner.next().unwrap())?;
-            if let Some(operator_pair) = inner.next() {
-                let right = build_ast(inner.next().unwrap())?;
-                match operator_pair.as_str() {
-                    "||" => Ok(AST::LogicalOr(Box::new(left), Box::new(right), line_info)),
-                    _ => Err(Error::new_from_span(
-                        ErrorVariant::CustomError {
-                            message: "Unexpected logical operator".to_string(),
-                        },
-                        operator_pair.as_span(),
-                    )),
-                }
-            } else {
-                Ok(left)
-            }
-        }
-        Rule::and_expr => {
-            let mut inner = pair.into_inner();
-            let left = build_ast(inner.next().unwrap())?;
-            if let Some(operator_pair) = inner.next() {
-                let right = build_ast(inner.next().unwrap())?;
-                match operator_pair.as_str() {
-                    "&&" => Ok(AST::LogicalAnd(Box::new(left), Box::new(right), line_info)),
-                    _ => Err(Error::new_from_span(
-                        ErrorVariant::CustomError {
-                            message: "Unexpected logical operator".to_string(),
-                        },
-                        operator_pair.as_span(),
-                    )),
-                }
-            } else {
-                Ok(left)
-            }
-        }
-        Rule::not_expr => {
-            let mut inner = pair.into_inner();
-
-            let exist_not_op = if inner.peek().unwrap().as_rule() == Rule::not_op {
-                inner.next();
-                true
-            } else {
-                false
-            };
-
-            let expr = build_ast(inner.next().unwrap())?;
-
-            if exist_not_op {
-                Ok(AST::LogicalNot(Box::new(expr), line_info))
-            } else {
-                Ok(expr)
-            }
-        }
-        Rule::comp_expr => {
-            let mut inner = pair.into_inner();
-            let left = build_ast(inner.next().unwrap())?;
-            if let Some(operator_pair) = inner.next() {
-                let right = build_ast(inner.next().unwrap())?;
-                match operator_pair.as_str() {
-                    "==" => Ok(AST::Equal(Box::new(left), Box::new(right), line_info)),
-                    "!=" => Ok(AST::NotEqual(Box::new(left), Box::new(right), line_info)),
-                    "<" => Ok(AST::LessThan(Box::new(left), Box::new(right), line_info)),
-                    "<=" => Ok(AST::LessThanOrEqual(
-                        Box::new(left),
-                        Box::new(right),
-                        line_info,
-                    )),
-                    ">" => Ok(AST::GreaterThan(Box::new(left), Box::new(right), line_info)),
-                    ">=" => Ok(AST::GreaterThanOrEqual(
-                        Box::new(left),
-                        Box::new(right),
-                        line_info,
-                    )),
-                    _ => Err(Error::new_from_span(
-                        ErrorVariant::CustomError {
-                            message: "Unexpected comparison operator".to_string(),
-                        },
-                        operator_pair.as_span(),
-                    )),
-                }
-            } else {
-                Ok(left)
-            }
-        }
-        Rule::add_expr => {
-            let mut inner = pair.into_inner();
-            let mut ast = build_ast(inner.next().unwrap())?;
-
-            while let Some(operator_pair) = inner.next() {
-                let right = build_ast(inner.next().unwrap())?;
-                ast = match operator_pair.as_str() {
-                    "+" => AST::Add(Box::new(ast), Box::new(right), line_info.clone()),
-                    "-" => AST::Sub(Box::new(ast), Box::new(right), line_info.clone()),
-                    _ => {
-                        return Err(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "Unexpected addition operator".to_string(),
-                            },
-                            operator_pair.as_span(),
-                        ));
-                    }
-                };
-            }
-            Ok(ast)
-        }
-        Rule::mul_expr => {
-            let mut inner = pair.into_inner();
-            let mut ast = build_ast(inner.next().unwrap())?;
-
-            while let Some(operator_pair) = inner.next() {
-                let right = build_ast(inner.next().unwrap())?;
-                ast = match operator_pair.as_str() {
-                    "*" => AST::Mul(Box::new(ast), Box::new(right), line_info.clone()),
-                    "/" => AST::Div(Box::new(ast), Box::new(right), line_info.clone()),
-                    "%" => AST::Mod(Box::new(ast), Box::new(right), line_info.clone()),
-                    _ => {
-                        return Err(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "Unexpected multiplication operator".to_string(),
-                            },
-                            operator_pair.as_span(),
-                        ));
-                    }
-                };
-            }
-            Ok(ast)
-        }
-        Rule::pow_expr => {
-            let mut inner = pair.into_inner();
-            let mut ast = build_ast(inner.next().unwrap())?;
-
-            while let Some(operator_pair) = inner.next() {
-                let right = build_ast(inner.next().unwrap())?;
-                ast = match operator_pair.as_str() {
-                    "^" => AST::PowArcana(Box::new(ast), Box::new(right), line_info.clone()),
-                    "**" => AST::PowAether(Box::new(ast), Box::new(right), line_info.clone()),
-                    _ => {
-                        return Err(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "Unexpected power operator".to_string(),
-                            },
-                            operator_pair.as_span(),
-                        ));
-                    }
-                };
-            }
-            Ok(ast)
-        }
+        Rule::or_expr => build_or_expr(pair, line_info),
+        Rule::and_expr => build_and_expr(pair, line_info),
+        Rule::not_expr => build_not_expr(pair, line_info),
+        Rule::comp_expr => build_comp_expr(pair, line_info),
+        Rule::add_expr => build_add_expr(pair, line_info),
+        Rule::mul_expr => build_mul_expr(pair, line_info),
+        Rule::pow_expr => build_pow_expr(pair, line_info),
+        Rule::postfix_expr => build_postfix_expr(pair),
         Rule::factor => build_ast(pair.into_inner().next().unwrap()),
         Rule::omen => {
             let value = pair.as_str();
@@ -204,388 +69,916 @@ pub fn build_ast(pair: Pair<Rule>) -> Result<AST, Error<Rule>> {
                 )),
             }
         }
-        Rule::arcana => {
-            let value = pair.as_str().parse().unwrap();
-            Ok(AST::Arcana(value, line_info))
-        }
+        Rule::arcana => match pair.as_str().parse() {
+            Ok(value) => Ok(AST::Arcana(value, line_info)),
+            Err(_) => Ok(AST::BigArcana(pair.as_str().parse().unwrap(), line_info)),
+        },
         Rule::aether => {
             let value = pair.as_str().parse().unwrap();
             Ok(AST::Aether(value, line_info))
         }
         Rule::rune => {
-            let value = pair.as_str().trim_matches('"').to_string();
-            Ok(AST::Rune(value, line_info))
+            let (value, style) = build_rune(pair)?;
+            Ok(AST::Rune(value, style, line_info))
         }
-        Rule::forge_var => {
-            let span = pair.as_span();
-            let mut inner = pair.into_inner();
-
-            let is_morph = if inner.peek().unwrap().as_rule() == Rule::morph {
-                inner.next();
-                true
-            } else {
-                false
-            };
-
-            let var_name = inner.next().unwrap().as_str().to_string();
-            let var_type = match inner.next().unwrap().as_str() {
-                "arcana" => Type::Arcana,
-                "aether" => Type::Aether,
-                "rune" => Type::Rune,
-                "omen" => Type::Omen,
-                _ => Err(Error::new_from_span(
-                    ErrorVariant::CustomError {
-                        message: "Unknown type in forge variable".to_string(),
-                    },
-                    span,
-                ))?,
-            };
-
-            let value = build_ast(inner.next().unwrap())?;
-
-            Ok(AST::VarAssign {
-                name: var_name,
-                value: Box::new(value),
-                var_type,
-                is_morph,
-                line_info,
-            })
+        Rule::interp_rune => build_interpolation(pair, line_info),
+        Rule::grimoire => {
+            let elements: Result<Vec<AST>, Error<Rule>> =
+                pair.into_inner().map(build_ast).collect();
+            Ok(AST::Grimoire(elements?, line_info))
         }
-        Rule::assignment => {
-            let span = pair.as_span();
-            let mut inner = pair.into_inner();
-            let var_name = inner.next().unwrap().as_str().to_string();
-            let op = match inner.next().unwrap().as_str() {
-                "=" => AssignmentOp::Assign,
-                "+=" => AssignmentOp::AddAssign,
-                "-=" => AssignmentOp::SubAssign,
-                "*=" => AssignmentOp::MulAssign,
-                "/=" => AssignmentOp::DivAssign,
-                "%=" => AssignmentOp::ModAssign,
-                "^=" => AssignmentOp::PowArcanaAssign,
-                "**=" => AssignmentOp::PowAetherAssign,
-                _ => Err(Error::new_from_span(
-                    ErrorVariant::CustomError {
-                        message: "Unexpected assignment operator".to_string(),
-                    },
-                    span,
-                ))?,
-            };
-            let value = build_ast(inner.next().unwrap())?;
-
-            Ok(AST::Assignment {
-                name: var_name,
-                value: Box::new(value),
-                op,
-                line_info,
-            })
+        Rule::codex => {
+            let entries: Result<Vec<(AST, AST)>, Error<Rule>> = pair
+                .into_inner()
+                .map(|entry| {
+                    let mut inner = entry.into_inner();
+                    let key = build_ast(inner.next().unwrap())?;
+                    let value = build_ast(inner.next().unwrap())?;
+                    Ok((key, value))
+                })
+                .collect();
+            Ok(AST::Codex(entries?, line_info))
         }
+        Rule::forge_var => build_forge_var(pair, line_info),
+        Rule::assignment => build_assignment(pair, line_info),
         Rule::identifier => {
             let var_name = pair.as_str().to_string();
-            Ok(AST::Var(var_name, line_info))
+            Ok(AST::Var(var_name, None, line_info))
         }
         Rule::unveil => {
             let inner = pair.into_inner();
-            let args: Result<Vec<AST>, Error<Rule>> = inner.map(|p| build_ast(p)).collect();
+            let args: Result<Vec<AST>, Error<Rule>> = inner.map(build_ast).collect();
             Ok(AST::Unveil(args?, line_info))
         }
-        Rule::trans_expr => {
-            let span = pair.as_span();
-            let mut inner = pair.into_inner();
-            let expr = build_ast(inner.next().unwrap())?;
-            let target_type = match inner.next().unwrap().as_str() {
-                "arcana" => Type::Arcana,
-                "aether" => Type::Aether,
-                "rune" => Type::Rune,
-                "omen" => Type::Omen,
-                _ => Err(Error::new_from_span(
+        Rule::trans_expr => build_trans(pair, line_info),
+        Rule::reveal => build_reveal(pair, line_info),
+        Rule::oracle_expr => build_oracle(pair, line_info),
+        Rule::pattern => build_ast(pair.into_inner().next().unwrap()),
+        Rule::pattern_element => {
+            if pair.as_span().as_str() == "_" {
+                Ok(AST::OracleDontCareItem(line_info))
+            } else {
+                Ok(build_ast(pair.into_inner().next().unwrap())?)
+            }
+        }
+        Rule::block => build_block(pair, line_info),
+        Rule::orbit => build_orbit(pair, line_info),
+        Rule::orbit_param => build_orbit_param(pair, line_info),
+        Rule::orbit_flow => build_orbit_flow(pair, line_info),
+        Rule::engrave => build_engrave(pair, line_info),
+        Rule::engrave_expr => build_engrave_expr(pair, line_info),
+        Rule::engrave_param => build_engrave_param(pair, line_info),
+        Rule::invocation => build_invocation(pair, line_info),
+        Rule::sigil => build_sigil(pair, line_info),
+        Rule::sigil_instance => build_sigil_instance(pair, line_info),
+        Rule::func_call => build_func_call(pair, line_info),
+        Rule::summon_expr => build_summon(pair, line_info),
+        Rule::curse_expr => {
+            let message = build_ast(pair.into_inner().next().unwrap())?;
+            Ok(AST::Curse(Box::new(message), line_info))
+        }
+        Rule::attempt_expr => {
+            let expr = build_ast(pair.into_inner().next().unwrap())?;
+            Ok(AST::Attempt(Box::new(expr), line_info))
+        }
+        Rule::COMMENT => {
+            let comment = pair.as_str().to_string();
+            Ok(AST::Comment(comment, line_info))
+        }
+        _ => Err(Error::new_from_span(
+            ErrorVariant::CustomError {
+                message: format!("Unexpected rule: {:?}", pair.as_rule()),
+            },
+            pair.as_span(),
+        )),
+    }
+}
+
+/// Builds a `LogicalOr` node from an `or_expr` rule.
+fn build_or_expr(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let left = build_ast(inner.next().unwrap())?;
+    if let Some(operator_pair) = inner.next() {
+        let right = build_ast(inner.next().unwrap())?;
+        match operator_pair.as_str() {
+            "||" => Ok(AST::LogicalOr(Box::new(left), Box::new(right), line_info)),
+            _ => Err(Error::new_from_span(
+                ErrorVariant::CustomError {
+                    message: "Unexpected logical operator".to_string(),
+                },
+                operator_pair.as_span(),
+            )),
+        }
+    } else {
+        Ok(left)
+    }
+}
+
+/// Builds a `LogicalAnd` node from an `and_expr` rule.
+fn build_and_expr(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let left = build_ast(inner.next().unwrap())?;
+    if let Some(operator_pair) = inner.next() {
+        let right = build_ast(inner.next().unwrap())?;
+        match operator_pair.as_str() {
+            "&&" => Ok(AST::LogicalAnd(Box::new(left), Box::new(right), line_info)),
+            _ => Err(Error::new_from_span(
+                ErrorVariant::CustomError {
+                    message: "Unexpected logical operator".to_string(),
+                },
+                operator_pair.as_span(),
+            )),
+        }
+    } else {
+        Ok(left)
+    }
+}
+
+/// Builds a `LogicalNot` node from a `not_expr` rule.
+fn build_not_expr(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+
+    let exist_not_op = if inner.peek().unwrap().as_rule() == Rule::not_op {
+        inner.next();
+        true
+    } else {
+        false
+    };
+
+    let expr = build_ast(inner.next().unwrap())?;
+
+    if exist_not_op {
+        Ok(AST::LogicalNot(Box::new(expr), line_info))
+    } else {
+        Ok(expr)
+    }
+}
+
+/// Builds a comparison node from a `comp_expr` rule.
+fn build_comp_expr(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let left = build_ast(inner.next().unwrap())?;
+    if let Some(operator_pair) = inner.next() {
+        let right = build_ast(inner.next().unwrap())?;
+        match operator_pair.as_str() {
+            "==" => Ok(AST::Equal(Box::new(left), Box::new(right), line_info)),
+            "!=" => Ok(AST::NotEqual(Box::new(left), Box::new(right), line_info)),
+            "<" => Ok(AST::LessThan(Box::new(left), Box::new(right), line_info)),
+            "<=" => Ok(AST::LessThanOrEqual(
+                Box::new(left),
+                Box::new(right),
+                line_info,
+            )),
+            ">" => Ok(AST::GreaterThan(Box::new(left), Box::new(right), line_info)),
+            ">=" => Ok(AST::GreaterThanOrEqual(
+                Box::new(left),
+                Box::new(right),
+                line_info,
+            )),
+            _ => Err(Error::new_from_span(
+                ErrorVariant::CustomError {
+                    message: "Unexpected comparison operator".to_string(),
+                },
+                operator_pair.as_span(),
+            )),
+        }
+    } else {
+        Ok(left)
+    }
+}
+
+/// Builds a left-associative chain of `Add`/`Sub` nodes from an `add_expr` rule.
+fn build_add_expr(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let mut ast = build_ast(inner.next().unwrap())?;
+
+    while let Some(operator_pair) = inner.next() {
+        let right = build_ast(inner.next().unwrap())?;
+        ast = match operator_pair.as_str() {
+            "+" => AST::Add(Box::new(ast), Box::new(right), line_info.clone()),
+            "-" => AST::Sub(Box::new(ast), Box::new(right), line_info.clone()),
+            _ => {
+                return Err(Error::new_from_span(
                     ErrorVariant::CustomError {
-                        message: "Unknown type in trans expression".to_string(),
+                        message: "Unexpected addition operator".to_string(),
                     },
-                    span,
-                ))?,
-            };
-            Ok(AST::Trans(Box::new(expr), target_type, line_info))
-        }
-        Rule::reveal => {
-            let mut inner = pair.into_inner();
-            match inner.next() {
-                Some(expr) => {
-                    let expression = build_ast(expr)?;
-                    Ok(AST::Reveal(Box::new(expression), line_info.clone()))
+                    operator_pair.as_span(),
+                ));
+            }
+        };
+    }
+    Ok(ast)
+}
+
+/// Builds a left-associative chain of `Mul`/`Div`/`Mod` nodes from a `mul_expr` rule.
+fn build_mul_expr(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let mut ast = build_ast(inner.next().unwrap())?;
+
+    while let Some(operator_pair) = inner.next() {
+        let right = build_ast(inner.next().unwrap())?;
+        ast = match operator_pair.as_str() {
+            "*" => AST::Mul(Box::new(ast), Box::new(right), line_info.clone()),
+            "/" => AST::Div(Box::new(ast), Box::new(right), line_info.clone()),
+            "%" => AST::Mod(Box::new(ast), Box::new(right), line_info.clone()),
+            _ => {
+                return Err(Error::new_from_span(
+                    ErrorVariant::CustomError {
+                        message: "Unexpected multiplication operator".to_string(),
+                    },
+                    operator_pair.as_span(),
+                ));
+            }
+        };
+    }
+    Ok(ast)
+}
+
+/// Builds a left-associative chain of `PowArcana`/`PowAether` nodes from a `pow_expr` rule.
+fn build_pow_expr(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let mut ast = build_ast(inner.next().unwrap())?;
+
+    while let Some(operator_pair) = inner.next() {
+        let right = build_ast(inner.next().unwrap())?;
+        ast = match operator_pair.as_str() {
+            "^" => AST::PowArcana(Box::new(ast), Box::new(right), line_info.clone()),
+            "**" => AST::PowAether(Box::new(ast), Box::new(right), line_info.clone()),
+            _ => {
+                return Err(Error::new_from_span(
+                    ErrorVariant::CustomError {
+                        message: "Unexpected power operator".to_string(),
+                    },
+                    operator_pair.as_span(),
+                ));
+            }
+        };
+    }
+    Ok(ast)
+}
+
+/// Builds a chain of `Index`, `Field` and `Call` nodes from a `postfix_expr` rule, such as
+/// `xs[0].name` or `fs[0](5)`. Each node carries the line information of its own suffix.
+fn build_postfix_expr(pair: Pair<Rule>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let mut ast = build_ast(inner.next().unwrap())?;
+
+    for postfix in inner {
+        let line_info = Some(LineInfo::from_span(&postfix.as_span()));
+        if postfix.as_rule() == Rule::call {
+            let mut args = Vec::new();
+            if let Some(arg_pairs) = postfix.into_inner().next() {
+                for arg_pair in arg_pairs.into_inner() {
+                    args.push(build_ast(arg_pair)?);
                 }
-                None => Ok(AST::Reveal(
-                    Box::new(AST::Abyss(line_info.clone())),
-                    line_info.clone(),
-                )),
             }
+            ast = AST::Call(Box::new(ast), args, line_info);
+            continue;
         }
-        Rule::oracle_expr => {
-            let mut inner = pair.into_inner();
-            let mut conditionals = Vec::new();
-            let mut branches = Vec::new();
-            let mut is_match = false;
-
-            if let Some(conditional_or_branches) = inner.peek() {
-                if conditional_or_branches.as_rule() == Rule::oracle_conditional {
-                    let mut condition_pair = inner.next().unwrap().into_inner();
-                    let conditions = condition_pair.next().unwrap().into_inner();
-                    for (idx, condition) in conditions.enumerate() {
-                        if condition.as_rule() == Rule::conditional_assignment {
-                            let mut inner_pairs = condition.into_inner();
-                            let identifier = inner_pairs.next().unwrap().as_str().to_string();
-                            let expression = build_ast(inner_pairs.next().unwrap())?;
-                            conditionals.push(ConditionalAssignment {
-                                variable: identifier,
-                                expression: Box::new(expression),
-                                line_info: line_info.clone(),
-                            });
-                        } else {
-                            is_match = true;
-                            let mut inner_pairs = condition.into_inner();
-                            let expression = build_ast(inner_pairs.next().unwrap())?;
-                            conditionals.push(ConditionalAssignment {
-                                variable: format!("__match_{}", idx),
-                                expression: Box::new(expression),
-                                line_info: line_info.clone(),
-                            });
+        let rule = postfix.as_rule();
+        let operand = postfix.into_inner().next().unwrap();
+        ast = match rule {
+            Rule::field => AST::Field(Box::new(ast), operand.as_str().to_string(), line_info),
+            _ => AST::Index(Box::new(ast), Box::new(build_ast(operand)?), line_info),
+        };
+    }
+    Ok(ast)
+}
+
+/// Builds a `Type` from a `type` or `codex_key` rule, including nested `grimoire<T>`,
+/// `codex<K, V>` and `cursed<T>` types, function types such as `engrave(arcana) -> rune`
+/// and the names of sigils.
+fn build_type(pair: Pair<Rule>) -> Type {
+    match pair.as_str() {
+        "arcana" => Type::Arcana,
+        "aether" => Type::Aether,
+        "rune" => Type::Rune,
+        "omen" => Type::Omen,
+        "abyss" => Type::Abyss,
+        _ => {
+            let collection_type = pair.into_inner().next().unwrap();
+            match collection_type.as_rule() {
+                Rule::sigil_type => Type::Sigil(collection_type.as_str().to_string()),
+                Rule::codex_type => {
+                    let mut inner = collection_type.into_inner();
+                    let key_type = build_type(inner.next().unwrap());
+                    let value_type = build_type(inner.next().unwrap());
+                    Type::Codex(Box::new(key_type), Box::new(value_type))
+                }
+                Rule::cursed_type => {
+                    let value_type = collection_type.into_inner().next().unwrap();
+                    Type::Cursed(Box::new(build_type(value_type)))
+                }
+                Rule::engrave_sig => {
+                    let mut param_types = Vec::new();
+                    let mut return_type = Type::Abyss;
+                    for inner in collection_type.into_inner() {
+                        match inner.as_rule() {
+                            Rule::engrave_type => {
+                                return_type = build_type(inner.into_inner().next().unwrap())
+                            }
+                            _ => param_types.push(build_type(inner)),
                         }
                     }
+                    Type::Engrave(param_types, Box::new(return_type))
+                }
+                _ => {
+                    let element_type = collection_type.into_inner().next().unwrap();
+                    Type::Grimoire(Box::new(build_type(element_type)))
                 }
             }
+        }
+    }
+}
 
-            for branch_pair in inner {
-                let branch_span = branch_pair.as_span();
+/// Builds a `VarAssign` node from a `forge_var` rule.
+fn build_forge_var(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
 
-                if branch_pair.as_rule() == Rule::COMMENT {
-                    let comment_text = branch_pair.as_str().to_string();
-                    branches.push(AST::Comment(comment_text, line_info.clone()));
-                    continue;
-                }
+    let is_morph = if inner.peek().unwrap().as_rule() == Rule::morph {
+        inner.next();
+        true
+    } else {
+        false
+    };
+
+    let var_name = inner.next().unwrap().as_str().to_string();
+    let var_type = match build_type(inner.next().unwrap()) {
+        Type::Abyss => Err(Error::new_from_span(
+            ErrorVariant::CustomError {
+                message: "Unknown type in forge variable".to_string(),
+            },
+            span,
+        ))?,
+        var_type => var_type,
+    };
+
+    let value = build_ast(inner.next().unwrap())?;
+
+    Ok(AST::VarAssign {
+        name: var_name,
+        value: Box::new(value),
+        var_type,
+        is_morph,
+        slot: None,
+        line_info,
+    })
+}
+
+/// Builds an `Assignment` node from an `assignment` rule.
+fn build_assignment(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let var_name = inner.next().unwrap().as_str().to_string();
+    let mut accessors = Vec::new();
+    while matches!(inner.peek().unwrap().as_rule(), Rule::index | Rule::field) {
+        let accessor = inner.next().unwrap();
+        let accessor_line_info = Some(LineInfo::from_span(&accessor.as_span()));
+        let rule = accessor.as_rule();
+        let operand = accessor.into_inner().next().unwrap();
+        accessors.push(match rule {
+            Rule::field => Accessor::Field(operand.as_str().to_string(), accessor_line_info),
+            _ => Accessor::Index(Box::new(build_ast(operand)?), accessor_line_info),
+        });
+    }
+    let op = match inner.next().unwrap().as_str() {
+        "=" => AssignmentOp::Assign,
+        "+=" => AssignmentOp::AddAssign,
+        "-=" => AssignmentOp::SubAssign,
+        "*=" => AssignmentOp::MulAssign,
+        "/=" => AssignmentOp::DivAssign,
+        "%=" => AssignmentOp::ModAssign,
+        "^=" => AssignmentOp::PowArcanaAssign,
+        "**=" => AssignmentOp::PowAetherAssign,
+        _ => Err(Error::new_from_span(
+            ErrorVariant::CustomError {
+                message: "Unexpected assignment operator".to_string(),
+            },
+            span,
+        ))?,
+    };
+    let value = build_ast(inner.next().unwrap())?;
+
+    Ok(AST::Assignment {
+        name: var_name,
+        accessors,
+        value: Box::new(value),
+        op,
+        slot: None,
+        line_info,
+    })
+}
+
+/// Builds a `Trans` node from a `trans_expr` rule.
+fn build_trans(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let expr = build_ast(inner.next().unwrap())?;
+    let target_type = match build_type(inner.next().unwrap()) {
+        Type::Abyss => Err(Error::new_from_span(
+            ErrorVariant::CustomError {
+                message: "Unknown type in trans expression".to_string(),
+            },
+            span,
+        ))?,
+        target_type => target_type,
+    };
+    Ok(AST::Trans(Box::new(expr), target_type, line_info))
+}
 
-                let mut branch_inner = branch_pair.into_inner();
-                if branch_inner.peek().unwrap().as_span().as_str() == "_" {
-                    branch_inner.next();
-                    let body_ast = if let Some(body) = branch_inner.next() {
-                        build_ast(body)?
-                    } else {
-                        return Err(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "Branch body is missing".to_string(),
-                            },
-                            branch_span,
-                        ));
-                    };
-                    branches.push(AST::OracleBranch {
-                        pattern: Vec::new(),
-                        body: Box::new(body_ast),
-                        line_info: Some(LineInfo::from_span(&branch_span)),
+/// Builds a `Reveal` node from a `reveal` rule. A bare `reveal` reveals `abyss`.
+fn build_reveal(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    match inner.next() {
+        Some(expr) => {
+            let expression = build_ast(expr)?;
+            Ok(AST::Reveal(Box::new(expression), line_info.clone()))
+        }
+        None => Ok(AST::Reveal(
+            Box::new(AST::Abyss(line_info.clone())),
+            line_info.clone(),
+        )),
+    }
+}
+
+/// Builds an `Oracle` node and its branches from an `oracle_expr` rule.
+fn build_oracle(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let mut conditionals = Vec::new();
+    let mut branches = Vec::new();
+    let mut is_match = false;
+
+    if let Some(conditional_or_branches) = inner.peek() {
+        if conditional_or_branches.as_rule() == Rule::oracle_conditional {
+            let mut condition_pair = inner.next().unwrap().into_inner();
+            let conditions = condition_pair.next().unwrap().into_inner();
+            for (idx, condition) in conditions.enumerate() {
+                if condition.as_rule() == Rule::conditional_assignment {
+                    let mut inner_pairs = condition.into_inner();
+                    let identifier = inner_pairs.next().unwrap().as_str().to_string();
+                    let expression = build_ast(inner_pairs.next().unwrap())?;
+                    conditionals.push(ConditionalAssignment {
+                        variable: identifier,
+                        expression: Box::new(expression),
+                        line_info: line_info.clone(),
                     });
                 } else {
-                    let rule = branch_inner
-                        .peek()
-                        .unwrap()
-                        .into_inner()
-                        .next()
-                        .unwrap()
-                        .as_rule();
-                    if rule == Rule::pattern_elements {
-                        let mut expr_pairs = branch_inner.next().unwrap().into_inner();
-                        let exprs = expr_pairs.next().unwrap().into_inner();
-                        let mut pats = vec![];
-                        for expr in exprs {
-                            let pat_ast = build_ast(expr)?;
-                            pats.push(pat_ast);
-                        }
-                        let body_ast = build_ast(branch_inner.next().unwrap())?;
-                        branches.push(AST::OracleBranch {
-                            pattern: pats,
-                            body: Box::new(body_ast),
-                            line_info: Some(LineInfo::from_span(&branch_span)),
-                        });
-                    }
+                    is_match = true;
+                    let mut inner_pairs = condition.into_inner();
+                    let expression = build_ast(inner_pairs.next().unwrap())?;
+                    conditionals.push(ConditionalAssignment {
+                        variable: format!("__match_{}", idx),
+                        expression: Box::new(expression),
+                        line_info: line_info.clone(),
+                    });
                 }
             }
-            Ok(AST::Oracle {
-                is_match,
-                conditionals,
-                branches,
-                line_info,
-            })
         }
-        Rule::pattern => build_ast(pair.into_inner().next().unwrap()),
-        Rule::pattern_element => {
-            if pair.as_span().as_str() == "_" {
-                Ok(AST::OracleDontCareItem(line_info))
+    }
+
+    for branch_pair in inner {
+        let branch_span = branch_pair.as_span();
+
+        if branch_pair.as_rule() == Rule::COMMENT {
+            let comment_text = branch_pair.as_str().to_string();
+            branches.push(AST::Comment(comment_text, line_info.clone()));
+            continue;
+        }
+
+        let mut branch_inner = branch_pair.into_inner();
+        if branch_inner.peek().unwrap().as_span().as_str() == "_" {
+            branch_inner.next();
+            let body_ast = if let Some(body) = branch_inner.next() {
+                build_ast(body)?
             } else {
-                Ok(build_ast(pair.into_inner().next().unwrap())?)
+                return Err(Error::new_from_span(
+                    ErrorVariant::CustomError {
+                        message: "Branch body is missing".to_string(),
+                    },
+                    branch_span,
+                ));
+            };
+            branches.push(AST::OracleBranch {
+                pattern: Vec::new(),
+                body: Box::new(body_ast),
+                line_info: Some(LineInfo::from_span(&branch_span)),
+            });
+        } else {
+            let rule = branch_inner
+                .peek()
+                .unwrap()
+                .into_inner()
+                .next()
+                .unwrap()
+                .as_rule();
+            if rule == Rule::pattern_elements {
+                let mut expr_pairs = branch_inner.next().unwrap().into_inner();
+                let exprs = expr_pairs.next().unwrap().into_inner();
+                let mut pats = vec![];
+                for expr in exprs {
+                    let pat_ast = build_ast(expr)?;
+                    pats.push(pat_ast);
+                }
+                let body_ast = build_ast(branch_inner.next().unwrap())?;
+                branches.push(AST::OracleBranch {
+                    pattern: pats,
+                    body: Box::new(body_ast),
+                    line_info: Some(LineInfo::from_span(&branch_span)),
+                });
             }
         }
-        Rule::block => {
-            let mut statements = Vec::new();
-            let inner = pair.into_inner();
-            for statement_pair in inner {
-                let statement = build_ast(statement_pair)?;
-                statements.push(statement);
-            }
-            Ok(AST::Block(statements, line_info))
+    }
+    Ok(AST::Oracle {
+        is_match,
+        conditionals,
+        branches,
+        line_info,
+    })
+}
+
+/// Builds a `Block` node from a `block` rule.
+fn build_block(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut statements = Vec::new();
+    let inner = pair.into_inner();
+    for statement_pair in inner {
+        let statement = build_ast(statement_pair)?;
+        statements.push(statement);
+    }
+    Ok(AST::Block(statements, line_info))
+}
+
+/// Builds an `Orbit` node from an `orbit` rule.
+fn build_orbit(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let mut params = Vec::new();
+    if inner.peek().unwrap().as_rule() == Rule::orbit_params {
+        let param_pairs = inner.next().unwrap().into_inner();
+        for param_pair in param_pairs {
+            let param = build_ast(param_pair)?;
+            params.push(param);
         }
-        Rule::orbit => {
-            let mut inner = pair.into_inner();
-            let mut params = Vec::new();
-            if inner.peek().unwrap().as_rule() == Rule::orbit_params {
-                let param_pairs = inner.next().unwrap().into_inner();
-                for param_pair in param_pairs {
-                    let param = build_ast(param_pair)?;
-                    params.push(param);
-                }
-            }
-            Ok(AST::Orbit {
-                params,
-                body: Box::new(build_ast(inner.next().unwrap())?),
-                line_info,
-            })
+    }
+    Ok(AST::Orbit {
+        params,
+        body: Box::new(build_ast(inner.next().unwrap())?),
+        line_info,
+    })
+}
+
+/// Builds an `OrbitParam` node for a range, or an `OrbitCollection` node for a collection,
+/// from an `orbit_param` rule. A collection may bind a `(key, value)` pair.
+fn build_orbit_param(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let names = inner.next().unwrap();
+    let (name, value_name) = match names.as_rule() {
+        Rule::orbit_pair => {
+            let mut pair_names = names.into_inner();
+            let name = pair_names.next().unwrap().as_str().to_string();
+            let value_name = pair_names.next().unwrap().as_str().to_string();
+            (name, Some(value_name))
         }
-        Rule::orbit_param => {
-            let mut inner = pair.into_inner();
-            let name = inner.next().unwrap().as_str().to_string();
-            let mut range_expr = inner.next().unwrap().into_inner();
-            let start = build_ast(range_expr.next().unwrap())?;
-            let op = range_expr.next().unwrap().as_str();
-            let end = build_ast(range_expr.next().unwrap())?;
-            Ok(AST::OrbitParam {
-                name,
-                start: Box::new(start),
-                end: Box::new(end),
-                op: op.to_string(),
-                line_info,
-            })
+        _ => (names.as_str().to_string(), None),
+    };
+    let iterable = inner.next().unwrap();
+    if iterable.as_rule() != Rule::range_expr {
+        return Ok(AST::OrbitCollection {
+            name,
+            value_name,
+            collection: Box::new(build_ast(iterable)?),
+            line_info,
+        });
+    }
+    if value_name.is_some() {
+        return Err(Error::new_from_span(
+            ErrorVariant::CustomError {
+                message: "A range in orbit binds a single parameter".to_string(),
+            },
+            span,
+        ));
+    }
+    let mut range_expr = iterable.into_inner();
+    let start = build_ast(range_expr.next().unwrap())?;
+    let op = range_expr.next().unwrap().as_str();
+    let end = build_ast(range_expr.next().unwrap())?;
+    Ok(AST::OrbitParam {
+        name,
+        start: Box::new(start),
+        end: Box::new(end),
+        op: op.to_string(),
+        line_info,
+    })
+}
+
+/// Builds a `Resume` or `Eject` node from an `orbit_flow` rule.
+fn build_orbit_flow(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let rule = inner.peek().unwrap().as_rule();
+    let identifier = inner
+        .next()
+        .unwrap()
+        .into_inner()
+        .next()
+        .map(|id| id.as_str().to_string());
+    match rule {
+        Rule::resume_expr => Ok(AST::Resume(identifier, line_info)),
+        Rule::eject_expr => Ok(AST::Eject(identifier, line_info)),
+        _ => Err(Error::new_from_span(
+            ErrorVariant::CustomError {
+                message: "Unexpected orbit flow".to_string(),
+            },
+            span,
+        )),
+    }
+}
+
+/// Builds an `Engrave` node from an `engrave` rule.
+fn build_engrave(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str().to_string();
+    let (params, return_type, body) = build_engrave_parts(inner)?;
+    Ok(AST::Engrave {
+        name,
+        params,
+        return_type,
+        body,
+        line_info,
+    })
+}
+
+/// Builds an `EngraveExpr` node from an `engrave_expr` rule, an anonymous function.
+fn build_engrave_expr(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let (params, return_type, body) = build_engrave_parts(pair.into_inner())?;
+    Ok(AST::EngraveExpr {
+        params,
+        return_type,
+        body,
+        line_info,
+    })
+}
+
+/// Builds the parameters, the return type and the body following the name of a function.
+fn build_engrave_parts(mut inner: Pairs<Rule>) -> Result<(Vec<AST>, Type, Box<AST>), Error<Rule>> {
+    let mut params = Vec::new();
+    if inner.peek().unwrap().as_rule() == Rule::engrave_params {
+        let param_pairs = inner.next().unwrap().into_inner();
+        for param_pair in param_pairs {
+            let param = build_ast(param_pair)?;
+            params.push(param);
         }
-        Rule::orbit_flow => {
-            let span = pair.as_span();
-            let mut inner = pair.into_inner();
-            let rule = inner.peek().unwrap().as_rule();
-            let identifier = match inner.next().unwrap().into_inner().next() {
-                Some(id) => Some(id.as_str().to_string()),
-                None => None,
-            };
-            match rule {
-                Rule::resume_expr => Ok(AST::Resume(identifier, line_info)),
-                Rule::eject_expr => Ok(AST::Eject(identifier, line_info)),
-                _ => Err(Error::new_from_span(
-                    ErrorVariant::CustomError {
-                        message: "Unexpected orbit flow".to_string(),
-                    },
-                    span,
-                )),
-            }
+    }
+    let return_type = match inner.peek().unwrap().as_rule() {
+        Rule::engrave_type => build_type(inner.next().unwrap().into_inner().next().unwrap()),
+        _ => Type::Abyss,
+    };
+    let body = Box::new(build_ast(inner.next().unwrap())?);
+    Ok((params, return_type, body))
+}
+
+/// Builds an `EngraveParam` node from an `engrave_param` rule.
+fn build_engrave_param(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str().to_string();
+    let param_type = match build_type(inner.next().unwrap()) {
+        Type::Abyss => Err(Error::new_from_span(
+            ErrorVariant::CustomError {
+                message: "Unknown type in engrave parameter".to_string(),
+            },
+            span,
+        ))?,
+        param_type => param_type,
+    };
+    Ok(AST::EngraveParam {
+        name,
+        param_type,
+        line_info,
+    })
+}
+
+/// Builds the text of a `rune` rule together with the way it is written. A raw rune is taken as
+/// written, the newline right after the opening quotes of a multi-line rune is dropped, and the
+/// escape sequences of plain and multi-line runes are expanded.
+fn build_rune(pair: Pair<Rule>) -> Result<(String, RuneStyle), Error<Rule>> {
+    let inner = pair.into_inner().next().unwrap();
+    let text = inner.as_str();
+    match inner.as_rule() {
+        Rule::raw_rune => {
+            let hashes = text[1..].find('"').unwrap();
+            let value = &text[hashes + 2..text.len() - hashes - 1];
+            Ok((value.to_string(), RuneStyle::Raw(hashes)))
         }
-        Rule::engrave => {
-            let span = pair.as_span();
-            let mut inner = pair.into_inner();
-            let name = inner.next().unwrap().as_str().to_string();
-            let mut params = Vec::new();
-            if inner.peek().unwrap().as_rule() == Rule::engrave_params {
-                let param_pairs = inner.next().unwrap().into_inner();
-                for param_pair in param_pairs {
-                    let param = build_ast(param_pair)?;
-                    params.push(param);
-                }
+        Rule::multi_rune => {
+            let body = &text[3..text.len() - 3];
+            let newline = ["\r\n", "\n"]
+                .iter()
+                .find(|newline| body.starts_with(**newline))
+                .map_or(0, |newline| newline.len());
+            let value = unescape(&inner.as_span(), 3 + newline, text.len() - 3)?;
+            Ok((value, RuneStyle::Multi))
+        }
+        _ => Ok((
+            unescape(&inner.as_span(), 1, text.len() - 1)?,
+            RuneStyle::Plain,
+        )),
+    }
+}
+
+/// Builds an `Interpolation` node from an `interp_rune` rule, such as `$"x = {x}"`. Each part of
+/// the text and each embedded expression carries its own position.
+fn build_interpolation(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let parts: Result<Vec<RunePart>, Error<Rule>> = pair
+        .into_inner()
+        .map(|part| match part.as_rule() {
+            Rule::interp_text => {
+                let span = part.as_span();
+                let text = unescape(&span, 0, span.as_str().len())?;
+                Ok(RunePart::Text(text, Some(LineInfo::from_span(&span))))
             }
-            let return_type = match inner.peek().unwrap().as_rule() {
-                Rule::engrave_type => {
-                    let return_type = inner.next().unwrap().as_str();
-                    match return_type {
-                        "arcana" => Type::Arcana,
-                        "aether" => Type::Aether,
-                        "rune" => Type::Rune,
-                        "omen" => Type::Omen,
-                        "abyss" => Type::Abyss,
-                        _ => Err(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: format!("Unknown return type in engrave: {}", return_type),
-                            },
-                            span,
-                        ))?,
+            _ => Ok(RunePart::Expr(build_ast(
+                part.into_inner().next().unwrap(),
+            )?)),
+        })
+        .collect();
+    Ok(AST::Interpolation(parts?, line_info))
+}
+
+/// Expands the escape sequences `\"`, `\\`, `\n`, `\r`, `\t`, `\u{...}`, `\{` and `\}` in the part
+/// of a rune literal between the byte offsets `start` and `end` of its span.
+fn unescape(span: &Span, start: usize, end: usize) -> Result<String, Error<Rule>> {
+    let text = &span.as_str()[start..end];
+    let error = |from: usize, to: usize, message: String| {
+        let offset = span.start() + start;
+        let span = Span::new(span.get_input(), offset + from, offset + to).unwrap();
+        Error::new_from_span(ErrorVariant::CustomError { message }, span)
+    };
+
+    let mut value = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        let (_, escape) = chars.next().unwrap();
+        match escape {
+            '"' | '\\' | '{' | '}' => value.push(escape),
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            'u' => {
+                let digits_start = position + 3;
+                let digits_end = match text[position + 2..].starts_with('{') {
+                    true => text[digits_start..].find('}').map(|end| digits_start + end),
+                    false => None,
+                };
+                let Some(digits_end) = digits_end else {
+                    return Err(error(
+                        position,
+                        position + 2,
+                        "Expected \\u{...} with a hexadecimal code point".to_string(),
+                    ));
+                };
+                let digits = &text[digits_start..digits_end];
+                let code = u32::from_str_radix(digits, 16)
+                    .ok()
+                    .filter(|_| (1..=6).contains(&digits.len()))
+                    .and_then(char::from_u32);
+                match code {
+                    Some(code) => value.push(code),
+                    None => {
+                        return Err(error(
+                            position,
+                            digits_end + 1,
+                            format!("Invalid unicode escape \\u{{{}}}", digits),
+                        ))
                     }
                 }
-                _ => Type::Abyss,
-            };
-            Ok(AST::Engrave {
-                name,
-                params,
-                return_type,
-                body: Box::new(build_ast(inner.next().unwrap())?),
-                line_info,
-            })
+                while chars.next_if(|&(i, _)| i <= digits_end).is_some() {}
+            }
+            other => {
+                return Err(error(
+                    position,
+                    position + 1 + other.len_utf8(),
+                    format!("Unknown escape sequence \\{}", other),
+                ))
+            }
         }
-        Rule::engrave_param => {
-            let span = pair.as_span();
-            let mut inner = pair.into_inner();
-            let name = inner.next().unwrap().as_str().to_string();
-            let param_type = match inner.next().unwrap().as_str() {
-                "arcana" => Type::Arcana,
-                "aether" => Type::Aether,
-                "rune" => Type::Rune,
-                "omen" => Type::Omen,
-                _ => Err(Error::new_from_span(
+    }
+    Ok(value)
+}
+
+/// Builds an `Invoke` node from an `invocation` rule, such as `invoke "lib.aby" { add };`.
+fn build_invocation(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let (path, _) = build_rune(inner.next().unwrap())?;
+    let names = inner.next().map(|names| {
+        names
+            .into_inner()
+            .map(|name| name.as_str().to_string())
+            .collect()
+    });
+    Ok(AST::Invoke {
+        path,
+        names,
+        line_info,
+    })
+}
+
+/// Builds a `Sigil` declaration node from a `sigil` rule.
+fn build_sigil(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str().to_string();
+    let mut fields: Vec<(String, Type)> = Vec::new();
+    if let Some(field_pairs) = inner.next() {
+        for field_pair in field_pairs.into_inner() {
+            let span = field_pair.as_span();
+            let mut field_inner = field_pair.into_inner();
+            let field_name = field_inner.next().unwrap().as_str().to_string();
+            if fields.iter().any(|(existing, _)| *existing == field_name) {
+                return Err(Error::new_from_span(
                     ErrorVariant::CustomError {
-                        message: "Unknown type in engrave parameter".to_string(),
+                        message: format!(
+                            "Field {} of sigil {} is declared twice",
+                            field_name, name
+                        ),
                     },
                     span,
-                ))?,
-            };
-            Ok(AST::EngraveParam {
-                name,
-                param_type,
-                line_info,
-            })
-        }
-        Rule::func_call => {
-            let mut inner = pair.into_inner();
-            let name = inner.next().unwrap().as_str().to_string();
-            let mut args = Vec::new();
-            if let Some(peeked) = inner.peek() {
-                if peeked.as_rule() == Rule::func_args {
-                    let arg_pairs = inner.next().unwrap().into_inner();
-                    for arg_pair in arg_pairs {
-                        let arg = build_ast(arg_pair)?;
-                        args.push(arg);
-                    }
-                }
+                ));
             }
-            Ok(AST::FuncCall {
-                name,
-                args,
-                line_info,
-            })
-        }
-        Rule::summon_expr => {
-            let span = pair.as_span();
-            let mut inner = pair.into_inner();
-            let prompt = inner.next().unwrap().as_str().to_string();
-            let var_type = match inner.next().unwrap().as_str() {
-                "arcana" => Type::Arcana,
-                "aether" => Type::Aether,
-                "rune" => Type::Rune,
-                _ => Err(Error::new_from_span(
+            let field_type = match build_type(field_inner.next().unwrap()) {
+                Type::Abyss => Err(Error::new_from_span(
                     ErrorVariant::CustomError {
-                        message: "Unknown type in summon expression".to_string(),
+                        message: format!("Unknown type for field {} of sigil {}", field_name, name),
                     },
                     span,
                 ))?,
+                field_type => field_type,
             };
-            Ok(AST::Summon(prompt, var_type, line_info))
+            fields.push((field_name, field_type));
         }
-        Rule::COMMENT => {
-            let comment = pair.as_str().to_string();
-            Ok(AST::Comment(comment, line_info))
+    }
+    Ok(AST::Sigil {
+        name,
+        fields,
+        line_info,
+    })
+}
+
+/// Builds a `SigilInstance` node from a `sigil_instance` constructor rule.
+fn build_sigil_instance(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str().to_string();
+    let mut fields = Vec::new();
+    for field_init in inner {
+        let mut field_inner = field_init.into_inner();
+        let field_name = field_inner.next().unwrap().as_str().to_string();
+        let value = build_ast(field_inner.next().unwrap())?;
+        fields.push((field_name, value));
+    }
+    Ok(AST::SigilInstance {
+        name,
+        fields,
+        line_info,
+    })
+}
+
+/// Builds a `FuncCall` node from a `func_call` rule.
+fn build_func_call(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str().to_string();
+    let mut args = Vec::new();
+    if let Some(peeked) = inner.peek() {
+        if peeked.as_rule() == Rule::func_args {
+            let arg_pairs = inner.next().unwrap().into_inner();
+            for arg_pair in arg_pairs {
+                let arg = build_ast(arg_pair)?;
+                args.push(arg);
+            }
         }
+    }
+    Ok(AST::FuncCall {
+        name,
+        args,
+        line_info,
+    })
+}
+
+/// Builds a `Summon` node from a `summon_expr` rule.
+fn build_summon(pair: Pair<Rule>, line_info: Option<LineInfo>) -> Result<AST, Error<Rule>> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let (prompt, _) = build_rune(inner.next().unwrap())?;
+    let var_type = match build_type(inner.next().unwrap()) {
+        var_type @ (Type::Arcana | Type::Aether | Type::Rune) => var_type,
         _ => Err(Error::new_from_span(
             ErrorVariant::CustomError {
-                message: format!("Unexpected rule: {:?}", pair.as_rule()),
+                message: "Unknown type in summon expression".to_string(),
             },
-            pair.as_span(),
-        )),
-    }
+            span,
+        ))?,
+    };
+    Ok(AST::Summon(prompt, var_type, line_info))
 }
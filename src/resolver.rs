@@ -0,0 +1,652 @@
+use crate::ast::{Accessor, ConditionalAssignment, LineInfo, RunePart, Slot, AST};
+use crate::env::Environment;
+use crate::eval::{misplaced_control, EvalError};
+use std::collections::{HashMap, HashSet};
+
+/// A scope known to the resolver, mirroring a scope pushed by the evaluator or the global scope.
+#[derive(Debug, Default)]
+struct Scope {
+    slots: HashMap<String, usize>, // The slot of each variable declared in the scope
+    count: usize,                  // The number of slots in the scope
+    imported: HashSet<String>,     // Names exposed by `invoke`, which are looked up by name
+    functions: HashSet<String>,    // Functions engraved in the scope, which are looked up by name
+    wildcard: bool,                // Whether an `invoke` exposed all of a module's names
+}
+
+impl Scope {
+    /// Declares a variable, returning its slot. A variable declared twice keeps its slot.
+    fn declare(&mut self, name: &str) -> usize {
+        if let Some(&index) = self.slots.get(name) {
+            return index;
+        }
+        let index = self.count;
+        self.slots.insert(name.to_string(), index);
+        self.count += 1;
+        index
+    }
+
+    /// Returns whether the scope exposes a name that is looked up by name.
+    fn exposes(&self, name: &str) -> bool {
+        self.wildcard || self.imported.contains(name) || self.functions.contains(name)
+    }
+}
+
+/// Assigns slots to the variables of a program and checks that the variables it reads are
+/// declared before they are used.
+///
+/// A slot counts the scopes between the code reading a variable and the scope declaring it,
+/// through the scopes of the enclosing functions, which a function sees from where it is
+/// engraved, up to the global scope. The parameters of a function, the variables of a parameter
+/// of an `orbit` and the variables of the conditionals of an oracle take the first slots of their
+/// scope, in order, so that the engines bind them without a slot in the syntax tree.
+///
+/// Inside a function, a variable may also be a global that the program forges, or a function it
+/// engraves, after the function. In the scoping migration mode, a function may read the variables
+/// of its callers, so the variables it does not declare itself are left to be looked up by name,
+/// and never reported.
+///
+/// A resolver is created for a whole program, whose top-level statements it resolves in order,
+/// so that each of them can be evaluated as soon as it is resolved.
+pub struct Resolver {
+    globals: Scope,                        // The global scope
+    later: HashMap<String, Option<usize>>, // The globals the program forges, with their slots, and the functions it engraves
+    scopes: Vec<Scope>, // The local scopes around the code being resolved, outermost first
+    function_base: Option<usize>, // The first scope of the function being resolved, if any
+    lexical: bool,      // Whether the variables of closures and globals are read by slot
+    engraved: HashSet<String>, // The functions engraved so far, anywhere
+    loops: Vec<Option<String>>, // The variables of the enclosing loops, `None` for `orbit {}`
+}
+
+/// Resolves a top-level statement against the variables of an environment.
+///
+/// # Arguments
+/// * `ast` - The statement to resolve.
+/// * `env` - The environment the statement will be evaluated in.
+///
+/// # Returns
+/// The statement with the slots of its variables filled in, or an error if it reads
+/// a variable that is not defined.
+pub fn resolve(ast: &AST, env: &Environment) -> Result<AST, EvalError> {
+    Resolver::new(std::slice::from_ref(ast), env).resolve(ast)
+}
+
+/// Resolves the top-level statements of a program against the variables of an environment.
+/// The globals declared by each statement are known to the statements after it, and to the
+/// functions engraved before it.
+///
+/// # Arguments
+/// * `program` - The statements to resolve.
+/// * `env` - The environment the program will be evaluated in.
+///
+/// # Returns
+/// The statements with the slots of their variables filled in, or the first error.
+pub fn resolve_program(program: &[AST], env: &Environment) -> Result<Vec<AST>, EvalError> {
+    let mut resolver = Resolver::new(program, env);
+    program.iter().map(|ast| resolver.resolve(ast)).collect()
+}
+
+/// Returns the globals the top-level statements of a program forge, with the slots they take
+/// after those of `globals`, and the functions they engrave, without a slot.
+fn later_globals(program: &[AST], globals: &Scope) -> HashMap<String, Option<usize>> {
+    fn declare(ast: &AST, later: &mut HashMap<String, Option<usize>>, count: &mut usize) {
+        match ast {
+            AST::Statement(statement, _) => declare(statement, later, count),
+            AST::Block(statements, _) => {
+                for statement in statements {
+                    declare(statement, later, count);
+                }
+            }
+            AST::VarAssign { name, .. } if !matches!(later.get(name), Some(Some(_))) => {
+                later.insert(name.clone(), Some(*count));
+                *count += 1;
+            }
+            AST::Engrave { name, .. } => {
+                later.entry(name.clone()).or_insert(None);
+            }
+            _ => {}
+        }
+    }
+    let mut later = globals
+        .slots
+        .iter()
+        .map(|(name, &index)| (name.clone(), Some(index)))
+        .collect();
+    let mut count = globals.count;
+    for ast in program {
+        declare(ast, &mut later, &mut count);
+    }
+    later
+}
+
+impl Resolver {
+    /// Creates a resolver for the top-level statements of a program, knowing the global
+    /// variables and functions of the environment it will be evaluated in, including its
+    /// native functions, which may be read as values. The globals stored
+    /// in slots keep them, and the others are looked up by name.
+    ///
+    /// # Arguments
+    /// * `program` - The statements the resolver will resolve, in order.
+    /// * `env` - The environment the program will be evaluated in.
+    pub fn new(program: &[AST], env: &Environment) -> Self {
+        let vars = env.global_vars();
+        let mut globals = Scope {
+            count: vars.slot_count(),
+            functions: env
+                .global_functions()
+                .keys()
+                .chain(env.natives().keys())
+                .cloned()
+                .collect(),
+            ..Scope::default()
+        };
+        for (name, _) in vars.iter() {
+            if let Some(index) = vars.slot_of(name) {
+                globals.slots.insert(name.clone(), index);
+            } else {
+                globals.imported.insert(name.clone());
+            }
+        }
+        Resolver {
+            later: later_globals(program, &globals),
+            globals,
+            scopes: Vec::new(),
+            function_base: None,
+            lexical: !env.scope_warnings(),
+            engraved: HashSet::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    /// Declares a variable in the current scope, which is the global scope at the top level,
+    /// returning its slot.
+    fn declare(&mut self, name: &str) -> Slot {
+        let scope = self.scopes.last_mut().unwrap_or(&mut self.globals);
+        Slot::current(scope.declare(name))
+    }
+
+    /// Declares a function engraved in the current scope, which may be read as a value.
+    fn declare_function(&mut self, name: &str) {
+        self.engraved.insert(name.to_string());
+        let scope = self.scopes.last_mut().unwrap_or(&mut self.globals);
+        scope.functions.insert(name.to_string());
+    }
+
+    /// Finds the slot of a variable that is read or assigned.
+    /// Returns `None` for variables that are looked up by name, and an error for variables
+    /// that are not declared.
+    fn lookup(&self, name: &str, line_info: &Option<LineInfo>) -> Result<Option<Slot>, EvalError> {
+        // Without lexical scoping, only the scopes of the current function are read by slot.
+        let visible = match (self.lexical, self.function_base) {
+            (false, Some(base)) => base,
+            _ => 0,
+        };
+        for (depth, scope) in self.scopes[visible..].iter().rev().enumerate() {
+            if let Some(&index) = scope.slots.get(name) {
+                return Ok(Some(Slot { depth, index }));
+            }
+            if scope.exposes(name) {
+                return Ok(None);
+            }
+        }
+        if self.function_base.is_some() && !self.lexical {
+            return Ok(None);
+        }
+        let depth = self.scopes.len();
+        if let Some(&index) = self.globals.slots.get(name) {
+            return Ok(Some(Slot { depth, index }));
+        }
+        if self.globals.exposes(name) {
+            return Ok(None);
+        }
+        // A function may read a global forged, or a function engraved, after it.
+        if self.function_base.is_some() {
+            match self.later.get(name) {
+                Some(&Some(index)) => return Ok(Some(Slot { depth, index })),
+                Some(None) => return Ok(None),
+                None if self.engraved.contains(name) => return Ok(None),
+                None => {}
+            }
+        }
+        Err(EvalError::UndefinedVariable(
+            name.to_string(),
+            line_info.clone(),
+        ))
+    }
+
+    /// Resolves the body of a function, whose parameters are the variables of its outermost
+    /// scope. The scopes around the function stay visible in its body, but not the loops.
+    fn resolve_function(&mut self, params: &[AST], body: &AST) -> Result<Box<AST>, EvalError> {
+        let mut scope = Scope::default();
+        for param in params {
+            if let AST::EngraveParam { name, .. } = param {
+                scope.declare(name);
+            }
+        }
+        self.scopes.push(scope);
+        let function_base = self.function_base.replace(self.scopes.len() - 1);
+        let loops = std::mem::take(&mut self.loops);
+        let body = self.resolve_box(body);
+        self.scopes.pop();
+        self.function_base = function_base;
+        self.loops = loops;
+        body
+    }
+
+    /// Runs `resolve` within a new local scope.
+    fn in_scope<T>(
+        &mut self,
+        resolve: impl FnOnce(&mut Self) -> Result<T, EvalError>,
+    ) -> Result<T, EvalError> {
+        self.scopes.push(Scope::default());
+        let result = resolve(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// Resolves a list of nodes.
+    fn resolve_all(&mut self, asts: &[AST]) -> Result<Vec<AST>, EvalError> {
+        asts.iter().map(|ast| self.resolve(ast)).collect()
+    }
+
+    /// Resolves a boxed node.
+    fn resolve_box(&mut self, ast: &AST) -> Result<Box<AST>, EvalError> {
+        self.resolve(ast).map(Box::new)
+    }
+
+    /// Resolves a node, returning a copy of it with the slots of its variables filled in.
+    /// The top-level statements of a program are resolved in order.
+    ///
+    /// # Returns
+    /// The resolved node, or an error if it reads a variable that is not defined.
+    pub fn resolve(&mut self, ast: &AST) -> Result<AST, EvalError> {
+        macro_rules! binary {
+            ($variant:ident, $left:expr, $right:expr, $line_info:expr) => {
+                AST::$variant(
+                    self.resolve_box($left)?,
+                    self.resolve_box($right)?,
+                    $line_info.clone(),
+                )
+            };
+        }
+
+        let resolved = match ast {
+            AST::Statement(statement, line_info) => {
+                AST::Statement(self.resolve_box(statement)?, line_info.clone())
+            }
+            AST::Grimoire(items, line_info) => {
+                AST::Grimoire(self.resolve_all(items)?, line_info.clone())
+            }
+            AST::Codex(entries, line_info) => AST::Codex(
+                entries
+                    .iter()
+                    .map(|(key, value)| Ok((self.resolve(key)?, self.resolve(value)?)))
+                    .collect::<Result<_, EvalError>>()?,
+                line_info.clone(),
+            ),
+            AST::Index(target, index, line_info) => binary!(Index, target, index, line_info),
+            AST::Field(target, field, line_info) => {
+                AST::Field(self.resolve_box(target)?, field.clone(), line_info.clone())
+            }
+            AST::Call(callee, args, line_info) => AST::Call(
+                self.resolve_box(callee)?,
+                self.resolve_all(args)?,
+                line_info.clone(),
+            ),
+            AST::Add(l, r, line_info) => binary!(Add, l, r, line_info),
+            AST::Sub(l, r, line_info) => binary!(Sub, l, r, line_info),
+            AST::Mul(l, r, line_info) => binary!(Mul, l, r, line_info),
+            AST::Div(l, r, line_info) => binary!(Div, l, r, line_info),
+            AST::Mod(l, r, line_info) => binary!(Mod, l, r, line_info),
+            AST::PowArcana(l, r, line_info) => binary!(PowArcana, l, r, line_info),
+            AST::PowAether(l, r, line_info) => binary!(PowAether, l, r, line_info),
+            AST::Equal(l, r, line_info) => binary!(Equal, l, r, line_info),
+            AST::NotEqual(l, r, line_info) => binary!(NotEqual, l, r, line_info),
+            AST::LessThan(l, r, line_info) => binary!(LessThan, l, r, line_info),
+            AST::LessThanOrEqual(l, r, line_info) => binary!(LessThanOrEqual, l, r, line_info),
+            AST::GreaterThan(l, r, line_info) => binary!(GreaterThan, l, r, line_info),
+            AST::GreaterThanOrEqual(l, r, line_info) => {
+                binary!(GreaterThanOrEqual, l, r, line_info)
+            }
+            AST::LogicalAnd(l, r, line_info) => binary!(LogicalAnd, l, r, line_info),
+            AST::LogicalOr(l, r, line_info) => binary!(LogicalOr, l, r, line_info),
+            AST::LogicalNot(expr, line_info) => {
+                AST::LogicalNot(self.resolve_box(expr)?, line_info.clone())
+            }
+            AST::VarAssign {
+                name,
+                value,
+                var_type,
+                is_morph,
+                line_info,
+                ..
+            } => {
+                // The value is resolved first: a variable cannot be read in its own declaration.
+                let value = self.resolve_box(value)?;
+                AST::VarAssign {
+                    name: name.clone(),
+                    value,
+                    var_type: var_type.clone(),
+                    is_morph: *is_morph,
+                    slot: Some(self.declare(name)),
+                    line_info: line_info.clone(),
+                }
+            }
+            AST::Assignment {
+                name,
+                accessors,
+                value,
+                op,
+                line_info,
+                ..
+            } => {
+                let value = self.resolve_box(value)?;
+                let slot = self.lookup(name, line_info)?;
+                let accessors = accessors
+                    .iter()
+                    .map(|accessor| match accessor {
+                        Accessor::Index(index, line_info) => {
+                            Ok(Accessor::Index(self.resolve_box(index)?, line_info.clone()))
+                        }
+                        Accessor::Field(_, _) => Ok(accessor.clone()),
+                    })
+                    .collect::<Result<_, EvalError>>()?;
+                AST::Assignment {
+                    name: name.clone(),
+                    accessors,
+                    value,
+                    op: op.clone(),
+                    slot,
+                    line_info: line_info.clone(),
+                }
+            }
+            AST::Var(name, _, line_info) => AST::Var(
+                name.clone(),
+                self.lookup(name, line_info)?,
+                line_info.clone(),
+            ),
+            AST::Unveil(args, line_info) => AST::Unveil(self.resolve_all(args)?, line_info.clone()),
+            AST::Interpolation(parts, line_info) => AST::Interpolation(
+                parts
+                    .iter()
+                    .map(|part| match part {
+                        RunePart::Text(_, _) => Ok(part.clone()),
+                        RunePart::Expr(expr) => Ok(RunePart::Expr(self.resolve(expr)?)),
+                    })
+                    .collect::<Result<_, EvalError>>()?,
+                line_info.clone(),
+            ),
+            AST::Trans(expr, target_type, line_info) => AST::Trans(
+                self.resolve_box(expr)?,
+                target_type.clone(),
+                line_info.clone(),
+            ),
+            AST::Curse(message, line_info) => {
+                AST::Curse(self.resolve_box(message)?, line_info.clone())
+            }
+            AST::Attempt(expr, line_info) => {
+                AST::Attempt(self.resolve_box(expr)?, line_info.clone())
+            }
+            AST::Reveal(expr, line_info) => {
+                if self.function_base.is_none() {
+                    return Err(EvalError::InvalidOperation(
+                        misplaced_control("reveal", None),
+                        line_info.clone(),
+                    ));
+                }
+                AST::Reveal(self.resolve_box(expr)?, line_info.clone())
+            }
+            AST::Resume(label, line_info) => {
+                self.check_loop("resume", label, line_info)?;
+                ast.clone()
+            }
+            AST::Eject(label, line_info) => {
+                self.check_loop("eject", label, line_info)?;
+                ast.clone()
+            }
+            AST::Oracle {
+                is_match,
+                conditionals,
+                branches,
+                line_info,
+            } => self.in_scope(|resolver| {
+                let conditionals = conditionals
+                    .iter()
+                    .map(|conditional| {
+                        let expression = resolver.resolve_box(&conditional.expression)?;
+                        resolver.declare(&conditional.variable);
+                        Ok(ConditionalAssignment {
+                            variable: conditional.variable.clone(),
+                            expression,
+                            line_info: conditional.line_info.clone(),
+                        })
+                    })
+                    .collect::<Result<_, EvalError>>()?;
+                let branches = branches
+                    .iter()
+                    .map(|branch| resolver.resolve_branch(branch, *is_match))
+                    .collect::<Result<_, EvalError>>()?;
+                Ok(AST::Oracle {
+                    is_match: *is_match,
+                    conditionals,
+                    branches,
+                    line_info: line_info.clone(),
+                })
+            })?,
+            AST::Block(statements, line_info) => {
+                AST::Block(self.resolve_all(statements)?, line_info.clone())
+            }
+            AST::Orbit {
+                params,
+                body,
+                line_info,
+            } => {
+                let (params, body) = match params.is_empty() {
+                    true => {
+                        self.loops.push(None);
+                        let body = self.in_scope(|resolver| resolver.resolve(body));
+                        self.loops.pop();
+                        (Vec::new(), body?)
+                    }
+                    false => self.resolve_orbit(params, body)?,
+                };
+                AST::Orbit {
+                    params,
+                    body: Box::new(body),
+                    line_info: line_info.clone(),
+                }
+            }
+            AST::Engrave {
+                name,
+                params,
+                return_type,
+                body,
+                line_info,
+            } => {
+                self.declare_function(name);
+                AST::Engrave {
+                    name: name.clone(),
+                    params: params.clone(),
+                    return_type: return_type.clone(),
+                    body: self.resolve_function(params, body)?,
+                    line_info: line_info.clone(),
+                }
+            }
+            AST::EngraveExpr {
+                params,
+                return_type,
+                body,
+                line_info,
+            } => AST::EngraveExpr {
+                params: params.clone(),
+                return_type: return_type.clone(),
+                body: self.resolve_function(params, body)?,
+                line_info: line_info.clone(),
+            },
+            AST::FuncCall {
+                name,
+                args,
+                line_info,
+            } => AST::FuncCall {
+                name: name.clone(),
+                args: self.resolve_all(args)?,
+                line_info: line_info.clone(),
+            },
+            AST::Invoke { names, .. } => {
+                let scope = self.scopes.last_mut().unwrap_or(&mut self.globals);
+                match names {
+                    Some(names) => scope.imported.extend(names.iter().cloned()),
+                    None => scope.wildcard = true,
+                }
+                ast.clone()
+            }
+            AST::SigilInstance {
+                name,
+                fields,
+                line_info,
+            } => AST::SigilInstance {
+                name: name.clone(),
+                fields: fields
+                    .iter()
+                    .map(|(field, value)| Ok((field.clone(), self.resolve(value)?)))
+                    .collect::<Result<_, EvalError>>()?,
+                line_info: line_info.clone(),
+            },
+            AST::Omen(_, _)
+            | AST::Arcana(_, _)
+            | AST::BigArcana(_, _)
+            | AST::Aether(_, _)
+            | AST::Rune(_, _, _)
+            | AST::Abyss(_)
+            | AST::OracleBranch { .. }
+            | AST::OracleDontCareItem(_)
+            | AST::Comment(_, _)
+            | AST::OrbitParam { .. }
+            | AST::OrbitCollection { .. }
+            | AST::EngraveParam { .. }
+            | AST::Summon(_, _, _)
+            | AST::Sigil { .. } => ast.clone(),
+        };
+        Ok(resolved)
+    }
+
+    /// Resolves a branch of an oracle within the oracle's scope. In a matching oracle, a
+    /// `curse(name)` pattern declares `name`, which the branch body can read. Since only one
+    /// branch runs, the variables a branch declares are not read by slot in the next branches,
+    /// whose slots they would leave empty.
+    fn resolve_branch(&mut self, branch: &AST, is_match: bool) -> Result<AST, EvalError> {
+        let declared = self.scopes.last().map(|scope| scope.slots.clone());
+        let resolved = self.resolve_branch_in_scope(branch, is_match);
+        if let (Some(scope), Some(declared)) = (self.scopes.last_mut(), declared) {
+            scope.slots = declared;
+        }
+        resolved
+    }
+
+    /// Resolves a branch of an oracle, declaring its variables in the oracle's scope.
+    fn resolve_branch_in_scope(&mut self, branch: &AST, is_match: bool) -> Result<AST, EvalError> {
+        let AST::OracleBranch {
+            pattern,
+            body,
+            line_info,
+        } = branch
+        else {
+            return self.resolve(branch);
+        };
+        let pattern = pattern
+            .iter()
+            .map(|item| match item {
+                AST::Curse(message, curse_line_info) if is_match => match message.as_ref() {
+                    AST::Var(name, _, var_line_info) => Ok(AST::Curse(
+                        Box::new(AST::Var(
+                            name.clone(),
+                            Some(self.declare(name)),
+                            var_line_info.clone(),
+                        )),
+                        curse_line_info.clone(),
+                    )),
+                    _ => self.resolve(item),
+                },
+                _ => self.resolve(item),
+            })
+            .collect::<Result<_, EvalError>>()?;
+        Ok(AST::OracleBranch {
+            pattern,
+            body: self.resolve_box(body)?,
+            line_info: line_info.clone(),
+        })
+    }
+
+    /// Resolves the parameters and body of an `orbit` loop. The range or collection of each
+    /// parameter is resolved in the scope of the previous one, and each parameter's loop
+    /// variables are declared in a new scope, which the body runs in.
+    fn resolve_orbit(&mut self, params: &[AST], body: &AST) -> Result<(Vec<AST>, AST), EvalError> {
+        let Some((param, rest)) = params.split_first() else {
+            return Ok((Vec::new(), self.resolve(body)?));
+        };
+        let (param, names) = match param {
+            AST::OrbitParam {
+                name,
+                start,
+                end,
+                op,
+                line_info,
+            } => (
+                AST::OrbitParam {
+                    name: name.clone(),
+                    start: self.resolve_box(start)?,
+                    end: self.resolve_box(end)?,
+                    op: op.clone(),
+                    line_info: line_info.clone(),
+                },
+                vec![name],
+            ),
+            AST::OrbitCollection {
+                name,
+                value_name,
+                collection,
+                line_info,
+            } => (
+                AST::OrbitCollection {
+                    name: name.clone(),
+                    value_name: value_name.clone(),
+                    collection: self.resolve_box(collection)?,
+                    line_info: line_info.clone(),
+                },
+                std::iter::once(name).chain(value_name).collect(),
+            ),
+            _ => (param.clone(), Vec::new()),
+        };
+        self.loops.push(names.first().map(|name| name.to_string()));
+        let resolved = self.in_scope(|resolver| {
+            for name in names {
+                resolver.declare(name);
+            }
+            resolver.resolve_orbit(rest, body)
+        });
+        self.loops.pop();
+        let (mut params, body) = resolved?;
+        params.insert(0, param);
+        Ok((params, body))
+    }
+
+    /// Checks that a `resume` or `eject` is inside a loop, and that its label, if any, names
+    /// the variable of an enclosing loop.
+    fn check_loop(
+        &self,
+        keyword: &str,
+        label: &Option<String>,
+        line_info: &Option<LineInfo>,
+    ) -> Result<(), EvalError> {
+        let found = self
+            .loops
+            .iter()
+            .any(|name| label.is_none() || name == label);
+        match found {
+            true => Ok(()),
+            false => Err(EvalError::InvalidOperation(
+                misplaced_control(keyword, label.as_deref()),
+                line_info.clone(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::ast::Type;
+use crate::env::Environment;
+use crate::eval::{aether_to_arcana, arcana_cmp, big_arcana, parse_arcana, EvalResult};
+use num_traits::Signed;
+
+/// Registers the core library of native functions: math, rune and conversion functions.
+/// Every new `Environment` starts with these functions registered.
+///
+/// # Arguments
+/// * `env` - The environment to register the functions in.
+pub fn register_core_library(env: &mut Environment) {
+    register_math(env);
+    register_rune(env);
+    register_conversion(env);
+}
+
+/// Returns a grimoire type holding elements of the given type.
+fn grimoire_of(element_type: Type) -> Type {
+    Type::Grimoire(Box::new(element_type))
+}
+
+/// Returns the message reported when a native receives arguments it cannot handle.
+/// Arguments are checked against the signature before the body runs, so this is never expected.
+fn unexpected(args: &[EvalResult]) -> String {
+    format!("Unexpected arguments: {:?}", args)
+}
+
+/// Registers `abs`, `min`, `max`, `sqrt`, `floor`, `ceil` and `round`.
+fn register_math(env: &mut Environment) {
+    env.register_native("abs", vec![Type::Arcana], Type::Arcana, |args| {
+        match args.as_slice() {
+            [EvalResult::Arcana(n)] => n
+                .checked_abs()
+                .map(EvalResult::Arcana)
+                .ok_or_else(|| format!("the absolute value of {} does not fit in arcana", n)),
+            [EvalResult::BigArcana(n)] => Ok(big_arcana(n.abs())),
+            _ => Err(unexpected(&args)),
+        }
+    });
+    env.register_native("abs", vec![Type::Aether], Type::Aether, |args| {
+        match args.as_slice() {
+            [EvalResult::Aether(n)] => Ok(EvalResult::Aether(n.abs())),
+            _ => Err(unexpected(&args)),
+        }
+    });
+
+    for (name, pick_first) in [("min", true), ("max", false)] {
+        env.register_native(
+            name,
+            vec![Type::Arcana, Type::Arcana],
+            Type::Arcana,
+            move |args| match args.as_slice() {
+                [a, b] => Ok(if arcana_cmp(a, b).is_le() == pick_first {
+                    a.clone()
+                } else {
+                    b.clone()
+                }),
+                _ => Err(unexpected(&args)),
+            },
+        );
+        env.register_native(
+            name,
+            vec![Type::Aether, Type::Aether],
+            Type::Aether,
+            move |args| match args.as_slice() {
+                [EvalResult::Aether(a), EvalResult::Aether(b)] => {
+                    Ok(EvalResult::Aether(if pick_first {
+                        a.min(*b)
+                    } else {
+                        a.max(*b)
+                    }))
+                }
+                _ => Err(unexpected(&args)),
+            },
+        );
+    }
+
+    env.register_native(
+        "sqrt",
+        vec![Type::Aether],
+        Type::Aether,
+        |args| match args.as_slice() {
+            [EvalResult::Aether(n)] if *n < 0.0 => {
+                Err(format!("Cannot take the square root of negative {}", n))
+            }
+            [EvalResult::Aether(n)] => Ok(EvalResult::Aether(n.sqrt())),
+            _ => Err(unexpected(&args)),
+        },
+    );
+
+    register_rounding(env, "floor", f64::floor);
+    register_rounding(env, "ceil", f64::ceil);
+    register_rounding(env, "round", f64::round);
+}
+
+/// Registers a native that rounds an aether to an arcana with the given rounding function.
+/// NaN and infinities cannot be rounded, and a result beyond 64 bits overflows outside
+/// bigint mode.
+fn register_rounding(env: &mut Environment, name: &str, rounding: fn(f64) -> f64) {
+    env.register_native(
+        name,
+        vec![Type::Aether],
+        Type::Arcana,
+        move |args| match args.as_slice() {
+            [EvalResult::Aether(n)] => aether_to_arcana(rounding(*n))
+                .ok_or_else(|| format!("Cannot round {} to an arcana", n)),
+            _ => Err(unexpected(&args)),
+        },
+    );
+}
+
+/// Registers `upper`, `lower`, `trim`, `substring`, `split`, `join`, `contains`, `replace`
+/// and `chars`. Positions within a rune count characters, not bytes.
+fn register_rune(env: &mut Environment) {
+    register_rune_mapping(env, "upper", str::to_uppercase);
+    register_rune_mapping(env, "lower", str::to_lowercase);
+    register_rune_mapping(env, "trim", |s| s.trim().to_string());
+
+    env.register_native(
+        "substring",
+        vec![Type::Rune, Type::Arcana, Type::Arcana],
+        Type::Rune,
+        |args| match args.as_slice() {
+            [EvalResult::Rune(s), EvalResult::Arcana(start), EvalResult::Arcana(end)] => {
+                let length = s.chars().count() as i64;
+                if *start < 0 || start > end || *end > length {
+                    return Err(format!(
+                        "Substring range {}..{} is out of bounds for a rune of length {}",
+                        start, end, length
+                    ));
+                }
+                Ok(EvalResult::Rune(
+                    s.chars()
+                        .skip(*start as usize)
+                        .take((end - start) as usize)
+                        .collect(),
+                ))
+            }
+            _ => Err(unexpected(&args)),
+        },
+    );
+
+    env.register_native(
+        "split",
+        vec![Type::Rune, Type::Rune],
+        grimoire_of(Type::Rune),
+        |args| match args.as_slice() {
+            [EvalResult::Rune(_), EvalResult::Rune(separator)] if separator.is_empty() => {
+                Err("Cannot split a rune by an empty separator".to_string())
+            }
+            [EvalResult::Rune(s), EvalResult::Rune(separator)] => Ok(EvalResult::Grimoire(
+                s.split(separator.as_str())
+                    .map(|part| EvalResult::Rune(part.to_string()))
+                    .collect(),
+            )),
+            _ => Err(unexpected(&args)),
+        },
+    );
+
+    env.register_native(
+        "join",
+        vec![grimoire_of(Type::Rune), Type::Rune],
+        Type::Rune,
+        |args| match args.as_slice() {
+            [EvalResult::Grimoire(items), EvalResult::Rune(separator)] => {
+                let parts: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                Ok(EvalResult::Rune(parts.join(separator)))
+            }
+            _ => Err(unexpected(&args)),
+        },
+    );
+
+    env.register_native(
+        "contains",
+        vec![Type::Rune, Type::Rune],
+        Type::Omen,
+        |args| match args.as_slice() {
+            [EvalResult::Rune(s), EvalResult::Rune(pattern)] => {
+                Ok(EvalResult::Omen(s.contains(pattern.as_str())))
+            }
+            _ => Err(unexpected(&args)),
+        },
+    );
+
+    env.register_native(
+        "replace",
+        vec![Type::Rune, Type::Rune, Type::Rune],
+        Type::Rune,
+        |args| match args.as_slice() {
+            [EvalResult::Rune(s), EvalResult::Rune(from), EvalResult::Rune(to)] => {
+                Ok(EvalResult::Rune(s.replace(from.as_str(), to)))
+            }
+            _ => Err(unexpected(&args)),
+        },
+    );
+
+    env.register_native(
+        "chars",
+        vec![Type::Rune],
+        grimoire_of(Type::Rune),
+        |args| match args.as_slice() {
+            [EvalResult::Rune(s)] => Ok(EvalResult::Grimoire(
+                s.chars().map(|c| EvalResult::Rune(c.to_string())).collect(),
+            )),
+            _ => Err(unexpected(&args)),
+        },
+    );
+}
+
+/// Registers a native that transforms a whole rune with the given mapping.
+fn register_rune_mapping(env: &mut Environment, name: &str, mapping: fn(&str) -> String) {
+    env.register_native(name, vec![Type::Rune], Type::Rune, move |args| {
+        match args.as_slice() {
+            [EvalResult::Rune(s)] => Ok(EvalResult::Rune(mapping(s))),
+            _ => Err(unexpected(&args)),
+        }
+    });
+}
+
+/// Registers `parse_arcana` and `parse_aether`, which turn a rune into a number
+/// and return a curse instead of failing when the rune is not a number. An integer that does
+/// not fit in 64 bits is parsed as an unbounded arcana, which is a curse outside bigint mode.
+fn register_conversion(env: &mut Environment) {
+    env.register_native(
+        "parse_arcana",
+        vec![Type::Rune],
+        Type::Cursed(Box::new(Type::Arcana)),
+        |args| match args.as_slice() {
+            [EvalResult::Rune(s)] => Ok(parse_arcana(s.trim(), true)
+                .unwrap_or_else(|| EvalResult::Curse(format!("{} is not an arcana", s), None))),
+            _ => Err(unexpected(&args)),
+        },
+    );
+    env.register_native(
+        "parse_aether",
+        vec![Type::Rune],
+        Type::Cursed(Box::new(Type::Aether)),
+        |args| match args.as_slice() {
+            [EvalResult::Rune(s)] => Ok(match s.trim().parse::<f64>() {
+                Ok(n) => EvalResult::Aether(n),
+                Err(_) => EvalResult::Curse(format!("{} is not an aether", s), None),
+            }),
+            _ => Err(unexpected(&args)),
+        },
+    );
+}
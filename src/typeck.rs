@@ -0,0 +1,1400 @@
+use crate::ast::{Accessor, AssignmentOp, LineInfo, RunePart, Type, AST};
+use crate::env::Environment;
+use crate::eval::{builtin_arity, place_of};
+use crate::format::format_type;
+use crate::module::{check_module_access, load_module, resolve_module_path};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Represents a type error found by the static checker before evaluation.
+#[derive(Debug, Clone)]
+pub struct TypeCheckError {
+    pub message: String,
+    pub line_info: Option<LineInfo>,
+}
+
+impl fmt::Display for TypeCheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Type error: {}", self.message)
+    }
+}
+impl std::error::Error for TypeCheckError {}
+
+/// Stores the declared type and mutability of a variable known to the checker.
+#[derive(Debug, Clone)]
+struct VarSig {
+    var_type: Type,
+    is_morph: bool,
+}
+
+/// Stores the declared parameter types and return type of an `engrave` function.
+#[derive(Debug, Clone)]
+struct FuncSig {
+    params: Vec<Type>,
+    return_type: Type,
+}
+
+/// A function body whose checking is deferred until the end of the enclosing block,
+/// so that it can refer to functions and variables declared after it.
+struct PendingBody {
+    params: Vec<(String, Type)>,
+    return_type: Type,
+    body: AST,
+}
+
+/// Walks the AST and resolves every variable and function reference against its declared type.
+/// Scopes are pushed and popped at the same places as in `eval::evaluate`.
+struct TypeChecker {
+    scopes: Vec<HashMap<String, VarSig>>,
+    function_scopes: Vec<HashMap<String, FuncSig>>,
+    sigil_scopes: Vec<HashMap<String, Vec<(String, Type)>>>,
+    reveal_targets: Vec<Type>,
+    pending: Vec<PendingBody>,
+    errors: Vec<TypeCheckError>,
+    natives: HashMap<String, Vec<FuncSig>>,
+    script_path: Option<PathBuf>,
+    module_chain: Vec<PathBuf>,
+    module_root: Option<PathBuf>,
+    file_access: bool,
+}
+
+/// Statically checks a whole program (the top-level statements of a script) for type errors.
+///
+/// # Arguments
+/// * `program` - The top-level AST nodes produced by `parser::build_ast`.
+///
+/// # Returns
+/// `Ok(())` if no type errors were found, otherwise every error found in source order.
+pub fn scrutinize(program: &[AST]) -> Result<(), Vec<TypeCheckError>> {
+    scrutinize_with_path(program, None)
+}
+
+/// Statically checks a whole program, resolving `invoke` statements relative to its script.
+///
+/// # Arguments
+/// * `program` - The top-level AST nodes produced by `parser::build_ast`.
+/// * `script_path` - The path of the script the program was read from, if any.
+///
+/// # Returns
+/// `Ok(())` if no type errors were found, otherwise every error found in source order.
+/// Errors found inside invoked modules name the module file in their line information.
+pub fn scrutinize_with_path(
+    program: &[AST],
+    script_path: Option<&Path>,
+) -> Result<(), Vec<TypeCheckError>> {
+    let env = match script_path {
+        Some(path) => Environment::with_script_path(path.to_path_buf()),
+        None => Environment::new(),
+    };
+    scrutinize_in(program, &env)
+}
+
+/// Statically checks a whole program that will be evaluated in the given environment,
+/// so that calls to the native functions registered in it are checked against their signatures,
+/// and the global variables, functions and sigils already defined in it are known.
+///
+/// # Arguments
+/// * `program` - The top-level AST nodes produced by `parser::build_ast`.
+/// * `env` - The environment the program will be evaluated in.
+///
+/// # Returns
+/// `Ok(())` if no type errors were found, otherwise every error found in source order.
+pub fn scrutinize_in(program: &[AST], env: &Environment) -> Result<(), Vec<TypeCheckError>> {
+    let mut checker = TypeChecker::new();
+    checker.natives = env
+        .natives()
+        .iter()
+        .map(|(name, overloads)| {
+            let signatures = overloads
+                .iter()
+                .map(|native| FuncSig {
+                    params: native.params.clone(),
+                    return_type: native.return_type.clone(),
+                })
+                .collect();
+            (name.clone(), signatures)
+        })
+        .collect();
+    for (name, var_info) in env.global_vars().iter() {
+        checker.set_var(name, var_info.var_type.clone(), var_info.is_morph);
+    }
+    for (name, function) in env.global_functions() {
+        if let Type::Engrave(params, return_type) = function.signature() {
+            checker.function_scopes[0].insert(
+                name.clone(),
+                FuncSig {
+                    params,
+                    return_type: *return_type,
+                },
+            );
+        }
+    }
+    for (name, sigil) in env.global_sigils() {
+        checker.sigil_scopes[0].insert(name.clone(), sigil.fields.clone());
+    }
+    if let Some(path) = env.script_path() {
+        checker.script_path = Some(path.to_path_buf());
+        checker.module_chain = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+    }
+    checker.module_root = env.module_root().map(Path::to_path_buf);
+    checker.file_access = env.file_access();
+    checker.check_block(program);
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.errors)
+    }
+}
+
+impl TypeChecker {
+    fn new() -> Self {
+        TypeChecker {
+            scopes: vec![HashMap::new()],
+            function_scopes: vec![HashMap::new()],
+            sigil_scopes: vec![HashMap::new()],
+            reveal_targets: Vec::new(),
+            pending: Vec::new(),
+            errors: Vec::new(),
+            natives: HashMap::new(),
+            script_path: None,
+            module_chain: Vec::new(),
+            module_root: None,
+            file_access: false,
+        }
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.function_scopes.push(HashMap::new());
+        self.sigil_scopes.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+        self.function_scopes.pop();
+        self.sigil_scopes.pop();
+    }
+
+    fn error(&mut self, message: String, line_info: &Option<LineInfo>) {
+        self.errors.push(TypeCheckError {
+            message,
+            line_info: line_info.clone(),
+        });
+    }
+
+    fn set_var(&mut self, name: &str, var_type: Type, is_morph: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), VarSig { var_type, is_morph });
+        }
+    }
+
+    fn get_var(&self, name: &str) -> Option<&VarSig> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn get_function(&self, name: &str) -> Option<&FuncSig> {
+        self.function_scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+    }
+
+    fn get_sigil(&self, name: &str) -> Option<&Vec<(String, Type)>> {
+        self.sigil_scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+    }
+
+    /// Returns the declared type of a field of a sigil, reporting an error naming the field
+    /// if the sigil has no such field.
+    fn field_type(
+        &mut self,
+        sigil_type: &Type,
+        field: &str,
+        line_info: &Option<LineInfo>,
+    ) -> Option<Type> {
+        let Type::Sigil(sigil_name) = sigil_type else {
+            self.error(
+                format!(
+                    "Only a sigil has fields, cannot read field {} of {:?}",
+                    field, sigil_type
+                ),
+                line_info,
+            );
+            return None;
+        };
+        let field_type = self.get_sigil(sigil_name).map(|fields| {
+            fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, field_type)| field_type.clone())
+        });
+        match field_type {
+            Some(Some(field_type)) => Some(field_type),
+            Some(None) => {
+                self.error(
+                    format!("Sigil {} has no field {}", sigil_name, field),
+                    line_info,
+                );
+                None
+            }
+            None => {
+                self.error(format!("Sigil {} is not defined", sigil_name), line_info);
+                None
+            }
+        }
+    }
+
+    /// Reports an error if a declared type refers to a sigil that is not defined.
+    fn check_declared_type(&mut self, declared: &Type, line_info: &Option<LineInfo>) {
+        match declared {
+            Type::Sigil(name) if self.get_sigil(name).is_none() => {
+                self.error(format!("Sigil {} is not defined", name), line_info)
+            }
+            Type::Grimoire(element_type) => self.check_declared_type(element_type, line_info),
+            Type::Codex(_, value_type) | Type::Cursed(value_type) => {
+                self.check_declared_type(value_type, line_info)
+            }
+            Type::Engrave(param_types, return_type) => {
+                for param_type in param_types {
+                    self.check_declared_type(param_type, line_info);
+                }
+                self.check_declared_type(return_type, line_info);
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks a sequence of statements and then the bodies of the functions engraved in it.
+    /// Returns the type of the last statement.
+    fn check_block(&mut self, statements: &[AST]) -> Option<Type> {
+        let pending_start = self.pending.len();
+        let mut last_type = Some(Type::Abyss);
+
+        for statement in statements {
+            last_type = self.check(statement);
+        }
+
+        let bodies = self.pending.split_off(pending_start);
+        for pending in bodies {
+            self.check_function_body(pending);
+        }
+
+        last_type
+    }
+
+    fn check_function_body(&mut self, pending: PendingBody) {
+        self.push_scope();
+        for (name, param_type) in &pending.params {
+            self.set_var(name, param_type.clone(), false);
+        }
+        self.reveal_targets.push(pending.return_type);
+        self.check(&pending.body);
+        self.reveal_targets.pop();
+        self.pop_scope();
+    }
+
+    /// Checks an AST node and returns its static type, or `None` if it cannot be determined.
+    fn check(&mut self, ast: &AST) -> Option<Type> {
+        match ast {
+            AST::Statement(node, _) if matches!(**node, AST::Oracle { .. }) => {
+                self.check_oracle(node, false)
+            }
+            AST::Statement(node, _) => self.check(node),
+            AST::Omen(_, _) => Some(Type::Omen),
+            AST::Arcana(_, _) | AST::BigArcana(_, _) => Some(Type::Arcana),
+            AST::Aether(_, _) => Some(Type::Aether),
+            AST::Rune(_, _, _) => Some(Type::Rune),
+            AST::Abyss(_) => Some(Type::Abyss),
+            AST::Grimoire(elements, line_info) => {
+                let element_types: Vec<Option<Type>> =
+                    elements.iter().map(|element| self.check(element)).collect();
+                let element_types: Vec<Type> = element_types.into_iter().collect::<Option<_>>()?;
+                match element_types.split_first() {
+                    Some((first, rest)) => {
+                        if let Some(other) = rest.iter().find(|t| !conforms(t, first)) {
+                            self.error(
+                                format!(
+                                    "Grimoire elements must share a single type, found {:?} and {:?}",
+                                    first, other
+                                ),
+                                line_info,
+                            );
+                            return None;
+                        }
+                        Some(Type::Grimoire(Box::new(first.clone())))
+                    }
+                    None => Some(Type::Grimoire(Box::new(Type::Abyss))),
+                }
+            }
+            AST::Field(target, field, line_info) => {
+                let target_type = self.check(target)?;
+                self.field_type(&target_type, field, line_info)
+            }
+            AST::Codex(entries, line_info) => {
+                let mut entry_types = Vec::new();
+                let mut literal_keys = HashSet::new();
+                for (key, value) in entries {
+                    let literal_key = match key {
+                        AST::Arcana(n, _) => Some(n.to_string()),
+                        AST::Rune(s, _, _) => Some(format!("\"{}\"", s)),
+                        _ => None,
+                    };
+                    if let Some(literal_key) = literal_key {
+                        if !literal_keys.insert(literal_key.clone()) {
+                            self.error(
+                                format!("Duplicate key {} in codex literal", literal_key),
+                                &key.line_info().cloned(),
+                            );
+                        }
+                    }
+                    let key_type = self.check(key);
+                    let value_type = self.check(value);
+                    if let Some(key_type) = &key_type {
+                        if !matches!(key_type, Type::Arcana | Type::Rune) {
+                            self.error(
+                                format!(
+                                    "Codex key must be of type Arcana or Rune, found {:?}",
+                                    key_type
+                                ),
+                                line_info,
+                            );
+                        }
+                    }
+                    entry_types.push((key_type, value_type));
+                }
+                let entry_types: Vec<(Type, Type)> = entry_types
+                    .into_iter()
+                    .map(|(key_type, value_type)| Some((key_type?, value_type?)))
+                    .collect::<Option<_>>()?;
+                match entry_types.split_first() {
+                    Some(((key_type, value_type), rest)) => {
+                        if let Some((other_key, other_value)) = rest
+                            .iter()
+                            .find(|(k, v)| k != key_type || !conforms(v, value_type))
+                        {
+                            self.error(
+                                format!(
+                                    "Codex entries must share a single type, found {:?}: {:?} and {:?}: {:?}",
+                                    key_type, value_type, other_key, other_value
+                                ),
+                                line_info,
+                            );
+                            return None;
+                        }
+                        Some(Type::Codex(
+                            Box::new(key_type.clone()),
+                            Box::new(value_type.clone()),
+                        ))
+                    }
+                    None => Some(Type::Codex(Box::new(Type::Abyss), Box::new(Type::Abyss))),
+                }
+            }
+            AST::Index(target, index, line_info) => {
+                let Some(target_type) = self.check(target) else {
+                    self.check(index);
+                    return None;
+                };
+                match target_type {
+                    Type::Grimoire(element_type) => {
+                        self.check_index(index, &Type::Arcana, line_info);
+                        Some(*element_type)
+                    }
+                    Type::Codex(key_type, value_type) => {
+                        self.check_index(index, &key_type, line_info);
+                        Some(*value_type)
+                    }
+                    t => {
+                        self.check(index);
+                        self.error(
+                            format!("Only a Grimoire or Codex can be indexed, found {:?}", t),
+                            line_info,
+                        );
+                        None
+                    }
+                }
+            }
+            AST::Add(left, right, line_info) => {
+                let (l, r) = self.check_operands(left, right)?;
+                match (&l, &r) {
+                    (Type::Arcana, Type::Arcana)
+                    | (Type::Aether, Type::Aether)
+                    | (Type::Rune, Type::Rune) => Some(l),
+                    _ => {
+                        self.error(
+                            format!(
+                                "Add operation requires either two Arcana, two Aether, or two Rune, found {:?} and {:?}",
+                                l, r
+                            ),
+                            line_info,
+                        );
+                        None
+                    }
+                }
+            }
+            AST::Sub(left, right, line_info)
+            | AST::Mul(left, right, line_info)
+            | AST::Div(left, right, line_info)
+            | AST::Mod(left, right, line_info) => {
+                let operation = match ast {
+                    AST::Sub(_, _, _) => "Subtract",
+                    AST::Mul(_, _, _) => "Multiply",
+                    AST::Div(_, _, _) => "Divide",
+                    _ => "Modulo",
+                };
+                let (l, r) = self.check_operands(left, right)?;
+                match (&l, &r) {
+                    (Type::Arcana, Type::Arcana) | (Type::Aether, Type::Aether) => Some(l),
+                    _ => {
+                        self.error(
+                            format!(
+                                "{} operation requires either two Arcana or two Aether, found {:?} and {:?}",
+                                operation, l, r
+                            ),
+                            line_info,
+                        );
+                        None
+                    }
+                }
+            }
+            AST::PowArcana(left, right, line_info) => {
+                self.check_binary(left, right, Type::Arcana, "PowArcana", line_info)
+            }
+            AST::PowAether(left, right, line_info) => {
+                self.check_binary(left, right, Type::Aether, "PowAether", line_info)
+            }
+            AST::Equal(left, right, line_info) | AST::NotEqual(left, right, line_info) => {
+                let (l, r) = self.check_operands(left, right)?;
+                match (&l, &r) {
+                    (Type::Arcana, Type::Arcana)
+                    | (Type::Aether, Type::Aether)
+                    | (Type::Rune, Type::Rune) => Some(Type::Omen),
+                    _ => {
+                        self.error(
+                            format!(
+                                "Comparison requires compatible types, found {:?} and {:?}",
+                                l, r
+                            ),
+                            line_info,
+                        );
+                        None
+                    }
+                }
+            }
+            AST::LessThan(left, right, line_info)
+            | AST::LessThanOrEqual(left, right, line_info)
+            | AST::GreaterThan(left, right, line_info)
+            | AST::GreaterThanOrEqual(left, right, line_info) => {
+                let (l, r) = self.check_operands(left, right)?;
+                match (&l, &r) {
+                    (Type::Arcana, Type::Arcana) | (Type::Aether, Type::Aether) => Some(Type::Omen),
+                    _ => {
+                        self.error(
+                            format!(
+                                "Comparison requires numeric types, found {:?} and {:?}",
+                                l, r
+                            ),
+                            line_info,
+                        );
+                        None
+                    }
+                }
+            }
+            AST::LogicalAnd(left, right, line_info) => {
+                self.check_binary(left, right, Type::Omen, "LogicalAnd", line_info)
+            }
+            AST::LogicalOr(left, right, line_info) => {
+                self.check_binary(left, right, Type::Omen, "LogicalOr", line_info)
+            }
+            AST::LogicalNot(expr, line_info) => {
+                let t = self.check(expr)?;
+                if t != Type::Omen {
+                    self.error(
+                        format!("LogicalNot operation requires Omen, found {:?}", t),
+                        line_info,
+                    );
+                    return None;
+                }
+                Some(Type::Omen)
+            }
+            AST::VarAssign {
+                name,
+                value,
+                var_type,
+                is_morph,
+                line_info,
+                ..
+            } => {
+                if let Some(value_type) = self.check(value) {
+                    if !accepts(&value_type, var_type) {
+                        self.error(
+                            format!(
+                                "Cannot forge variable {} of type {:?} with a value of type {:?}",
+                                name, var_type, value_type
+                            ),
+                            line_info,
+                        );
+                    }
+                }
+                self.check_declared_type(var_type, line_info);
+                self.set_var(name, var_type.clone(), *is_morph);
+                Some(Type::Abyss)
+            }
+            AST::Assignment {
+                name,
+                accessors,
+                value,
+                op,
+                line_info,
+                ..
+            } => {
+                let value_type = self.check(value);
+                let var = match self.get_var(name) {
+                    Some(var) => var.clone(),
+                    None => {
+                        self.error(format!("Variable {} is not defined", name), line_info);
+                        return Some(Type::Abyss);
+                    }
+                };
+                if !var.is_morph {
+                    self.error(
+                        format!("Cannot reassign to immutable variable {}", name),
+                        line_info,
+                    );
+                }
+                let mut target_type = var.var_type.clone();
+                let mut target_name = name.clone();
+                for accessor in accessors {
+                    match accessor {
+                        Accessor::Index(index, index_line_info) => match target_type {
+                            Type::Grimoire(element_type) => {
+                                self.check_index(index, &Type::Arcana, index_line_info);
+                                target_type = *element_type;
+                            }
+                            Type::Codex(key_type, value_type) => {
+                                self.check_index(index, &key_type, index_line_info);
+                                target_type = *value_type;
+                            }
+                            _ => {
+                                self.error(
+                                    format!("Variable {} cannot be indexed", name),
+                                    line_info,
+                                );
+                                return Some(Type::Abyss);
+                            }
+                        },
+                        Accessor::Field(field, field_line_info) => {
+                            match self.field_type(&target_type, field, field_line_info) {
+                                Some(field_type) => target_type = field_type,
+                                None => return Some(Type::Abyss),
+                            }
+                            target_name = format!("{}.{}", target_name, field);
+                        }
+                    }
+                }
+                if let Some(value_type) = value_type {
+                    if !accepts(&value_type, &target_type) {
+                        self.error(
+                            format!(
+                                "Cannot assign {:?} to variable {} of type {:?}",
+                                value_type, target_name, target_type
+                            ),
+                            line_info,
+                        );
+                    } else if !supports_assignment_op(&target_type, op) {
+                        self.error(
+                            format!(
+                                "Unsupported operation {:?} for variable {} of type {:?}",
+                                op, target_name, target_type
+                            ),
+                            line_info,
+                        );
+                    }
+                }
+                Some(Type::Abyss)
+            }
+            AST::Var(name, _, line_info) => match self.get_var(name) {
+                Some(var) => Some(var.var_type.clone()),
+                None => match self.get_function(name).or_else(|| {
+                    self.natives
+                        .get(name)
+                        .and_then(|overloads| overloads.first())
+                }) {
+                    Some(function) => Some(Type::Engrave(
+                        function.params.clone(),
+                        Box::new(function.return_type.clone()),
+                    )),
+                    None => {
+                        self.error(format!("Variable {} is not defined", name), line_info);
+                        None
+                    }
+                },
+            },
+            AST::Unveil(args, _) => {
+                for arg in args {
+                    self.check(arg);
+                }
+                Some(Type::Abyss)
+            }
+            AST::Interpolation(parts, _) => {
+                for part in parts {
+                    if let RunePart::Expr(expr) = part {
+                        self.check(expr);
+                    }
+                }
+                Some(Type::Rune)
+            }
+            AST::Trans(expr, target_type, line_info) => {
+                let source = self.check(expr)?;
+                let valid = matches!(
+                    (&source, target_type),
+                    (Type::Aether | Type::Rune, Type::Arcana)
+                        | (Type::Arcana | Type::Rune, Type::Aether)
+                        | (Type::Arcana | Type::Aether, Type::Rune)
+                );
+                if !valid {
+                    self.error(
+                        format!("Invalid cast from {:?} to {:?}", source, target_type),
+                        line_info,
+                    );
+                }
+                Some(target_type.clone())
+            }
+            AST::Curse(message, line_info) => {
+                if let Some(t) = self.check(message) {
+                    if t != Type::Rune {
+                        self.error(
+                            format!("Curse message must be of type Rune, found {:?}", t),
+                            line_info,
+                        );
+                    }
+                }
+                Some(Type::Cursed(Box::new(Type::Abyss)))
+            }
+            AST::Attempt(expr, _) => match self.check(expr)? {
+                Type::Cursed(value_type) => Some(Type::Cursed(value_type)),
+                value_type => Some(Type::Cursed(Box::new(value_type))),
+            },
+            AST::Reveal(expr, line_info) => {
+                let revealed = self.check(expr);
+                if let (Some(revealed), Some(expected)) = (&revealed, self.reveal_targets.last()) {
+                    if !accepts(revealed, expected) {
+                        let message = format!(
+                            "Revealed value of type {:?} does not match the declared return type {:?}",
+                            revealed, expected
+                        );
+                        self.error(message, line_info);
+                    }
+                }
+                revealed
+            }
+            AST::Oracle { .. } => self.check_oracle(ast, true),
+            AST::Block(statements, _) => self.check_block(statements),
+            AST::Orbit {
+                params,
+                body,
+                line_info,
+            } => {
+                self.push_scope();
+                for param in params {
+                    if let AST::OrbitParam {
+                        name, start, end, ..
+                    } = param
+                    {
+                        for bound in [start, end] {
+                            if let Some(t) = self.check(bound) {
+                                if t != Type::Arcana {
+                                    self.error(
+                                        format!(
+                                            "Orbit parameter must be of type Arcana: {} (found {:?})",
+                                            name, t
+                                        ),
+                                        line_info,
+                                    );
+                                }
+                            }
+                        }
+                        self.set_var(name, Type::Arcana, true);
+                    }
+                    if let AST::OrbitCollection {
+                        name,
+                        value_name,
+                        collection,
+                        ..
+                    } = param
+                    {
+                        // A single name binds grimoire elements or codex keys, while a
+                        // `(key, value)` pair binds grimoire positions or codex keys with values.
+                        let (single_type, key_type, value_type) = match self.check(collection) {
+                            Some(Type::Grimoire(element_type)) => {
+                                (*element_type.clone(), Type::Arcana, *element_type)
+                            }
+                            Some(Type::Codex(key_type, value_type)) => {
+                                (*key_type.clone(), *key_type, *value_type)
+                            }
+                            Some(t) => {
+                                self.error(
+                                    format!(
+                                        "Orbit collection must be of type Grimoire or Codex: {} (found {:?})",
+                                        name, t
+                                    ),
+                                    line_info,
+                                );
+                                continue;
+                            }
+                            None => continue,
+                        };
+                        match value_name {
+                            Some(value_name) => {
+                                self.set_var(name, key_type, false);
+                                self.set_var(value_name, value_type, false);
+                            }
+                            None => self.set_var(name, single_type, false),
+                        }
+                    }
+                }
+                self.check(body);
+                self.pop_scope();
+                Some(Type::Abyss)
+            }
+            AST::Resume(_, _) | AST::Eject(_, _) => None,
+            AST::Engrave {
+                name,
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                let params: Vec<(String, Type)> = params
+                    .iter()
+                    .filter_map(|param| match param {
+                        AST::EngraveParam {
+                            name, param_type, ..
+                        } => Some((name.clone(), param_type.clone())),
+                        _ => None,
+                    })
+                    .collect();
+                if let Some(scope) = self.function_scopes.last_mut() {
+                    scope.insert(
+                        name.clone(),
+                        FuncSig {
+                            params: params.iter().map(|(_, t)| t.clone()).collect(),
+                            return_type: return_type.clone(),
+                        },
+                    );
+                }
+                self.pending.push(PendingBody {
+                    params,
+                    return_type: return_type.clone(),
+                    body: *body.clone(),
+                });
+                Some(Type::Abyss)
+            }
+            AST::EngraveExpr {
+                params,
+                return_type,
+                body,
+                line_info,
+            } => {
+                let params: Vec<(String, Type)> = params
+                    .iter()
+                    .filter_map(|param| match param {
+                        AST::EngraveParam {
+                            name, param_type, ..
+                        } => Some((name.clone(), param_type.clone())),
+                        _ => None,
+                    })
+                    .collect();
+                for (_, param_type) in &params {
+                    self.check_declared_type(param_type, line_info);
+                }
+                self.check_declared_type(return_type, line_info);
+                let param_types = params.iter().map(|(_, t)| t.clone()).collect();
+                self.check_function_body(PendingBody {
+                    params,
+                    return_type: return_type.clone(),
+                    body: *body.clone(),
+                });
+                Some(Type::Engrave(param_types, Box::new(return_type.clone())))
+            }
+            AST::FuncCall {
+                name,
+                args,
+                line_info,
+            } => {
+                let arg_types: Vec<Option<Type>> = args.iter().map(|arg| self.check(arg)).collect();
+                let held = match self.get_var(name).map(|var| &var.var_type) {
+                    Some(Type::Engrave(params, return_type)) => Some(FuncSig {
+                        params: params.clone(),
+                        return_type: *return_type.clone(),
+                    }),
+                    _ => None,
+                };
+                let function = match self.get_function(name).cloned().or(held) {
+                    Some(function) => function,
+                    None if self.natives.contains_key(name) => {
+                        return self.check_native(name, &arg_types, line_info)
+                    }
+                    None => return self.check_builtin(name, args, &arg_types, line_info),
+                };
+                self.check_args(name, &function.params, &arg_types, line_info);
+                Some(function.return_type)
+            }
+            AST::Call(callee, args, line_info) => {
+                let callee_type = self.check(callee);
+                let arg_types: Vec<Option<Type>> = args.iter().map(|arg| self.check(arg)).collect();
+                match callee_type? {
+                    Type::Engrave(params, return_type) => {
+                        self.check_args("value", &params, &arg_types, line_info);
+                        Some(*return_type)
+                    }
+                    t => {
+                        self.error(
+                            format!("Only a function can be called, found {:?}", t),
+                            line_info,
+                        );
+                        None
+                    }
+                }
+            }
+            AST::Summon(_, var_type, _) => Some(var_type.clone()),
+            AST::Sigil { name, fields, .. } => {
+                if let Some(scope) = self.sigil_scopes.last_mut() {
+                    scope.insert(name.clone(), fields.clone());
+                }
+                Some(Type::Abyss)
+            }
+            AST::SigilInstance {
+                name,
+                fields,
+                line_info,
+            } => {
+                let field_types: Vec<Option<Type>> =
+                    fields.iter().map(|(_, value)| self.check(value)).collect();
+                let Some(declared) = self.get_sigil(name).cloned() else {
+                    self.error(format!("Sigil {} is not defined", name), line_info);
+                    return None;
+                };
+                for ((field, _), value_type) in fields.iter().zip(field_types) {
+                    let Some(field_type) = declared
+                        .iter()
+                        .find(|(declared_name, _)| declared_name == field)
+                        .map(|(_, field_type)| field_type)
+                    else {
+                        self.error(format!("Sigil {} has no field {}", name, field), line_info);
+                        continue;
+                    };
+                    if let Some(value_type) = value_type {
+                        if !accepts(&value_type, field_type) {
+                            self.error(
+                                format!(
+                                    "Field {} of sigil {} expects {:?} but found {:?}",
+                                    field, name, field_type, value_type
+                                ),
+                                line_info,
+                            );
+                        }
+                    }
+                }
+                for (field, _) in &declared {
+                    if !fields.iter().any(|(given, _)| given == field) {
+                        self.error(
+                            format!("Field {} of sigil {} is missing", field, name),
+                            line_info,
+                        );
+                    }
+                }
+                Some(Type::Sigil(name.clone()))
+            }
+            AST::Invoke {
+                path,
+                names,
+                line_info,
+            } => {
+                self.check_invoke(path, names, line_info);
+                Some(Type::Abyss)
+            }
+            AST::Comment(_, _) => Some(Type::Abyss),
+            _ => None,
+        }
+    }
+
+    /// Checks the arguments of a call against the parameters of the function called, which is
+    /// named `name` in the errors.
+    fn check_args(
+        &mut self,
+        name: &str,
+        params: &[Type],
+        arg_types: &[Option<Type>],
+        line_info: &Option<LineInfo>,
+    ) {
+        if arg_types.len() != params.len() {
+            self.error(
+                format!(
+                    "Function {} expects {} argument(s) but {} were given",
+                    name,
+                    params.len(),
+                    arg_types.len()
+                ),
+                line_info,
+            );
+        }
+        for (idx, (arg_type, param_type)) in arg_types.iter().zip(params.iter()).enumerate() {
+            if let Some(arg_type) = arg_type {
+                if !accepts(arg_type, param_type) {
+                    self.error(
+                        format!(
+                            "Argument {} of function {} expects {:?} but found {:?}",
+                            idx + 1,
+                            name,
+                            param_type,
+                            arg_type
+                        ),
+                        line_info,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Checks an invoked module with its own checker and declares what it exports:
+    /// its functions, immutable globals and sigils, or only the selected `names`.
+    fn check_invoke(
+        &mut self,
+        path: &str,
+        names: &Option<Vec<String>>,
+        line_info: &Option<LineInfo>,
+    ) {
+        let module_path = resolve_module_path(self.script_path.as_deref(), path);
+        let canonical_path = match module_path.canonicalize() {
+            Ok(canonical_path) => canonical_path,
+            Err(e) => {
+                self.error(format!("Cannot read module {}: {}", path, e), line_info);
+                return;
+            }
+        };
+        if let Err(message) = check_module_access(
+            &canonical_path,
+            self.module_root.as_deref(),
+            self.file_access,
+        ) {
+            self.error(message, line_info);
+            return;
+        }
+        if self.module_chain.contains(&canonical_path) {
+            self.error(format!("Cyclic invoke of module {}", path), line_info);
+            return;
+        }
+        let program = match load_module(&module_path) {
+            Ok(program) => program,
+            Err(message) => {
+                self.error(message, line_info);
+                return;
+            }
+        };
+
+        let file = module_path.display().to_string();
+        let mut module = TypeChecker::new();
+        module.natives = self.natives.clone();
+        module.script_path = Some(module_path);
+        module.module_chain = self.module_chain.clone();
+        module.module_chain.push(canonical_path);
+        module.module_root = self.module_root.clone();
+        module.file_access = self.file_access;
+        module.check_block(&program);
+        for mut error in module.errors.drain(..) {
+            if let Some(error_line_info) = &mut error.line_info {
+                if error_line_info.file.is_none() {
+                    error_line_info.file = Some(file.clone());
+                }
+            }
+            // A module invoked more than once reports its errors only once.
+            let is_duplicate = self.errors.iter().any(|reported| {
+                reported.message == error.message && reported.line_info == error.line_info
+            });
+            if !is_duplicate {
+                self.errors.push(error);
+            }
+        }
+
+        let functions = module.function_scopes.swap_remove(0);
+        let sigils = module.sigil_scopes.swap_remove(0);
+        let vars: HashMap<String, VarSig> = module
+            .scopes
+            .swap_remove(0)
+            .into_iter()
+            .filter(|(_, var)| !var.is_morph)
+            .collect();
+        let exported_names: Vec<String> = match names {
+            Some(names) => names.clone(),
+            None => functions
+                .keys()
+                .chain(sigils.keys())
+                .chain(vars.keys())
+                .cloned()
+                .collect(),
+        };
+        for name in exported_names {
+            if let Some(function) = functions.get(&name) {
+                if let Some(scope) = self.function_scopes.last_mut() {
+                    scope.insert(name, function.clone());
+                }
+            } else if let Some(fields) = sigils.get(&name) {
+                if let Some(scope) = self.sigil_scopes.last_mut() {
+                    scope.insert(name, fields.clone());
+                }
+            } else if let Some(var) = vars.get(&name) {
+                self.set_var(&name, var.var_type.clone(), false);
+            } else {
+                self.error(
+                    format!("Module {} does not export {}", path, name),
+                    line_info,
+                );
+            }
+        }
+    }
+
+    /// Checks that an index into a grimoire or codex has the expected type.
+    fn check_index(&mut self, index: &AST, expected: &Type, line_info: &Option<LineInfo>) {
+        if let Some(t) = self.check(index) {
+            if t != *expected {
+                self.error(
+                    format!("Index must be of type {:?}, found {:?}", expected, t),
+                    line_info,
+                );
+            }
+        }
+    }
+
+    /// Checks a call to one of the builtin collection functions `len`, `has`, `push` and `pop`,
+    /// or to one of the file I/O builtins `read`, `read_lines`, `write`, `append` and `exists`.
+    fn check_builtin(
+        &mut self,
+        name: &str,
+        args: &[AST],
+        arg_types: &[Option<Type>],
+        line_info: &Option<LineInfo>,
+    ) -> Option<Type> {
+        let expected_count = match builtin_arity(name) {
+            Some(count) => count,
+            None => {
+                let message = match self.get_var(name) {
+                    Some(var) => format!(
+                        "{} is not a function, it is of type {:?}",
+                        name, var.var_type
+                    ),
+                    None => format!("Function {} is not defined", name),
+                };
+                self.error(message, line_info);
+                return None;
+            }
+        };
+        if arg_types.len() != expected_count {
+            self.error(
+                format!(
+                    "Function {} expects {} argument(s) but {} were given",
+                    name,
+                    expected_count,
+                    arg_types.len()
+                ),
+                line_info,
+            );
+            return None;
+        }
+        if matches!(name, "push" | "pop") {
+            match place_of(&args[0]) {
+                Some((var_name, _, _)) => match self.get_var(var_name) {
+                    Some(var) if !var.is_morph => {
+                        let message = format!("Cannot {} immutable grimoire {}", name, var_name);
+                        self.error(message, line_info);
+                    }
+                    _ => {}
+                },
+                None => self.error(format!("{} requires a grimoire variable", name), line_info),
+            }
+        }
+        match (name, arg_types[0].clone()?) {
+            ("len", Type::Grimoire(_) | Type::Codex(_, _) | Type::Rune) => Some(Type::Arcana),
+            ("has", Type::Codex(key_type, _)) => {
+                if let Some(key) = &arg_types[1] {
+                    if key != key_type.as_ref() {
+                        self.error(
+                            format!(
+                                "Cannot look up {:?} in a codex keyed by {:?}",
+                                key, key_type
+                            ),
+                            line_info,
+                        );
+                    }
+                }
+                Some(Type::Omen)
+            }
+            ("push", Type::Grimoire(element_type)) => {
+                if let Some(pushed) = &arg_types[1] {
+                    if !accepts(pushed, &element_type) {
+                        self.error(
+                            format!(
+                                "Cannot push {:?} into a grimoire of {:?}",
+                                pushed, element_type
+                            ),
+                            line_info,
+                        );
+                    }
+                }
+                Some(Type::Abyss)
+            }
+            ("pop", Type::Grimoire(element_type)) => Some(*element_type),
+            ("read", Type::Rune) => Some(Type::Rune),
+            ("read_lines", Type::Rune) => Some(Type::Grimoire(Box::new(Type::Rune))),
+            ("exists", Type::Rune) => Some(Type::Omen),
+            ("write" | "append", Type::Rune) => {
+                if let Some(t) = &arg_types[1] {
+                    if *t != Type::Rune {
+                        self.error(
+                            format!("Argument 2 of function {} has invalid type {:?}", name, t),
+                            line_info,
+                        );
+                    }
+                }
+                Some(Type::Abyss)
+            }
+            (_, t) => {
+                self.error(
+                    format!("Argument 1 of function {} has invalid type {:?}", name, t),
+                    line_info,
+                );
+                None
+            }
+        }
+    }
+
+    /// Checks a call to a native function against the signatures of its overloads,
+    /// and returns the return type of the first overload that accepts the arguments.
+    fn check_native(
+        &mut self,
+        name: &str,
+        arg_types: &[Option<Type>],
+        line_info: &Option<LineInfo>,
+    ) -> Option<Type> {
+        let overloads = self.natives.get(name)?;
+        let accepted = overloads.iter().find(|signature| {
+            signature.params.len() == arg_types.len()
+                && arg_types
+                    .iter()
+                    .zip(&signature.params)
+                    .all(|(arg_type, param_type)| match arg_type {
+                        Some(arg_type) => accepts(arg_type, param_type),
+                        None => true,
+                    })
+        });
+        if let Some(signature) = accepted {
+            return Some(signature.return_type.clone());
+        }
+
+        let arities: Vec<usize> = overloads.iter().map(|s| s.params.len()).collect();
+        if !arities.contains(&arg_types.len()) {
+            self.error(
+                format!(
+                    "Function {} expects {} argument(s) but {} were given",
+                    name,
+                    arities[0],
+                    arg_types.len()
+                ),
+                line_info,
+            );
+        } else {
+            let arg_types: Vec<String> = arg_types
+                .iter()
+                .map(|t| match t {
+                    Some(t) => format_type(t),
+                    None => "?".to_string(),
+                })
+                .collect();
+            self.error(
+                format!(
+                    "No signature of function {} accepts ({})",
+                    name,
+                    arg_types.join(", ")
+                ),
+                line_info,
+            );
+        }
+        None
+    }
+
+    /// Checks both operands of a binary operator, so that errors on either side are reported.
+    fn check_operands(&mut self, left: &AST, right: &AST) -> Option<(Type, Type)> {
+        let l = self.check(left);
+        let r = self.check(right);
+        Some((l?, r?))
+    }
+
+    /// Checks both operands of a binary operator that requires a single operand type.
+    fn check_binary(
+        &mut self,
+        left: &AST,
+        right: &AST,
+        expected: Type,
+        operation: &str,
+        line_info: &Option<LineInfo>,
+    ) -> Option<Type> {
+        let (l, r) = self.check_operands(left, right)?;
+        if l != expected || r != expected {
+            self.error(
+                format!(
+                    "{} operation requires two {:?}, found {:?} and {:?}",
+                    operation, expected, l, r
+                ),
+                line_info,
+            );
+            return None;
+        }
+        Some(expected)
+    }
+
+    /// Checks an `oracle` node, whose type is the type shared by its branches. Branches of
+    /// different types are reported when the value of the oracle is `used`, rather than
+    /// discarded as a statement.
+    fn check_oracle(&mut self, ast: &AST, used: bool) -> Option<Type> {
+        let AST::Oracle {
+            is_match,
+            conditionals,
+            branches,
+            line_info: oracle_line_info,
+        } = ast
+        else {
+            return None;
+        };
+
+        self.push_scope();
+        let mut conditional_types = Vec::new();
+        for conditional in conditionals {
+            let t = self.check(&conditional.expression);
+            if let Some(t) = &t {
+                self.set_var(&conditional.variable, t.clone(), false);
+            }
+            conditional_types.push(t);
+        }
+
+        let mut branch_types = Vec::new();
+        for branch in branches {
+            if let AST::OracleBranch {
+                pattern,
+                body,
+                line_info,
+            } = branch
+            {
+                for (idx, element) in pattern.iter().enumerate() {
+                    if let AST::OracleDontCareItem(_) = element {
+                        continue;
+                    }
+                    let expected = if *is_match {
+                        conditional_types.get(idx).cloned().flatten()
+                    } else {
+                        Some(Type::Omen)
+                    };
+                    if let AST::Curse(message, _) = element {
+                        if let Some(t) = expected.as_ref().filter(|t| !matches!(t, Type::Cursed(_)))
+                        {
+                            self.error(
+                                format!("Curse pattern cannot match a conditional of type {:?}", t),
+                                line_info,
+                            );
+                        }
+                        if let AST::Var(name, _, _) = message.as_ref() {
+                            self.set_var(name, Type::Rune, false);
+                            continue;
+                        }
+                    }
+                    let Some(pattern_type) = self.check(element) else {
+                        continue;
+                    };
+                    if let Some(expected) = expected {
+                        if !conforms(&pattern_type, &expected) {
+                            self.error(
+                                format!(
+                                    "Oracle branch pattern of type {:?} does not match conditional type {:?}",
+                                    pattern_type, expected
+                                ),
+                                line_info,
+                            );
+                        }
+                    }
+                }
+                if let Some(t) = self.check(body) {
+                    branch_types.push(t);
+                }
+            }
+        }
+
+        self.pop_scope();
+
+        let (first, rest) = branch_types.split_first()?;
+        if let Some(other) = rest
+            .iter()
+            .find(|t| !conforms(t, first) && !conforms(first, t))
+        {
+            if used {
+                self.error(
+                    format!(
+                        "Oracle branches have different types: {:?} and {:?}",
+                        first, other
+                    ),
+                    oracle_line_info,
+                );
+            }
+            return None;
+        }
+        Some(first.clone())
+    }
+}
+
+/// Returns true if the assignment operator can be applied to a variable of the given type.
+fn supports_assignment_op(var_type: &Type, op: &AssignmentOp) -> bool {
+    match var_type {
+        Type::Arcana => !matches!(op, AssignmentOp::PowAetherAssign),
+        Type::Aether => !matches!(op, AssignmentOp::PowArcanaAssign),
+        Type::Rune => matches!(op, AssignmentOp::Assign | AssignmentOp::AddAssign),
+        Type::Omen
+        | Type::Grimoire(_)
+        | Type::Codex(_, _)
+        | Type::Sigil(_)
+        | Type::Cursed(_)
+        | Type::Engrave(_, _) => matches!(op, AssignmentOp::Assign),
+        Type::Abyss => false,
+    }
+}
+
+/// Returns true if a value of type `actual` can be stored where `expected` is declared.
+/// Empty grimoire and codex literals have `abyss` element types and conform to any
+/// grimoire or codex type respectively.
+fn conforms(actual: &Type, expected: &Type) -> bool {
+    match (actual, expected) {
+        (Type::Grimoire(actual), Type::Grimoire(expected)) => {
+            **actual == Type::Abyss || conforms(actual, expected)
+        }
+        (Type::Codex(actual_key, actual_value), Type::Codex(expected_key, expected_value)) => {
+            **actual_key == Type::Abyss
+                || (actual_key == expected_key && conforms(actual_value, expected_value))
+        }
+        (Type::Cursed(actual), Type::Cursed(expected)) => {
+            **actual == Type::Abyss || conforms(actual, expected)
+        }
+        (actual, Type::Cursed(expected)) => conforms(actual, expected),
+        _ => actual == expected,
+    }
+}
+
+/// Returns true if a value of type `actual` can be stored where `expected` is declared.
+/// Unlike `conforms`, a `cursed` value is also accepted where its value type is expected:
+/// it is stored if it holds a value, and its curse is raised otherwise.
+fn accepts(actual: &Type, expected: &Type) -> bool {
+    match (actual, expected) {
+        (Type::Cursed(actual), expected) if !matches!(expected, Type::Cursed(_)) => {
+            **actual == Type::Abyss || conforms(actual, expected)
+        }
+        _ => conforms(actual, expected),
+    }
+}
@@ -0,0 +1,1057 @@
+use crate::ast::{Accessor, LineInfo, Slot, Type, AST};
+use crate::compiler::{
+    compile, AssignTarget, Chunk, CompiledFunction, Instruction, IterBinding, Var,
+};
+use crate::env::{Environment, Function, Value, VarInfo, VarRef};
+use crate::eval::{
+    accessed_value, apply_builtin, apply_function, assign_value, big_arcana_literal, binary_op,
+    bind_argument, call_engrave, callee_function, conditional_value, declared_value,
+    evaluate_invoke, field_value, function_value, index_value, is_caught, logical_not, make_codex,
+    make_curse, make_sigil, native_value, not_a_function, orbit_bindings, orbit_range, param_of,
+    pattern_matches, push_or_pop, return_value, summon, trans, unveil, unveil_text,
+    value_to_result, Access, Bindings, EvalError, EvalResult,
+};
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::ops::Range;
+use std::rc::Rc;
+
+/// Compiles a program to bytecode and runs it on the stack machine, as an alternative to
+/// evaluating its statements with `eval::evaluate`. Both engines share the environment, the
+/// builtins and the errors they report.
+///
+/// # Arguments
+/// * `program` - The top-level statements of the program.
+/// * `env` - The environment to run the program in. Variables declared at the top level
+///   are defined in it.
+///
+/// # Returns
+/// The result of the last statement, or the error that stopped the program. Like the curses
+/// of the other statements, a curse the last statement results in is raised unless it was
+/// caught.
+pub fn run(program: &[AST], env: &mut Environment) -> Result<EvalResult, EvalError> {
+    let chunk = Rc::new(compile(program));
+    let depth = env.scope_depth();
+    let call_depth = env.call_depth();
+    let mut vm = Vm::new(chunk, env);
+    let result = vm.execute().and_then(|result| match result {
+        EvalResult::Curse(message, line_info) if !program.last().is_some_and(is_caught) => {
+            Err(EvalError::Curse(message, line_info))
+        }
+        result => Ok(result),
+    });
+    if result.is_err() {
+        env.unwind_scopes(depth);
+        env.unwind_calls(call_depth);
+    }
+    result
+}
+
+/// The activation of a program or function: the chunk it runs, the position of the next
+/// instruction, and its local variables. The values of oracle conditionals are only allocated
+/// once the frame runs an oracle.
+struct Frame {
+    chunk: Rc<Chunk>,
+    ip: usize,
+    slots: Vec<Option<VarInfo>>,
+    conditionals: Vec<Option<EvalResult>>,
+    function: Option<Rc<CompiledFunction>>,
+    stack_base: usize,
+    scope_base: usize,
+    loop_base: usize,
+    call_site: Option<LineInfo>,
+}
+
+impl Frame {
+    /// Creates a frame running a chunk, whose operands start at `stack_base` on the stack.
+    fn new(chunk: Rc<Chunk>, function: Option<Rc<CompiledFunction>>, stack_base: usize) -> Self {
+        let slot_count = chunk.slot_count();
+        Frame {
+            chunk,
+            ip: 0,
+            slots: vec![None; slot_count],
+            conditionals: Vec::new(),
+            function,
+            stack_base,
+            scope_base: 0,
+            loop_base: 0,
+            call_site: None,
+        }
+    }
+}
+
+/// A scope opened by `EnterScope` or a function call. The scope of a function call, and the
+/// scopes the compiler keeps in the environment, are mirrored by a scope of the environment,
+/// which holds its functions, its sigils, the variables imported into it and the variables of
+/// code that engraves functions.
+struct Scope {
+    frame: usize,
+    first_slot: usize,
+    env: bool,
+}
+
+/// The values an `orbit` still has to bind to its variables.
+enum Iteration {
+    Range(Range<i64>),
+    Collection(Bindings),
+    Endless,
+}
+
+/// A running `orbit`, together with the state its `resume` and `eject` return to.
+struct Loop {
+    iteration: Iteration,
+    stack: usize,
+    scopes: usize,
+    env_depth: usize,
+    handlers: usize,
+}
+
+/// The state to restore when an error is caught by `attempt`.
+struct Handler {
+    frames: usize,
+    calls: usize,
+    stack: usize,
+    scopes: usize,
+    loops: usize,
+    env_depth: usize,
+    target: usize,
+}
+
+/// Where a variable is stored: in a local slot of a frame, or in the environment, at the slot
+/// the resolver gave it or by name.
+enum Location {
+    Slot(usize, usize),
+    Env(Option<Slot>),
+}
+
+/// Hashes the address of a function body to find its compiled function on each call, which
+/// unlike a name needs no keyed hash: mixing its bits spreads the aligned addresses.
+#[derive(Default)]
+struct AddressHasher(u64);
+
+impl Hasher for AddressHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u64(self.0 << 8 | u64::from(*byte));
+        }
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        let mixed = n.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        self.0 = mixed ^ mixed >> 32;
+    }
+}
+
+/// The compiled functions, found by the address of their body.
+type CompiledFunctions =
+    HashMap<*const AST, Rc<CompiledFunction>, BuildHasherDefault<AddressHasher>>;
+
+/// The stack machine running compiled programs.
+struct Vm<'a> {
+    env: &'a mut Environment,
+    stack: Vec<EvalResult>,
+    frames: Vec<Frame>,
+    scopes: Vec<Scope>,
+    compiled: CompiledFunctions, // The compiled functions, by body
+    loops: Vec<Loop>,
+    handlers: Vec<Handler>,
+    steps: u64, // The instructions run since the limits were last checked
+}
+
+impl<'a> Vm<'a> {
+    /// Creates a machine about to run the chunk of a program.
+    fn new(chunk: Rc<Chunk>, env: &'a mut Environment) -> Self {
+        Vm {
+            env,
+            stack: Vec::new(),
+            frames: vec![Frame::new(chunk, None, 0)],
+            scopes: Vec::new(),
+            compiled: HashMap::default(),
+            loops: Vec::new(),
+            handlers: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    /// Runs instructions until the program returns. An error is caught by the innermost
+    /// `attempt`, if any, unless it halts the script. The chunk of the current frame is only
+    /// fetched again when a call or a return changes the frame.
+    fn execute(&mut self) -> Result<EvalResult, EvalError> {
+        let mut frames = self.frames.len();
+        let mut chunk = self.current_chunk();
+        loop {
+            if self.frames.len() != frames {
+                frames = self.frames.len();
+                chunk = self.current_chunk();
+            }
+            let Some(chunk) = chunk.as_deref() else {
+                return Ok(EvalResult::Abyss);
+            };
+            match self.step(chunk) {
+                Ok(Some(result)) => return Ok(result),
+                Ok(None) => {}
+                Err(e) if e.is_halt() => {
+                    self.env.record_trace();
+                    return Err(e);
+                }
+                Err(e) => match self.handlers.pop() {
+                    Some(handler) => self.recover(handler, e),
+                    None => {
+                        self.env.record_trace();
+                        return Err(e);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Returns the chunk run by the current frame.
+    fn current_chunk(&self) -> Option<Rc<Chunk>> {
+        self.frames.last().map(|frame| Rc::clone(&frame.chunk))
+    }
+
+    /// Restores the state saved by `TryBegin` and pushes the error as a curse.
+    fn recover(&mut self, handler: Handler, error: EvalError) {
+        while self.scopes.len() > handler.scopes {
+            self.close_scope();
+        }
+        self.env.unwind_scopes(handler.env_depth);
+        self.env.unwind_calls(handler.calls);
+        self.env.clear_trace();
+        self.frames.truncate(handler.frames);
+        self.stack.truncate(handler.stack);
+        self.loops.truncate(handler.loops);
+        self.stack.push(error.into_curse());
+        self.jump(handler.target);
+    }
+
+    /// Runs the next instruction of `chunk`, the chunk of the current frame. The instruction
+    /// counts as a step against the resource limits, which are only checked on backward jumps
+    /// and calls, since the instructions between them run in bounded time; the sizes of the
+    /// values are checked by the instructions building them.
+    ///
+    /// # Returns
+    /// The result of the program once it returns, `None` while it is running.
+    #[inline(always)]
+    fn step(&mut self, chunk: &Chunk) -> Result<Option<EvalResult>, EvalError> {
+        let frame = match self.frames.last_mut() {
+            Some(frame) => frame,
+            None => return Ok(Some(EvalResult::Abyss)),
+        };
+        let ip = frame.ip;
+        frame.ip += 1;
+        let line_info = &chunk.lines[ip];
+        self.steps += 1;
+
+        match chunk.code[ip] {
+            Instruction::Constant(index) => {
+                self.push_checked(chunk.constants[index].clone(), line_info)?
+            }
+            Instruction::BigConstant(index) => {
+                let result = match &chunk.constants[index] {
+                    EvalResult::BigArcana(n) => {
+                        big_arcana_literal(n, self.env.bigint(), line_info)?
+                    }
+                    constant => constant.clone(),
+                };
+                self.push_checked(result, line_info)?;
+            }
+            Instruction::Pop => {
+                self.pop();
+            }
+            Instruction::Drop => {
+                if let EvalResult::Curse(message, line_info) = self.pop() {
+                    return Err(EvalError::Curse(message, line_info));
+                }
+            }
+            Instruction::LoadSlot(slot) => {
+                let result = match self.current_slot(slot) {
+                    Some(var_info) => value_to_result(&var_info.value),
+                    None => self.load(&chunk.slot_names[slot], line_info)?,
+                };
+                self.stack.push(result);
+            }
+            Instruction::LoadEnv(index) => {
+                let var = &chunk.env_vars[index];
+                let var_info = var
+                    .slot
+                    .and_then(|slot| self.env.get_resolved_var(&var.name, Some(slot)));
+                let result = match var_info {
+                    Some(var_info) => value_to_result(&var_info.value),
+                    None => self.load(&var.name, line_info)?,
+                };
+                self.stack.push(result);
+            }
+            Instruction::Define(index) => {
+                let definition = &chunk.definitions[index];
+                let value = declared_value(self.pop(), &definition.var_type, line_info)?;
+                self.bind(
+                    definition.var,
+                    chunk,
+                    VarInfo {
+                        value,
+                        var_type: definition.var_type.clone(),
+                        is_morph: definition.is_morph,
+                        line_info: line_info.clone(),
+                    },
+                );
+                self.stack.push(EvalResult::Abyss);
+            }
+            Instruction::Assign(index) => {
+                let target = &chunk.assignments[index];
+                let indexes = self.pop_n(target.index_count);
+                let value = self.pop();
+                let location = self.locate_var(&target.var, &target.name, chunk, line_info)?;
+                self.lend(&location, &target.name, |var_info, env| {
+                    assign_value(
+                        &target.name,
+                        var_info,
+                        &target.accessors,
+                        indexes,
+                        value,
+                        &target.op,
+                        env,
+                        line_info,
+                    )
+                })
+                .ok_or_else(|| {
+                    EvalError::UndefinedVariable(target.name.clone(), line_info.clone())
+                })??;
+                self.stack.push(EvalResult::Abyss);
+            }
+            Instruction::Binary(op) => {
+                let right = self.pop();
+                let left = self.pop();
+                self.env
+                    .check_arcana_operation(op, &left, &right, line_info)?;
+                let result = binary_op(op, left, right, self.env.bigint(), line_info)?;
+                self.push_checked(result, line_info)?;
+            }
+            Instruction::BinarySlot(op, slot) => {
+                let left = self.pop();
+                let right = match self.current_slot(slot) {
+                    Some(var_info) => value_to_result(&var_info.value),
+                    None => self.load(&chunk.slot_names[slot], line_info)?,
+                };
+                self.env
+                    .check_arcana_operation(op, &left, &right, line_info)?;
+                let result = binary_op(op, left, right, self.env.bigint(), line_info)?;
+                self.push_checked(result, line_info)?;
+            }
+            Instruction::BinaryConstant(op, index) => {
+                let left = self.pop();
+                let right = chunk.constants[index].clone();
+                self.env
+                    .check_arcana_operation(op, &left, &right, line_info)?;
+                let result = binary_op(op, left, right, self.env.bigint(), line_info)?;
+                self.push_checked(result, line_info)?;
+            }
+            Instruction::Not => {
+                let value = self.pop();
+                self.stack.push(logical_not(value, line_info)?);
+            }
+            Instruction::MakeGrimoire(count) => {
+                let items = self.pop_n(count);
+                self.push_checked(EvalResult::Grimoire(items), line_info)?;
+            }
+            Instruction::MakeCodex(count) => {
+                let mut values = self.pop_n(count * 2).into_iter();
+                let mut entries = Vec::new();
+                while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                    entries.push((key, value));
+                }
+                let result = make_codex(entries, line_info)?;
+                self.push_checked(result, line_info)?;
+            }
+            Instruction::Index => {
+                let index = self.pop();
+                let target = self.pop();
+                self.stack.push(index_value(target, index, line_info)?);
+            }
+            Instruction::Access(index) => {
+                let target = &chunk.assignments[index];
+                let indexes = self.pop_n(target.index_count);
+                let result = self.access(target, chunk, indexes, line_info)?;
+                self.stack.push(result);
+            }
+            Instruction::Field(field) => {
+                let target = self.pop();
+                let result = field_value(target, &chunk.names[field], line_info)?;
+                self.stack.push(result);
+            }
+            Instruction::MakeSigil(index) => {
+                let (name, fields) = &chunk.sigil_instances[index];
+                let values = self.pop_n(fields.len());
+                let fields = fields.iter().cloned().zip(values).collect();
+                let result = make_sigil(name, fields, self.env, line_info)?;
+                self.stack.push(result);
+            }
+            Instruction::Trans(index) => {
+                let value = self.pop();
+                let result = trans(value, &chunk.types[index], self.env.bigint(), line_info)?;
+                self.push_checked(result, line_info)?;
+            }
+            Instruction::Curse => {
+                let message = self.pop();
+                self.stack.push(make_curse(message, line_info)?);
+            }
+            Instruction::Reveal => {
+                let scope_base = self.frames.last().map_or(0, |frame| frame.scope_base);
+                while self.scopes.len() > scope_base {
+                    self.exit_scope();
+                }
+                return self.return_from_frame();
+            }
+            Instruction::Unveil(count) => {
+                let values = self.pop_n(count);
+                self.stack.push(unveil(&values, self.env)?);
+            }
+            Instruction::Interpolate(count) => {
+                let values = self.pop_n(count);
+                self.push_checked(EvalResult::Rune(unveil_text(&values)), line_info)?;
+            }
+            Instruction::Summon(index) => {
+                let (prompt, var_type) = &chunk.summons[index];
+                let result = summon(prompt, var_type, self.env, line_info)?;
+                self.push_checked(result, line_info)?;
+            }
+            Instruction::Jump(target) => {
+                if target <= ip {
+                    self.check_limits(line_info)?;
+                }
+                self.jump(target);
+            }
+            Instruction::JumpUnlessBoon(target) => {
+                if !matches!(self.pop(), EvalResult::Omen(true)) {
+                    self.jump(target);
+                }
+            }
+            Instruction::EnterScope { first_slot, env } => self.enter_scope(first_slot, env),
+            Instruction::ExitScope => self.exit_scope(),
+            Instruction::TryBegin(target) => self.handlers.push(Handler {
+                frames: self.frames.len(),
+                calls: self.env.call_depth(),
+                stack: self.stack.len(),
+                scopes: self.scopes.len(),
+                loops: self.loops.len(),
+                env_depth: self.env.scope_depth(),
+                target,
+            }),
+            Instruction::TryEnd => {
+                self.handlers.pop();
+            }
+            Instruction::BindConditional { var, hidden } => {
+                let result = self.pop();
+                let (value, var_type) = conditional_value(&result, line_info)?;
+                self.bind(
+                    var,
+                    chunk,
+                    VarInfo {
+                        value,
+                        var_type,
+                        is_morph: false,
+                        line_info: line_info.clone(),
+                    },
+                );
+                if let Some(frame) = self.frames.last_mut() {
+                    if frame.conditionals.is_empty() {
+                        frame.conditionals.resize(frame.slots.len(), None);
+                    }
+                    frame.conditionals[hidden] = Some(result);
+                }
+            }
+            Instruction::MatchCurseBind { hidden, var, next } => match self.conditional(hidden) {
+                Some(EvalResult::Curse(message, _)) => {
+                    let value = Value::Rune(message.clone());
+                    self.bind(
+                        var,
+                        chunk,
+                        VarInfo {
+                            value,
+                            var_type: Type::Rune,
+                            is_morph: false,
+                            line_info: line_info.clone(),
+                        },
+                    );
+                }
+                _ => self.jump(next),
+            },
+            Instruction::JumpUnlessCurse { hidden, target } => {
+                if !matches!(self.conditional(hidden), Some(EvalResult::Curse(_, _))) {
+                    self.jump(target);
+                }
+            }
+            Instruction::JumpIfCurse { hidden, target } => {
+                if matches!(self.conditional(hidden), Some(EvalResult::Curse(_, _))) {
+                    self.jump(target);
+                }
+            }
+            Instruction::MatchCurseMessage { hidden, next } => {
+                let pattern = self.pop();
+                let matched = match (pattern, self.conditional(hidden)) {
+                    (EvalResult::Curse(expected, _), Some(EvalResult::Curse(message, _))) => {
+                        expected == *message
+                    }
+                    _ => false,
+                };
+                if !matched {
+                    self.jump(next);
+                }
+            }
+            Instruction::MatchValue { hidden, next } => {
+                let pattern = self.pop();
+                let conditional = self
+                    .conditional(hidden)
+                    .cloned()
+                    .unwrap_or(EvalResult::Abyss);
+                if !pattern_matches(conditional, pattern, line_info)? {
+                    self.jump(next);
+                }
+            }
+            Instruction::IterRange { inclusive, name } => {
+                let end = self.pop();
+                let start = self.pop();
+                let range = orbit_range(start, end, inclusive, &chunk.names[name], line_info)?;
+                self.start_loop(Iteration::Range(range));
+            }
+            Instruction::IterCollection { pair, name } => {
+                let collection = self.pop();
+                let bindings = orbit_bindings(collection, pair, &chunk.names[name], line_info)?;
+                self.start_loop(Iteration::Collection(bindings));
+            }
+            Instruction::IterEndless => self.start_loop(Iteration::Endless),
+            Instruction::IterNext(index) => {
+                let binding = &chunk.iterations[index];
+                // A number of a range is bound without collecting the values of the iteration.
+                match self.loops.last_mut().map(|lp| &mut lp.iteration) {
+                    Some(Iteration::Range(range)) => match range.next() {
+                        Some(n) => self.bind_iteration(
+                            binding,
+                            chunk,
+                            [(Value::Arcana(n), Type::Arcana)],
+                            true,
+                            line_info,
+                        ),
+                        None => self.jump(binding.done),
+                    },
+                    Some(Iteration::Collection(bindings)) => match bindings.next() {
+                        Some(values) => {
+                            self.bind_iteration(binding, chunk, values, false, line_info)
+                        }
+                        None => self.jump(binding.done),
+                    },
+                    Some(Iteration::Endless) | None => self.jump(binding.done),
+                }
+            }
+            Instruction::IterEnd => {
+                self.loops.pop();
+            }
+            Instruction::LoopJump { depth, target } => {
+                self.check_limits(line_info)?;
+                let index = self.loops.len().saturating_sub(depth + 1);
+                if let Some(lp) = self.loops.get(index) {
+                    let (stack, scopes, env_depth, handlers) =
+                        (lp.stack, lp.scopes, lp.env_depth, lp.handlers);
+                    while self.scopes.len() > scopes {
+                        self.close_scope();
+                    }
+                    self.env.unwind_scopes(env_depth);
+                    self.stack.truncate(stack);
+                    self.handlers.truncate(handlers);
+                    self.loops.truncate(index + 1);
+                }
+                self.jump(target);
+            }
+            Instruction::DefineFunction(index) => {
+                let compiled = Rc::clone(&chunk.functions[index]);
+                let mut function = compiled.function.clone();
+                function.closure = self.env.capture_scopes();
+                self.compiled.insert(Rc::as_ptr(&function.body), compiled);
+                self.env.set_function(function.name.clone(), function);
+                self.stack.push(EvalResult::Abyss);
+            }
+            Instruction::MakeEngrave(index) => {
+                let compiled = Rc::clone(&chunk.functions[index]);
+                let mut function = compiled.function.clone();
+                function.closure = self.env.capture_scopes().held();
+                self.compiled.insert(Rc::as_ptr(&function.body), compiled);
+                self.stack.push(EvalResult::Engrave(Rc::new(function)));
+            }
+            Instruction::DefineSigil(index) => {
+                let sigil = &chunk.sigils[index];
+                self.env.set_sigil(sigil.name.clone(), sigil.clone());
+                self.stack.push(EvalResult::Abyss);
+            }
+            Instruction::Invoke(index) => {
+                let invoke = &chunk.invokes[index];
+                let result = evaluate_invoke(&invoke.path, &invoke.names, self.env, line_info)?;
+                self.push_checked(result, line_info)?;
+            }
+            Instruction::JumpIfFunction { name, target } => {
+                let name = &chunk.names[name];
+                if self.env.get_function(name).is_some()
+                    || self.function_in_var(name, line_info).is_some()
+                {
+                    self.jump(target);
+                }
+            }
+            Instruction::Builtin { name, argc } => {
+                let args = self.pop_n(argc);
+                let result = apply_builtin(&chunk.names[name], args, self.env, line_info)?;
+                self.push_checked(result, line_info)?;
+            }
+            Instruction::PushPop { target, push } => {
+                let target = &chunk.assignments[target];
+                let pushed = match push {
+                    true => Some(self.pop()),
+                    false => None,
+                };
+                let indexes = self.pop_n(target.index_count);
+                let location = self.locate_var(&target.var, &target.name, chunk, line_info)?;
+                let result = self
+                    .lend(&location, &target.name, |var_info, env| {
+                        push_or_pop(
+                            &target.name,
+                            var_info,
+                            &target.accessors,
+                            indexes,
+                            pushed,
+                            env,
+                            line_info,
+                        )
+                    })
+                    .ok_or_else(|| {
+                        EvalError::UndefinedVariable(target.name.clone(), line_info.clone())
+                    })??;
+                self.stack.push(result);
+            }
+            Instruction::Call { name, argc } => {
+                self.check_limits(line_info)?;
+                let name = &chunk.names[name];
+                let args = self.pop_n(argc);
+                self.env.check_scoping(name, true, line_info);
+                let function = self
+                    .env
+                    .get_function(name)
+                    .or_else(|| self.function_in_var(name, line_info));
+                match function {
+                    Some(function) => self.call_value(&function, args, line_info)?,
+                    None if self.env.get_native(name).is_none()
+                        && self
+                            .locate(name, line_info)
+                            .and_then(|location| self.var_info(&location, name))
+                            .is_some() =>
+                    {
+                        return Err(not_a_function(name, line_info));
+                    }
+                    None => {
+                        let result = apply_function(name, args, self.env, line_info)?;
+                        self.push_checked(result, line_info)?;
+                    }
+                }
+            }
+            Instruction::CallValue(argc) => {
+                self.check_limits(line_info)?;
+                let args = self.pop_n(argc);
+                let function = callee_function(self.pop(), line_info)?;
+                self.call_value(&function, args, line_info)?;
+            }
+            Instruction::Return => return self.return_from_frame(),
+            Instruction::Fail(message) => {
+                return Err(EvalError::InvalidOperation(
+                    chunk.names[message].clone(),
+                    line_info.clone(),
+                ))
+            }
+        }
+        Ok(None)
+    }
+
+    /// Pushes a value built by an instruction, checking its size against the limits.
+    #[inline]
+    fn push_checked(
+        &mut self,
+        result: EvalResult,
+        line_info: &Option<LineInfo>,
+    ) -> Result<(), EvalError> {
+        self.env.check_result(&result, line_info.as_ref())?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    /// Counts the instructions run since the limits were last checked against them.
+    fn check_limits(&mut self, line_info: &Option<LineInfo>) -> Result<(), EvalError> {
+        self.env
+            .steps(std::mem::take(&mut self.steps), line_info.as_ref())
+    }
+
+    /// Pops the value on top of the stack.
+    fn pop(&mut self) -> EvalResult {
+        self.stack.pop().unwrap_or(EvalResult::Abyss)
+    }
+
+    /// Pops the given number of values, in the order they were pushed.
+    fn pop_n(&mut self, count: usize) -> Vec<EvalResult> {
+        if count == 0 {
+            return Vec::new();
+        }
+        let at = self.stack.len().saturating_sub(count);
+        self.stack.split_off(at)
+    }
+
+    /// Continues at the given position of the current chunk.
+    fn jump(&mut self, target: usize) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.ip = target;
+        }
+    }
+
+    /// Returns a local variable of the current frame, if it is set.
+    fn current_slot(&self, slot: usize) -> Option<&VarInfo> {
+        self.frames.last()?.slots[slot].as_ref()
+    }
+
+    /// Sets a local variable of the current frame.
+    fn set_slot(&mut self, slot: usize, var_info: VarInfo) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.slots[slot] = Some(var_info);
+        }
+    }
+
+    /// Returns the value of an oracle conditional held by a hidden slot of the current frame.
+    fn conditional(&self, hidden: usize) -> Option<&EvalResult> {
+        self.frames.last()?.conditionals.get(hidden)?.as_ref()
+    }
+
+    /// Binds a variable of the current frame, in its slot or in the innermost scope of the
+    /// environment.
+    fn bind(&mut self, var: Var, chunk: &Chunk, var_info: VarInfo) {
+        match var {
+            Var::Slot(slot) => self.set_slot(slot, var_info),
+            Var::Env(index) => self.env.set_resolved_var(
+                &chunk.env_vars[index].name,
+                chunk.env_vars[index].slot,
+                var_info.value,
+                var_info.var_type,
+                var_info.is_morph,
+                var_info.line_info,
+            ),
+        }
+    }
+
+    /// Opens the scope of an iteration of an `orbit` and binds its values to the variables of
+    /// the loop.
+    fn bind_iteration(
+        &mut self,
+        binding: &IterBinding,
+        chunk: &Chunk,
+        values: impl IntoIterator<Item = (Value, Type)>,
+        is_morph: bool,
+        line_info: &Option<LineInfo>,
+    ) {
+        self.enter_scope(binding.first_slot, binding.env);
+        for (var, (value, var_type)) in binding.vars.iter().zip(values) {
+            self.bind(
+                *var,
+                chunk,
+                VarInfo {
+                    value,
+                    var_type,
+                    is_morph,
+                    line_info: line_info.clone(),
+                },
+            );
+        }
+    }
+
+    /// Finds a variable by name. Like the scopes of the tree-walker, the slots of the current
+    /// frame are searched before the environment, which holds the variables a function sees
+    /// from where it is engraved. In the scoping migration mode, the frames of the callers are
+    /// searched too, with a warning.
+    fn locate(&self, name: &str, line_info: &Option<LineInfo>) -> Option<Location> {
+        let frames = match self.env.scope_warnings() {
+            true => 0,
+            false => self.frames.len() - 1,
+        };
+        for (index, frame) in self.frames.iter().enumerate().skip(frames).rev() {
+            if !frame.chunk.declared.contains(name) {
+                continue;
+            }
+            let slot = (0..frame.slots.len())
+                .rev()
+                .find(|slot| frame.slots[*slot].is_some() && frame.chunk.slot_names[*slot] == name);
+            if let Some(slot) = slot {
+                if index + 1 < self.frames.len() {
+                    self.env.warn_caller_lookup(name, false, line_info);
+                }
+                return Some(Location::Slot(index, slot));
+            }
+        }
+        self.env.check_scoping(name, false, line_info);
+        self.env.get_var(name).map(|_| Location::Env(None))
+    }
+
+    /// Returns the variable stored at a location.
+    fn var_info(&self, location: &Location, name: &str) -> Option<VarRef<'_>> {
+        match location {
+            Location::Slot(frame, slot) => self.frames[*frame].slots[*slot]
+                .as_ref()
+                .map(VarRef::Direct),
+            Location::Env(slot) => self.env.get_resolved_var(name, *slot),
+        }
+    }
+
+    /// Finds a variable referred to by an instruction, failing if it is not defined.
+    fn locate_var(
+        &self,
+        var: &Var,
+        name: &str,
+        chunk: &Chunk,
+        line_info: &Option<LineInfo>,
+    ) -> Result<Location, EvalError> {
+        let location = match var {
+            Var::Slot(slot) if self.current_slot(*slot).is_some() => {
+                Some(Location::Slot(self.frames.len() - 1, *slot))
+            }
+            Var::Slot(slot) => self.locate(&chunk.slot_names[*slot], line_info),
+            Var::Env(index) => {
+                // Like in the tree-walker, a variable with a resolved slot is not looked up by
+                // name, so it is only found to be undefined when it is read or written.
+                let var = &chunk.env_vars[*index];
+                match var.slot {
+                    Some(slot) => Some(Location::Env(Some(slot))),
+                    None => self.locate(&var.name, line_info),
+                }
+            }
+        };
+        location.ok_or_else(|| EvalError::UndefinedVariable(name.to_string(), line_info.clone()))
+    }
+
+    /// Lends the variable stored at a location to the given function, which can modify its
+    /// value in place while reading the environment.
+    ///
+    /// # Returns
+    /// The result of the function, or `None` if the variable is not defined.
+    fn lend<R>(
+        &mut self,
+        location: &Location,
+        name: &str,
+        lend: impl FnOnce(&mut VarInfo, &Environment) -> R,
+    ) -> Option<R> {
+        match location {
+            Location::Slot(frame, slot) => {
+                let var_info = self.frames[*frame].slots[*slot].as_mut()?;
+                Some(lend(var_info, self.env))
+            }
+            Location::Env(slot) => self.env.lend_resolved_var(name, *slot, lend),
+        }
+    }
+
+    /// Reads the element of a variable reached through the indexes and fields of an access
+    /// such as `xs[i].hp`, without copying the collections it goes through.
+    fn access(
+        &self,
+        target: &AssignTarget,
+        chunk: &Chunk,
+        indexes: Vec<EvalResult>,
+        line_info: &Option<LineInfo>,
+    ) -> Result<EvalResult, EvalError> {
+        let mut indexes = indexes.into_iter();
+        let accesses = target.accessors.iter().map(|accessor| match accessor {
+            Accessor::Index(_, line_info) => {
+                Access::Index(indexes.next().unwrap_or(EvalResult::Abyss), line_info)
+            }
+            Accessor::Field(field, line_info) => Access::Field(field, line_info),
+        });
+        let location = self.locate_var(&target.var, &target.name, chunk, line_info);
+        match location
+            .ok()
+            .and_then(|location| self.var_info(&location, &target.name))
+        {
+            Some(var_info) => Ok(value_to_result(accessed_value(&var_info.value, accesses)?)),
+            // Without such a variable, the accesses apply to the function with its name.
+            None => accesses.fold(
+                self.load(&target.name, line_info),
+                |result, access| match access {
+                    Access::Index(index, line_info) => index_value(result?, index, line_info),
+                    Access::Field(field, line_info) => field_value(result?, field, line_info),
+                },
+            ),
+        }
+    }
+
+    /// Returns the value of a variable looked up by name, or of the function with that name.
+    fn load(&self, name: &str, line_info: &Option<LineInfo>) -> Result<EvalResult, EvalError> {
+        self.locate(name, line_info)
+            .and_then(|location| {
+                self.var_info(&location, name)
+                    .map(|var_info| value_to_result(&var_info.value))
+            })
+            .or_else(|| {
+                self.env
+                    .get_function(name)
+                    .map(|function| function_value(&function))
+            })
+            .or_else(|| native_value(name, self.env))
+            .ok_or_else(|| EvalError::UndefinedVariable(name.to_string(), line_info.clone()))
+    }
+
+    /// Returns the function held by a variable, if the variable holds one.
+    fn function_in_var(&self, name: &str, line_info: &Option<LineInfo>) -> Option<Rc<Function>> {
+        let location = self.locate(name, line_info)?;
+        match &self.var_info(&location, name)?.value {
+            Value::Engrave(function) => Some(Rc::clone(function)),
+            _ => None,
+        }
+    }
+
+    /// Starts a loop, recording the state its `resume` and `eject` return to.
+    fn start_loop(&mut self, iteration: Iteration) {
+        self.loops.push(Loop {
+            iteration,
+            stack: self.stack.len(),
+            scopes: self.scopes.len(),
+            env_depth: self.env.scope_depth(),
+            handlers: self.handlers.len(),
+        });
+    }
+
+    /// Opens a scope of the current frame whose local variables start at `first_slot`, mirrored
+    /// by a scope of the environment if `env` is set.
+    fn enter_scope(&mut self, first_slot: usize, env: bool) {
+        if env {
+            self.env.push_scope();
+        }
+        self.scopes.push(Scope {
+            frame: self.frames.len() - 1,
+            first_slot,
+            env,
+        });
+    }
+
+    /// Closes the innermost scope, together with the scope of the environment mirroring it.
+    fn exit_scope(&mut self) {
+        if self.close_scope() {
+            self.env.pop_scope();
+        }
+    }
+
+    /// Closes the innermost scope and clears its local variables.
+    ///
+    /// # Returns
+    /// Whether the scope is mirrored by a scope of the environment.
+    fn close_scope(&mut self) -> bool {
+        let Some(scope) = self.scopes.pop() else {
+            return false;
+        };
+        if let Some(frame) = self.frames.get_mut(scope.frame) {
+            for slot in &mut frame.slots[scope.first_slot..] {
+                *slot = None;
+            }
+            if let Some(conditionals) = frame.conditionals.get_mut(scope.first_slot..) {
+                conditionals.fill(None);
+            }
+        }
+        scope.env
+    }
+
+    /// Calls a function defined by `engrave`: compiled functions run on the machine, while
+    /// imported functions and functions defined by the tree-walker are evaluated.
+    fn call_value(
+        &mut self,
+        function: &Rc<Function>,
+        args: Vec<EvalResult>,
+        line_info: &Option<LineInfo>,
+    ) -> Result<(), EvalError> {
+        // A function imported from a module runs in the environment of its module.
+        let compiled = match function.module {
+            Some(_) => None,
+            None => self.compiled.get(&Rc::as_ptr(&function.body)).cloned(),
+        };
+        match compiled {
+            Some(compiled) => self.call(function, compiled, args, line_info),
+            None => {
+                let result = call_engrave(function, args, self.env, line_info)?;
+                self.stack.push(result);
+                Ok(())
+            }
+        }
+    }
+
+    /// Calls a compiled function: pushes its frame, which sees the scopes of the function's
+    /// closure, and binds the arguments to its parameters.
+    fn call(
+        &mut self,
+        function: &Function,
+        compiled: Rc<CompiledFunction>,
+        args: Vec<EvalResult>,
+        line_info: &Option<LineInfo>,
+    ) -> Result<(), EvalError> {
+        let mut bindings = Vec::new();
+        for (arg, param) in args.into_iter().zip(&function.params) {
+            let (name, param_type) = param_of(function, param, line_info)?;
+            let value = bind_argument(arg, name, param_type, line_info)?;
+            bindings.push(VarInfo {
+                value,
+                var_type: param_type.clone(),
+                is_morph: false,
+                line_info: line_info.clone(),
+            });
+        }
+        self.env.push_call(&function.name, line_info)?;
+        let mut frame = Frame::new(
+            Rc::clone(&compiled.chunk),
+            Some(Rc::clone(&compiled)),
+            self.stack.len(),
+        );
+        frame.call_site = line_info.clone();
+        frame.scope_base = self.scopes.len() + 1;
+        frame.loop_base = self.loops.len();
+        self.frames.push(frame);
+        self.env.push_function_scope(function);
+        self.scopes.push(Scope {
+            frame: self.frames.len() - 1,
+            first_slot: 0,
+            env: true,
+        });
+        for (var, var_info) in compiled.params.iter().zip(bindings) {
+            self.bind(*var, &compiled.chunk, var_info);
+        }
+        Ok(())
+    }
+
+    /// Returns the value on top of the stack from the current frame.
+    ///
+    /// # Returns
+    /// The result of the program when returning from its top level, `None` otherwise.
+    fn return_from_frame(&mut self) -> Result<Option<EvalResult>, EvalError> {
+        let result = self.pop();
+        let function = match self.frames.last().and_then(|frame| frame.function.clone()) {
+            Some(function) => function,
+            None => return Ok(Some(result)),
+        };
+        self.exit_scope();
+        self.env.pop_call();
+        let call_site = match self.frames.pop() {
+            Some(frame) => {
+                self.stack.truncate(frame.stack_base);
+                self.loops.truncate(frame.loop_base);
+                frame.call_site
+            }
+            None => None,
+        };
+        let frames = self.frames.len();
+        self.handlers.retain(|handler| handler.frames <= frames);
+        self.stack
+            .push(return_value(result, &function.function, &call_site)?);
+        Ok(None)
+    }
+}
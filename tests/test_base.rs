@@ -1,9 +1,114 @@
+#![allow(dead_code)]
+
 use abyss_lang::{
+    ast::AST,
     env::Environment,
     eval::{display_error_with_source, evaluate, EvalError, EvalResult},
+    io::IoContext,
     parser::{build_ast, parse, Rule},
+    resolver::resolve_program,
+    vm,
 };
 
+/// Parses a script into its top-level statements.
+pub fn build_program(input: &str) -> Vec<AST> {
+    let pair = parse(input).expect("Failed to parse input");
+    pair.into_inner()
+        .filter(|p| p.as_rule() != Rule::EOI)
+        .map(|p| build_ast(p).expect("Failed to build AST"))
+        .collect()
+}
+
+/// Resolves a script and runs it in an environment on the given engine, as the `invoke` command
+/// does: compiled and run on the VM, or evaluated statement by statement by the tree-walker.
+/// Checks that no call is left in progress, and that a script that succeeds leaves no scope open.
+///
+/// # Returns
+/// The result of the last statement, or the error that stopped the script.
+pub fn run_in(input: &str, env: &mut Environment, use_vm: bool) -> Result<EvalResult, EvalError> {
+    let program = resolve_program(&build_program(input), env)?;
+    let depth = env.scope_depth();
+    let result = match use_vm {
+        true => vm::run(&program, env),
+        false => program
+            .iter()
+            .try_fold(EvalResult::Abyss, |_, ast| evaluate(ast, env)),
+    };
+    if result.is_ok() {
+        assert_eq!(env.scope_depth(), depth);
+    }
+    assert_eq!(env.call_depth(), 0);
+    result
+}
+
+/// What a script did on one engine: the environment it ran in, the result of its last
+/// statement or the error that stopped it, and everything it wrote to the output and to the
+/// error stream.
+pub struct Run {
+    pub env: Environment,
+    pub result: Result<EvalResult, EvalError>,
+    pub output: String,
+    pub errors: String,
+}
+
+/// Runs a script on the given engine in a new environment.
+pub fn run_on(input: &str, use_vm: bool) -> Run {
+    run_on_with(input, use_vm, "", |_| {})
+}
+
+/// Runs a script on the given engine in a new environment prepared by `setup`, answering
+/// `summon` with the lines of `stdin`.
+pub fn run_on_with(
+    input: &str,
+    use_vm: bool,
+    stdin: &str,
+    setup: impl FnOnce(&mut Environment),
+) -> Run {
+    let (io, output, errors) = IoContext::scripted(stdin);
+    let mut env = Environment::new();
+    env.set_io(io);
+    setup(&mut env);
+    let result = run_in(input, &mut env, use_vm);
+    Run {
+        env,
+        result,
+        output: output.contents(),
+        errors: errors.contents(),
+    }
+}
+
+/// Runs a script on both engines in new environments, checks that they agree and returns
+/// what it did on the tree-walker.
+pub fn run_both(input: &str) -> Run {
+    run_both_with(input, "", |_| {})
+}
+
+/// Runs a script on both engines in new environments prepared by `setup`, answering `summon`
+/// with the lines of `stdin`, checks that they agree and returns what it did on the
+/// tree-walker.
+pub fn run_both_with(input: &str, stdin: &str, setup: impl Fn(&mut Environment)) -> Run {
+    let tree = run_on_with(input, false, stdin, &setup);
+    let vm = run_on_with(input, true, stdin, &setup);
+    assert_eq!(
+        format!("{:?}", tree.result),
+        format!("{:?}", vm.result),
+        "The engines disagree"
+    );
+    assert_eq!(tree.output, vm.output, "The engines disagree");
+    assert_eq!(tree.errors, vm.errors, "The engines disagree");
+    tree
+}
+
+/// Runs a script on both engines, checks that it succeeds with the same output and returns
+/// the output.
+pub fn output_of(input: &str) -> String {
+    let run = run_both(input);
+    if let Err(e) = run.result {
+        panic!("The script should succeed: {:?}", e);
+    }
+    run.output
+}
+
 pub fn test_base(input: &str) -> Result<Vec<EvalResult>, Box<dyn std::error::Error>> {
     let mut env = Environment::new();
     match parse(input) {
@@ -19,20 +124,8 @@ pub fn test_base(input: &str) -> Result<Vec<EvalResult>, Box<dyn std::error::Err
                                     results.push(result);
                                 }
                                 Err(e) => {
-                                    let error_message = e.to_string();
-                                    match &e {
-                                        EvalError::UndefinedVariable(_, line_info)
-                                        | EvalError::InvalidOperation(_, line_info)
-                                        | EvalError::NegativeExponent(line_info)
-                                        | EvalError::TypeError(_, line_info) => {
-                                            display_error_with_source(
-                                                input,
-                                                line_info.clone(),
-                                                &error_message,
-                                            );
-                                            return Err(Box::new(e));
-                                        }
-                                    }
+                                    display_error_with_source(input, e.line_info(), &e.to_string());
+                                    return Err(Box::new(e));
                                 }
                             }
                         }
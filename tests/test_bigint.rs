@@ -0,0 +1,159 @@
+mod test_base;
+
+use abyss_lang::{
+    env::Value,
+    eval::{EvalError, EvalResult},
+};
+use test_base::{run_both_with, Run};
+
+/// Runs a script on both engines, with or without bigint mode, checks that they agree and
+/// returns what it did on the tree-walker.
+fn run_both(input: &str, bigint: bool) -> Run {
+    run_both_with(input, "170141183460469231731687303715884105727\n", |env| {
+        env.set_bigint(bigint)
+    })
+}
+
+#[test]
+fn test_bigint_arithmetic() {
+    let Run {
+        env,
+        result,
+        output,
+        ..
+    } = run_both(
+        r#"
+        forge morph factorial: arcana = 1;
+        orbit (i = 1..=30) {
+            factorial *= i;
+        };
+        unveil(factorial);
+        unveil(factorial / 1000000000000, " ", factorial % 1000000007, " ", 0 - factorial);
+        unveil(2 ^ 100, " ", (2 ^ 100) - (2 ^ 100 - 1));
+        forge small: arcana = factorial / factorial;
+        small;
+        "#,
+        true,
+    );
+
+    assert_eq!(
+        output,
+        "265252859812191058636308480000000\n\
+         265252859812191058636 109361473 -265252859812191058636308480000000\n\
+         1267650600228229401496703205376 1\n"
+    );
+    // Values that fit in 64 bits keep the fast representation.
+    assert!(matches!(result, Ok(EvalResult::Arcana(1))));
+    assert!(matches!(
+        env.global_vars().get("small").map(|var| &var.value),
+        Some(Value::Arcana(1))
+    ));
+    assert!(matches!(
+        env.global_vars().get("factorial").map(|var| &var.value),
+        Some(Value::BigArcana(_))
+    ));
+}
+
+#[test]
+fn test_bigint_comparisons_and_patterns() {
+    let Run { result, output, .. } = run_both(
+        r#"
+        forge big: arcana = 99999999999999999999;
+        unveil(big > 9223372036854775807, " ", big == 99999999999999999999, " ", 1 < big);
+        unveil(max(big, 1), " ", min(big, 0 - big), " ", abs(0 - big));
+        oracle (big) {
+            (99999999999999999999) => unveil("matched");
+            _ => unveil("missed");
+        };
+        attempt([1: "one"][big]);
+        "#,
+        true,
+    );
+
+    assert_eq!(
+        output,
+        "boon boon boon\n\
+         99999999999999999999 -99999999999999999999 99999999999999999999\n\
+         matched\n"
+    );
+    match result {
+        Ok(EvalResult::Curse(message, _)) => assert!(message.contains("does not fit in 64 bits")),
+        result => panic!("Expected a curse, found {:?}", result),
+    }
+}
+
+#[test]
+fn test_bigint_trans_and_summon() {
+    let Run { result, output, .. } = run_both(
+        r#"
+        forge parsed: arcana = trans("123456789012345678901234567890" as arcana);
+        forge read: arcana = summon("", arcana);
+        unveil(trans(parsed as rune), " ", read + 1);
+        trans(parsed as aether);
+        "#,
+        true,
+    );
+
+    assert_eq!(
+        output,
+        "123456789012345678901234567890 170141183460469231731687303715884105728\n"
+    );
+    assert!(
+        matches!(result, Ok(EvalResult::Aether(n)) if (n - 1.2345678901234568e29).abs() < 1e15)
+    );
+}
+
+#[test]
+fn test_bigint_parse_arcana_and_trans_aether() {
+    let script = r#"
+        unveil(parse_arcana("123456789012345678901234567890"));
+        unveil(attempt(trans(10.0 ** 30.0 as arcana)));
+        "#;
+    let Run { output, .. } = run_both(script, true);
+    assert_eq!(
+        output,
+        "123456789012345678901234567890\n1000000000000000019884624838656\n"
+    );
+
+    let Run { output, .. } = run_both(script, false);
+    assert_eq!(
+        output,
+        "curse(\"Arithmetic overflow: 123456789012345678901234567890 does not fit in arcana!\")\n\
+curse(\"Arithmetic overflow: 1000000000000000019884624838656 does not fit in arcana!\")\n"
+    );
+}
+
+#[test]
+fn test_bigint_rounding() {
+    let Run { result, .. } = run_both("floor(10.0 ** 30.0);", true);
+    match result {
+        Ok(EvalResult::BigArcana(n)) => {
+            assert_eq!(n.to_string(), "1000000000000000019884624838656")
+        }
+        result => panic!("Expected a bigint, found {:?}", result),
+    }
+}
+
+#[test]
+fn test_bigint_is_opt_in() {
+    let Run { result, .. } = run_both("forge big: arcana = 99999999999999999999;", false);
+    match result {
+        Err(EvalError::ArithmeticOverflow(operation, Some(line_info))) => {
+            assert_eq!(operation, "99999999999999999999");
+            assert_eq!(line_info.line, 1);
+        }
+        result => panic!("Expected an arithmetic overflow, found {:?}", result),
+    }
+
+    let Run { result, .. } = run_both(
+        r#"
+        forge morph n: arcana = 9223372036854775807;
+        n += 1;
+        "#,
+        false,
+    );
+    assert!(matches!(result, Err(EvalError::ArithmeticOverflow(_, _))));
+
+    let Run { result, .. } = run_both("trans(\"99999999999999999999\" as arcana);", false);
+    assert!(matches!(result, Err(EvalError::InvalidOperation(_, _))));
+}
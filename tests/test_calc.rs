@@ -1107,3 +1107,84 @@ fn test_aether_pow_assign() {
         Err(e) => panic!("Error: {:?}", e),
     }
 }
+
+#[test]
+fn test_arcana_overflow_error() {
+    for input in [
+        "9223372036854775807 + 1;",
+        "-9223372036854775807 - 2;",
+        "4611686018427387904 * 2;",
+        "(-9223372036854775807 - 1) / -1;",
+        "2 ^ 63;",
+        "2 ^ 4294967296;",
+    ] {
+        match test_base(input) {
+            Err(e) => match e.downcast_ref::<EvalError>() {
+                Some(EvalError::ArithmeticOverflow(_, Some(line_info))) => {
+                    assert_eq!(line_info.line, 1)
+                }
+                _ => panic!("Expected an arithmetic overflow error for {}", input),
+            },
+            Ok(_) => panic!("Expected an error for {}", input),
+        }
+    }
+}
+
+#[test]
+fn test_arcana_division_by_zero_error() {
+    for input in ["1 / 0;", "7 % (3 - 3);"] {
+        match test_base(input) {
+            Err(e) => match e.downcast_ref::<EvalError>() {
+                Some(EvalError::DivisionByZero(Some(_))) => {}
+                _ => panic!("Expected a division by zero error for {}", input),
+            },
+            Ok(_) => panic!("Expected an error for {}", input),
+        }
+    }
+}
+
+#[test]
+fn test_arcana_compound_assignment_overflow_error() {
+    let input = r#"
+        forge morph n: arcana = 9223372036854775807;
+        n -= 1;
+        n += 2;
+    "#;
+    match test_base(input) {
+        Err(e) => match e.downcast_ref::<EvalError>() {
+            Some(EvalError::ArithmeticOverflow(operation, Some(line_info))) => {
+                assert_eq!(operation, "9223372036854775806 + 2");
+                assert_eq!(line_info.line, 4);
+            }
+            _ => panic!("Expected an arithmetic overflow error"),
+        },
+        Ok(_) => panic!("Expected an error for an overflowing compound assignment"),
+    }
+
+    let input = "forge morph n: arcana = 5; n /= 0;";
+    match test_base(input) {
+        Err(e) => assert!(matches!(
+            e.downcast_ref::<EvalError>(),
+            Some(EvalError::DivisionByZero(_))
+        )),
+        Ok(_) => panic!("Expected an error for a compound division by zero"),
+    }
+}
+
+#[test]
+fn test_arcana_edge_powers() {
+    let input = "1 ^ 4294967296; -1 ^ 4294967297; 0 ^ 0;";
+    match test_base(input) {
+        Ok(results) => {
+            let values: Vec<i64> = results
+                .iter()
+                .map(|result| match result {
+                    EvalResult::Arcana(n) => *n,
+                    _ => panic!("Expected an arcana result"),
+                })
+                .collect();
+            assert_eq!(values, vec![1, -1, 1]);
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
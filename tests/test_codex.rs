@@ -0,0 +1,251 @@
+mod test_base;
+
+use abyss_lang::{
+    env::Environment,
+    eval::{evaluate, EvalError, EvalResult},
+    format::format_ast,
+    parser::{build_ast, parse, Rule},
+};
+use test_base::test_base;
+
+/// Evaluates the input and returns the first evaluation error.
+fn eval_error(input: &str) -> EvalError {
+    let mut env = Environment::new();
+    let pair = parse(input).expect("Failed to parse input");
+    for inner_pair in pair.into_inner() {
+        if inner_pair.as_rule() != Rule::EOI {
+            let ast = build_ast(inner_pair).expect("Failed to build AST");
+            if let Err(e) = evaluate(&ast, &mut env) {
+                return e;
+            }
+        }
+    }
+    panic!("Expected an evaluation error");
+}
+
+#[test]
+fn test_codex_literal_and_lookup() {
+    let input = r#"
+    forge ages: codex<rune, arcana> = ["alice": 30, "bob": 25];
+    ages["alice"] + ages["bob"];
+    ages;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            if let EvalResult::Arcana(n) = results[1] {
+                assert_eq!(n, 55);
+            } else {
+                panic!("Expected Arcana result");
+            }
+            assert_eq!(results[2].to_string(), r#"["alice": 30, "bob": 25]"#);
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_codex_insertion_and_update() {
+    let input = r#"
+    forge morph counts: codex<arcana, arcana> = [:];
+    counts[3] = 1;
+    counts[1] = 10;
+    counts[3] += 4;
+    counts[1] *= 2;
+    counts;
+    len(counts);
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            assert_eq!(results[5].to_string(), "[1: 20, 3: 5]");
+            if let EvalResult::Arcana(n) = results[6] {
+                assert_eq!(n, 2);
+            } else {
+                panic!("Expected Arcana result");
+            }
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_codex_nested_in_grimoire() {
+    let input = r#"
+    forge morph tags: codex<rune, grimoire<rune>> = ["fire": ["hot"]];
+    tags["ice"] = ["cold"];
+    tags["fire"][0] = "burning";
+    tags;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => assert_eq!(
+            results[3].to_string(),
+            r#"["fire": ["burning"], "ice": ["cold"]]"#
+        ),
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_orbit_over_codex() {
+    let input = r#"
+    forge prices: codex<rune, arcana> = ["apple": 3, "pear": 5];
+    forge morph total: arcana = 0;
+    forge morph names: rune = "";
+    orbit ((name, price) = prices) {
+        total += price;
+    };
+    orbit (name = prices) {
+        names += name;
+    };
+    total;
+    names;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            if let EvalResult::Arcana(n) = results[5] {
+                assert_eq!(n, 8);
+            } else {
+                panic!("Expected Arcana result");
+            }
+            if let EvalResult::Rune(s) = &results[6] {
+                assert_eq!(s, "applepear");
+            } else {
+                panic!("Expected Rune result");
+            }
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_orbit_over_grimoire_with_position() {
+    let input = r#"
+    forge xs: grimoire<arcana> = [10, 20, 30];
+    forge morph sum: arcana = 0;
+    orbit ((i, x) = xs) {
+        sum += i * x;
+    };
+    sum;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            if let EvalResult::Arcana(n) = results[3] {
+                assert_eq!(n, 80);
+            } else {
+                panic!("Expected Arcana result");
+            }
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_codex_has() {
+    let input = r#"
+    forge ages: codex<rune, arcana> = ["alice": 30];
+    has(ages, "alice");
+    has(ages, "bob");
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            assert!(matches!(results[1], EvalResult::Omen(true)));
+            assert!(matches!(results[2], EvalResult::Omen(false)));
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_codex_missing_key_lookup() {
+    let input = r#"
+    forge ages: codex<rune, arcana> = ["alice": 30];
+    unveil(ages["bob"]);
+    "#;
+
+    match eval_error(input) {
+        EvalError::KeyNotFound(key, line_info) => {
+            assert_eq!(key, r#""bob""#);
+            let line_info = line_info.unwrap();
+            assert_eq!(line_info.line, 3);
+            assert_eq!(line_info.column, 16);
+        }
+        e => panic!("Expected KeyNotFound, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_codex_missing_key_update() {
+    let input = r#"
+    forge morph counts: codex<arcana, arcana> = [:];
+    counts[7] += 1;
+    "#;
+
+    match eval_error(input) {
+        EvalError::KeyNotFound(key, line_info) => {
+            assert_eq!(key, "7");
+            assert_eq!(line_info.unwrap().column, 11);
+        }
+        e => panic!("Expected KeyNotFound, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_codex_key_type_mismatch() {
+    let input = r#"
+    forge morph ages: codex<rune, arcana> = [:];
+    ages[1] = 2;
+    "#;
+
+    assert!(matches!(eval_error(input), EvalError::TypeError(_, _)));
+}
+
+#[test]
+fn test_codex_duplicate_key() {
+    let input = r#"
+    forge ages: codex<rune, arcana> = ["alice": 30, "bob": 25, "alice": 31];
+    "#;
+
+    match eval_error(input) {
+        EvalError::InvalidOperation(message, line_info) => {
+            assert_eq!(message, r#"Duplicate key "alice" in codex literal"#);
+            assert_eq!(line_info.unwrap().line, 2);
+        }
+        e => panic!("Expected an invalid operation, got {:?}", e),
+    }
+
+    // A key computed at run time is rejected too.
+    let input = r#"
+    forge n: arcana = 1;
+    forge squares: codex<arcana, arcana> = [1: 1, n: 1];
+    "#;
+
+    assert!(matches!(
+        eval_error(input),
+        EvalError::InvalidOperation(_, _)
+    ));
+}
+
+#[test]
+fn test_codex_invalid_key_type() {
+    assert!(parse("forge m: codex<aether, arcana> = [:];").is_err());
+}
+
+#[test]
+fn test_format_codex() {
+    let input = r#"forge morph m: codex<rune, grimoire<arcana>> = ["a": [1], "b": []];
+forge empty: codex<arcana, rune> = [:];
+m["a"][0] += 1;"#;
+
+    let pair = parse(input).expect("Failed to parse input");
+    let formatted: Vec<String> = pair
+        .into_inner()
+        .filter(|p| p.as_rule() != Rule::EOI)
+        .map(|p| format_ast(&build_ast(p).expect("Failed to build AST"), 0))
+        .collect();
+    assert_eq!(formatted.join("\n"), input);
+}
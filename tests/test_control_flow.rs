@@ -0,0 +1,141 @@
+mod test_base;
+
+use abyss_lang::{
+    eval::EvalError,
+    interpreter::{AbyssError, Interpreter},
+};
+use test_base::output_of;
+
+/// Resolves and runs a script with the interpreter, returning the error it reports.
+fn error_of(input: &str) -> String {
+    match Interpreter::new().eval_str(input) {
+        Err(AbyssError::Eval(e @ EvalError::InvalidOperation(_, _))) => e.to_string(),
+        result => panic!("Expected an invalid operation, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_reveal_returns_from_function() {
+    let output = output_of(
+        r#"engrave first_even(limit: arcana) -> arcana {
+    orbit (i = 1..limit) {
+        orbit (j = 0..3) {
+            oracle (i % 2 == 0) {
+                (boon) => reveal i * 10 + j;
+            };
+        };
+    };
+    reveal -1;
+};
+engrave sign(n: arcana) -> rune {
+    forge label: rune = oracle (n > 0) {
+        (boon) => reveal "positive";
+        (hex) => "other";
+    };
+    reveal label + "!";
+};
+engrave guarded() -> arcana {
+    forge r: cursed<arcana> = attempt(oracle {
+        (boon) => reveal 7;
+    });
+    reveal 0;
+};
+unveil(first_even(5), " ", first_even(1));
+unveil(sign(3), " ", sign(-3));
+unveil(guarded());
+"#,
+    );
+    assert_eq!(output, "20 -1\npositive other!\n7\n");
+}
+
+#[test]
+fn test_labelled_resume_and_eject() {
+    let output = output_of(
+        r#"orbit (i = 0..3) {
+    orbit (j = 0..3) {
+        oracle (j == 1) {
+            (boon) => resume i;
+        };
+        unveil(i, " ", j);
+    };
+};
+orbit (i = 0..3, j = 0..3) {
+    oracle {
+        (j == 2) => eject;
+        (i == 2) => eject i;
+    };
+    unveil(i, j);
+};
+forge morph n: arcana = 0;
+orbit {
+    orbit (k = 0..10) {
+        n += 1;
+        oracle (k == 2) {
+            (boon) => eject;
+        };
+    };
+    oracle (n > 5) {
+        (boon) => eject;
+    };
+};
+unveil(n);
+"#,
+    );
+    assert_eq!(output, "0 0\n1 0\n2 0\n00\n01\n10\n11\n6\n");
+}
+
+#[test]
+fn test_control_leaves_nested_scopes() {
+    // Values and scopes opened inside the loop are discarded when jumping out of it.
+    let output = output_of(
+        r#"engrave total(xs: grimoire<arcana>) -> arcana {
+    forge morph sum: arcana = 0;
+    orbit (x = xs) {
+        forge doubled: arcana = x * 2;
+        forge step: arcana = oracle (x) {
+            (0) => {
+                forge skipped: arcana = 1;
+                resume;
+            }
+            (9) => eject x;
+            _ => doubled;
+        };
+        sum += 1 + step;
+    };
+    reveal sum;
+};
+unveil(total([1, 0, 2, 9, 5]));
+orbit (i = 0..3) {
+    forge r: cursed<arcana> = attempt(oracle {
+        (i == 1) => eject;
+        _ => i;
+    });
+    unveil(r);
+};
+forge e: cursed<arcana> = attempt(1 / 0);
+unveil(e);
+"#,
+    );
+    assert_eq!(output, "8\n0\ncurse(\"Division by zero!\")\n");
+}
+
+#[test]
+fn test_misplaced_control_is_rejected() {
+    assert_eq!(
+        error_of("forge x: arcana = 1;\nreveal x;"),
+        "Invalid operation: reveal can only be used inside an engrave function"
+    );
+    assert_eq!(
+        error_of("oracle (boon) {\n    (boon) => eject;\n};"),
+        "Invalid operation: eject can only be used inside an orbit"
+    );
+    assert_eq!(
+        error_of("orbit (i = 0..3) {\n    orbit (j = 0..3) {\n        resume k;\n    };\n};"),
+        "Invalid operation: resume k does not name an enclosing orbit"
+    );
+    // A loop does not extend into the functions engraved in it.
+    assert_eq!(
+        error_of("orbit (i = 0..3) {\n    engrave f() -> abyss {\n        eject i;\n    };\n};"),
+        "Invalid operation: eject i does not name an enclosing orbit"
+    );
+}
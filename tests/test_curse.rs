@@ -0,0 +1,226 @@
+mod test_base;
+
+use abyss_lang::{
+    env::Environment,
+    eval::{evaluate, EvalError, EvalResult},
+    format::format_ast,
+    parser::{build_ast, parse, Rule},
+};
+use test_base::{run_both, test_base};
+
+/// Evaluates the input and returns the first evaluation error.
+fn eval_error(input: &str) -> EvalError {
+    let mut env = Environment::new();
+    let pair = parse(input).expect("Failed to parse input");
+    for inner_pair in pair.into_inner() {
+        if inner_pair.as_rule() != Rule::EOI {
+            let ast = build_ast(inner_pair).expect("Failed to build AST");
+            if let Err(e) = evaluate(&ast, &mut env) {
+                return e;
+            }
+        }
+    }
+    panic!("Expected an evaluation error");
+}
+
+#[test]
+fn test_attempt_catches_error() {
+    let input = r#"
+    forge bad: cursed<arcana> = attempt(trans("abc" as arcana));
+    forge good: cursed<arcana> = attempt(trans("42" as arcana));
+    bad;
+    good;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            match &results[2] {
+                EvalResult::Curse(message, line_info) => {
+                    assert!(message.contains("Failed to convert Rune to Arcana"));
+                    assert_eq!(line_info.clone().unwrap().line, 2);
+                }
+                result => panic!("Expected Curse result, found {:?}", result),
+            }
+            assert!(matches!(results[3], EvalResult::Arcana(42)));
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_oracle_curse_pattern() {
+    let input = r#"
+    engrave parse(s: rune) -> cursed<arcana> {
+        reveal attempt(trans(s as arcana));
+    };
+    engrave describe(s: rune) -> rune {
+        reveal oracle (parse(s)) {
+            (curse(message)) => "cursed";
+            (0) => "zero";
+            _ => "number";
+        };
+    };
+    describe("abc");
+    describe("0");
+    describe("7");
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            let described: Vec<String> = results[2..].iter().map(|r| r.to_string()).collect();
+            assert_eq!(described, vec!["cursed", "zero", "number"]);
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_oracle_curse_message_binding() {
+    let input = r#"
+    forge r: cursed<arcana> = curse("out of mana");
+    oracle (r) {
+        (curse("out of time")) => "time";
+        (curse(message)) => message;
+    };
+    "#;
+
+    match test_base(input) {
+        Ok(results) => assert_eq!(results[1].to_string(), "out of mana"),
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_curse_from_engrave() {
+    let input = r#"
+    engrave half(n: arcana) -> arcana {
+        oracle (n % 2 == 1) {
+            (boon) => reveal curse("odd number");
+            (hex) => reveal n / 2;
+        };
+    };
+    forge caught: cursed<arcana> = attempt(half(3));
+    caught;
+    forge morph total: arcana = 0;
+    total = half(8);
+    total;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            assert_eq!(results[2].to_string(), r#"curse("odd number")"#);
+            assert!(matches!(results[5], EvalResult::Arcana(4)));
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_uncaught_curse() {
+    let input = r#"
+    engrave fail() -> arcana {
+        reveal curse("the seal is broken");
+    };
+    forge x: arcana = fail();
+    "#;
+
+    match eval_error(input) {
+        EvalError::Curse(message, line_info) => {
+            assert_eq!(message, "the seal is broken");
+            let line_info = line_info.unwrap();
+            assert_eq!(line_info.line, 3);
+            assert_eq!(line_info.column, 16);
+        }
+        e => panic!("Expected Curse, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_unwrapping_curse_raises() {
+    let input = r#"
+    forge r: cursed<arcana> = attempt(trans("x" as arcana));
+    forge n: arcana = r;
+    "#;
+
+    match eval_error(input) {
+        EvalError::Curse(message, line_info) => {
+            assert!(message.contains("Failed to convert"));
+            assert_eq!(line_info.unwrap().line, 2);
+        }
+        e => panic!("Expected Curse, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_curse_in_statement_position_is_raised() {
+    let scripts = [
+        ("curse(\"top\");\nunveil(\"unreachable\");", 1),
+        (
+            "engrave risky() -> cursed<arcana> {\n    reveal curse(\"risky\");\n};\nrisky();\nunveil(\"unreachable\");",
+            2,
+        ),
+        (
+            "engrave run() {\n    curse(\"inner\");\n    unveil(\"unreachable\");\n};\nrun();",
+            2,
+        ),
+        ("orbit (i = 0..3) {\n    curse(\"loop\");\n};", 2),
+    ];
+    for (script, line) in scripts {
+        let run = run_both(script);
+        match run.result {
+            Err(EvalError::Curse(_, Some(line_info))) => assert_eq!(line_info.line, line),
+            result => panic!("Expected a curse, found {:?}", result),
+        }
+        assert_eq!(run.output, "");
+    }
+}
+
+#[test]
+fn test_caught_curse_in_statement_position_is_dropped() {
+    let run = run_both(
+        r#"
+    engrave risky() -> cursed<arcana> {
+        reveal curse("risky");
+    };
+    attempt(risky());
+    forge stored: cursed<arcana> = risky();
+    stored;
+    orbit (i = 0..2) {
+        attempt(risky());
+    };
+    unveil("done");
+    "#,
+    );
+    assert!(run.result.is_ok());
+    assert_eq!(run.output, "done\n");
+}
+
+#[test]
+fn test_attempt_restores_scopes() {
+    let input = r#"
+    engrave fail(secret: arcana) -> arcana {
+        reveal missing;
+    };
+    attempt(fail(1));
+    secret;
+    "#;
+
+    match eval_error(input) {
+        EvalError::UndefinedVariable(name, _) => assert_eq!(name, "secret"),
+        e => panic!("Expected UndefinedVariable, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_format_curse() {
+    let input = r#"forge r: cursed<grimoire<arcana>> = attempt(load("spells"));
+forge c: cursed<rune> = curse("no spells");"#;
+
+    let pair = parse(input).expect("Failed to parse input");
+    let formatted: Vec<String> = pair
+        .into_inner()
+        .filter(|p| p.as_rule() != Rule::EOI)
+        .map(|p| format_ast(&build_ast(p).expect("Failed to build AST"), 0))
+        .collect();
+    assert_eq!(formatted.join("\n"), input);
+}
@@ -0,0 +1,300 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+const SCRIPT: &str = r#"forge morph total: arcana = 0;
+engrave add(n: arcana) -> arcana {
+    total += n;
+    reveal total * 2;
+};
+orbit (i = 1..=3) {
+    forge doubled: arcana = add(i);
+    unveil(doubled);
+};
+unveil(total);
+"#;
+
+/// A minimal Debug Adapter Protocol client talking to `abyss invoke --dap` over stdio.
+struct Client {
+    adapter: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    seq: u64,
+    events: Vec<Value>,
+}
+
+impl Client {
+    /// Writes a script to a temporary file and starts the adapter for it.
+    fn start(name: &str, script: &str) -> (Self, PathBuf) {
+        let path = std::env::temp_dir().join(format!("abyss_{}_{}.aby", name, std::process::id()));
+        fs::write(&path, script).unwrap();
+        let mut adapter = Command::new(env!("CARGO_BIN_EXE_abyss"))
+            .arg("invoke")
+            .arg("--dap")
+            .arg(&path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to start the debug adapter");
+        let input = adapter.stdin.take().unwrap();
+        let output = BufReader::new(adapter.stdout.take().unwrap());
+        let client = Client {
+            adapter,
+            input,
+            output,
+            seq: 0,
+            events: Vec::new(),
+        };
+        (client, path)
+    }
+
+    /// Reads a message from the adapter.
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            self.output.read_line(&mut header).unwrap();
+            match header.trim_end().strip_prefix("Content-Length: ") {
+                Some(value) => length = value.parse().unwrap(),
+                None if header.trim_end().is_empty() => break,
+                None => {}
+            }
+        }
+        let mut body = vec![0; length];
+        self.output.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Sends a request and waits for its response, keeping the events received meanwhile.
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let seq = self.seq;
+        let body = json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.input.flush().unwrap();
+        loop {
+            let message = self.receive();
+            if message["type"] == "response" && message["request_seq"] == json!(seq) {
+                assert_eq!(message["command"], command);
+                return message;
+            }
+            self.events.push(message);
+        }
+    }
+
+    /// Waits for an event, returning its body and keeping the other events received meanwhile.
+    fn event(&mut self, name: &str) -> Value {
+        loop {
+            if let Some(index) = self
+                .events
+                .iter()
+                .position(|message| message["event"] == name)
+            {
+                return self.events.remove(index)["body"].clone();
+            }
+            let message = self.receive();
+            self.events.push(message);
+        }
+    }
+
+    /// Takes everything the script wrote to a category of output so far.
+    fn output(&mut self, category: &str) -> String {
+        let mut output = String::new();
+        self.events.retain(|message| {
+            let is_output = message["event"] == "output" && message["body"]["category"] == category;
+            if is_output {
+                output.push_str(message["body"]["output"].as_str().unwrap());
+            }
+            !is_output
+        });
+        output
+    }
+
+    /// Returns the names and lines of the frames of the paused script, from the innermost.
+    fn frames(&mut self) -> Vec<(String, u64)> {
+        let trace = self.request("stackTrace", json!({ "threadId": 1 }));
+        trace["body"]["stackFrames"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|frame| {
+                let name = frame["name"].as_str().unwrap().to_string();
+                (name, frame["line"].as_u64().unwrap())
+            })
+            .collect()
+    }
+
+    /// Returns the variables of a container as `name: type = value`.
+    fn variables(&mut self, reference: &Value) -> Vec<String> {
+        let variables = self.request("variables", json!({ "variablesReference": reference }));
+        variables["body"]["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|var| {
+                let name = var["name"].as_str().unwrap();
+                format!(
+                    "{}: {} = {}",
+                    name,
+                    var["type"].as_str().unwrap(),
+                    var["value"].as_str().unwrap()
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the local and global variables of a frame of the paused script.
+    fn scopes(&mut self, frame: u64) -> (Vec<String>, Vec<String>) {
+        let scopes = self.request("scopes", json!({ "frameId": frame }));
+        let scopes = scopes["body"]["scopes"].clone();
+        assert_eq!(scopes[0]["name"], "Locals");
+        assert_eq!(scopes[1]["name"], "Globals");
+        (
+            self.variables(&scopes[0]["variablesReference"]),
+            self.variables(&scopes[1]["variablesReference"]),
+        )
+    }
+
+    /// Ends the session and checks that the adapter exits.
+    fn disconnect(mut self, path: PathBuf) {
+        assert_eq!(self.request("disconnect", json!({}))["success"], true);
+        assert!(self.adapter.wait().unwrap().success());
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn test_dap_breakpoints_and_steps() {
+    let (mut client, path) = Client::start("dap_steps", SCRIPT);
+    let initialize = client.request("initialize", json!({ "adapterID": "abyss" }));
+    assert_eq!(initialize["body"]["supportsConfigurationDoneRequest"], true);
+    client.event("initialized");
+    client.request("launch", json!({ "program": path }));
+    let breakpoints = client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": path }, "breakpoints": [{ "line": 7 }] }),
+    );
+    assert_eq!(breakpoints["body"]["breakpoints"][0]["verified"], true);
+    client.request("configurationDone", json!({}));
+
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["reason"], "breakpoint");
+    assert_eq!(client.frames(), vec![("<script>".to_string(), 7)]);
+    let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(
+        trace["body"]["stackFrames"][0]["source"]["path"],
+        json!(path.canonicalize().unwrap())
+    );
+    let (locals, globals) = client.scopes(0);
+    assert_eq!(locals, vec!["i: arcana = 1"]);
+    assert_eq!(globals, vec!["total: arcana = 0"]);
+
+    // Stepping into `add` pauses at its first statement, with the call below it.
+    client.request("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    assert_eq!(
+        client.frames(),
+        vec![("add".to_string(), 3), ("<script>".to_string(), 7)]
+    );
+    assert_eq!(client.scopes(0).0, vec!["n: arcana = 1"]);
+    assert_eq!(client.scopes(1).0, vec!["i: arcana = 1"]);
+    let evaluate = client.request("evaluate", json!({ "expression": "n + 41", "frameId": 0 }));
+    assert_eq!(evaluate["body"]["result"], "42");
+
+    // Stepping out finishes the call and pauses at the next statement of the script.
+    client.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    assert_eq!(client.frames(), vec![("<script>".to_string(), 8)]);
+    assert_eq!(
+        client.scopes(0).0,
+        vec!["i: arcana = 1", "doubled: arcana = 2"]
+    );
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+    assert_eq!(client.output("stdout"), "2\n");
+    client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": path }, "breakpoints": [] }),
+    );
+    client.request("continue", json!({ "threadId": 1 }));
+
+    assert_eq!(client.event("exited")["exitCode"], 0);
+    client.event("terminated");
+    assert_eq!(client.output("stdout"), "6\n12\n6\n");
+    client.disconnect(path);
+}
+
+#[test]
+fn test_dap_entry_values_and_errors() {
+    let script = "forge xs: grimoire<arcana> = [10, 20];\nforge n: arcana = xs[0] / 0;\n";
+    let (mut client, path) = Client::start("dap_errors", script);
+    client.request("initialize", json!({ "linesStartAt1": false }));
+    client.request("launch", json!({ "program": path, "stopOnEntry": true }));
+    client.request("configurationDone", json!({}));
+
+    // Lines are numbered from zero, as the client asked.
+    assert_eq!(client.event("stopped")["reason"], "entry");
+    assert_eq!(client.frames(), vec![("<script>".to_string(), 0)]);
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.frames(), vec![("<script>".to_string(), 1)]);
+
+    // A grimoire can be expanded into its elements.
+    let scopes = client.request("scopes", json!({ "frameId": 0 }));
+    let globals = scopes["body"]["scopes"][1]["variablesReference"].clone();
+    let variables = client.request("variables", json!({ "variablesReference": globals }));
+    let xs = variables["body"]["variables"][0].clone();
+    assert_eq!(xs["name"], "xs");
+    assert_eq!(xs["type"], "grimoire<arcana>");
+    assert_eq!(
+        client.variables(&xs["variablesReference"]),
+        vec!["0: arcana = 10", "1: arcana = 20"]
+    );
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("exited")["exitCode"], 1);
+    assert!(client.output("stderr").contains("Division by zero"));
+    client.disconnect(path);
+}
+
+#[test]
+fn test_dap_breakpoints_on_lines_without_statements() {
+    let script = "forge morph total: arcana = 0;\n\n// Add one\ntotal += 1;\nunveil(total);\n\n";
+    let (mut client, path) = Client::start("dap_blank_lines", script);
+    client.request("initialize", json!({ "adapterID": "abyss" }));
+    client.event("initialized");
+    client.request("launch", json!({ "program": path }));
+    let breakpoints = client.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": path },
+            "breakpoints": [{ "line": 2 }, { "line": 6 }],
+        }),
+    );
+
+    // The breakpoint on the blank line moves to the statement after the comment, and the one
+    // after the last statement cannot be set.
+    let breakpoints = breakpoints["body"]["breakpoints"].clone();
+    assert_eq!(breakpoints[0]["verified"], true);
+    assert_eq!(breakpoints[0]["line"], 4);
+    assert_eq!(breakpoints[1]["verified"], false);
+    assert_eq!(breakpoints[1]["line"], 6);
+    assert!(breakpoints[1]["message"].is_string());
+    client.request("configurationDone", json!({}));
+
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+    assert_eq!(client.frames(), vec![("<script>".to_string(), 4)]);
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("exited")["exitCode"], 0);
+    client.event("terminated");
+    assert_eq!(client.output("stdout"), "1\n");
+    client.disconnect(path);
+}
@@ -0,0 +1,207 @@
+mod test_base;
+
+use abyss_lang::{
+    ast::{LineInfo, AST},
+    debugger::{Debugger, EvalHook},
+    env::{Environment, Function},
+    eval::{evaluate, EvalError, EvalResult},
+    io::IoContext,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use test_base::build_program;
+
+const SCRIPT: &str = r#"forge morph total: arcana = 0;
+engrave add(n: arcana) -> arcana {
+    total += n;
+    reveal total * 2;
+};
+orbit (i = 1..=3) {
+    forge doubled: arcana = add(i);
+    unveil(doubled);
+};
+unveil(total);
+"#;
+
+/// Runs a script with a hook, returning the result of the last statement and everything
+/// written to the output.
+fn run_with_hook(
+    input: &str,
+    hook: Rc<RefCell<dyn EvalHook>>,
+    commands: &str,
+) -> (Result<EvalResult, EvalError>, String) {
+    let (io, output, _) = IoContext::scripted(commands);
+    let mut env = Environment::new();
+    env.set_io(io);
+    env.set_hook(Some(hook));
+    let result = build_program(input)
+        .iter()
+        .try_fold(EvalResult::Abyss, |_, ast| evaluate(ast, &mut env));
+    (result, output.contents())
+}
+
+/// Runs a script in the debugger, answering its prompts with the given commands.
+fn debug(input: &str, commands: &str) -> (Result<EvalResult, EvalError>, String) {
+    run_with_hook(input, Rc::new(RefCell::new(Debugger::new(None))), commands)
+}
+
+#[test]
+fn test_debugger_breakpoints_and_variables() {
+    let (result, output) = debug(
+        SCRIPT,
+        "break 8\ncontinue\nvars\nprint doubled + 1\nset total = 100\nset total = \"x\"\ndelete 8\ncontinue\n",
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(
+        output,
+        "Paused at line 1: forge morph total: arcana = 0;\n\
+         (debug) Breakpoint set at line 8\n\
+         (debug) Paused at line 8: unveil(doubled);\n\
+         (debug) Scope 1:\n  morph i: arcana = 1\n  doubled: arcana = 2\n\
+         Globals:\n  morph total: arcana = 1\n\
+         (debug) 3\n\
+         (debug) total = 100\n\
+         (debug) Type mismatch: total is of type arcana\n\
+         (debug) Breakpoint removed from line 8\n\
+         (debug) 2\n204\n210\n105\n"
+    );
+}
+
+#[test]
+fn test_debugger_vars_follow_lexical_scopes() {
+    let (result, output) = debug(SCRIPT, "break 3\ncontinue\nvars\nquit\n");
+
+    // The loop variable of the caller is not visible inside `add`.
+    assert!(matches!(result, Err(EvalError::Halted(_, _))));
+    assert_eq!(
+        output,
+        "Paused at line 1: forge morph total: arcana = 0;\n\
+         (debug) Breakpoint set at line 3\n\
+         (debug) Paused at line 3: total += n;\n\
+         (debug) Scope 1:\n  n: arcana = 1\n\
+         Globals:\n  morph total: arcana = 0\n\
+         (debug) "
+    );
+
+    let script = r#"orbit (i = 1..2) {
+    engrave show(n: arcana) {
+        unveil(n + i);
+    };
+    show(10);
+};
+"#;
+    let (_, output) = debug(script, "break 3\ncontinue\nvars\nquit\n");
+    assert!(output.contains("Scope 2:\n  n: arcana = 10\nScope 1:\n  morph i: arcana = 1\n"));
+}
+
+#[test]
+fn test_debugger_steps_and_backtrace() {
+    let (result, output) = debug(
+        SCRIPT,
+        "next\nnext\nstep\nstep\nbacktrace\nfinish\nnext\nnext\nnext\nstep\nbacktrace\nquit\n",
+    );
+
+    assert!(matches!(result, Err(EvalError::Halted(_, _))));
+    assert_eq!(
+        output,
+        "Paused at line 1: forge morph total: arcana = 0;\n\
+         (debug) Paused at line 2: engrave add(n: arcana) -> arcana {\n\
+         (debug) Paused at line 6: orbit (i = 1..=3){\n\
+         (debug) Paused at line 7: forge doubled: arcana = add(i);\n\
+         (debug) Paused at line 3: total += n;\n\
+         (debug) #0 add at line 3\n#1 <script> at line 7\n\
+         (debug) Paused at line 8: unveil(doubled);\n\
+         (debug) 2\nPaused at line 7: forge doubled: arcana = add(i);\n\
+         (debug) Paused at line 8: unveil(doubled);\n\
+         (debug) 6\nPaused at line 7: forge doubled: arcana = add(i);\n\
+         (debug) Paused at line 3: total += n;\n\
+         (debug) #0 add at line 3\n#1 <script> at line 7\n\
+         (debug) "
+    );
+}
+
+#[test]
+fn test_debugger_halt_is_not_caught() {
+    let (result, output) = debug(
+        "engrave f() -> arcana {\n    reveal 1;\n};\nattempt(f());\nunveil(2);\n",
+        "break 2\ncontinue\nquit\n",
+    );
+
+    // `attempt` cannot turn stopping the script into a curse.
+    assert!(matches!(result, Err(EvalError::Halted(_, _))));
+    assert_eq!(
+        output,
+        "Paused at line 1: engrave f() -> arcana {\n\
+         (debug) Breakpoint set at line 2\n\
+         (debug) Paused at line 2: reveal 1;\n\
+         (debug) "
+    );
+
+    // Without commands left, the script runs to its end.
+    let (result, output) = debug("unveil(1);\nunveil(2);\n", "");
+    assert!(result.is_ok());
+    assert_eq!(output, "Paused at line 1: unveil(1);\n(debug) \n1\n2\n");
+}
+
+/// A hook recording the lines of the statements and the calls it is notified of.
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+}
+
+impl EvalHook for Recorder {
+    fn on_statement(&mut self, statement: &AST, _env: &mut Environment) -> Result<(), EvalError> {
+        if let AST::Statement(_, Some(line_info)) = statement {
+            self.events.push(format!("line {}", line_info.line));
+        }
+        Ok(())
+    }
+
+    fn on_call(
+        &mut self,
+        function: &Function,
+        _line_info: &Option<LineInfo>,
+        env: &mut Environment,
+    ) -> Result<(), EvalError> {
+        let n = env.get_var("n").map(|var| format!("{:?}", var.value));
+        self.events
+            .push(format!("call {} {}", function.name, n.unwrap_or_default()));
+        Ok(())
+    }
+
+    fn on_return(&mut self, function: &Function, _env: &mut Environment) {
+        self.events.push(format!("return {}", function.name));
+    }
+}
+
+#[test]
+fn test_eval_hook() {
+    let recorder = Rc::new(RefCell::new(Recorder::default()));
+    let (result, output) = run_with_hook(
+        r#"
+        engrave add(n: arcana) -> arcana {
+            reveal n + 1;
+        };
+        unveil(add(add(1)));
+        "#,
+        recorder.clone(),
+        "",
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(output, "3\n");
+    assert_eq!(
+        recorder.borrow().events,
+        vec![
+            "line 2",
+            "line 5",
+            "call add Arcana(1)",
+            "line 3",
+            "return add",
+            "call add Arcana(2)",
+            "line 3",
+            "return add",
+        ]
+    );
+}
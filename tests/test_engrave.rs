@@ -157,6 +157,7 @@ fn test_function_with_no_arguments() {
 }
 
 #[test]
+#[allow(clippy::identity_op)]
 fn test_function_with_recursive_calls() {
     let input = r#"
     engrave factorial(n: arcana) -> arcana {
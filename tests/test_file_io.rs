@@ -0,0 +1,113 @@
+use abyss_lang::{
+    env::Environment,
+    eval::{evaluate, EvalError, EvalResult},
+    parser::{build_ast, parse, Rule},
+};
+use std::fs;
+use std::path::PathBuf;
+
+/// Evaluates the input with or without file access and returns the result of every statement.
+fn run(input: &str, allow_files: bool) -> Result<Vec<EvalResult>, EvalError> {
+    let mut env = Environment::new();
+    env.set_file_access(allow_files);
+    let pair = parse(input).expect("Failed to parse input");
+    pair.into_inner()
+        .filter(|p| p.as_rule() != Rule::EOI)
+        .map(|p| evaluate(&build_ast(p).expect("Failed to build AST"), &mut env))
+        .collect()
+}
+
+/// Returns a path in the temporary directory that does not exist yet.
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("abyss_{}_{}.txt", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn test_write_append_and_read() {
+    let path = temp_path("write_append");
+    let input = format!(
+        r#"
+        forge path: rune = "{}";
+        exists(path);
+        write(path, "first\n");
+        append(path, "second\n");
+        exists(path);
+        read(path);
+        "#,
+        path.display()
+    );
+
+    match run(&input, true) {
+        Ok(results) => {
+            assert!(matches!(results[1], EvalResult::Omen(false)));
+            assert!(matches!(results[4], EvalResult::Omen(true)));
+            assert_eq!(results[5].to_string(), "first\nsecond\n");
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+}
+
+#[test]
+fn test_orbit_over_read_lines() {
+    let path = temp_path("read_lines");
+    fs::write(&path, "3\n4\n5\n").unwrap();
+    let input = format!(
+        r#"
+        forge morph total: arcana = 0;
+        orbit (line = read_lines("{}")) {{
+            total += trans(line as arcana);
+        }};
+        total;
+        "#,
+        path.display()
+    );
+
+    match run(&input, true) {
+        Ok(results) => assert!(matches!(results[2], EvalResult::Arcana(12))),
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_read_missing_file() {
+    let path = temp_path("missing");
+    let input = format!("\nread(\"{}\");", path.display());
+
+    match run(&input, true) {
+        Err(EvalError::IoError(message, line_info)) => {
+            assert!(message.contains(&path.display().to_string()));
+            assert_eq!(line_info.unwrap().line, 2);
+        }
+        result => panic!("Expected IoError, found {:?}", result),
+    }
+}
+
+#[test]
+fn test_file_access_forbidden_by_default() {
+    let path = temp_path("forbidden");
+    let input = format!(r#"write("{}", "secret");"#, path.display());
+
+    match run(&input, false) {
+        Err(EvalError::IoError(message, _)) => assert!(message.contains("--allow-files")),
+        result => panic!("Expected IoError, found {:?}", result),
+    }
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_attempt_read() {
+    let path = temp_path("attempt_read");
+    let input = format!(
+        r#"forge contents: cursed<rune> = attempt(read("{}")); contents;"#,
+        path.display()
+    );
+
+    match run(&input, true) {
+        Ok(results) => assert!(matches!(&results[1], EvalResult::Curse(message, _)
+            if message.starts_with("I/O error"))),
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
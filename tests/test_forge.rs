@@ -41,6 +41,7 @@ fn test_forge_morph_and_usage() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_forge_redeclaration_with_different_type() {
     let input = "
         forge x: arcana = 42;
@@ -94,6 +95,7 @@ fn test_forge_morph_reassign_arcana() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_forge_morph_reassign_aether() {
     let input = "
         forge morph x: aether = 3.14;
@@ -113,6 +115,7 @@ fn test_forge_morph_reassign_aether() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_forge_morph_reassign_omen() {
     let input = "
         forge morph x: omen = boon;
@@ -181,6 +184,7 @@ fn test_forge_morph_different_type_assignment() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_forge_boolean_logic() {
     let input = "
         forge b: omen = boon;
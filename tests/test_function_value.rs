@@ -0,0 +1,207 @@
+mod test_base;
+
+use abyss_lang::{eval::EvalError, format::format_ast, typeck::scrutinize};
+use test_base::{build_program, output_of, run_both, run_on};
+
+#[test]
+fn test_functions_as_values() {
+    let output = output_of(
+        r#"engrave apply(f: engrave(arcana) -> arcana, x: arcana) -> arcana {
+    reveal f(x);
+};
+engrave double(n: arcana) -> arcana {
+    reveal n * 2;
+};
+engrave make_adder(k: arcana) -> engrave(arcana) -> arcana {
+    reveal engrave(n: arcana) -> arcana {
+        reveal n + k;
+    };
+};
+forge add3: engrave(arcana) -> arcana = make_adder(3);
+forge fs: grimoire<engrave(arcana) -> arcana> = [double, add3];
+orbit (f = fs) {
+    unveil(apply(f, 10));
+};
+unveil(add3(1), " ", double);
+"#,
+    );
+    assert_eq!(output, "20\n13\n4 engrave(arcana) -> arcana\n");
+}
+
+#[test]
+fn test_anonymous_functions_share_enclosing_variables() {
+    let output = output_of(
+        r#"engrave each(xs: grimoire<arcana>, f: engrave(arcana)) {
+    orbit (x = xs) {
+        f(x);
+    };
+};
+forge morph total: arcana = 0;
+each([1, 2, 3], engrave(n: arcana) {
+    total += n;
+});
+engrave counter() -> engrave() -> arcana {
+    forge morph count: arcana = 0;
+    reveal engrave() -> arcana {
+        count += 1;
+        reveal count;
+    };
+};
+forge next: engrave() -> arcana = counter();
+next();
+unveil(total, " ", next());
+"#,
+    );
+    assert_eq!(output, "6 2\n");
+}
+
+#[test]
+fn test_calls_on_indexes_fields_and_calls() {
+    let output = output_of(
+        r#"sigil Op { f: engrave(arcana) -> arcana };
+engrave double(n: arcana) -> arcana {
+    reveal n * 2;
+};
+engrave mk() -> engrave(arcana) -> engrave(arcana) -> arcana {
+    reveal engrave(k: arcana) -> engrave(arcana) -> arcana {
+        reveal engrave(n: arcana) -> arcana {
+            reveal n + k;
+        };
+    };
+};
+forge fs: grimoire<engrave(arcana) -> arcana> = [double, mk()(1)];
+forge op: Op = Op { f: double };
+unveil(fs[0](5), " ", fs[1](5));
+unveil(mk()(3)(4), " ", op.f(6));
+unveil(engrave(n: arcana) -> arcana { reveal n * n; }(4));
+"#,
+    );
+    assert_eq!(output, "10 6\n7 12\n16\n");
+}
+
+#[test]
+fn test_calling_a_value_that_is_not_a_function_fails() {
+    let script = r#"forge xs: grimoire<arcana> = [1];
+unveil(xs[0](2));
+engrave mk() -> engrave(arcana) -> arcana {
+    reveal engrave(n: arcana) -> arcana {
+        reveal n;
+    };
+};
+mk()("a", 2);
+"#;
+    let errors = scrutinize(&build_program(script)).expect_err("Expected type errors");
+    let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Only a function can be called, found Arcana",
+            "Function value expects 1 argument(s) but 2 were given",
+            "Argument 1 of function value expects Arcana but found Rune",
+        ]
+    );
+
+    match run_both(script).result {
+        Err(EvalError::TypeError(message, Some(line_info))) => {
+            assert_eq!(message, "Only a function can be called");
+            assert_eq!(line_info.line, 2);
+        }
+        result => panic!("Expected a type error, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_native_functions_as_values() {
+    let script = r#"forge f: engrave(arcana) -> arcana = abs;
+engrave apply(g: engrave(aether) -> aether, x: aether) -> aether {
+    reveal g(x);
+};
+engrave twice() {
+    forge h: engrave(arcana) -> arcana = abs;
+    unveil(h(h(-2)));
+};
+unveil(f(-5), " ", apply(sqrt, 16.0), " ", abs);
+twice();
+"#;
+    assert!(scrutinize(&build_program(script)).is_ok());
+    assert_eq!(output_of(script), "5 4 engrave(arcana) -> arcana\n2\n");
+}
+
+#[test]
+fn test_calling_a_variable_that_is_not_a_function_fails() {
+    let script = r#"forge x: arcana = 1;
+x(1);
+"#;
+    let errors = scrutinize(&build_program(script)).expect_err("Expected a type error");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].message,
+        "x is not a function, it is of type Arcana"
+    );
+
+    match run_both(script).result {
+        Err(EvalError::TypeError(message, Some(line_info))) => {
+            assert_eq!(message, "x is not a function");
+            assert_eq!(line_info.line, 2);
+        }
+        result => panic!("Expected a type error, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_mismatched_function_value_is_rejected() {
+    let script = r#"engrave shout(s: rune) -> rune {
+    reveal s + "!";
+};
+forge f: engrave(arcana) -> arcana = shout;
+"#;
+    let errors = scrutinize(&build_program(script)).expect_err("Expected a type error");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0]
+        .message
+        .contains("Cannot forge variable f of type Engrave([Arcana], Arcana)"));
+
+    for use_vm in [false, true] {
+        match run_on(script, use_vm).result {
+            Err(EvalError::InvalidOperation(_, Some(line_info))) => assert_eq!(line_info.line, 4),
+            result => panic!("Expected an invalid operation, got {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn test_scrutinize_calls_through_variables() {
+    let script = r#"forge h: engrave(rune) -> rune = engrave(s: rune) -> rune {
+    reveal s;
+};
+h(3);
+h("a", "b");
+forge n: arcana = h("a");
+"#;
+    let errors = scrutinize(&build_program(script)).expect_err("Expected type errors");
+    let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Argument 1 of function h expects Rune but found Arcana",
+            "Function h expects 1 argument(s) but 2 were given",
+            "Cannot forge variable n of type Arcana with a value of type Rune",
+        ]
+    );
+}
+
+#[test]
+fn test_format_function_values() {
+    let input = r#"engrave apply(f: engrave(arcana, rune) -> omen, g: engrave()) {
+    g();
+};
+forge f: engrave(arcana) -> arcana = engrave(n: arcana) -> arcana {
+    reveal n;
+};
+unveil(fs[0](1), mk()(2)(3));"#;
+    let formatted: Vec<String> = build_program(input)
+        .iter()
+        .map(|ast| format_ast(ast, 0))
+        .collect();
+    assert_eq!(formatted.join("\n"), input);
+}
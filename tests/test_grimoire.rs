@@ -0,0 +1,261 @@
+mod test_base;
+
+use abyss_lang::{
+    eval::EvalResult,
+    format::format_ast,
+    parser::{build_ast, parse, Rule},
+};
+use std::time::{Duration, Instant};
+use test_base::{output_of, run_both, test_base};
+
+#[test]
+fn test_grimoire_literal_and_index() {
+    let input = r#"
+    forge xs: grimoire<arcana> = [1, 2, 3];
+    xs[0] + xs[2];
+    xs;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            if let EvalResult::Arcana(n) = results[1] {
+                assert_eq!(n, 4);
+            } else {
+                panic!("Expected Arcana result");
+            }
+            assert_eq!(results[2].to_string(), "[1, 2, 3]");
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_grimoire_index_assignment() {
+    let input = r#"
+    forge morph xs: grimoire<arcana> = [1, 2, 3];
+    xs[1] = 20;
+    xs[2] += 5;
+    xs;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => assert_eq!(results[3].to_string(), "[1, 20, 8]"),
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_grimoire_nested() {
+    let input = r#"
+    forge morph grid: grimoire<grimoire<rune>> = [["a", "b"], ["c"]];
+    grid[1][0] = "z";
+    grid;
+    grid[0][1];
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            assert_eq!(results[2].to_string(), r#"[["a", "b"], ["z"]]"#);
+            if let EvalResult::Rune(s) = &results[3] {
+                assert_eq!(s, "b");
+            } else {
+                panic!("Expected Rune result");
+            }
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_grimoire_builtins() {
+    let input = r#"
+    forge morph xs: grimoire<aether> = [];
+    push(xs, 1.5);
+    push(xs, 2.5);
+    push(xs, 4.0);
+    pop(xs);
+    len(xs);
+    len("abyss");
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            if let EvalResult::Aether(n) = results[4] {
+                assert_eq!(n, 4.0);
+            } else {
+                panic!("Expected Aether result");
+            }
+            if let EvalResult::Arcana(n) = results[5] {
+                assert_eq!(n, 2);
+            } else {
+                panic!("Expected Arcana result");
+            }
+            if let EvalResult::Arcana(n) = results[6] {
+                assert_eq!(n, 5);
+            } else {
+                panic!("Expected Arcana result");
+            }
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_orbit_over_grimoire() {
+    let input = r#"
+    forge xs: grimoire<arcana> = [1, 2, 3, 4];
+    forge morph sum: arcana = 0;
+    orbit(x = xs) {
+        sum += x;
+    };
+    sum;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            if let EvalResult::Arcana(n) = results[3] {
+                assert_eq!(n, 10);
+            } else {
+                panic!("Expected Arcana result");
+            }
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_grimoire_function_param_and_return() {
+    let input = r#"
+    engrave doubled(xs: grimoire<arcana>) -> grimoire<arcana> {
+        forge morph result: grimoire<arcana> = [];
+        orbit(x = xs) {
+            push(result, x * 2);
+        };
+        reveal result;
+    };
+    doubled([1, 2, 3]);
+    "#;
+
+    match test_base(input) {
+        Ok(results) => assert_eq!(results[1].to_string(), "[2, 4, 6]"),
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_grimoire_index_out_of_bounds() {
+    let input = r#"
+    forge xs: grimoire<arcana> = [1, 2, 3];
+    xs[3];
+    "#;
+
+    assert!(test_base(input).is_err());
+}
+
+#[test]
+fn test_grimoire_type_mismatch() {
+    let input = r#"
+    forge xs: grimoire<arcana> = [1, "two"];
+    "#;
+
+    assert!(test_base(input).is_err());
+}
+
+#[test]
+fn test_grimoire_pop_empty() {
+    let input = r#"
+    forge morph xs: grimoire<arcana> = [];
+    pop(xs);
+    "#;
+
+    assert!(test_base(input).is_err());
+}
+
+#[test]
+fn test_grimoire_immutable_push() {
+    let input = r#"
+    forge xs: grimoire<arcana> = [1];
+    push(xs, 2);
+    "#;
+
+    assert!(test_base(input).is_err());
+}
+
+#[test]
+fn test_push_and_pop_through_fields_and_indexes() {
+    // `push` and `pop` reach the grimoire they modify like the target of an assignment.
+    let input = r#"
+    sigil Bag { xs: grimoire<arcana> };
+    forge morph bag: Bag = Bag { xs: [1] };
+    push(bag.xs, 2);
+    forge morph grid: grimoire<grimoire<arcana>> = [[1, 2], []];
+    push(grid[1], pop(grid[0]));
+    unveil(bag, grid);
+    "#;
+    assert_eq!(output_of(input), "Bag { xs: [1, 2] }[[1], [2]]\n");
+
+    let immutable = r#"
+    sigil Bag { xs: grimoire<arcana> };
+    forge bag: Bag = Bag { xs: [1] };
+    push(bag.xs, 2);
+    "#;
+    let empty = r#"
+    forge morph grid: grimoire<grimoire<arcana>> = [[]];
+    pop(grid[0]);
+    "#;
+    for (input, message) in [
+        (immutable, "Cannot reassign to immutable variable bag"),
+        (empty, "Cannot pop from empty grimoire grid"),
+    ] {
+        match run_both(input).result {
+            Err(e) => assert!(e.to_string().contains(message), "{}", e),
+            Ok(result) => panic!("Expected an error, got {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn test_grow_large_grimoire() {
+    // Pushing, assigning and reading an element must not copy the whole grimoire, or growing
+    // it this large would take minutes.
+    let input = r#"
+    forge morph xs: grimoire<arcana> = [];
+    orbit (i = 0..20000) {
+        push(xs, i);
+    };
+    orbit (i = 0..20000) {
+        xs[i] = xs[i] * 2;
+    };
+    forge morph total: arcana = 0;
+    orbit (i = 0..10000) {
+        total += pop(xs) - xs[i];
+    };
+    len(xs);
+    total;
+    "#;
+
+    let started = Instant::now();
+    match test_base(input) {
+        Ok(results) => {
+            assert_eq!(results[results.len() - 2].to_string(), "10000");
+            assert_eq!(results[results.len() - 1].to_string(), "200000000");
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+    assert!(started.elapsed() < Duration::from_secs(20));
+}
+
+#[test]
+fn test_format_grimoire() {
+    let input = r#"forge morph xs: grimoire<grimoire<arcana>> = [[1, 2], []];
+xs[0][1] += 1;
+unveil(len(xs[0]));"#;
+
+    let pair = parse(input).expect("Failed to parse input");
+    let formatted: Vec<String> = pair
+        .into_inner()
+        .filter(|p| p.as_rule() != Rule::EOI)
+        .map(|p| format_ast(&build_ast(p).expect("Failed to build AST"), 0))
+        .collect();
+    assert_eq!(formatted.join("\n"), input);
+}
@@ -0,0 +1,70 @@
+mod test_base;
+
+use abyss_lang::{
+    eval::{EvalError, EvalResult},
+    format::format_ast,
+    typeck::scrutinize,
+};
+use test_base::{build_program, run_both, run_on};
+
+#[test]
+fn test_interpolated_runes() {
+    let script = r#"forge x: arcana = 3;
+forge name: rune = "Lia";
+engrave greet(who: rune) -> rune {
+    reveal $"Hello, {who}!";
+};
+unveil($"x = {x}, double = { x * 2 }, {greet(name)}");
+unveil($"{[1, 2]} {1.5} {boon} \{x\} {$"nested {x + 1}"} \"quoted\"");
+forge empty: omen = $"" == "";
+$"{name}{x}";
+"#;
+    let run = run_both(script);
+    assert!(matches!(run.result, Ok(EvalResult::Rune(ref s)) if s == "Lia3"));
+    assert_eq!(
+        run.output,
+        "x = 3, double = 6, Hello, Lia!\n[1, 2] 1.5 boon {x} nested 4 \"quoted\"\n"
+    );
+}
+
+#[test]
+fn test_interpolated_expressions_report_their_own_position() {
+    let script = r#"forge zero: arcana = 0;
+unveil($"ratio:
+    {10 / zero}");
+"#;
+    for use_vm in [false, true] {
+        match run_on(script, use_vm).result {
+            Err(EvalError::DivisionByZero(Some(line_info))) => {
+                assert_eq!((line_info.line, line_info.column), (3, 6))
+            }
+            result => panic!("Expected a division by zero, got {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn test_scrutinize_interpolated_runes() {
+    let script = r#"forge n: arcana = $"{1}";
+unveil($"{missing}");
+"#;
+    let errors = scrutinize(&build_program(script)).expect_err("Expected type errors");
+    let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Cannot forge variable n of type Arcana with a value of type Rune",
+            "Variable missing is not defined",
+        ]
+    );
+}
+
+#[test]
+fn test_format_interpolated_runes() {
+    let input = r#"unveil($"x = {x * 2}, \{literal\} \"{name + "!"}\"\t{$"inner {r"raw\n"}"}");"#;
+    let formatted: Vec<String> = build_program(input)
+        .iter()
+        .map(|ast| format_ast(ast, 0))
+        .collect();
+    assert_eq!(formatted.join("\n"), input);
+}
@@ -0,0 +1,256 @@
+use abyss_lang::{
+    ast::Type,
+    env::Value,
+    eval::{EvalError, EvalResult},
+    interpreter::{AbyssError, Interpreter},
+    io::IoContext,
+};
+use std::fs;
+
+#[test]
+fn test_eval_str_keeps_state() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval_str("forge morph total: arcana = 1;")
+        .expect("Failed to evaluate");
+    let result = interpreter
+        .eval_str("total += 41; total;")
+        .expect("Failed to evaluate");
+
+    assert!(matches!(result, EvalResult::Arcana(42)));
+}
+
+#[test]
+fn test_call_function() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval_str(
+            r#"
+            engrave greet(name: rune, times: arcana) -> rune {
+                forge morph result: rune = "";
+                orbit (i = 0..times) {
+                    result += name;
+                };
+                reveal result;
+            };
+            engrave nothing() {
+                forge x: arcana = 1;
+            };
+            "#,
+        )
+        .expect("Failed to evaluate");
+
+    let result = interpreter
+        .call_function(
+            "greet",
+            vec![Value::Rune("ab".to_string()), Value::Arcana(2)],
+        )
+        .expect("Failed to call greet");
+    assert!(matches!(result, Some(Value::Rune(s)) if s == "abab"));
+    assert!(matches!(
+        interpreter.call_function("nothing", vec![]),
+        Ok(None)
+    ));
+    assert!(matches!(
+        interpreter.call_function("abs", vec![Value::Arcana(-3)]),
+        Ok(Some(Value::Arcana(3)))
+    ));
+    assert!(matches!(
+        interpreter.call_function("greet", vec![Value::Arcana(1), Value::Arcana(2)]),
+        Err(AbyssError::Eval(EvalError::TypeError(_, _)))
+    ));
+    assert!(matches!(
+        interpreter.call_function("missing", vec![]),
+        Err(AbyssError::UndefinedFunction(name)) if name == "missing"
+    ));
+}
+
+#[test]
+fn test_call_function_checks_arity() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval_str("engrave power(x: arcana) -> arcana { reveal x * 3; };")
+        .expect("Failed to evaluate");
+
+    assert!(matches!(
+        interpreter.call_function("power", vec![]),
+        Err(AbyssError::ArityMismatch(name, 1, 0)) if name == "power"
+    ));
+    assert!(matches!(
+        interpreter.call_function("power", vec![Value::Arcana(1), Value::Arcana(2)]),
+        Err(AbyssError::ArityMismatch(_, 1, 2))
+    ));
+    assert!(matches!(
+        interpreter.call_function("abs", vec![]),
+        Err(AbyssError::ArityMismatch(_, 1, 0))
+    ));
+    let error = interpreter.call_function("power", vec![]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Function power takes 1 argument(s) but was given 0"
+    );
+}
+
+#[test]
+fn test_call_builtin_function() {
+    let mut interpreter = Interpreter::new();
+    let spells = Value::Grimoire(vec![
+        Value::Rune("fire".to_string()),
+        Value::Rune("ice".to_string()),
+    ]);
+
+    assert!(matches!(
+        interpreter.call_function("len", vec![spells.clone()]),
+        Ok(Some(Value::Arcana(2)))
+    ));
+    assert!(matches!(
+        interpreter.call_function("len", vec![Value::Rune("abyss".to_string())]),
+        Ok(Some(Value::Arcana(5)))
+    ));
+    assert!(matches!(
+        interpreter.call_function("len", vec![Value::Arcana(1)]),
+        Err(AbyssError::Eval(EvalError::TypeError(_, _)))
+    ));
+    assert!(matches!(
+        interpreter.call_function("push", vec![spells, Value::Rune("wind".to_string())]),
+        Err(AbyssError::Eval(EvalError::InvalidOperation(_, _)))
+    ));
+}
+
+#[test]
+fn test_get_and_set_global() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval_str("forge morph level: arcana = 1; forge name: rune = \"Lia\";")
+        .expect("Failed to evaluate");
+
+    assert!(matches!(
+        interpreter.get_global("level"),
+        Some(Value::Arcana(1))
+    ));
+    assert!(interpreter.get_global("missing").is_none());
+
+    interpreter
+        .set_global("level", Value::Arcana(5))
+        .expect("Failed to set level");
+    interpreter
+        .set_global(
+            "spells",
+            Value::Grimoire(vec![Value::Rune("fire".to_string())]),
+        )
+        .expect("Failed to set spells");
+    let result = interpreter
+        .eval_str("level += 1; level + len(spells);")
+        .expect("Failed to evaluate");
+    assert!(matches!(result, EvalResult::Arcana(7)));
+
+    assert!(interpreter.set_global("level", Value::Aether(1.0)).is_err());
+    assert!(interpreter
+        .set_global("empty", Value::Grimoire(vec![]))
+        .is_err());
+    // Globals set by the host are immutable to scripts.
+    assert!(interpreter.eval_str("spells = [];").is_err());
+}
+
+#[test]
+fn test_errors_are_returned() {
+    let mut interpreter = Interpreter::new();
+
+    match interpreter.eval_str("forge x: arcana = ;") {
+        Err(e @ AbyssError::Parse(_)) => assert_eq!(e.line_info().unwrap().line, 1),
+        result => panic!("Expected a parse error, found {:?}", result),
+    }
+    match interpreter.eval_str("\nunveil(missing);") {
+        Err(e @ AbyssError::Eval(_)) => assert_eq!(e.line_info().unwrap().line, 2),
+        result => panic!("Expected an evaluation error, found {:?}", result),
+    }
+    assert!(matches!(
+        interpreter.eval_file("/definitely/missing.aby"),
+        Err(AbyssError::Io(_))
+    ));
+}
+
+#[test]
+fn test_error_inside_function_restores_scopes() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval_str("engrave fail(secret: arcana) -> arcana { reveal secret / 0; };")
+        .expect("Failed to evaluate");
+
+    assert!(interpreter
+        .call_function("fail", vec![Value::Arcana(1)])
+        .is_err());
+    assert!(interpreter.eval_str("forge x: arcana = 2;").is_ok());
+    assert!(matches!(
+        interpreter.get_global("x"),
+        Some(Value::Arcana(2))
+    ));
+    assert!(interpreter.eval_str("secret;").is_err());
+}
+
+#[test]
+fn test_eval_file_and_register_native() {
+    let dir = std::env::temp_dir().join(format!("abyss_interpreter_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("lib.aby"), "forge BONUS: arcana = 10;").unwrap();
+    fs::write(
+        dir.join("main.aby"),
+        "invoke \"lib.aby\";\nforge power: arcana = double(BONUS);\nunveil(missing);\n",
+    )
+    .unwrap();
+
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .env_mut()
+        .register_native(
+            "double",
+            vec![Type::Arcana],
+            Type::Arcana,
+            |args| match args.as_slice() {
+                [EvalResult::Arcana(n)] => Ok(EvalResult::Arcana(n * 2)),
+                _ => Err("unexpected arguments".to_string()),
+            },
+        );
+
+    match interpreter.eval_file(dir.join("main.aby")) {
+        Err(e) => {
+            let line_info = e.line_info().unwrap();
+            assert_eq!(line_info.line, 3);
+            assert!(line_info.file.unwrap().ends_with("main.aby"));
+        }
+        Ok(result) => panic!("Expected an error, found {:?}", result),
+    }
+    assert!(matches!(
+        interpreter.get_global("power"),
+        Some(Value::Arcana(20))
+    ));
+}
+
+#[test]
+fn test_eval_str_runs_type_checker() {
+    let (io, output, _) = IoContext::scripted("");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_io(io);
+    interpreter.set_type_check(true);
+    interpreter
+        .eval_str(
+            "forge morph total: arcana = 1; engrave twice(n: arcana) -> arcana { reveal n * 2; };",
+        )
+        .expect("Failed to evaluate");
+
+    // Nothing runs when the code has type errors.
+    match interpreter.eval_str("unveil(\"before\");\ntotal = twice(\"a\");") {
+        Err(AbyssError::Type(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].line_info.clone().unwrap().line, 2);
+        }
+        result => panic!("Expected a type error, found {:?}", result),
+    }
+    assert_eq!(output.contents(), "");
+
+    // Globals and functions defined by earlier calls are known to the checker.
+    let result = interpreter
+        .eval_str("total = twice(total); total;")
+        .expect("Failed to evaluate");
+    assert!(matches!(result, EvalResult::Arcana(2)));
+}
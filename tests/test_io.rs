@@ -0,0 +1,109 @@
+use abyss_lang::{
+    env::Environment,
+    eval::{evaluate, EvalError, EvalResult},
+    interpreter::Interpreter,
+    io::IoContext,
+    parser::{build_ast, parse, Rule},
+};
+use std::fs;
+
+/// Evaluates the input in the given environment and returns the result of every statement.
+fn run(input: &str, env: &mut Environment) -> Result<Vec<EvalResult>, EvalError> {
+    let pair = parse(input).expect("Failed to parse input");
+    pair.into_inner()
+        .filter(|p| p.as_rule() != Rule::EOI)
+        .map(|p| evaluate(&build_ast(p).expect("Failed to build AST"), env))
+        .collect()
+}
+
+#[test]
+fn test_unveil_writes_to_output() {
+    let (io, output, error) = IoContext::scripted("");
+    let mut env = Environment::new();
+    env.set_io(io);
+
+    run(
+        r#"
+        forge spells: grimoire<rune> = ["fire", "ice"];
+        unveil("Spells: ", spells);
+        unveil(1 + 2, " ", boon);
+        "#,
+        &mut env,
+    )
+    .expect("Failed to evaluate");
+
+    assert_eq!(output.contents(), "Spells: [\"fire\", \"ice\"]\n3 boon\n");
+    assert_eq!(error.contents(), "");
+}
+
+#[test]
+fn test_summon_reads_scripted_input() {
+    let (io, output, _) = IoContext::scripted("Lia\n17\n2.5\n");
+    let mut env = Environment::new();
+    env.set_io(io);
+
+    let results = run(
+        r#"
+        summon("Name: ", rune);
+        summon("Age: ", arcana);
+        summon("Power: ", aether);
+        "#,
+        &mut env,
+    )
+    .expect("Failed to evaluate");
+
+    assert!(matches!(&results[0], EvalResult::Rune(s) if s == "Lia"));
+    assert!(matches!(results[1], EvalResult::Arcana(17)));
+    assert!(matches!(results[2], EvalResult::Aether(n) if n == 2.5));
+    assert_eq!(output.contents(), "Name: Age: Power: ");
+}
+
+#[test]
+fn test_summon_invalid_input() {
+    let (io, _, _) = IoContext::scripted("many\n");
+    let mut env = Environment::new();
+    env.set_io(io);
+
+    match run("\nsummon(\"Age: \", arcana);", &mut env) {
+        Err(EvalError::InvalidOperation(_, line_info)) => assert_eq!(line_info.unwrap().line, 2),
+        result => panic!("Expected InvalidOperation, found {:?}", result),
+    }
+}
+
+#[test]
+fn test_module_shares_output() {
+    let dir = std::env::temp_dir().join(format!("abyss_io_module_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("lib.aby"),
+        "engrave greet() { unveil(\"from module\"); };",
+    )
+    .unwrap();
+    fs::write(dir.join("main.aby"), "invoke \"lib.aby\";\ngreet();\n").unwrap();
+
+    let (io, output, _) = IoContext::scripted("");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_io(io);
+    interpreter
+        .eval_file(dir.join("main.aby"))
+        .expect("Failed to evaluate");
+
+    assert_eq!(output.contents(), "from module\n");
+}
+
+#[test]
+fn test_report_error_writes_to_error_stream() {
+    let (io, output, error) = IoContext::scripted("");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_io(io);
+
+    let source = "unveil(\"before\");\nunveil(missing);";
+    let e = interpreter.eval_str(source).unwrap_err();
+    interpreter.report_error(source, &e);
+
+    assert_eq!(output.contents(), "before\n");
+    let error = error.contents();
+    assert!(error.contains("line 2"), "{}", error);
+    assert!(error.contains("missing is not defined"), "{}", error);
+    assert!(error.contains("unveil(missing);"), "{}", error);
+}
@@ -0,0 +1,165 @@
+mod test_base;
+
+use abyss_lang::{
+    env::Value,
+    eval::{EvalError, EvalResult},
+    interpreter::{AbyssError, Interpreter},
+    limits::Limits,
+};
+use std::time::{Duration, Instant};
+use test_base::run_on_with;
+
+/// Runs a script on the given engine with the given limits.
+fn run_on(input: &str, limits: Limits, use_vm: bool) -> Result<EvalResult, EvalError> {
+    run_on_with(input, use_vm, "", |env| env.set_limits(limits)).result
+}
+
+/// Runs a script on both engines and returns the message of the limit it exceeds.
+fn exceeded(input: &str, limits: Limits) -> String {
+    let mut messages = [false, true].map(|use_vm| match run_on(input, limits.clone(), use_vm) {
+        Err(EvalError::LimitExceeded(message, _)) => message,
+        result => panic!("Expected a limit to be exceeded, got {:?}", result),
+    });
+    assert_eq!(messages[0], messages[1]);
+    std::mem::take(&mut messages[0])
+}
+
+#[test]
+fn test_max_steps() {
+    let limits = Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    };
+    assert_eq!(
+        exceeded("orbit {\n};", limits.clone()),
+        "the script evaluated more than 1000 steps"
+    );
+    // `attempt` cannot catch the error.
+    assert_eq!(
+        exceeded(
+            "engrave spin() -> arcana {\n    orbit {\n    };\n    reveal 1;\n};\nforge r: cursed<arcana> = attempt(spin());\nunveil(1);",
+            limits.clone()
+        ),
+        "the script evaluated more than 1000 steps"
+    );
+    // A script within the budget runs to completion.
+    for use_vm in [false, true] {
+        let result = run_on(
+            "forge morph n: arcana = 0;\norbit (i = 0..10) {\n    n += i;\n};\nn;",
+            limits.clone(),
+            use_vm,
+        );
+        assert!(matches!(result, Ok(EvalResult::Arcana(45))));
+    }
+}
+
+#[test]
+fn test_timeout() {
+    let limits = Limits {
+        timeout: Some(Duration::from_millis(50)),
+        ..Limits::default()
+    };
+    for use_vm in [false, true] {
+        let start = Instant::now();
+        match run_on("orbit {\n};", limits.clone(), use_vm) {
+            Err(EvalError::LimitExceeded(message, _)) => {
+                assert_eq!(message, "the script ran for more than 50ms")
+            }
+            result => panic!("Expected a timeout, got {:?}", result),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
+
+#[test]
+fn test_value_sizes() {
+    let limits = Limits {
+        max_rune_length: Some(8),
+        max_collection_size: Some(3),
+        ..Limits::default()
+    };
+    assert_eq!(
+        exceeded("forge s: rune = \"abcd\" + \"efghi\";", limits.clone()),
+        "a rune of 9 bytes exceeds the maximum rune length of 8"
+    );
+    assert_eq!(
+        exceeded(
+            "forge morph xs: grimoire<arcana> = [1, 2, 3];\npush(xs, 4);",
+            limits.clone()
+        ),
+        "a grimoire of 4 elements exceeds the maximum collection size of 3"
+    );
+    let error = run_on("forge xs: grimoire<arcana> = [1, 2, 3, 4];", limits, false).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Limit exceeded: a grimoire of 4 elements exceeds the maximum collection size of 3"
+    );
+    assert_eq!(error.line_info().unwrap().line, 1);
+}
+
+#[test]
+fn test_arcana_bits() {
+    let limits = Limits {
+        max_arcana_bits: Some(1000),
+        timeout: Some(Duration::from_secs(1)),
+        ..Limits::default()
+    };
+    let run = |input: &str, use_vm: bool| {
+        let limits = limits.clone();
+        run_on_with(input, use_vm, "", move |env| {
+            env.set_bigint(true);
+            env.set_limits(limits.clone());
+        })
+        .result
+    };
+    for use_vm in [false, true] {
+        let start = Instant::now();
+        match run("forge n: arcana = 3 ^ 300000000;", use_vm) {
+            Err(EvalError::LimitExceeded(message, _)) => assert_eq!(
+                message,
+                "an arcana of 300000001 bits exceeds the maximum of 1000 bits"
+            ),
+            result => panic!("Expected a limit to be exceeded, got {:?}", result),
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+        match run("forge morph n: arcana = 2 ^ 900;\nn *= n;", use_vm) {
+            Err(EvalError::LimitExceeded(message, line_info)) => {
+                assert_eq!(
+                    message,
+                    "an arcana of 1801 bits exceeds the maximum of 1000 bits"
+                );
+                assert_eq!(line_info.unwrap().line, 2);
+            }
+            result => panic!("Expected a limit to be exceeded, got {:?}", result),
+        }
+        assert!(run("2 ^ 998 * 2;", use_vm).is_ok());
+    }
+}
+
+#[test]
+fn test_interpreter_limits() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_limits(Limits {
+        max_steps: Some(200),
+        ..Limits::default()
+    });
+    interpreter
+        .eval_str("engrave count(n: arcana) -> arcana {\n    forge morph total: arcana = 0;\n    orbit (i = 0..n) {\n        total += 1;\n    };\n    reveal total;\n};")
+        .unwrap();
+
+    // Each evaluation gets the full budget.
+    for _ in 0..3 {
+        let result = interpreter.call_function("count", vec![Value::Arcana(20)]);
+        assert!(matches!(result, Ok(Some(Value::Arcana(20)))));
+        assert!(matches!(
+            interpreter.eval_str("count(20);"),
+            Ok(EvalResult::Arcana(20))
+        ));
+    }
+    let error = interpreter.eval_str("count(1000);").unwrap_err();
+    assert!(matches!(
+        error,
+        AbyssError::Eval(EvalError::LimitExceeded(_, _))
+    ));
+    assert_eq!(interpreter.env().call_depth(), 0);
+}
@@ -0,0 +1,323 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// A minimal Language Server Protocol client talking to `abyss familiar-lsp` over stdio.
+struct Client {
+    server: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    next_id: u64,
+    notifications: Vec<Value>,
+}
+
+impl Client {
+    /// Starts the server and initializes it.
+    fn start() -> Self {
+        let mut server = Command::new(env!("CARGO_BIN_EXE_abyss"))
+            .arg("familiar-lsp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to start the language server");
+        let input = server.stdin.take().unwrap();
+        let output = BufReader::new(server.stdout.take().unwrap());
+        let mut client = Client {
+            server,
+            input,
+            output,
+            next_id: 0,
+            notifications: Vec::new(),
+        };
+        let capabilities = client.request("initialize", json!({ "capabilities": {} }));
+        assert_eq!(capabilities["capabilities"]["hoverProvider"], json!(true));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    /// Sends a message to the server.
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.input.flush().unwrap();
+    }
+
+    /// Reads a message from the server.
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            self.output.read_line(&mut header).unwrap();
+            match header.trim_end().strip_prefix("Content-Length: ") {
+                Some(value) => length = value.parse().unwrap(),
+                None if header.trim_end().is_empty() => break,
+                None => {}
+            }
+        }
+        let mut body = vec![0; length];
+        self.output.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Sends a notification to the server.
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Sends a request and waits for its result, keeping the notifications received meanwhile.
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.receive();
+            if message["id"] == json!(id) {
+                return message["result"].clone();
+            }
+            self.notifications.push(message);
+        }
+    }
+
+    /// Opens a document and returns the diagnostics the server publishes for it.
+    fn open(&mut self, uri: &str, text: &str) -> Vec<Value> {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": uri, "languageId": "abyss", "version": 1, "text": text },
+            }),
+        );
+        self.diagnostics()
+    }
+
+    /// Waits for the next diagnostics published by the server.
+    fn diagnostics(&mut self) -> Vec<Value> {
+        let message = match self.notifications.pop() {
+            Some(message) => message,
+            None => self.receive(),
+        };
+        assert_eq!(message["method"], "textDocument/publishDiagnostics");
+        message["params"]["diagnostics"].as_array().unwrap().clone()
+    }
+
+    /// Sends a request about a position in a document.
+    fn at(&mut self, method: &str, uri: &str, line: u64, character: u64) -> Value {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": uri },
+                "position": { "line": line, "character": character },
+            }),
+        )
+    }
+
+    /// Shuts the server down and checks that it exits.
+    fn shutdown(mut self) {
+        assert_eq!(self.request("shutdown", Value::Null), Value::Null);
+        self.notify("exit", Value::Null);
+        assert!(self.server.wait().unwrap().success());
+    }
+}
+
+#[test]
+fn test_lsp_diagnostics() {
+    let mut client = Client::start();
+    let uri = "file:///tmp/diagnostics.aby";
+
+    let diagnostics = client.open(uri, "forge x: arcana = 1;\nforge y: rune = x + 1;\n");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 1, "character": 0 })
+    );
+    assert!(diagnostics[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Type error"));
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": "forge x: arcana = 1;\nunveil(x +);\n" }],
+        }),
+    );
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+    assert!(diagnostics[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Syntax error"));
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 3 },
+            "contentChanges": [{ "text": "forge x: arcana = 1;\nunveil(x + 1);\n" }],
+        }),
+    );
+    assert!(client.diagnostics().is_empty());
+
+    client.notify(
+        "textDocument/didClose",
+        json!({ "textDocument": { "uri": uri } }),
+    );
+    assert!(client.diagnostics().is_empty());
+    client.shutdown();
+}
+
+#[test]
+fn test_lsp_definition_and_hover() {
+    let mut client = Client::start();
+    let uri = "file:///tmp/navigation.aby";
+    let text = r#"forge morph total: arcana = 0;
+engrave add(n: arcana) -> arcana {
+    total += n;
+    reveal double(total);
+};
+engrave double(n: arcana) -> arcana {
+    reveal n * 2;
+};
+orbit (i = 0..1) {
+    forge total: rune = "shadowed";
+    unveil(total);
+};
+unveil(add(1), total);
+"#;
+    assert!(client.open(uri, text).is_empty());
+
+    // `total` in the body of `add` is the morph variable, `double` is declared after `add`.
+    let definition = client.at("textDocument/definition", uri, 2, 6);
+    assert_eq!(
+        definition["range"],
+        json!({
+            "start": { "line": 0, "character": 12 },
+            "end": { "line": 0, "character": 17 },
+        })
+    );
+    let definition = client.at("textDocument/definition", uri, 3, 13);
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 5, "character": 8 })
+    );
+    let definition = client.at("textDocument/definition", uri, 6, 11);
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 5, "character": 15 })
+    );
+
+    // The `total` in the orbit refers to the declaration that shadows the first one.
+    let hover = client.at("textDocument/hover", uri, 10, 13);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```abyss\nforge total: rune\n```"
+    );
+    let hover = client.at("textDocument/hover", uri, 12, 17);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```abyss\nforge morph total: arcana\n```"
+    );
+    let hover = client.at("textDocument/hover", uri, 12, 8);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```abyss\nengrave add(n: arcana) -> arcana\n```"
+    );
+    let hover = client.at("textDocument/hover", uri, 5, 16);
+    assert_eq!(hover["contents"]["value"], "```abyss\nn: arcana\n```");
+    assert_eq!(client.at("textDocument/hover", uri, 12, 0), Value::Null);
+    client.shutdown();
+}
+
+#[test]
+fn test_lsp_symbols_and_formatting() {
+    let mut client = Client::start();
+    let uri = "file:///tmp/symbols.aby";
+    let text = "sigil Point { x: arcana, y: arcana };\nengrave origin() -> Point {\nforge p: Point = Point { x: 0, y: 0 };\nreveal p;\n};\nforge   o: Point=origin();\n";
+    assert!(client.open(uri, text).is_empty());
+
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": uri } }),
+    );
+    let names: Vec<_> = symbols
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| (symbol["name"].clone(), symbol["kind"].clone()))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            (json!("Point"), json!(23)),
+            (json!("origin"), json!(12)),
+            (json!("o"), json!(13)),
+        ]
+    );
+    assert_eq!(symbols[1]["children"][0]["name"], "p");
+    assert_eq!(symbols[1]["children"][0]["detail"], "forge p: Point");
+
+    let definition = client.at("textDocument/definition", uri, 2, 17);
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 0, "character": 6 })
+    );
+
+    let edits = client.request(
+        "textDocument/formatting",
+        json!({
+            "textDocument": { "uri": uri },
+            "options": { "tabSize": 4, "insertSpaces": true },
+        }),
+    );
+    assert_eq!(
+        edits[0]["range"]["end"],
+        json!({ "line": 6, "character": 0 })
+    );
+    assert_eq!(
+        edits[0]["newText"],
+        "sigil Point {\n    x: arcana,\n    y: arcana,\n};\nengrave origin() -> Point {\n    forge p: Point = Point { x: 0, y: 0 };\n    reveal p;\n};\nforge o: Point = origin();\n"
+    );
+    client.shutdown();
+}
+
+#[test]
+fn test_lsp_keeps_program_with_type_errors() {
+    let mut client = Client::start();
+    let uri = "file:///tmp/type_error.aby";
+    let text = "forge   n: arcana = \"one\";\nunveil(n);\n";
+    let diagnostics = client.open(uri, text);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["severity"], 1);
+
+    let hover = client.at("textDocument/hover", uri, 1, 7);
+    assert_eq!(hover["contents"]["value"], "```abyss\nforge n: arcana\n```");
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": uri } }),
+    );
+    assert_eq!(symbols[0]["name"], "n");
+    let formatting = json!({
+        "textDocument": { "uri": uri },
+        "options": { "tabSize": 4, "insertSpaces": true },
+    });
+    let edits = client.request("textDocument/formatting", formatting.clone());
+    assert_eq!(
+        edits[0]["newText"],
+        "forge n: arcana = \"one\";\nunveil(n);\n"
+    );
+
+    // A document with syntax errors cannot be formatted.
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": "forge n: arcana = ;\n" }],
+        }),
+    );
+    assert_eq!(client.diagnostics().len(), 1);
+    assert_eq!(
+        client.request("textDocument/formatting", formatting),
+        Value::Null
+    );
+    client.shutdown();
+}
@@ -0,0 +1,235 @@
+mod test_base;
+
+use abyss_lang::{
+    env::Environment,
+    eval::{evaluate, EvalError, EvalResult},
+    format::format_ast,
+    typeck::scrutinize_with_path,
+};
+use std::fs;
+use std::path::PathBuf;
+use test_base::build_program;
+
+/// Writes the given files into a fresh temporary directory and returns its path.
+fn write_scripts(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("abyss_{}_{}", test_name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for (name, contents) in files {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        fs::write(path, contents).expect("Failed to write script");
+    }
+    dir
+}
+
+/// Evaluates the script at `path` and returns the result of every statement.
+fn run_script(path: PathBuf) -> Result<Vec<EvalResult>, EvalError> {
+    let input = fs::read_to_string(&path).expect("Failed to read script");
+    let mut env = Environment::with_script_path(path);
+    build_program(&input)
+        .iter()
+        .map(|ast| evaluate(ast, &mut env))
+        .collect()
+}
+
+const MATH: &str = r#"
+forge PI: aether = 2.5;
+forge morph count: arcana = 0;
+engrave square(x: arcana) -> arcana {
+    reveal x * x;
+};
+engrave counter() -> arcana {
+    count += 1;
+    reveal count;
+};
+"#;
+
+#[test]
+fn test_invoke_module() {
+    let dir = write_scripts(
+        "invoke_module",
+        &[
+            ("lib/math.aby", MATH),
+            (
+                "main.aby",
+                r#"
+                invoke "lib/math.aby";
+                square(4);
+                PI;
+                counter();
+                counter();
+                "#,
+            ),
+        ],
+    );
+
+    match run_script(dir.join("main.aby")) {
+        Ok(results) => {
+            assert!(matches!(results[1], EvalResult::Arcana(16)));
+            assert!(matches!(results[2], EvalResult::Aether(n) if n == 2.5));
+            // The module's morph globals keep their state between calls.
+            assert!(matches!(results[4], EvalResult::Arcana(2)));
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_invoke_selected_names() {
+    let dir = write_scripts(
+        "invoke_selected_names",
+        &[
+            ("math.aby", MATH),
+            (
+                "main.aby",
+                r#"
+                invoke "math.aby" { square };
+                square(3);
+                counter();
+                "#,
+            ),
+        ],
+    );
+
+    match run_script(dir.join("main.aby")) {
+        Err(EvalError::UndefinedVariable(name, _)) => assert_eq!(name, "counter"),
+        result => panic!("Expected UndefinedVariable, found {:?}", result),
+    }
+}
+
+#[test]
+fn test_invoke_does_not_export_morph_globals() {
+    let dir = write_scripts(
+        "invoke_morph_globals",
+        &[
+            ("math.aby", MATH),
+            ("main.aby", r#"invoke "math.aby" { count };"#),
+        ],
+    );
+
+    match run_script(dir.join("main.aby")) {
+        Err(EvalError::ModuleError(message, _)) => assert!(message.contains("count")),
+        result => panic!("Expected ModuleError, found {:?}", result),
+    }
+}
+
+#[test]
+fn test_invoke_cycle() {
+    let dir = write_scripts(
+        "invoke_cycle",
+        &[
+            ("main.aby", r#"invoke "lib/a.aby";"#),
+            ("lib/a.aby", r#"invoke "../main.aby";"#),
+        ],
+    );
+
+    match run_script(dir.join("main.aby")) {
+        Err(EvalError::ModuleError(message, line_info)) => {
+            assert!(message.contains("Cyclic"));
+            let file = line_info.unwrap().file.unwrap();
+            assert!(file.ends_with("a.aby"), "{}", file);
+        }
+        result => panic!("Expected ModuleError, found {:?}", result),
+    }
+}
+
+#[test]
+fn test_invoke_outside_script_directory_needs_file_access() {
+    let dir = write_scripts(
+        "invoke_outside",
+        &[
+            ("app/main.aby", "invoke \"../secret.txt\";\n"),
+            ("secret.txt", "root:x:0:0:root:/root:/bin/bash\n"),
+        ],
+    );
+    let path = dir.join("app/main.aby");
+    let program = build_program(&fs::read_to_string(&path).unwrap());
+
+    match run_script(path.clone()) {
+        Err(EvalError::ModuleError(message, _)) => {
+            assert!(
+                message.contains("unless file access is allowed"),
+                "{}",
+                message
+            );
+        }
+        result => panic!("Expected ModuleError, found {:?}", result),
+    }
+    match scrutinize_with_path(&program, Some(&path)) {
+        Err(errors) => assert!(errors[0].message.contains("unless file access is allowed")),
+        Ok(()) => panic!("Expected a module error"),
+    }
+
+    let mut env = Environment::with_script_path(path);
+    env.set_file_access(true);
+    match evaluate(&program[0], &mut env) {
+        Err(EvalError::ModuleError(message, _)) => {
+            assert!(message.ends_with("at line 1, column 5"), "{}", message);
+            assert!(!message.contains("root:x"), "{}", message);
+        }
+        result => panic!("Expected ModuleError, found {:?}", result),
+    }
+}
+
+#[test]
+fn test_invoke_error_names_module_file() {
+    let dir = write_scripts(
+        "invoke_error_file",
+        &[
+            (
+                "lib.aby",
+                "engrave broken() -> arcana {\n    reveal missing;\n};\n",
+            ),
+            ("main.aby", "invoke \"lib.aby\";\nbroken();\n"),
+        ],
+    );
+
+    match run_script(dir.join("main.aby")) {
+        Err(EvalError::UndefinedVariable(name, line_info)) => {
+            assert_eq!(name, "missing");
+            let line_info = line_info.unwrap();
+            assert_eq!(line_info.line, 2);
+            assert!(line_info.file.unwrap().ends_with("lib.aby"));
+        }
+        result => panic!("Expected UndefinedVariable, found {:?}", result),
+    }
+}
+
+#[test]
+fn test_scrutinize_invoke() {
+    let dir = write_scripts(
+        "scrutinize_invoke",
+        &[
+            ("math.aby", MATH),
+            (
+                "main.aby",
+                "invoke \"math.aby\" { square, PI };\nforge n: rune = square(2);\nPI + 1.0;\n",
+            ),
+        ],
+    );
+
+    let path = dir.join("main.aby");
+    let program = build_program(&fs::read_to_string(&path).unwrap());
+    match scrutinize_with_path(&program, Some(&path)) {
+        Err(errors) => {
+            let lines: Vec<usize> = errors
+                .iter()
+                .map(|e| e.line_info.as_ref().unwrap().line)
+                .collect();
+            assert_eq!(lines, vec![2]);
+        }
+        Ok(()) => panic!("Expected a type error"),
+    }
+}
+
+#[test]
+fn test_format_invoke() {
+    let input = r#"invoke "lib/math.aby";
+invoke "lib/math.aby" { square, PI };"#;
+
+    let formatted: Vec<String> = build_program(input)
+        .iter()
+        .map(|ast| format_ast(ast, 0))
+        .collect();
+    assert_eq!(formatted.join("\n"), input);
+}
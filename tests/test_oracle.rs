@@ -10,7 +10,7 @@ fn test_oracle_simple_positive() {
     oracle {
         (x > 0) => "x is positive";
         (x < 0) => "x is negative";
-        _ => reveal("x is zero");
+        _ => "x is zero";
     };
     "#;
     match test_base(input) {
@@ -42,8 +42,8 @@ fn test_oracle_with_omen_hex() {
     let input = r#"
     forge x: arcana = -1;
     oracle (x > 0) {
-        (boon) => reveal("x is positive");
-        (hex) => reveal("x is negative or zero");
+        (boon) => "x is positive";
+        (hex) => "x is negative or zero";
     };
     "#;
     match test_base(input) {
@@ -93,10 +93,10 @@ fn test_oracle_with_multiple_conditions_1() {
     forge a: arcana = 3;
     forge b: arcana = 2;
     oracle (a, b) {
-        (1, 2) => reveal("a is 1 and b is 2");
-        (_, 2) => reveal("a is not 1 and b is 2");
-        (1, _) => reveal("a is 1 and b is not 2");
-        _ => reveal("a is not 1 and b is not 2");
+        (1, 2) => "a is 1 and b is 2";
+        (_, 2) => "a is not 1 and b is 2";
+        (1, _) => "a is 1 and b is not 2";
+        _ => "a is not 1 and b is not 2";
     };
     "#;
     match test_base(input) {
@@ -111,10 +111,10 @@ fn test_oracle_with_multiple_conditions_1() {
 fn test_oracle_with_multiple_conditions_2() {
     let input = r#"
     oracle (a = 1, b = 3) {
-        (a == 1 && b == 2) => reveal("a is 1 and b is 2");
-        (a != 1 && b == 2) => reveal("a is not 1 and b is 2");
-        (a == 1 && b != 2) => reveal("a is 1 and b is not 2");
-        _ => reveal("a is not 1 and b is not 2");
+        (a == 1 && b == 2) => "a is 1 and b is 2";
+        (a != 1 && b == 2) => "a is not 1 and b is 2";
+        (a == 1 && b != 2) => "a is 1 and b is not 2";
+        _ => "a is not 1 and b is not 2";
     };
     "#;
     match test_base(input) {
@@ -128,21 +128,28 @@ fn test_oracle_with_multiple_conditions_2() {
 #[test]
 fn test_oracle_with_block_and_reveal() {
     let input = r#"
-    forge x: arcana = -10;
-    forge y: arcana = oracle (x > 0) {
-        (boon) => reveal(x);
-        (hex) => {
-            forge z: arcana = x + 5;
-            oracle (z > 0) {
-                (boon) => reveal(x + 5);
-                (hex) => reveal(x - 5);
-            };
-        }
+    engrave classify(x: arcana) -> arcana {
+        forge y: arcana = oracle (x > 0) {
+            (boon) => reveal x;
+            (hex) => {
+                forge z: arcana = x + 5;
+                oracle (z > 0) {
+                    (boon) => x + 5;
+                    (hex) => x - 5;
+                };
+            }
+        };
+        reveal y * 100;
     };
-    y;
+    classify(-10);
+    classify(3);
     "#;
     match test_base(input) {
-        Ok(results) => assert!(matches!(results[2], EvalResult::Arcana(-15))),
+        Ok(results) => {
+            // `reveal` returns from the function, skipping the rest of its body.
+            assert!(matches!(results[1], EvalResult::Arcana(-1500)));
+            assert!(matches!(results[2], EvalResult::Arcana(3)));
+        }
         Err(e) => panic!("Error: {:?}", e),
     }
 }
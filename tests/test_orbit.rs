@@ -1,6 +1,6 @@
 mod test_base;
 
-use abyss_lang::eval::EvalResult;
+use abyss_lang::{eval::EvalResult, interpreter::Interpreter};
 use test_base::test_base;
 
 #[test]
@@ -243,3 +243,28 @@ fn test_infinite_orbit_with_eject() {
         Err(e) => panic!("Error: {:?}", e),
     }
 }
+
+#[test]
+fn test_orbit_control_flow_releases_scopes() {
+    let mut interpreter = Interpreter::new();
+    let depth = interpreter.env().scope_depth();
+    interpreter
+        .eval_str(
+            r#"
+            orbit {
+                eject;
+            };
+            orbit (i = 0..3) {
+                resume;
+            };
+            orbit (i = 0..2) {
+                orbit (j = 0..2) {
+                    eject i;
+                };
+            };
+            "#,
+        )
+        .expect("Failed to evaluate");
+
+    assert_eq!(interpreter.env().scope_depth(), depth);
+}
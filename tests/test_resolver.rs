@@ -0,0 +1,181 @@
+mod test_base;
+
+use abyss_lang::{
+    env::Environment,
+    eval::{EvalError, EvalResult},
+    interpreter::{AbyssError, Interpreter},
+    io::IoContext,
+    resolver::resolve_program,
+};
+use test_base::{build_program, run_both};
+
+/// Evaluates a script with an interpreter and returns its result and everything it wrote.
+fn run(input: &str) -> (Result<EvalResult, AbyssError>, String) {
+    let mut interpreter = Interpreter::new();
+    let (io, output, _) = IoContext::scripted("");
+    interpreter.set_io(io);
+    let result = interpreter.eval_str(input);
+    (result, output.contents())
+}
+
+#[test]
+fn test_resolver_assigns_slots() {
+    let program = build_program(
+        r#"
+        forge total: arcana = 0;
+        orbit (i = 0..2) {
+            forge doubled: arcana = i * 2;
+            oracle (half = doubled / 2) {
+                _ => unveil(i, half, total);
+            };
+        };
+        "#,
+    );
+    let resolved = resolve_program(&program, &Environment::new()).expect("Failed to resolve");
+    let resolved = format!("{:?}", resolved);
+
+    assert!(resolved.contains(r#"VarAssign { name: "total", "#));
+    assert!(resolved.contains("slot: Some(Slot { depth: 0, index: 1 })"));
+    assert!(resolved.contains(r#"Var("doubled", Some(Slot { depth: 1, index: 1 })"#));
+    assert!(resolved.contains(r#"Var("i", Some(Slot { depth: 1, index: 0 })"#));
+    assert!(resolved.contains(r#"Var("half", Some(Slot { depth: 0, index: 0 })"#));
+    assert!(resolved.contains(r#"Var("total", Some(Slot { depth: 2, index: 0 })"#));
+}
+
+#[test]
+fn test_resolver_rejects_undefined_variables() {
+    let program = build_program(
+        r#"
+        forge x: arcana = 1;
+        unveil(x + y);
+        "#,
+    );
+    let error = resolve_program(&program, &Environment::new()).unwrap_err();
+    assert!(matches!(
+        error,
+        EvalError::UndefinedVariable(ref name, Some(ref info)) if name == "y" && info.line == 3
+    ));
+
+    let program = build_program("forge x: arcana = x + 1;");
+    assert!(resolve_program(&program, &Environment::new()).is_err());
+}
+
+#[test]
+fn test_resolver_rejects_reads_before_forge() {
+    let (result, output) = run(r#"
+        unveil("before");
+        orbit (i = 0..2) {
+            unveil(i);
+            unveil(later);
+            forge later: arcana = i;
+        };
+        "#);
+
+    // The orbit is rejected before any of it runs.
+    assert_eq!(output, "before\n");
+    assert!(matches!(
+        result,
+        Err(AbyssError::Eval(EvalError::UndefinedVariable(ref name, Some(ref info))))
+            if name == "later" && info.line == 5
+    ));
+}
+
+#[test]
+fn test_resolver_allows_enclosing_and_late_names_in_functions() {
+    let (result, output) = run(r#"
+        engrave counter() -> arcana {
+            forge morph count: arcana = 0;
+            engrave bump() {
+                count += 1;
+            };
+            bump();
+            bump();
+            reveal count + total;
+        };
+        forge total: arcana = 100;
+        unveil(counter());
+        "#);
+
+    assert!(result.is_ok());
+    assert_eq!(output, "102\n");
+}
+
+#[test]
+fn test_resolver_shadowing_and_branch_forges() {
+    let (result, output) = run(r#"
+        forge x: arcana = 1;
+        orbit (i = 0..2) {
+            oracle (i) {
+                (0) => forge x: arcana = 2;
+                _ => unveil(x);
+            };
+            forge x: arcana = 3;
+            unveil(x);
+        };
+        x;
+        "#);
+
+    assert_eq!(output, "3\n1\n3\n");
+    assert!(matches!(result, Ok(EvalResult::Arcana(1))));
+}
+
+#[test]
+fn test_resolver_rejects_undefined_names_in_functions() {
+    let (result, output) = run(r#"
+        unveil("before");
+        engrave broken(n: arcana) -> arcana {
+            reveal n + missing;
+        };
+        unveil("after");
+        "#);
+
+    assert_eq!(output, "before\n");
+    assert!(matches!(
+        result,
+        Err(AbyssError::Eval(EvalError::UndefinedVariable(ref name, Some(ref info))))
+            if name == "missing" && info.line == 4
+    ));
+
+    // Globals forged and functions engraved after a function are known to it.
+    let (result, output) = run(r#"
+        engrave pick() -> arcana {
+            forge chosen: engrave(arcana) -> arcana = double;
+            reveal chosen(base);
+        };
+        engrave double(n: arcana) -> arcana {
+            reveal n * 2;
+        };
+        forge base: arcana = 21;
+        unveil(pick());
+        "#);
+    assert!(result.is_ok());
+    assert_eq!(output, "42\n");
+}
+
+#[test]
+fn test_resolver_slots_reach_closures_and_globals() {
+    let input = r#"
+        forge offset: arcana = 10;
+        engrave make(step: arcana) -> engrave() -> arcana {
+            forge morph count: arcana = 0;
+            reveal engrave() -> arcana {
+                count += step;
+                reveal count + offset;
+            };
+        };
+        forge tick: engrave() -> arcana = make(5);
+        tick();
+        unveil(tick());
+        "#;
+    let resolved =
+        resolve_program(&build_program(input), &Environment::new()).expect("Failed to resolve");
+    let resolved = format!("{:?}", resolved);
+
+    assert!(resolved.contains(r#"slot: Some(Slot { depth: 1, index: 1 })"#));
+    assert!(resolved.contains(r#"Var("step", Some(Slot { depth: 1, index: 0 })"#));
+    assert!(resolved.contains(r#"Var("offset", Some(Slot { depth: 2, index: 0 })"#));
+
+    // Both engines read the slots of the closure and of the globals.
+    let run = run_both(input);
+    assert_eq!(run.output, "20\n");
+}
@@ -1,6 +1,10 @@
 mod test_base;
 
-use abyss_lang::eval::EvalResult;
+use abyss_lang::{
+    eval::EvalResult,
+    format::format_ast,
+    parser::{build_ast, parse, Rule},
+};
 use test_base::test_base;
 
 #[test]
@@ -153,3 +157,71 @@ fn test_trans_with_assignment_operator() {
         Err(e) => panic!("Error: {:?}", e),
     }
 }
+
+#[test]
+fn test_rune_escape_sequences() {
+    let input = r#""say \"hi\"\t\\ \u{3b1}\n" == "say " + "\u{22}hi\u{22}\u{9}\u{5c} α\u{a}";"#;
+    match test_base(input) {
+        Ok(results) => assert!(matches!(results[0], EvalResult::Omen(true))),
+        Err(e) => panic!("Error: {:?}", e),
+    }
+    match test_base(r#""say \"hi\"\t\\ \u{3b1}\n";"#) {
+        Ok(results) => {
+            assert!(matches!(&results[0], EvalResult::Rune(s) if s == "say \"hi\"\t\\ α\n"))
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_invalid_rune_escapes_are_rejected() {
+    for (input, message) in [
+        (r#""bad \q";"#, "Unknown escape sequence \\q"),
+        (r#""bad \u{110000}";"#, "Invalid unicode escape \\u{110000}"),
+        (
+            r#""bad \u41";"#,
+            "Expected \\u{...} with a hexadecimal code point",
+        ),
+    ] {
+        match test_base(input) {
+            Ok(results) => panic!("Expected an error, got {:?}", results),
+            Err(e) => assert!(e.to_string().contains(message), "Unexpected error {}", e),
+        }
+    }
+}
+
+#[test]
+fn test_raw_and_multiline_runes() {
+    let input = r##"
+        r"C:\dir\new";
+        r#"say "hi""#;
+        """
+Dear "friend",
+\tbye""";
+    "##;
+    match test_base(input) {
+        Ok(results) => {
+            assert!(matches!(&results[0], EvalResult::Rune(s) if s == "C:\\dir\\new"));
+            assert!(matches!(&results[1], EvalResult::Rune(s) if s == "say \"hi\""));
+            assert!(matches!(&results[2], EvalResult::Rune(s) if s == "Dear \"friend\",\n\tbye"));
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_format_rune_literals() {
+    let input = r##"forge a: rune = "tab\t\"quoted\" \\ \u{7}";
+forge b: rune = r#"raw "\n""#;
+forge c: rune = """
+line "one"
+"two\"""";
+summon("Name: \"", rune);"##;
+    let pair = parse(input).expect("Failed to parse input");
+    let formatted: Vec<String> = pair
+        .into_inner()
+        .filter(|p| p.as_rule() != Rule::EOI)
+        .map(|p| format_ast(&build_ast(p).expect("Failed to build AST"), 0))
+        .collect();
+    assert_eq!(formatted.join("\n"), input);
+}
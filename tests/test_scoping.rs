@@ -0,0 +1,152 @@
+mod test_base;
+
+use abyss_lang::eval::EvalError;
+use std::fs;
+use std::process::Command;
+use test_base::{output_of, run_on, run_on_with, Run};
+
+#[test]
+fn test_functions_do_not_see_caller_locals() {
+    let script = r#"engrave peek() -> arcana {
+    reveal hidden;
+};
+engrave caller() -> arcana {
+    forge hidden: arcana = 1;
+    reveal peek();
+};
+caller();
+"#;
+    for use_vm in [false, true] {
+        let result = run_on(script, use_vm).result;
+        assert!(
+            matches!(result, Err(EvalError::UndefinedVariable(ref name, _)) if name == "hidden"),
+            "Unexpected result {:?}",
+            result
+        );
+    }
+}
+
+#[test]
+fn test_closures_see_enclosing_scopes() {
+    let output = output_of(
+        r#"forge base: arcana = 100;
+engrave peek() -> arcana {
+    reveal base;
+};
+engrave counter(start: arcana) -> arcana {
+    forge morph count: arcana = start;
+    engrave bump(by: arcana) {
+        count += by;
+    };
+    orbit (i = 1..4) {
+        bump(i);
+    };
+    forge base: arcana = 1;
+    reveal count + peek();
+};
+unveil(counter(10));
+orbit (base = 0..2) {
+    engrave twice() -> arcana {
+        reveal base * 2;
+    };
+    unveil(twice(), " ", peek());
+};
+"#,
+    );
+    assert_eq!(output, "116\n0 100\n2 100\n");
+}
+
+#[test]
+fn test_recursion_in_nested_and_global_functions() {
+    let output = output_of(
+        r#"engrave is_even(n: arcana) -> omen {
+    oracle (n == 0) {
+        (boon) => reveal boon;
+    };
+    reveal is_odd(n - 1);
+};
+engrave is_odd(n: arcana) -> omen {
+    oracle (n == 0) {
+        (boon) => reveal hex;
+    };
+    reveal is_even(n - 1);
+};
+engrave sum_to(limit: arcana) -> arcana {
+    engrave go(n: arcana) -> arcana {
+        oracle (n > limit) {
+            (boon) => reveal 0;
+        };
+        reveal n + go(n + 1);
+    };
+    reveal go(1);
+};
+unveil(is_even(10), " ", is_odd(7), " ", sum_to(4));
+"#,
+    );
+    assert_eq!(output, "boon boon 10\n");
+}
+
+#[test]
+fn test_scope_warnings_keep_dynamic_lookup() {
+    let script = r#"forge count: arcana = 1;
+engrave peek() -> arcana {
+    reveal count + extra;
+};
+engrave caller() -> arcana {
+    forge count: arcana = 7;
+    forge extra: arcana = 0;
+    reveal peek();
+};
+unveil(caller());
+unveil(caller());
+"#;
+    for use_vm in [false, true] {
+        let Run {
+            result,
+            output,
+            errors: error,
+            ..
+        } = run_on_with(script, use_vm, "", |env| env.set_scope_warnings(true));
+        assert!(result.is_ok(), "Unexpected result {:?}", result);
+        assert_eq!(output, "7\n7\n");
+        assert_eq!(
+            error,
+            "Warning at line 3, column 12: peek uses the variable count of its caller; \
+with lexical scoping it would use the variable count visible where peek is engraved\n\
+Warning at line 3, column 20: peek uses the variable extra of its caller; \
+with lexical scoping it would use no variable, as extra is not visible where peek is engraved\n"
+        );
+    }
+}
+
+#[test]
+fn test_scope_warnings_run_scripts_through_the_cli() {
+    let script = r#"engrave peek() -> arcana {
+    reveal hidden;
+};
+engrave caller() -> arcana {
+    forge hidden: arcana = 4;
+    reveal peek();
+};
+unveil(caller());
+"#;
+    let path =
+        std::env::temp_dir().join(format!("abyss_scope_warnings_{}.aby", std::process::id()));
+    fs::write(&path, script).unwrap();
+    for engine in ["tree", "vm"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_abyss"))
+            .arg("invoke")
+            .arg(&path)
+            .arg("--scope-warnings")
+            .args(["--engine", engine])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "4\n");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "Warning at line 2, column 12: peek uses the variable hidden of its caller; \
+with lexical scoping it would use no variable, as hidden is not visible where peek is engraved\n"
+        );
+    }
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,144 @@
+mod test_base;
+
+use abyss_lang::{
+    eval::EvalResult,
+    format::format_ast,
+    parser::{build_ast, parse, Rule},
+};
+use test_base::test_base;
+
+#[test]
+fn test_sigil_constructor_and_field_read() {
+    let input = r#"
+    sigil Point {
+        x: arcana,
+        y: arcana,
+    };
+    forge p: Point = Point { y: 2, x: 1 };
+    p.x + p.y;
+    p;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            if let EvalResult::Arcana(n) = results[2] {
+                assert_eq!(n, 3);
+            } else {
+                panic!("Expected Arcana result");
+            }
+            assert_eq!(results[3].to_string(), "Point { x: 1, y: 2 }");
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_sigil_field_assignment() {
+    let input = r#"
+    sigil Hero {
+        name: rune,
+        level: arcana,
+        items: grimoire<rune>,
+    };
+    forge morph hero: Hero = Hero { name: "Lia", level: 1, items: [] };
+    hero.level += 1;
+    hero.name = "Lia the Brave";
+    hero.items = ["staff"];
+    hero.items[0] = "wand";
+    hero;
+    "#;
+
+    match test_base(input) {
+        Ok(results) => assert_eq!(
+            results[6].to_string(),
+            r#"Hero { name: "Lia the Brave", level: 2, items: ["wand"] }"#
+        ),
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_sigil_nested_and_function() {
+    let input = r#"
+    sigil Point {
+        x: arcana,
+        y: arcana,
+    };
+    sigil Line {
+        from: Point,
+        to: Point,
+    };
+    engrave length(line: Line) -> arcana {
+        reveal (line.to.x - line.from.x) + (line.to.y - line.from.y);
+    };
+    forge morph line: Line = Line { from: Point { x: 0, y: 0 }, to: Point { x: 3, y: 4 } };
+    line.to.x *= 2;
+    length(line);
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            if let EvalResult::Arcana(n) = results[5] {
+                assert_eq!(n, 10);
+            } else {
+                panic!("Expected Arcana result");
+            }
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_sigil_immutable_field_assignment() {
+    let input = r#"
+    sigil Point {
+        x: arcana,
+    };
+    forge p: Point = Point { x: 1 };
+    p.x += 1;
+    "#;
+
+    assert!(test_base(input).is_err());
+}
+
+#[test]
+fn test_sigil_field_errors() {
+    let missing = r#"
+    sigil Point { x: arcana, y: arcana };
+    forge p: Point = Point { x: 1 };
+    "#;
+    let unknown = r#"
+    sigil Point { x: arcana };
+    forge p: Point = Point { x: 1 };
+    p.z;
+    "#;
+    let mismatch = r#"
+    sigil Point { x: arcana };
+    forge p: Point = Point { x: "one" };
+    "#;
+
+    for input in [missing, unknown, mismatch] {
+        match test_base(input) {
+            Err(e) => assert!(e.to_string().to_lowercase().contains("field"), "{}", e),
+            Ok(_) => panic!("Expected a field error"),
+        }
+    }
+}
+
+#[test]
+fn test_format_sigil() {
+    let input = r#"sigil Point {
+    x: arcana,
+    y: arcana,
+};
+forge morph p: Point = Point { x: 1, y: 2 };
+p.x += p.y;"#;
+
+    let pair = parse(input).expect("Failed to parse input");
+    let formatted: Vec<String> = pair
+        .into_inner()
+        .filter(|p| p.as_rule() != Rule::EOI)
+        .map(|p| format_ast(&build_ast(p).expect("Failed to build AST"), 0))
+        .collect();
+    assert_eq!(formatted.join("\n"), input);
+}
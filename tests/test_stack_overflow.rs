@@ -0,0 +1,80 @@
+mod test_base;
+
+use abyss_lang::{
+    env::DEFAULT_MAX_CALL_DEPTH,
+    eval::{EvalError, EvalResult},
+};
+use test_base::run_on_with;
+
+const SUM: &str = r#"engrave sum(n: arcana) -> arcana {
+    oracle (n <= 1) {
+        (boon) => reveal n;
+        (hex) => reveal n + sum(n - 1);
+    };
+};
+"#;
+
+/// Runs a script on the given engine with the given maximum call depth.
+fn run_on(input: &str, max_call_depth: usize, use_vm: bool) -> Result<EvalResult, EvalError> {
+    run_on_with(input, use_vm, "", |env| {
+        env.set_max_call_depth(max_call_depth)
+    })
+    .result
+}
+
+#[test]
+fn test_max_call_depth() {
+    for use_vm in [false, true] {
+        let result = run_on(&format!("{}sum(50);", SUM), 50, use_vm);
+        assert!(matches!(result, Ok(EvalResult::Arcana(1275))));
+
+        let error = run_on(&format!("{}sum(51);", SUM), 50, use_vm).unwrap_err();
+        assert!(matches!(error, EvalError::StackOverflow(_, _)));
+        assert_eq!(
+            error.to_string(),
+            "Stack overflow: calling sum exceeds the maximum call depth of 50"
+        );
+        // The error points at the call that exceeds the depth.
+        let line_info = error.line_info().unwrap();
+        assert_eq!((line_info.line, line_info.column), (4, 29));
+    }
+}
+
+#[test]
+fn test_stack_overflow_is_caught_by_attempt() {
+    let script = format!(
+        "{}forge r: cursed<arcana> = attempt(sum(100));\nforge ok: arcana = sum(10);\nr;",
+        SUM
+    );
+    for use_vm in [false, true] {
+        match run_on(&script, 20, use_vm) {
+            Ok(EvalResult::Curse(message, _)) => assert_eq!(
+                message,
+                "Stack overflow: calling sum exceeds the maximum call depth of 20"
+            ),
+            result => panic!("Expected a curse, got {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn test_deep_recursion_does_not_exhaust_native_stack() {
+    // Test threads have a small native stack, which the default depth would exceed without
+    // growing it.
+    let script = format!("{}sum({});", SUM, DEFAULT_MAX_CALL_DEPTH);
+    for use_vm in [false, true] {
+        let result = run_on(&script, DEFAULT_MAX_CALL_DEPTH, use_vm);
+        assert!(matches!(result, Ok(EvalResult::Arcana(500500))));
+    }
+}
+
+#[test]
+fn test_recursion_at_documented_call_depth() {
+    // The README shows how to allow a depth of 100000.
+    let depth = 100000;
+    let script = format!("{}sum({});", SUM, depth);
+    for use_vm in [false, true] {
+        let result = run_on(&script, depth, use_vm);
+        assert!(matches!(result, Ok(EvalResult::Arcana(5000050000))));
+    }
+}
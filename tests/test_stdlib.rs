@@ -0,0 +1,197 @@
+mod test_base;
+
+use abyss_lang::{
+    ast::Type,
+    env::Environment,
+    eval::{evaluate, EvalError, EvalResult},
+    typeck::scrutinize_in,
+};
+use test_base::{build_program, test_base};
+
+/// Evaluates the input in the given environment and returns the result of every statement.
+fn run_in(input: &str, env: &mut Environment) -> Result<Vec<EvalResult>, EvalError> {
+    build_program(input)
+        .iter()
+        .map(|ast| evaluate(ast, env))
+        .collect()
+}
+
+#[test]
+fn test_math_functions() {
+    let input = r#"
+    abs(-3);
+    abs(-2.5);
+    min(3, 4);
+    max(1.5, 0.5);
+    sqrt(16.0);
+    floor(2.7);
+    ceil(2.1);
+    round(-2.5);
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            let results: Vec<String> = results.iter().map(|r| r.to_string()).collect();
+            assert_eq!(results, vec!["3", "2.5", "3", "1.5", "4", "2", "3", "-3"]);
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_rune_functions() {
+    let input = r#"
+    upper("abyss");
+    lower("ABYSS");
+    trim("  rune  ");
+    substring("grimoire", 0, 4);
+    split("fire,ice,wind", ",");
+    join(["a", "b", "c"], "-");
+    contains("abyss", "yss");
+    replace("hex", "h", "v");
+    chars("ルーン");
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            let results: Vec<String> = results.iter().map(|r| r.to_string()).collect();
+            assert_eq!(
+                results,
+                vec![
+                    "ABYSS",
+                    "abyss",
+                    "rune",
+                    "grim",
+                    r#"["fire", "ice", "wind"]"#,
+                    "a-b-c",
+                    "boon",
+                    "vex",
+                    r#"["ル", "ー", "ン"]"#,
+                ]
+            );
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_conversion_functions() {
+    let input = r#"
+    forge bad: cursed<arcana> = parse_arcana("12x");
+    bad;
+    forge n: arcana = parse_arcana(" 42 ");
+    n + 1;
+    parse_aether("2.5");
+    "#;
+
+    match test_base(input) {
+        Ok(results) => {
+            assert_eq!(results[1].to_string(), r#"curse("12x is not an arcana")"#);
+            assert!(matches!(results[3], EvalResult::Arcana(43)));
+            assert!(matches!(results[4], EvalResult::Aether(n) if n == 2.5));
+        }
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_native_signature_errors() {
+    let mut env = Environment::new();
+    match run_in("\nabs(\"x\");", &mut env) {
+        Err(EvalError::TypeError(message, line_info)) => {
+            assert!(message.contains("abs"));
+            assert_eq!(line_info.unwrap().line, 2);
+        }
+        result => panic!("Expected TypeError, found {:?}", result),
+    }
+    assert!(matches!(
+        run_in("min(1);", &mut env),
+        Err(EvalError::TypeError(_, _))
+    ));
+    match run_in("substring(\"abc\", 2, 5);", &mut env) {
+        Err(EvalError::InvalidOperation(message, _)) => {
+            assert!(message.starts_with("substring: "))
+        }
+        result => panic!("Expected InvalidOperation, found {:?}", result),
+    }
+    match run_in("abs(-9223372036854775807 - 1);", &mut env) {
+        Err(EvalError::InvalidOperation(message, _)) => {
+            assert!(message.contains("does not fit in arcana"))
+        }
+        result => panic!("Expected InvalidOperation, found {:?}", result),
+    }
+}
+
+#[test]
+fn test_rounding_is_checked() {
+    let mut env = Environment::new();
+    match run_in("floor(10.0 ** 30.0);", &mut env) {
+        Err(EvalError::ArithmeticOverflow(operation, _)) => {
+            assert_eq!(operation, "1000000000000000019884624838656")
+        }
+        result => panic!("Expected ArithmeticOverflow, found {:?}", result),
+    }
+    match run_in("floor(0.0 / 0.0);", &mut env) {
+        Err(EvalError::InvalidOperation(message, _)) => {
+            assert_eq!(message, "floor: Cannot round NaN to an arcana")
+        }
+        result => panic!("Expected InvalidOperation, found {:?}", result),
+    }
+    assert!(matches!(
+        run_in("round(1.0 / 0.0);", &mut env),
+        Err(EvalError::InvalidOperation(_, _))
+    ));
+    assert!(matches!(
+        run_in("ceil(-9223372036854775808.0);", &mut env).unwrap()[0],
+        EvalResult::Arcana(i64::MIN)
+    ));
+}
+
+#[test]
+fn test_engrave_shadows_native() {
+    let input = r#"
+    engrave abs(n: arcana) -> arcana {
+        reveal 0;
+    };
+    abs(-5);
+    "#;
+
+    match test_base(input) {
+        Ok(results) => assert!(matches!(results[1], EvalResult::Arcana(0))),
+        Err(e) => panic!("Error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_register_native() {
+    let mut env = Environment::new();
+    env.register_native(
+        "mana_cost",
+        vec![Type::Rune, Type::Arcana],
+        Type::Arcana,
+        |args| match args.as_slice() {
+            [EvalResult::Rune(spell), EvalResult::Arcana(level)] => {
+                Ok(EvalResult::Arcana(spell.len() as i64 * level))
+            }
+            _ => Err("unexpected arguments".to_string()),
+        },
+    );
+
+    let input = r#"forge cost: arcana = mana_cost("fire", 3); cost;"#;
+    assert!(scrutinize_in(&build_program(input), &env).is_ok());
+    match run_in(input, &mut env) {
+        Ok(results) => assert!(matches!(results[1], EvalResult::Arcana(12))),
+        Err(e) => panic!("Error: {:?}", e),
+    }
+
+    let wrong = r#"forge cost: rune = mana_cost(3, "fire");"#;
+    match scrutinize_in(&build_program(wrong), &env) {
+        Err(errors) => {
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0]
+                .message
+                .contains("No signature of function mana_cost"));
+        }
+        Ok(()) => panic!("Expected type errors"),
+    }
+}
@@ -0,0 +1,149 @@
+mod test_base;
+
+use abyss_lang::{
+    ast::LineInfo,
+    env::{CallFrame, Environment},
+    eval::{write_traceback, EvalError},
+    interpreter::Interpreter,
+    io::IoContext,
+};
+use std::fs;
+use test_base::run_in;
+
+/// Runs a script on the given engine, returning the error that stopped it and the calls that
+/// were in progress, as `name@file:line:column`.
+fn trace_on(input: &str, env: &mut Environment, use_vm: bool) -> (EvalError, Vec<String>) {
+    let error = run_in(input, env, use_vm).expect_err("The script should fail");
+    let trace = env
+        .take_trace()
+        .iter()
+        .map(|frame| {
+            let info = frame.line_info.as_ref().unwrap();
+            let file = info
+                .file
+                .as_deref()
+                .map(|file| file.rsplit('/').next().unwrap());
+            format!(
+                "{}@{}:{}:{}",
+                frame.function,
+                file.unwrap_or("-"),
+                info.line,
+                info.column
+            )
+        })
+        .collect();
+    (error, trace)
+}
+
+/// Runs a script on both engines and checks that they report the same error and calls.
+fn trace_both(input: &str) -> (EvalError, Vec<String>) {
+    let (error, trace) = trace_on(input, &mut Environment::new(), false);
+    let (vm_error, vm_trace) = trace_on(input, &mut Environment::new(), true);
+    assert_eq!(error.to_string(), vm_error.to_string());
+    assert_eq!(error.line_info(), vm_error.line_info());
+    assert_eq!(trace, vm_trace);
+    (error, trace)
+}
+
+#[test]
+fn test_trace_of_nested_calls() {
+    let (error, trace) = trace_both(
+        r#"engrave divide(a: arcana, b: arcana) -> arcana {
+    reveal a / b;
+};
+engrave average(total: arcana, count: arcana) -> arcana {
+    forge caught: cursed<arcana> = attempt(divide(1, 0));
+    reveal divide(total, count);
+};
+unveil(average(10, 0));
+"#,
+    );
+
+    // The error caught by `attempt` leaves no trace behind.
+    assert!(matches!(error, EvalError::DivisionByZero(_)));
+    assert_eq!(error.line_info().unwrap().line, 2);
+    assert_eq!(trace, vec!["average@-:8:8", "divide@-:6:12"]);
+
+    // An error outside any call has no call in progress.
+    let (_, trace) = trace_both("engrave f() -> arcana {\n    reveal 1;\n};\nunveil(f() / 0);\n");
+    assert!(trace.is_empty());
+}
+
+#[test]
+fn test_return_type_mismatch_points_at_call() {
+    // The type checker would reject the script, so it is run without it.
+    let (error, trace) = trace_both(
+        r#"engrave name() -> arcana {
+    reveal "Lia";
+};
+engrave greet() -> rune {
+    forge n: arcana = name();
+    reveal "hi";
+};
+unveil(greet());
+"#,
+    );
+
+    assert!(error.to_string().contains("return value of function name"));
+    assert_eq!(error.line_info().unwrap().line, 5);
+    assert_eq!(trace, vec!["greet@-:8:8"]);
+}
+
+#[test]
+fn test_trace_through_module() {
+    let dir = std::env::temp_dir().join(format!("abyss_traceback_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("lib.aby"),
+        "engrave inner(n: arcana) -> arcana {\n    reveal 10 / n;\n};\nengrave outer(n: arcana) -> arcana {\n    reveal inner(n);\n};\n",
+    )
+    .unwrap();
+    let script = "invoke \"lib.aby\";\nunveil(outer(0));\n";
+
+    for use_vm in [false, true] {
+        let mut env = Environment::with_script_path(dir.join("main.aby"));
+        let (error, trace) = trace_on(script, &mut env, use_vm);
+        assert!(error
+            .line_info()
+            .unwrap()
+            .file
+            .unwrap()
+            .ends_with("lib.aby"));
+        assert_eq!(trace, vec!["outer@-:2:8", "inner@lib.aby:5:12"]);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_traceback_output() {
+    let script = "engrave f(n: arcana) -> arcana {\n    reveal 1 / n;\n};\nunveil(f(0));\n";
+    let trace = vec![CallFrame {
+        function: "f".to_string(),
+        line_info: Some(LineInfo {
+            line: 4,
+            column: 8,
+            file: None,
+        }),
+    }];
+    let mut out = Vec::new();
+    colored::control::set_override(false);
+    write_traceback(&mut out, script, &trace).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "Traceback (most recent call first):\n  in f, called at line 4, column 8\n    unveil(f(0));\n           ^\n"
+    );
+
+    // The interpreter reports the calls along with the error.
+    let (io, _, error) = IoContext::scripted("");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_io(io);
+    let e = interpreter.eval_str(script).unwrap_err();
+    interpreter.report_error(script, &e);
+    let error = error.contents();
+    assert!(error.contains("Division by zero"), "{}", error);
+    assert!(
+        error.contains("in f, called at line 4, column 8"),
+        "{}",
+        error
+    );
+}
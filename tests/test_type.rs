@@ -34,6 +34,7 @@ fn test_cast_aether_to_arcana() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_cast_rune_to_aether() {
     let input = "trans(\"3.14\" as aether);";
     match test_base(input) {
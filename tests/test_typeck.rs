@@ -0,0 +1,405 @@
+use abyss_lang::{
+    ast::AST,
+    parser::{build_ast, parse, Rule},
+    typeck::{scrutinize, TypeCheckError},
+};
+
+fn scrutinize_base(input: &str) -> Result<(), Vec<TypeCheckError>> {
+    let pair = parse(input).expect("Failed to parse input");
+    let program: Vec<AST> = pair
+        .into_inner()
+        .filter(|p| p.as_rule() != Rule::EOI)
+        .map(|p| build_ast(p).expect("Failed to build AST"))
+        .collect();
+    scrutinize(&program)
+}
+
+#[test]
+fn test_scrutinize_valid_script() {
+    let input = r#"
+    engrave add(a: arcana, b: arcana) -> arcana {
+        reveal a + b;
+    };
+    forge morph sum: arcana = add(1, 2);
+    orbit(i = 0..10) {
+        sum += i;
+    };
+    forge message: rune = "sum = " + trans(sum as rune);
+    unveil(message);
+    "#;
+
+    assert!(scrutinize_base(input).is_ok());
+}
+
+#[test]
+fn test_scrutinize_forge_type_mismatch() {
+    let input = r#"
+    forge x: arcana = "a";
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            assert_eq!(errors.len(), 1);
+            let line_info = errors[0].line_info.clone().unwrap();
+            assert_eq!(line_info.line, 2);
+        }
+        Ok(_) => panic!("Expected a type error for forge with mismatched value"),
+    }
+}
+
+#[test]
+fn test_scrutinize_argument_type_mismatch() {
+    let input = r#"
+    engrave double(n: arcana) -> arcana {
+        reveal n * 2;
+    };
+    double(1.5);
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0].message.contains("Argument 1 of function double"));
+            assert_eq!(errors[0].line_info.clone().unwrap().line, 5);
+        }
+        Ok(_) => panic!("Expected a type error for mismatched argument"),
+    }
+}
+
+#[test]
+fn test_scrutinize_argument_count_mismatch() {
+    let input = r#"
+    engrave add(a: arcana, b: arcana) -> arcana {
+        reveal a + b;
+    };
+    add(1);
+    "#;
+
+    assert!(scrutinize_base(input).is_err());
+}
+
+#[test]
+fn test_scrutinize_reveal_type_mismatch() {
+    let input = r#"
+    engrave name() -> rune {
+        reveal 42;
+    };
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].line_info.clone().unwrap().line, 3);
+        }
+        Ok(_) => panic!("Expected a type error for mismatched reveal"),
+    }
+}
+
+#[test]
+fn test_scrutinize_error_in_untaken_branch() {
+    let input = r#"
+    forge x: arcana = 1;
+    oracle {
+        (x > 100) => {
+            forge y: rune = x;
+        }
+        _ => unveil("small");
+    };
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => assert_eq!(errors[0].line_info.clone().unwrap().line, 5),
+        Ok(_) => panic!("Expected a type error in an untaken oracle branch"),
+    }
+}
+
+#[test]
+fn test_scrutinize_reports_all_errors() {
+    let input = r#"
+    forge a: arcana = 1.0;
+    forge b: rune = boon;
+    forge c: omen = 1 + "x";
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            let lines: Vec<usize> = errors
+                .iter()
+                .map(|e| e.line_info.clone().unwrap().line)
+                .collect();
+            assert_eq!(lines, vec![2, 3, 4]);
+        }
+        Ok(_) => panic!("Expected every type error to be reported"),
+    }
+}
+
+#[test]
+fn test_scrutinize_undefined_variable_and_function() {
+    let input = r#"
+    unveil(missing);
+    summon_spell(1);
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => assert_eq!(errors.len(), 2),
+        Ok(_) => panic!("Expected errors for undefined names"),
+    }
+}
+
+#[test]
+fn test_scrutinize_recursive_function() {
+    let input = r#"
+    engrave factorial(n: arcana) -> arcana {
+        oracle(n <= 1) {
+            (boon) => reveal 1;
+            (hex) => reveal n * factorial(n - 1);
+        };
+    };
+    forge result: arcana = factorial(5);
+    "#;
+
+    assert!(scrutinize_base(input).is_ok());
+}
+
+#[test]
+fn test_scrutinize_oracle_value() {
+    let input = r#"
+    forge x: arcana = -10;
+    forge y: arcana = oracle (x > 0) {
+        (boon) => x;
+        (hex) => -999;
+    };
+    forge z: rune = oracle (x > 0) {
+        (boon) => x;
+        (hex) => -999;
+    };
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].line_info.clone().unwrap().line, 7);
+        }
+        Ok(_) => panic!("Expected a type error for oracle value"),
+    }
+}
+
+#[test]
+fn test_scrutinize_oracle_branches_of_different_types() {
+    let input = r#"
+    forge x: arcana = 1;
+    forge y: rune = oracle (x > 0) {
+        (boon) => "p";
+        (hex) => 5;
+    };
+    oracle (x > 0) {
+        (boon) => unveil("p");
+        (hex) => 5;
+    };
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(
+                errors[0].message,
+                "Oracle branches have different types: Rune and Arcana"
+            );
+            assert_eq!(errors[0].line_info.clone().unwrap().line, 3);
+        }
+        Ok(_) => panic!("Expected a type error for the oracle branches"),
+    }
+}
+
+#[test]
+fn test_scrutinize_grimoire() {
+    let input = r#"
+    forge morph xs: grimoire<arcana> = [];
+    push(xs, 1);
+    xs[0] += 2;
+    orbit(x = xs) {
+        forge y: rune = x;
+    };
+    push(xs, "three");
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            let lines: Vec<usize> = errors
+                .iter()
+                .map(|e| e.line_info.clone().unwrap().line)
+                .collect();
+            assert_eq!(lines, vec![6, 8]);
+        }
+        Ok(_) => panic!("Expected type errors for grimoire elements"),
+    }
+}
+
+#[test]
+fn test_scrutinize_push_through_field() {
+    let input = r#"
+    sigil Bag { xs: grimoire<arcana> };
+    forge morph bag: Bag = Bag { xs: [] };
+    push(bag.xs, 1);
+    forge rest: Bag = Bag { xs: [] };
+    push(rest.xs, "two");
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+            assert_eq!(
+                messages,
+                vec![
+                    "Cannot push immutable grimoire rest",
+                    "Cannot push Rune into a grimoire of Arcana"
+                ]
+            );
+        }
+        Ok(_) => panic!("Expected type errors for the push into rest.xs"),
+    }
+}
+
+#[test]
+fn test_scrutinize_codex() {
+    let input = r#"
+    forge morph ages: codex<rune, arcana> = [:];
+    ages["alice"] = 30;
+    ages[1] = 2;
+    forge name: arcana = ages["alice"];
+    orbit ((key, age) = ages) {
+        forge years: rune = age;
+    };
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            let lines: Vec<usize> = errors
+                .iter()
+                .map(|e| e.line_info.clone().unwrap().line)
+                .collect();
+            assert_eq!(lines, vec![4, 7]);
+        }
+        Ok(_) => panic!("Expected type errors for codex keys and values"),
+    }
+}
+
+#[test]
+fn test_scrutinize_duplicate_codex_key() {
+    let input = r#"
+    forge ages: codex<rune, arcana> = ["alice": 30,
+        "alice": 31];
+    forge squares: codex<arcana, arcana> = [1: 1, 2: 4];
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(
+                errors[0].message,
+                r#"Duplicate key "alice" in codex literal"#
+            );
+            assert_eq!(errors[0].line_info.clone().unwrap().line, 3);
+        }
+        Ok(()) => panic!("Expected a duplicate key error"),
+    }
+}
+
+#[test]
+fn test_scrutinize_sigil_fields() {
+    let input = r#"
+    sigil Point { x: arcana, y: arcana };
+    forge morph p: Point = Point { x: 1, y: "two" };
+    p.x = 1.5;
+    p.z;
+    forge q: Pointy = p;
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            assert_eq!(errors.len(), 5);
+            assert!(errors[0].message.contains("Field y of sigil Point"));
+            assert!(errors[1].message.contains("p.x"));
+            assert!(errors[2].message.contains("no field z"));
+        }
+        Ok(_) => panic!("Expected type errors naming sigil fields"),
+    }
+}
+
+#[test]
+fn test_scrutinize_cursed() {
+    let input = r#"
+    engrave parse(s: rune) -> cursed<arcana> {
+        reveal attempt(trans(s as arcana));
+    };
+    forge r: cursed<arcana> = parse("1");
+    forge n: arcana = r;
+    forge s: rune = r;
+    r + 1;
+    oracle (r) {
+        (curse(message)) => unveil(message);
+        ("one") => unveil("one");
+    };
+    forge c: cursed<arcana> = curse(1);
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            let lines: Vec<usize> = errors
+                .iter()
+                .map(|e| e.line_info.clone().unwrap().line)
+                .collect();
+            assert_eq!(lines, vec![7, 8, 11, 13]);
+        }
+        Ok(_) => panic!("Expected type errors for cursed values"),
+    }
+}
+
+#[test]
+fn test_scrutinize_file_builtins() {
+    let input = r#"
+    forge text: rune = read("spells.txt");
+    forge lines: grimoire<rune> = read_lines("spells.txt");
+    forge found: omen = exists("spells.txt");
+    write("spells.txt", 1);
+    append(1, text);
+    forge wrong: arcana = read("spells.txt");
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            let lines: Vec<usize> = errors
+                .iter()
+                .map(|e| e.line_info.clone().unwrap().line)
+                .collect();
+            assert_eq!(lines, vec![5, 6, 7]);
+        }
+        Ok(_) => panic!("Expected type errors for file builtins"),
+    }
+}
+
+#[test]
+fn test_scrutinize_native_functions() {
+    let input = r#"
+    forge a: arcana = abs(-1);
+    forge b: aether = abs(-1.5);
+    forge c: arcana = floor(2.5);
+    forge parts: grimoire<rune> = split("a,b", ",");
+    forge d: arcana = sqrt(4.0);
+    abs("one");
+    max(1, 2, 3);
+    "#;
+
+    match scrutinize_base(input) {
+        Err(errors) => {
+            let lines: Vec<usize> = errors
+                .iter()
+                .map(|e| e.line_info.clone().unwrap().line)
+                .collect();
+            assert_eq!(lines, vec![6, 7, 8]);
+            assert!(errors[1].message.contains("No signature of function abs"));
+            assert!(errors[2].message.contains("expects 2 argument(s)"));
+        }
+        Ok(_) => panic!("Expected type errors for native functions"),
+    }
+}
@@ -0,0 +1,379 @@
+mod test_base;
+
+use abyss_lang::env::Environment;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use test_base::{run_in, run_on_with, Run};
+
+/// The outcome of running a script on one engine: the result of its last statement or the
+/// error and the line it occurred on, and everything it wrote to the output.
+type Outcome = (Result<String, (String, Option<usize>)>, String);
+
+/// Runs a script on the given engine with scripted input and captures its outcome.
+fn run_on(input: &str, path: Option<PathBuf>, use_vm: bool) -> Outcome {
+    let Run { result, output, .. } =
+        run_on_with(input, use_vm, "Lia\n", |env| env.set_script_path(path));
+    let result = result
+        .map(|result| format!("{:?}", result))
+        .map_err(|e| (e.to_string(), e.line_info().map(|info| info.line)));
+    (result, output)
+}
+
+/// Runs a script on both engines, checks that they agree and returns the outcome.
+fn run_both(input: &str) -> Outcome {
+    run_both_at(input, None)
+}
+
+/// Runs the script at a path on both engines, checks that they agree and returns the outcome.
+fn run_both_at(input: &str, path: Option<PathBuf>) -> Outcome {
+    let tree = run_on(input, path.clone(), false);
+    let vm = run_on(input, path, true);
+    assert_eq!(tree, vm, "The engines disagree");
+    vm
+}
+
+#[test]
+fn test_vm_recursion_and_loops() {
+    let (result, output) = run_both(
+        r#"
+        engrave fib(n: arcana) -> arcana {
+            oracle (n < 2) {
+                (boon) => reveal n;
+                _ => reveal fib(n - 1) + fib(n - 2);
+            };
+        };
+        forge morph total: arcana = 0;
+        orbit (i = 0..=6) {
+            total += fib(i);
+        };
+        unveil(total);
+        forge morph count: arcana = 0;
+        orbit {
+            count += 1;
+            oracle (count == 5) {
+                (boon) => eject;
+            };
+        };
+        count;
+        "#,
+    );
+
+    assert_eq!(output, "20\n");
+    assert_eq!(result, Ok("Arcana(5)".to_string()));
+}
+
+#[test]
+fn test_vm_labeled_resume_and_eject() {
+    let (result, output) = run_both(
+        r#"
+        orbit (i = 0..3, j = 0..3) {
+            oracle (i == j) {
+                (boon) => resume j;
+            };
+            unveil(i, " ", j);
+        };
+        orbit (i = 0..3) {
+            orbit (j = 0..3) {
+                oracle (j == 1) {
+                    (boon) => resume i;
+                };
+                oracle (i == 2) {
+                    (boon) => eject i;
+                };
+                unveil(i, ":", j);
+            };
+        };
+        "#,
+    );
+
+    assert_eq!(output, "0 1\n0 2\n1 0\n1 2\n2 0\n2 1\n0:0\n1:0\n");
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_vm_oracle_patterns_and_curses() {
+    let (result, output) = run_both(
+        r#"
+        engrave parse(s: rune) -> cursed<arcana> {
+            reveal attempt(trans(s as arcana));
+        };
+        engrave describe(s: rune) -> rune {
+            reveal oracle (parse(s)) {
+                (curse("none")) => "none";
+                (curse(message)) => message;
+                (0) => "zero";
+                _ => "number";
+            };
+        };
+        unveil(describe("abc"));
+        unveil(describe("0"));
+        unveil(describe("7"));
+        forge caught: cursed<arcana> = attempt(parse("1") / 0);
+        caught;
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        "Invalid operation: Failed to convert Rune to Arcana\nzero\nnumber\n"
+    );
+    let result = result.expect("Failed to evaluate");
+    assert!(result.contains("Division by zero!"));
+}
+
+#[test]
+fn test_vm_collections_and_sigils() {
+    let (result, output) = run_both(
+        r#"
+        sigil Hero {
+            name: rune,
+            level: arcana,
+            items: grimoire<rune>,
+        };
+        forge morph hero: Hero = Hero { name: summon("Name: ", rune), level: 1, items: [] };
+        hero.level += 1;
+        hero.items = ["staff"];
+        hero.items[0] = "wand";
+        forge morph stock: codex<rune, arcana> = ["apple": 3];
+        stock["pear"] = 5;
+        orbit ((name, count) = stock) {
+            unveil(name, "=", count);
+        };
+        forge morph xs: grimoire<arcana> = [1, 2];
+        push(xs, len(stock));
+        unveil(pop(xs), " ", xs, " ", has(stock, "pear"), " ", sqrt(16.0));
+        hero;
+        "#,
+    );
+
+    assert_eq!(output, "Name: apple=3\npear=5\n2 [1, 2] boon 4\n");
+    assert_eq!(
+        result,
+        Ok(
+            r#"Sigil("Hero", [("name", Rune("Lia")), ("level", Arcana(2)), ("items", Grimoire([Rune("wand")]))])"#
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn test_vm_lexical_scoping() {
+    let (result, output) = run_both(
+        r#"
+        forge base: arcana = 100;
+        engrave peek() -> arcana {
+            reveal base;
+        };
+        engrave outer(base: arcana) -> arcana {
+            engrave inner() -> arcana {
+                reveal base * 2;
+            };
+            reveal peek() + inner();
+        };
+        orbit (i = 0..2) {
+            unveil(outer(i));
+        };
+        outer(5);
+        "#,
+    );
+
+    assert_eq!(output, "100\n102\n");
+    assert_eq!(result, Ok("Arcana(110)".to_string()));
+}
+
+#[test]
+fn test_vm_errors_match_tree_walker() {
+    let (result, _) = run_both(
+        r#"
+        engrave fail(n: arcana) -> arcana {
+            reveal n + missing;
+        };
+        forge x: arcana = 1;
+        fail(x);
+        "#,
+    );
+    assert_eq!(
+        result,
+        Err(("Variable missing is not defined!".to_string(), Some(3)))
+    );
+
+    let (result, _) = run_both(
+        r#"
+        forge x: arcana = 1;
+        x = 2;
+        "#,
+    );
+    assert_eq!(
+        result,
+        Err((
+            "Invalid operation: Cannot reassign to immutable variable x".to_string(),
+            Some(3)
+        ))
+    );
+}
+
+#[test]
+fn test_vm_checked_arithmetic() {
+    let (result, output) = run_both(
+        r#"
+        forge morph n: arcana = 9223372036854775806;
+        n += 1;
+        unveil(attempt(n + 1));
+        unveil(attempt(n / 0));
+        n *= 2;
+        "#,
+    );
+    assert_eq!(
+        output,
+        "curse(\"Arithmetic overflow: 9223372036854775807 + 1 does not fit in arcana!\")\ncurse(\"Division by zero!\")\n"
+    );
+    assert_eq!(
+        result,
+        Err((
+            "Arithmetic overflow: 9223372036854775807 * 2 does not fit in arcana!".to_string(),
+            Some(6)
+        ))
+    );
+}
+
+#[test]
+fn test_vm_invokes_modules() {
+    let dir = std::env::temp_dir().join(format!("abyss_vm_modules_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Failed to create directory");
+    fs::write(
+        dir.join("math.aby"),
+        r#"
+        forge morph calls: arcana = 0;
+        engrave square(n: arcana) -> arcana {
+            calls += 1;
+            reveal n * n;
+        };
+        "#,
+    )
+    .expect("Failed to write module");
+
+    let (result, _) = run_both_at(
+        r#"
+        invoke "math.aby";
+        square(3) + square(4);
+        "#,
+        Some(dir.join("main.aby")),
+    );
+    assert_eq!(result, Ok("Arcana(25)".to_string()));
+}
+
+#[test]
+fn test_vm_grows_large_collections() {
+    // Assigning and reading an element must not copy the whole collection on either engine.
+    let (result, output) = run_both(
+        r#"
+        sigil Cell {
+            hp: arcana,
+        };
+        forge morph cells: grimoire<Cell> = [];
+        forge morph seen: codex<arcana, arcana> = [:];
+        orbit (i = 0..20000) {
+            push(cells, Cell { hp: i });
+            seen[i] = i;
+        };
+        orbit (i = 0..20000) {
+            cells[i].hp += seen[i];
+        };
+        unveil(len(cells), " ", len(seen), " ", cells[19999].hp);
+        pop(cells).hp - cells[0].hp;
+        "#,
+    );
+    assert_eq!(output, "20000 20000 39998\n");
+    assert_eq!(result, Ok("Arcana(39998)".to_string()));
+}
+
+#[test]
+fn test_vm_rejects_duplicate_codex_keys() {
+    let (result, _) = run_both(
+        r#"
+        forge n: arcana = 1;
+        forge squares: codex<arcana, arcana> = [1: 1, n: 1];
+        "#,
+    );
+    assert_eq!(
+        result,
+        Err((
+            "Invalid operation: Duplicate key 1 in codex literal".to_string(),
+            Some(3)
+        ))
+    );
+}
+
+/// Serializes the tests comparing the speed of the engines, which would slow each other down.
+static TIMING: Mutex<()> = Mutex::new(());
+
+/// Returns the shortest times a script takes to run on the tree-walker and on the VM over a few
+/// runs, alternating between the engines so that both run under the same load.
+fn best_times(input: &str) -> (Duration, Duration) {
+    let _timing = TIMING.lock().unwrap_or_else(|e| e.into_inner());
+    let mut best = [Duration::MAX; 2];
+    for _ in 0..7 {
+        for (engine, use_vm) in [false, true].into_iter().enumerate() {
+            let mut env = Environment::new();
+            let start = Instant::now();
+            run_in(input, &mut env, use_vm).expect("Failed to evaluate");
+            best[engine] = best[engine].min(start.elapsed());
+        }
+    }
+    (best[0], best[1])
+}
+
+#[test]
+fn test_vm_is_faster_than_tree_walker() {
+    let input = r#"
+        engrave fib(n: arcana) -> arcana {
+            oracle {
+                (n < 2) => reveal n;
+            };
+            reveal fib(n - 1) + fib(n - 2);
+        };
+        forge morph total: arcana = fib(16);
+        orbit (i = 0..150) {
+            orbit (j = 0..150) {
+                total += i * j % 7;
+            };
+        };
+        total;
+        "#;
+
+    let (tree, vm) = best_times(input);
+    assert!(
+        vm < tree,
+        "The VM took {:?}, the tree-walker {:?}",
+        vm,
+        tree
+    );
+}
+
+#[test]
+fn test_vm_is_faster_on_nested_loops() {
+    // The limits are checked on backward jumps rather than on every instruction, and the
+    // right operand is read from its slot, so the loop body runs in fewer instructions.
+    let input = r#"
+        forge morph total: arcana = 0;
+        orbit (i = 0..200) {
+            orbit (j = 0..1000) {
+                total += (i * j) % 7;
+            };
+        };
+        total;
+        "#;
+
+    let (result, _) = run_both(input);
+    assert_eq!(result, Ok("Arcana(512910)".to_string()));
+    let (tree, vm) = best_times(input);
+    assert!(
+        vm < tree,
+        "The VM took {:?}, the tree-walker {:?}",
+        vm,
+        tree
+    );
+}